@@ -2,13 +2,37 @@
 
 use serde::{Deserialize, Serialize};
 
-pub type Epoch = u64;
+pub use time::{EpochTime, EventTime};
+
 pub type Depth = u32;
 pub type ScenarioId = u64;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct Prob(pub f64);
 
+impl Prob {
+    /// One in Q32.32 fixed point.
+    pub const FIXED_ONE: u64 = 1 << 32;
+
+    /// The probability in Q32.32 fixed point, rounded to nearest; negative
+    /// and NaN values map to 0.
+    pub fn to_fixed(self) -> u64 {
+        (self.0.max(0.0) * Self::FIXED_ONE as f64).round() as u64
+    }
+
+    /// Exact for every Q32.32 value up to 2^21, so fixed-point weights
+    /// round-trip through `f64` bit for bit.
+    pub fn from_fixed(raw: u64) -> Self {
+        Prob(raw as f64 / Self::FIXED_ONE as f64)
+    }
+
+    /// Product in Q32.32, truncated toward zero.
+    pub fn fixed_mul(self, other: Prob) -> Prob {
+        let raw = (self.to_fixed() as u128 * other.to_fixed() as u128) >> 32;
+        Self::from_fixed(u64::try_from(raw).unwrap_or(u64::MAX))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Diff {
     Int(i64),
@@ -19,7 +43,7 @@ pub enum Diff {
 pub struct EventMeta {
     pub domain: String,
     pub kind: String,
-    pub epoch: Epoch,
+    pub epoch: EpochTime,
     pub source: String,
     pub key: Option<String>,
 }
@@ -45,5 +69,7 @@ pub enum CoreError {
     Serde(#[from] serde_json::Error),
 }
 
+pub mod quantity;
+pub mod time;
 pub mod retail;
 pub mod manufacturing;
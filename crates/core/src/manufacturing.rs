@@ -1,15 +1,21 @@
 use serde::{Deserialize, Serialize};
 
+use crate::time::EventTime;
+
 pub type JobId = u64;
 pub type OperationId = u32;
 pub type MachineId = u64;
+pub type OperatorId = u64;
+pub type LineId = u64;
+pub type MaterialId = u64;
+pub type LotId = u64;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct OperationStart {
     pub job_id: JobId,
     pub operation_id: OperationId,
     pub machine_id: MachineId,
-    pub ts_ms: u64,
+    pub ts_ms: EventTime,
     pub expected_duration_ms: u64,
 }
 
@@ -18,7 +24,78 @@ pub struct OperationComplete {
     pub job_id: JobId,
     pub operation_id: OperationId,
     pub machine_id: MachineId,
-    pub ts_ms: u64,
+    pub ts_ms: EventTime,
+}
+
+/// An operation finished but its output failed inspection and was discarded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OperationScrapped {
+    pub job_id: JobId,
+    pub operation_id: OperationId,
+    pub machine_id: MachineId,
+    pub line_id: LineId,
+    pub ts_ms: EventTime,
+}
+
+/// An operation's output needs another pass before it can complete.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReworkRequired {
+    pub job_id: JobId,
+    pub operation_id: OperationId,
+    pub machine_id: MachineId,
+    pub line_id: LineId,
+    pub ts_ms: EventTime,
+}
+
+/// Units of a material delivered to the floor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaterialReceived {
+    pub material_id: MaterialId,
+    pub units: i64,
+    pub ts_ms: EventTime,
+}
+
+/// Units of a material drawn by an operation on a machine.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaterialConsumed {
+    pub material_id: MaterialId,
+    pub machine_id: MachineId,
+    pub units: i64,
+    pub ts_ms: EventTime,
+}
+
+/// A lot of material produced by a job.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LotCreated {
+    pub lot_id: LotId,
+    pub material_id: MaterialId,
+    pub job_id: JobId,
+    pub ts_ms: EventTime,
+}
+
+/// A lot divided into new lots.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LotSplit {
+    pub lot_id: LotId,
+    pub into: Vec<LotId>,
+    pub ts_ms: EventTime,
+}
+
+/// Lots combined into one new lot.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LotMerged {
+    pub lots: Vec<LotId>,
+    pub into: LotId,
+    pub ts_ms: EventTime,
+}
+
+/// A job drew from a lot on a machine.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LotConsumed {
+    pub lot_id: LotId,
+    pub job_id: JobId,
+    pub machine_id: MachineId,
+    pub ts_ms: EventTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -33,12 +110,84 @@ pub enum MachineStatus {
 pub struct MachineStateChange {
     pub machine_id: MachineId,
     pub status: MachineStatus,
-    pub ts_ms: u64,
+    pub ts_ms: EventTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OperatorClockedIn {
+    pub operator_id: OperatorId,
+    pub ts_ms: EventTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OperatorClockedOut {
+    pub operator_id: OperatorId,
+    pub ts_ms: EventTime,
+}
+
+/// Assigns an operator to staff a machine; replaces any earlier assignment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OperatorAssigned {
+    pub operator_id: OperatorId,
+    pub machine_id: MachineId,
+    pub ts_ms: EventTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ManufacturingEvent {
     OperationStart(OperationStart),
     OperationComplete(OperationComplete),
+    OperationScrapped(OperationScrapped),
+    ReworkRequired(ReworkRequired),
     MachineStateChange(MachineStateChange),
+    OperatorClockedIn(OperatorClockedIn),
+    OperatorClockedOut(OperatorClockedOut),
+    OperatorAssigned(OperatorAssigned),
+    MaterialReceived(MaterialReceived),
+    MaterialConsumed(MaterialConsumed),
+    LotCreated(LotCreated),
+    LotSplit(LotSplit),
+    LotMerged(LotMerged),
+    LotConsumed(LotConsumed),
+}
+
+impl ManufacturingEvent {
+    pub fn ts_ms(&self) -> EventTime {
+        match self {
+            Self::OperationStart(e) => e.ts_ms,
+            Self::OperationComplete(e) => e.ts_ms,
+            Self::OperationScrapped(e) => e.ts_ms,
+            Self::ReworkRequired(e) => e.ts_ms,
+            Self::MachineStateChange(e) => e.ts_ms,
+            Self::OperatorClockedIn(e) => e.ts_ms,
+            Self::OperatorClockedOut(e) => e.ts_ms,
+            Self::OperatorAssigned(e) => e.ts_ms,
+            Self::MaterialReceived(e) => e.ts_ms,
+            Self::MaterialConsumed(e) => e.ts_ms,
+            Self::LotCreated(e) => e.ts_ms,
+            Self::LotSplit(e) => e.ts_ms,
+            Self::LotMerged(e) => e.ts_ms,
+            Self::LotConsumed(e) => e.ts_ms,
+        }
+    }
+
+    /// The machine the event happened on, if it names one.
+    pub fn machine_id(&self) -> Option<MachineId> {
+        match self {
+            Self::OperationStart(e) => Some(e.machine_id),
+            Self::OperationComplete(e) => Some(e.machine_id),
+            Self::OperationScrapped(e) => Some(e.machine_id),
+            Self::ReworkRequired(e) => Some(e.machine_id),
+            Self::MachineStateChange(e) => Some(e.machine_id),
+            Self::OperatorAssigned(e) => Some(e.machine_id),
+            Self::MaterialConsumed(e) => Some(e.machine_id),
+            Self::LotConsumed(e) => Some(e.machine_id),
+            Self::OperatorClockedIn(_)
+            | Self::OperatorClockedOut(_)
+            | Self::MaterialReceived(_)
+            | Self::LotCreated(_)
+            | Self::LotSplit(_)
+            | Self::LotMerged(_) => None,
+        }
+    }
 }
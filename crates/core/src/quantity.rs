@@ -0,0 +1,293 @@
+//! Unit-aware fixed-point quantities. The unit is a type parameter, so adding
+//! cents to machine units or hours is a compile error rather than a silent
+//! mix-up in an overlay delta.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, Neg, Sub};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Denominator for basis-point scaling (1 bps = 1/10_000).
+pub const BPS_SCALE: i64 = 10_000;
+
+/// A unit of measure for [`Quantity`].
+pub trait Unit: 'static {
+    /// Type name shown in `Debug` output.
+    const NAME: &'static str;
+    /// Suffix shown in `Display` output.
+    const SYMBOL: &'static str;
+    /// Stored values per displayed whole unit, a power of ten (100 for cents).
+    const SCALE: i64;
+}
+
+/// Currency amounts stored in cents.
+#[derive(Debug)]
+pub enum Cents {}
+
+impl Unit for Cents {
+    const NAME: &'static str = "Cents";
+    const SYMBOL: &'static str = "USD";
+    const SCALE: i64 = 100;
+}
+
+/// Discrete item counts (WIP, scrapped parts).
+#[derive(Debug)]
+pub enum Units {}
+
+impl Unit for Units {
+    const NAME: &'static str = "Units";
+    const SYMBOL: &'static str = "units";
+    const SCALE: i64 = 1;
+}
+
+/// Durations stored in hundredths of an hour.
+#[derive(Debug)]
+pub enum Hours {}
+
+impl Unit for Hours {
+    const NAME: &'static str = "Hours";
+    const SYMBOL: &'static str = "h";
+    const SCALE: i64 = 100;
+}
+
+/// How fixed-point division resolves remainders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    #[default]
+    HalfAwayFromZero,
+    HalfEven,
+    TowardZero,
+    Floor,
+    Ceil,
+}
+
+impl Rounding {
+    /// `num / den` rounded by this mode; `den` must be positive.
+    pub fn divide(self, num: i128, den: i128) -> i128 {
+        let quot = num / den;
+        let rem = num % den;
+        if rem == 0 {
+            return quot;
+        }
+        let away = quot + num.signum();
+        match self {
+            Rounding::TowardZero => quot,
+            Rounding::Floor => quot.min(away),
+            Rounding::Ceil => quot.max(away),
+            Rounding::HalfAwayFromZero | Rounding::HalfEven => match (2 * rem.abs()).cmp(&den) {
+                Ordering::Less => quot,
+                Ordering::Greater => away,
+                Ordering::Equal if self == Rounding::HalfEven && quot % 2 == 0 => quot,
+                Ordering::Equal => away,
+            },
+        }
+    }
+}
+
+/// Fixed-point amount of `U`. Serializes as a bare integer so payloads that
+/// carried raw `i64` values stay wire-compatible.
+pub struct Quantity<U: Unit> {
+    value: i64,
+    unit: PhantomData<U>,
+}
+
+impl<U: Unit> Quantity<U> {
+    pub const ZERO: Self = Self::new(0);
+
+    /// Wrap a raw value already expressed in `U`'s stored scale.
+    pub const fn new(value: i64) -> Self {
+        Self { value, unit: PhantomData }
+    }
+
+    pub const fn value(self) -> i64 {
+        self.value
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.value.checked_add(rhs.value).map(Self::new)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.value.checked_sub(rhs.value).map(Self::new)
+    }
+
+    pub fn checked_mul(self, qty: i64) -> Option<Self> {
+        self.value.checked_mul(qty).map(Self::new)
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        Self::new(self.value.saturating_add(rhs.value))
+    }
+
+    pub fn saturating_mul(self, qty: i64) -> Self {
+        Self::new(self.value.saturating_mul(qty))
+    }
+
+    /// Scale by `bps` basis points, rounding half away from zero.
+    pub fn checked_scale_bps(self, bps: i64) -> Option<Self> {
+        self.checked_scale_bps_with(bps, Rounding::HalfAwayFromZero)
+    }
+
+    /// Scale by `bps` basis points with explicit rounding. Computed in `i128`
+    /// so no precision is lost before the final range check.
+    pub fn checked_scale_bps_with(self, bps: i64, rounding: Rounding) -> Option<Self> {
+        let scaled = (self.value as i128) * (bps as i128);
+        i64::try_from(rounding.divide(scaled, BPS_SCALE as i128)).ok().map(Self::new)
+    }
+
+    pub fn saturating_scale_bps(self, bps: i64) -> Self {
+        self.saturating_scale_bps_with(bps, Rounding::HalfAwayFromZero)
+    }
+
+    pub fn saturating_scale_bps_with(self, bps: i64, rounding: Rounding) -> Self {
+        match self.checked_scale_bps_with(bps, rounding) {
+            Some(scaled) => scaled,
+            None if (self.value < 0) == (bps < 0) => Self::new(i64::MAX),
+            None => Self::new(i64::MIN),
+        }
+    }
+}
+
+impl Quantity<Cents> {
+    pub const fn from_cents(cents: i64) -> Self {
+        Self::new(cents)
+    }
+
+    pub const fn cents(self) -> i64 {
+        self.value
+    }
+}
+
+impl Quantity<Units> {
+    pub const fn from_units(units: i64) -> Self {
+        Self::new(units)
+    }
+
+    pub const fn units(self) -> i64 {
+        self.value
+    }
+}
+
+// Manual impls so the marker type needs no derives of its own.
+impl<U: Unit> Clone for Quantity<U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U: Unit> Copy for Quantity<U> {}
+
+impl<U: Unit> Default for Quantity<U> {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl<U: Unit> PartialEq for Quantity<U> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<U: Unit> Eq for Quantity<U> {}
+
+impl<U: Unit> PartialOrd for Quantity<U> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<U: Unit> Ord for Quantity<U> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<U: Unit> Hash for Quantity<U> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl<U: Unit> fmt::Debug for Quantity<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", U::NAME, self.value)
+    }
+}
+
+impl<U: Unit> fmt::Display for Quantity<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if U::SCALE <= 1 {
+            return write!(f, "{} {}", self.value, U::SYMBOL);
+        }
+        let sign = if self.value < 0 { "-" } else { "" };
+        let abs = (self.value as i128).abs();
+        let scale = U::SCALE as i128;
+        let width = (U::SCALE - 1).to_string().len();
+        write!(f, "{sign}{}.{:0width$} {}", abs / scale, abs % scale, U::SYMBOL)
+    }
+}
+
+impl<U: Unit> Serialize for Quantity<U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, U: Unit> Deserialize<'de> for Quantity<U> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        i64::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl<U: Unit> Add for Quantity<U> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        self.saturating_add(rhs)
+    }
+}
+
+impl<U: Unit> Sub for Quantity<U> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.value.saturating_sub(rhs.value))
+    }
+}
+
+impl<U: Unit> Neg for Quantity<U> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(self.value.saturating_neg())
+    }
+}
+
+impl<U: Unit> Sum for Quantity<U> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Self::saturating_add)
+    }
+}
+
+/// A projected value plus or minus `z` standard deviations, rounded outward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interval {
+    pub low: i64,
+    pub high: i64,
+}
+
+impl Interval {
+    pub fn around(value: i64, variance: i64, z: f64) -> Self {
+        let spread = (variance.max(0) as f64).sqrt() * z.max(0.0);
+        Self {
+            low: (value as f64 - spread).floor() as i64,
+            high: (value as f64 + spread).ceil() as i64,
+        }
+    }
+}
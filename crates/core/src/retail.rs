@@ -1,16 +1,55 @@
 use serde::{Deserialize, Serialize};
 
+pub use crate::quantity::BPS_SCALE;
+use crate::quantity::{Cents, Quantity};
+use crate::time::EventTime;
+
 pub type CustomerId = u64;
 pub type SkuId = u64;
 pub type OrderId = u64;
+/// Customer segment used to split demand projections.
+pub type CohortId = u64;
+pub type StoreId = u64;
+/// [`Channel`] encoded as an integer, for dataflow keys.
+pub type ChannelId = u64;
 
-pub type MoneyCents = i64;
+/// Fixed-point money amount in cents; serializes as a bare integer.
+pub type Money = Quantity<Cents>;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct OrderLine {
     pub sku_id: SkuId,
     pub qty: u32,
-    pub price_cents: MoneyCents,
+    pub price_cents: Money,
+}
+
+/// Where an order was placed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    #[default]
+    Web,
+    Marketplace,
+    Store(StoreId),
+}
+
+impl Channel {
+    /// `0` for web, `1` for marketplace and `2 + store id` for stores.
+    pub fn id(self) -> ChannelId {
+        match self {
+            Channel::Web => 0,
+            Channel::Marketplace => 1,
+            Channel::Store(store) => store.saturating_add(2),
+        }
+    }
+
+    pub fn from_id(id: ChannelId) -> Self {
+        match id {
+            0 => Channel::Web,
+            1 => Channel::Marketplace,
+            store => Channel::Store(store - 2),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -18,11 +57,14 @@ pub struct OrderPlaced {
     pub order_id: OrderId,
     pub customer_id: CustomerId,
     pub lines: Vec<OrderLine>,
-    pub ts_ms: u64,
+    pub ts_ms: EventTime,
+    /// Orders recorded before channels were tracked count as web orders.
+    #[serde(default)]
+    pub channel: Channel,
 }
 
 impl OrderPlaced {
-    pub fn total_cents(&self) -> MoneyCents {
+    pub fn total_cents(&self) -> Money {
         self.lines
             .iter()
             .map(|l| l.price_cents.saturating_mul(l.qty as i64))
@@ -30,9 +72,28 @@ impl OrderPlaced {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PriceChanged {
+    pub sku_id: SkuId,
+    pub old_cents: Money,
+    pub new_cents: Money,
+    pub ts_ms: EventTime,
+}
+
+impl PriceChanged {
+    /// Relative price change in basis points, or `None` if the old price was not positive.
+    pub fn change_bps(&self) -> Option<i64> {
+        if self.old_cents <= Money::ZERO {
+            return None;
+        }
+        let diff = (self.new_cents.cents() as i128) - (self.old_cents.cents() as i128);
+        i64::try_from(diff * (BPS_SCALE as i128) / (self.old_cents.cents() as i128)).ok()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum RetailEvent {
     OrderPlaced(OrderPlaced),
-    InventoryAdjusted { sku_id: SkuId, delta_qty: i32, ts_ms: u64 },
+    PriceChanged(PriceChanged),
+    InventoryAdjusted { sku_id: SkuId, delta_qty: i32, ts_ms: EventTime },
 }
-
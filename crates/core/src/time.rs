@@ -0,0 +1,98 @@
+//! Event time and engine epochs as distinct types, so milliseconds, seconds
+//! and epoch counters cannot be mixed up without an explicit conversion.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Wall-clock time an event happened, in milliseconds since the Unix epoch.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct EventTime(u64);
+
+impl EventTime {
+    pub const fn from_millis(ms: u64) -> Self {
+        EventTime(ms)
+    }
+
+    pub const fn from_secs(secs: u64) -> Self {
+        EventTime(secs.saturating_mul(1_000))
+    }
+
+    pub const fn as_millis(self) -> u64 {
+        self.0
+    }
+
+    /// Whole seconds, truncated.
+    pub const fn as_secs(self) -> u64 {
+        self.0 / 1_000
+    }
+
+    /// Milliseconds between `self` and `other`, in either direction.
+    pub const fn abs_diff_ms(self, other: EventTime) -> u64 {
+        self.0.abs_diff(other.0)
+    }
+
+    /// The epoch containing this time when each epoch spans `epoch_ms`.
+    pub fn epoch(self, epoch_ms: u64) -> EpochTime {
+        EpochTime(self.0 / epoch_ms.max(1))
+    }
+}
+
+impl fmt::Display for EventTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}ms", self.0)
+    }
+}
+
+/// An engine epoch: the batch counter inputs and dataflow frontiers advance
+/// through. Dataflow timestamps stay plain `u64`; use [`EpochTime::get`] at
+/// that boundary.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct EpochTime(u64);
+
+impl EpochTime {
+    pub const ZERO: EpochTime = EpochTime(0);
+    pub const MAX: EpochTime = EpochTime(u64::MAX);
+
+    pub const fn new(epoch: u64) -> Self {
+        EpochTime(epoch)
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    pub const fn next(self) -> Self {
+        EpochTime(self.0.saturating_add(1))
+    }
+
+    pub const fn saturating_add(self, epochs: u64) -> Self {
+        EpochTime(self.0.saturating_add(epochs))
+    }
+
+    pub const fn saturating_sub(self, epochs: u64) -> Self {
+        EpochTime(self.0.saturating_sub(epochs))
+    }
+
+    /// Epochs elapsed from `earlier` to `self`, or 0 if `earlier` is later.
+    pub const fn since(self, earlier: EpochTime) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Event time at which this epoch starts when each epoch spans `epoch_ms`.
+    pub const fn start(self, epoch_ms: u64) -> EventTime {
+        EventTime(self.0.saturating_mul(epoch_ms))
+    }
+}
+
+impl fmt::Display for EpochTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
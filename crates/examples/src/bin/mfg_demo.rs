@@ -1,28 +1,95 @@
 use anyhow::Result;
 use clap::Parser;
-use tracing::info;
-use tw_runtime::metrics::{EpochTimer, MetricsRegistry};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use tw_runtime::alerting::{
+    AlertGate, AlertGateState, AlertPolicy, Persistence, PersistenceTracker,
+};
+use tw_runtime::causal::{manufacturing_order, CausalConfig, CausalOrderer};
+use tw_runtime::control::ControlPlane;
+use tw_runtime::dedup::{DedupConfig, Deduplicator};
+use tw_runtime::hooks::{self, EpochHooks};
+use tw_runtime::jsonl::JsonlSink;
+use tw_runtime::leader::{FileLease, LeaseKeeper, Leadership};
+use tw_runtime::ledger::{LedgeredInput, RetirementInput};
+use tw_runtime::metrics::{EpochTimer, MetricsBaseline, MetricsRegistry};
+use tw_runtime::profiling::{Stage, StageProfiler};
+use tw_runtime::projection::{ProjectionTracker, RedisProjectionSink};
+use tw_runtime::replay::{PredictedRow, RecentEvents, ReplayBundle};
+use tw_runtime::reporter::MetricsReporter;
+use tw_runtime::resources::ResourceUsage;
+use tw_runtime::rollout::{Rollout, RolloutConfig, Route};
+use tw_runtime::sampling::{EventSampler, SamplingConfig};
+use tw_runtime::scheduler::{self, PipelineScheduler};
+use tw_runtime::subscription::Subscriptions;
+use tw_runtime::validation::{manufacturing_rules, ValidationConfig};
 use tw_runtime::{init_tracing, start_runtime};
 
 use differential_dataflow::input::InputSession;
 use differential_dataflow::operators::reduce::Reduce;
+use differential_dataflow::operators::Join;
 use timely::dataflow::operators::probe::{Handle as ProbeHandle, Probe};
 use timely::dataflow::operators::{Inspect, Map};
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use tw_core::manufacturing::{ManufacturingEvent, OperationComplete, OperationStart};
-use tw_core::{EventEnvelope, EventMeta};
-use tw_predictors::QueueGrowthPredictor;
+use tw_core::manufacturing::{
+    LotConsumed, LotCreated, LotMerged, LotSplit, MachineStateChange, MachineStatus,
+    ManufacturingEvent, MaterialConsumed, MaterialReceived, OperationComplete, OperationScrapped,
+    OperationStart, OperatorAssigned, OperatorClockedIn, OperatorClockedOut, ReworkRequired,
+};
+use tw_core::quantity::{Interval, Quantity, Units, BPS_SCALE};
+use tw_core::{EpochTime, EventEnvelope, EventMeta, EventTime};
+use tw_views::exclusion::{
+    combined_probability, exclusive_weights, expected_delta, expected_variance,
+};
+use tw_views::funnel::{
+    conversion_bps, current_stage, operation_lifecycle, scenario_stage_counts, stage_counts, stuck,
+};
+use tw_views::genealogy::{descendant_lots, hold_impact, jobs_on_hold};
+use tw_views::interval::with_variance;
+use tw_views::material::{on_hand, starved_machines};
+use tw_views::overlay::{gated, live_scenarios};
+use tw_views::pushdown::{prob_bps, pushdown_candidates, PredicateRow, SubscriptionPredicate};
+use tw_views::throughput::{projected_clear, scenario_projected_clear, windowed_throughput};
+use tw_views::rank::RankTracker;
+use tw_views::{ranked_top_k, sampled_total, top_k, TopKConfig};
+use tw_predictors::guard::{GuardConfig, Guarded};
+use tw_predictors::warmup::{Readiness, WarmUpConfig, WarmUpGate, WarmUpTracker};
+use tw_predictors::{
+    CoverageShortagePredictor, FailureHazardPredictor, MachineHealth, MaterialStatus,
+    MachineBacklogPredictor, QueueGrowthPredictor, Tier, YieldLossRatePredictor,
+};
 use tw_scenarios::manufacturing::{
-    ManufacturingBeamConfig, ManufacturingScenarioDelta, ManufacturingScenarioManager,
+    ManufacturingBeamConfig, ManufacturingExpansionOutcome, ManufacturingScenarioDelta,
+    ManufacturingScenarioManager, ManufacturingScenarioState, WipSeed,
 };
+use tw_scenarios::bulk::BulkOp;
+use tw_scenarios::external::{ExternalPlan, ExternalScenario};
+use tw_scenarios::priors::PriorsConfig;
+use tw_scenarios::state::{read_json, write_json};
+use tw_scenarios::heatmap::ImpactHeatmap;
+use tw_scenarios::history::RetirementReason;
+use tw_scenarios::throttle::LagThrottleConfig;
+use tw_scenarios::timeline::ScenarioTimelines;
+use tw_scenarios::validate::Proposal;
+use tw_scenarios::valve::{SafetyCaps, SafetyValve};
+use tw_scenarios::what_if::WhatIfPlan;
+use tw_scenarios::{DepthQuota, Renormalization, ScenarioMeta, WeightMode};
 
 #[derive(Parser, Debug)]
 #[command(name = "mfg_demo", about = "Manufacturing branching futures demo with configurable parameters")]
 struct ManufacturingOpts {
     #[arg(long, default_value_t = 3)]
     top_k: usize,
+    /// Keep every key tied with the k-th value instead of cutting at exactly k.
+    #[arg(long)]
+    topk_include_ties: bool,
     #[arg(long, default_value_t = 8)]
     machines: u64,
     #[arg(long, default_value_t = 12)]
@@ -45,13 +112,282 @@ struct ManufacturingOpts {
     backlog_threshold: i64,
     #[arg(long, default_value_t = 0.3)]
     prob_threshold: f64,
+    /// Standard deviation of predicted backlog, as a fraction of the prediction.
+    #[arg(long, default_value_t = 0.0)]
+    backlog_relative_sd: f64,
+    /// Alternative backlog as <probability>:<factor> on the estimate, such as 0.2:3;
+    /// repeatable. Each operation then branches one child per tier.
+    #[arg(long)]
+    backlog_tier: Vec<Tier<f64>>,
+    /// Standard deviations either side of a projected value its interval spans.
+    #[arg(long, default_value_t = 1.96)]
+    interval_z: f64,
+    /// Trailing window, in epochs, for machine throughput and queue-clearing estimates.
+    #[arg(long, default_value_t = 4)]
+    throughput_window: u64,
+    /// Epochs an operation may run unfinished before the lifecycle funnel reports it stuck.
+    #[arg(long, default_value_t = 8)]
+    stuck_after: u64,
+    /// JSON file with quiet hours, hourly cap, and escalation settings.
+    #[arg(long)]
+    alert_policy: Option<PathBuf>,
+    /// JSON file of rollout flags; a flagged subscription only logs its alerts at first.
+    #[arg(long)]
+    rollout: Option<PathBuf>,
+    /// Sample metrics on this interval between epochs; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    report_interval_ms: u64,
+    /// Keep a separate beam per machine so unrelated futures don't evict each other.
+    #[arg(long)]
+    partition_beam: bool,
+    /// Operations seen per machine before the backlog predictor branches on it; 0 needs none.
+    #[arg(long, default_value_t = 0)]
+    warm_up_observations: u64,
+    /// Epochs of history before the backlog predictor branches at all.
+    #[arg(long, default_value_t = 0)]
+    warm_up_epochs: u64,
+    /// Branch with a flat fallback predictor while warming up instead of not branching.
+    #[arg(long)]
+    warm_up_fallback: bool,
+    /// Rank only the scenarios and rows a subscription could fire on; the
+    /// scenario top-K log then covers only those.
+    #[arg(long)]
+    pushdown_subscriptions: bool,
+    /// Worker steps per epoch to wait on subscriptions, shared by weight; one
+    /// that runs out is left to catch up later and its alerts land late.
+    #[arg(long)]
+    pipeline_steps: Option<u64>,
+    /// Subscription step weight as name=weight; repeatable, default 1.
+    #[arg(long)]
+    pipeline_weight: Vec<String>,
+    /// Shell command run after each epoch, with TW_EPOCH set and the metrics
+    /// snapshot as JSON on stdin; repeatable.
+    #[arg(long)]
+    epoch_hook_cmd: Vec<String>,
+    /// Accept control commands as JSON lines on this address, e.g. 127.0.0.1:7071.
+    #[arg(long)]
+    control_addr: Option<String>,
+    /// Reject proposed scenarios whose WIP delta exceeds this many units.
+    #[arg(long)]
+    max_wip_delta: Option<i64>,
+    /// Log each proposed scenario a validator rejects.
+    #[arg(long)]
+    log_rejections: bool,
+    /// Cap on active scenarios across all beam partitions.
+    #[arg(long)]
+    global_cap: Option<usize>,
+    /// With --partition-beam, cap on partitions holding active scenarios;
+    /// the least recently branched one is evicted first.
+    #[arg(long)]
+    max_keys: Option<usize>,
+    /// Scale scenario weights by this factor every epoch, so unconfirmed branches fade.
+    #[arg(long)]
+    epoch_decay: Option<f64>,
+    /// Retire scenarios this many epochs after creation unless confirmed since.
+    #[arg(long)]
+    scenario_ttl: Option<u64>,
+    /// Cap on active scenarios at --deep-min-depth or deeper.
+    #[arg(long)]
+    deep_quota: Option<usize>,
+    #[arg(long, default_value_t = 3)]
+    deep_min_depth: u32,
+    /// Beam slots kept for the highest-impact scenarios probability would drop.
+    #[arg(long, default_value_t = 0)]
+    tail_slots: usize,
+    /// Most children one triggering event may create, off the heaviest parents.
+    #[arg(long)]
+    max_children_per_event: Option<usize>,
+    /// Compute and rank scenario weights in Q32.32 fixed point for bit-reproducible runs.
+    #[arg(long)]
+    fixed_point_weights: bool,
+    /// Rescale surviving weights after each expansion: none, sum_to_one or redistribute_to_root.
+    #[arg(long, default_value = "none")]
+    renormalize: Renormalization,
+    /// Operator bulk operation to submit at --bulk-op-at, such as
+    /// retire:tag=quality_hold:, reweight:0.5:12,13 or, with --partition-beam,
+    /// freeze:42; repeatable.
+    #[arg(long)]
+    bulk_op: Vec<BulkOp>,
+    /// Batch at which --bulk-op operations are submitted.
+    #[arg(long, default_value_t = 0)]
+    bulk_op_at: u64,
+    /// Hard cap on active scenarios summed over every manager in the process.
+    #[arg(long)]
+    max_active_scenarios: Option<usize>,
+    /// Hard cap on overlay rows summed over every manager in the process.
+    #[arg(long)]
+    max_overlay_rows: Option<usize>,
+    /// Drop alerts beyond this many per epoch.
+    #[arg(long)]
+    max_alerts_per_epoch: Option<u64>,
+    /// Drop events repeating a (source, kind, key, ts) seen within this many epochs; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    dedup_ttl_epochs: u64,
+    /// Drop events whose ts_ms is further than this from their epoch's clock.
+    #[arg(long)]
+    max_ts_skew_ms: Option<u64>,
+    /// Input rule to leave unenforced; repeatable.
+    #[arg(long)]
+    disable_rule: Vec<String>,
+    /// Append events that break an input rule to this JSONL file.
+    #[arg(long)]
+    dead_letters: Option<PathBuf>,
+    /// Write a replay bundle for each delivered persistent alert into this directory.
+    #[arg(long)]
+    replay_bundles: Option<PathBuf>,
+    /// Epochs of a key's events kept for replay bundles.
+    #[arg(long, default_value_t = 5)]
+    replay_epochs: u64,
+    /// Keep this share of event keys, in basis points, and scale aggregates to match.
+    #[arg(long, default_value_t = 10_000)]
+    sample_bps: i64,
+    /// Report a per-stage time breakdown every this many epochs; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    profile_every: u64,
+    /// Write per-scenario lifecycle timelines as JSON to this file on exit.
+    #[arg(long)]
+    timeline_out: Option<PathBuf>,
+    /// Machine whose projected deltas go into the impact heatmap; repeatable.
+    #[arg(long)]
+    heatmap_key: Vec<u64>,
+    /// Write the per-epoch (key × scenario) impact heatmap as Parquet to this file on exit.
+    #[arg(long)]
+    heatmap_out: Option<PathBuf>,
+    /// Machine whose latest base, expected and tagged-scenario WIP is published to
+    /// --redis-url as epochs close; repeatable.
+    #[arg(long)]
+    projection_key: Vec<u64>,
+    /// Redis server for published projections, such as redis://127.0.0.1/.
+    #[arg(long)]
+    redis_url: Option<String>,
+    /// Prefix of the Redis keys projections are written under.
+    #[arg(long, default_value = "tw:mfg")]
+    redis_prefix: String,
+    /// Create scenarios no deeper than --lag-throttle-depth while the source lags by more
+    /// than this many ms, backfilling skipped expansions once it catches up.
+    #[arg(long)]
+    lag_throttle_ms: Option<u64>,
+    #[arg(long, default_value_t = 1)]
+    lag_throttle_depth: u32,
+    /// Rewrite this file with Prometheus-format metrics, labeled by domain, after every epoch.
+    #[arg(long)]
+    metrics_prom: Option<PathBuf>,
+    /// Wall time the source takes to produce one epoch; slower epochs add to its lag.
+    #[arg(long, default_value_t = 1_000)]
+    epoch_budget_ms: u64,
+    /// Keep timelines of retired scenarios for this many epochs.
+    #[arg(long, default_value_t = 50)]
+    timeline_epochs: u64,
+    /// Remember this many retired scenarios for --explain-scenario.
+    #[arg(long, default_value_t = 256)]
+    retired_history: usize,
+    /// On exit, log what became of this scenario, or its lineage while live; repeatable.
+    #[arg(long)]
+    explain_scenario: Vec<u64>,
+    /// Clamp each predicted delta to this magnitude.
+    #[arg(long)]
+    max_delta: Option<i64>,
+    /// Clamp each predicted delta relative to the key's base value, in basis points.
+    #[arg(long)]
+    max_uplift_bps: Option<i64>,
+    /// Lock file shared with a standby instance; only the holder emits alerts.
+    #[arg(long)]
+    leader_lock: Option<PathBuf>,
+    /// Standby takes over once the leader has not renewed for this long.
+    #[arg(long, default_value_t = 5_000)]
+    lease_ttl_ms: u64,
+    /// Project scrap and rework into scenario overlays.
+    #[arg(long)]
+    yield_loss: bool,
+    /// Track material stock and branch scenarios on projected shortages.
+    #[arg(long)]
+    material_shortages: bool,
+    /// Simulate breakdowns and branch scenarios on predicted machine failures.
+    #[arg(long)]
+    machine_failures: bool,
+    /// Branch scenarios on operators missing the shift they clock in for.
+    #[arg(long)]
+    operator_absences: bool,
+    /// Branch probability of an operator absence scenario.
+    #[arg(long, default_value_t = 0.1)]
+    absence_prob: f64,
+    /// Log each machine's scenario risk and expected WIP delta, counting
+    /// mutually exclusive scenarios once.
+    #[arg(long)]
+    machine_risk: bool,
+    /// JSON file of externally planned scenarios, seeded at the root on the first epoch.
+    #[arg(long)]
+    external_scenarios: Option<PathBuf>,
+    /// JSON file of prior scenarios per domain, seeded at the root on the first epoch and
+    /// decayed on their schedules.
+    #[arg(long)]
+    priors: Option<PathBuf>,
+    /// JSON file of user-defined what-if scenarios, each injected at its epoch as a pinned
+    /// scenario. When resuming, entries at or before the checkpoint's last epoch are skipped,
+    /// since the checkpoint already holds them.
+    #[arg(long)]
+    what_if: Option<PathBuf>,
+    /// Simulate lot genealogy: completed jobs create lots that are split, merged and drawn
+    /// by later jobs.
+    #[arg(long)]
+    lot_genealogy: bool,
+    /// Seed a quality-hold scenario suspecting this lot on the first epoch; repeatable.
+    #[arg(long)]
+    suspect_lot: Vec<u64>,
+    /// Probability of each quality-hold scenario.
+    #[arg(long, default_value_t = 0.3)]
+    suspect_lot_prob: f64,
+    /// Resume the scenario tree from this checkpoint when it exists, instead of seeding
+    /// external scenarios and priors, and write it back on exit. Metrics counter totals
+    /// are kept beside it in <checkpoint>.metrics.json, alert gate state in
+    /// <checkpoint>.alerts.json.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Also write the checkpoint every this many epochs; 0 only writes it on exit.
+    #[arg(long, default_value_t = 0)]
+    checkpoint_every: u64,
+    /// Reconcile the scenario weight input against the live scenarios every this many
+    /// epochs, retracting weights no retirement took back; 0 only at startup.
+    #[arg(long, default_value_t = 100)]
+    weight_gc_every: u64,
+    /// Hold a job's completion, scrap, rework and material draws until its start is in,
+    /// releasing them anyway after --causal-wait-epochs.
+    #[arg(long)]
+    causal_order: bool,
+    #[arg(long, default_value_t = 5)]
+    causal_wait_epochs: u64,
+    /// Settle operation scenarios against their jobs' completions: contradicted branches
+    /// retire and confirmed ones are promoted.
+    #[arg(long)]
+    reconcile: bool,
+    /// Reweight the beam each epoch on machine load: WIP build-up scenarios on machines
+    /// that started more than their fair share are scaled by this likelihood ratio, then
+    /// renormalized.
+    #[arg(long)]
+    evidence_gain: Option<f64>,
+    /// Alert when a machine's yield drops below this many basis points.
+    #[arg(long, default_value_t = 9_500)]
+    yield_alert_bps: i64,
 }
 
+/// Domain label on events, metrics and alerts.
+const DOMAIN: &str = "manufacturing";
+const MACHINES_PER_LINE: u64 = 4;
+const REWORK_EVERY: u64 = 23;
+const SCRAP_EVERY: u64 = 17;
+const MATERIALS: u64 = 3;
+const RESUPPLY_EVERY: u64 = 4;
+const FAIL_EVERY: u64 = 29;
+const SPLIT_EVERY: u64 = 5;
+const MERGE_EVERY: u64 = 6;
+const OPEN_LOTS: usize = 12;
+
 #[derive(Debug, Clone)]
 struct ActiveJob {
     job_id: u64,
     machine_id: u64,
-    ready_epoch: u64,
+    ready_epoch: EpochTime,
+    reworked: bool,
 }
 
 fn main() -> Result<()> {
@@ -59,33 +395,305 @@ fn main() -> Result<()> {
     info!("mfg_demo starting");
     let opts = ManufacturingOpts::parse();
     info!(?opts, "mfg opts");
+    let alert_policy = match &opts.alert_policy {
+        Some(path) => AlertPolicy::from_json_file(path)?,
+        None => AlertPolicy::default(),
+    };
+    // Condition windows per worker start from this empty tracker
+    let conditions = PersistenceTracker::new(alert_policy.persistence)?;
+    // A checkpointed tree already holds the external, prior and hold scenarios it was seeded with
+    let checkpoint: Option<ManufacturingScenarioState> = match &opts.checkpoint {
+        Some(path) if path.exists() => Some(read_json(path)?),
+        _ => None,
+    };
+    let external_scenarios = match &opts.external_scenarios {
+        Some(path) if checkpoint.is_none() => {
+            ExternalPlan::<WipSeed>::from_json_file(path)?.scenarios
+        }
+        _ => Vec::new(),
+    };
+    let what_ifs = match &opts.what_if {
+        Some(path) => WhatIfPlan::<WipSeed>::from_json_file(path)?.what_ifs,
+        None => Vec::new(),
+    };
+    let priors = match &opts.priors {
+        Some(path) if checkpoint.is_none() => {
+            PriorsConfig::<WipSeed>::from_json_file(path, DOMAIN)?.priors
+        }
+        _ => Vec::new(),
+    };
+    // Quality holds overlay no WIP; the genealogy view works out what they stop
+    let hold_scenarios: Vec<(u64, ExternalScenario<WipSeed>)> = opts
+        .suspect_lot
+        .iter()
+        .filter(|_| checkpoint.is_none())
+        .map(|&lot| {
+            let scenario = ExternalScenario {
+                source: "quality_hold".to_string(),
+                name: format!("lot:{lot}"),
+                probability: opts.suspect_lot_prob,
+                rows: Vec::new(),
+            };
+            (lot, scenario)
+        })
+        .collect();
+    let rollout_cfg = match &opts.rollout {
+        Some(path) => RolloutConfig::from_json_file(path)?,
+        None => RolloutConfig::default(),
+    };
     let beam_cfg = ManufacturingBeamConfig {
         max_depth: opts.max_depth,
         beam_width: opts.beam_width,
         min_prob: opts.min_prob,
         branch_prob: opts.branch_prob,
+        absence_prob: opts.absence_prob,
         delta_multiplier: opts.delta_multiplier,
         min_delta_units: opts.min_delta_units,
+        global_cap: opts.global_cap,
+        max_keys: opts.max_keys,
+        epoch_decay: opts.epoch_decay,
+        ttl_epochs: opts.scenario_ttl,
+        depth_quotas: opts
+            .deep_quota
+            .map(|max_scenarios| DepthQuota { min_depth: opts.deep_min_depth, max_scenarios })
+            .into_iter()
+            .collect(),
+        tail_slots: opts.tail_slots,
+        max_children_per_event: opts.max_children_per_event,
+        weight_mode: if opts.fixed_point_weights { WeightMode::Fixed } else { WeightMode::Float },
+        renormalization: opts.renormalize,
+    };
+    let safety_caps = SafetyCaps {
+        max_active_scenarios: opts.max_active_scenarios,
+        max_overlay_rows: opts.max_overlay_rows,
+        max_alerts_per_epoch: opts.max_alerts_per_epoch,
+    };
+    let pipeline_weights: BTreeMap<String, u32> = opts
+        .pipeline_weight
+        .iter()
+        .map(|spec| scheduler::parse_weight(spec))
+        .collect::<Result<_>>()?;
+    let guard_cfg = GuardConfig {
+        max_delta: opts.max_delta,
+        max_uplift_bps: opts.max_uplift_bps,
+        ..GuardConfig::default()
     };
     start_runtime(1, move |_index, worker| {
         info!("mfg_demo worker running");
 
         let mut input: InputSession<_, EventEnvelope<ManufacturingEvent>, isize> = InputSession::new();
-        let mut pred_input: InputSession<_, (u64, u64, i64), isize> = InputSession::new();
-        let mut scen_weight_input: InputSession<_, (u64, f64), isize> = InputSession::new();
+        // Predicted overlay input: (scenario_id, machine_id, delta_wip, variance)
+        let mut pred_input: InputSession<_, (u64, u64, i64, i64), isize> = InputSession::new();
+        let mut scen_weight_input: LedgeredInput<_, (u64, f64)> = LedgeredInput::new();
+        // Retired scenario ids, retracted once their epoch is complete
+        let mut retire_input: RetirementInput<_> = RetirementInput::new();
+        // Exclusion group per grouped scenario
+        let mut group_input: InputSession<_, (u64, u64), isize> = InputSession::new();
+        // Machine each breakdown scenario predicts down
+        let mut down_input: InputSession<_, (u64, u64), isize> = InputSession::new();
+        // Projected scrap overlay input: (scenario_id, machine_id, delta_scrapped)
+        let mut scrap_input: InputSession<_, (u64, u64, i64), isize> = InputSession::new();
+        // Staffing overlay input: (scenario_id, machine_id, delta_staff)
+        let mut staff_input: InputSession<_, (u64, u64, i64), isize> = InputSession::new();
+        // Suspect lot per quality-hold scenario
+        let mut hold_input: InputSession<_, (u64, u64), isize> = InputSession::new();
+        // One predicate row per live subscription, for pushdown
+        let mut predicate_input: InputSession<_, PredicateRow, isize> = InputSession::new();
         let mut probe = ProbeHandle::new();
+        // Stage probes in dataflow order, for sampled epoch breakdowns
+        let mut profiler = StageProfiler::new(opts.profile_every);
+        let mut reduce_probe = profiler.probe(Stage::BaseReduce);
+        let mut overlay_probe = profiler.probe(Stage::OverlayJoin);
+        let mut topk_probe = profiler.probe(Stage::TopK);
+        let mut alert_eval_probe = profiler.probe(Stage::AlertEval);
+        // Each subscription completes on its own probe
+        let mut subscriptions = Subscriptions::new();
+        let mut labor_probe = subscriptions.register("labor_starved");
+        let mut material_probe = subscriptions.register("material_starved");
+        let mut yield_probe = subscriptions.register("machine_yield");
+        let mut alert_probe = subscriptions.register("machine_backlog");
+        let mut clear_probe = subscriptions.register("machine_clear_eta");
+        let mut hold_probe = subscriptions.register("quality_hold");
+        let pipelines = opts.pipeline_steps.map(|steps| {
+            let mut pipelines = PipelineScheduler::new(steps);
+            for (name, probe) in subscriptions.probes() {
+                let weight = pipeline_weights.get(name).copied().unwrap_or(1);
+                pipelines.add(name, weight, probe.clone());
+            }
+            pipelines
+        });
 
-        let predictor = Arc::new(QueueGrowthPredictor::default());
-        let mut scenario_manager = ManufacturingScenarioManager::new(
-            beam_cfg.clone(),
-            predictor,
-        );
-        let metrics = MetricsRegistry::default();
+        let backlog_predictor = QueueGrowthPredictor {
+            relative_sd: opts.backlog_relative_sd,
+            backlog_tiers: opts.backlog_tier.clone(),
+            ..QueueGrowthPredictor::default()
+        };
+        let mut predictor = Arc::new(Guarded::new(backlog_predictor, guard_cfg.clone()));
+        // The backlog predictor needs a few operations per machine before it means anything
+        let warm_up = (opts.warm_up_observations > 0 || opts.warm_up_epochs > 0).then(|| {
+            Arc::new(WarmUpTracker::new(WarmUpConfig {
+                min_observations: opts.warm_up_observations,
+                min_epochs: opts.warm_up_epochs,
+            }))
+        });
+        let valve = Arc::new(SafetyValve::new(safety_caps.clone()));
+        let rollout = Arc::new(Rollout::new(rollout_cfg.clone()));
+        let scenario_manager = match checkpoint.clone() {
+            Some(state) => {
+                ManufacturingScenarioManager::restore(state, beam_cfg.clone(), predictor.clone())
+            }
+            None => ManufacturingScenarioManager::new(beam_cfg.clone(), predictor.clone()),
+        }
+        .with_beam(|beam| {
+            beam.with_safety_valve(valve.clone()).with_retired_history(opts.retired_history)
+        });
+        let scenario_manager = match &warm_up {
+            Some(tracker) => {
+                let fallback = opts.warm_up_fallback.then(|| {
+                    Arc::new(QueueGrowthPredictor {
+                        duration_multiplier: 0.0,
+                        ..QueueGrowthPredictor::default()
+                    }) as Arc<dyn MachineBacklogPredictor>
+                });
+                scenario_manager.with_warm_up(WarmUpGate::new(tracker.clone(), fallback))
+            }
+            None => scenario_manager,
+        };
+        let scenario_manager = match opts.max_wip_delta {
+            Some(cap) => scenario_manager.with_beam(|beam| {
+                beam.with_validator(
+                    "max_wip_delta",
+                    move |proposal: &Proposal<'_, Vec<ManufacturingScenarioDelta>>| {
+                        for delta in proposal.overlay {
+                            let units = delta.delta_wip.units();
+                            if units.abs() > cap {
+                                return Err(format!("wip delta {units} exceeds {cap}"));
+                            }
+                        }
+                        Ok(())
+                    },
+                )
+            }),
+            None => scenario_manager,
+        };
+        let scenario_manager = if opts.partition_beam {
+            scenario_manager.with_partition_key(|op: &OperationStart| op.machine_id)
+        } else {
+            scenario_manager
+        };
+        let scenario_manager = if opts.yield_loss {
+            scenario_manager.with_yield_predictor(Arc::new(YieldLossRatePredictor::default()))
+        } else {
+            scenario_manager
+        };
+        let scenario_manager = if opts.material_shortages {
+            scenario_manager.with_shortage_predictor(Arc::new(CoverageShortagePredictor::default()))
+        } else {
+            scenario_manager
+        };
+        let scenario_manager = if opts.machine_failures {
+            scenario_manager.with_failure_predictor(Arc::new(FailureHazardPredictor::default()))
+        } else {
+            scenario_manager
+        };
+        let mut scenario_manager = match opts.lag_throttle_ms {
+            Some(max_lag_ms) => scenario_manager.with_beam(|beam| {
+                beam.with_lag_throttle(LagThrottleConfig {
+                    max_lag_ms,
+                    max_depth: opts.lag_throttle_depth,
+                    ..LagThrottleConfig::default()
+                })
+            }),
+            None => scenario_manager,
+        };
+        let lease = opts.leader_lock.as_ref().map(|path| {
+            let holder = format!("mfg_demo:{}", std::process::id());
+            FileLease::new(path, holder, Duration::from_millis(opts.lease_ttl_ms))
+        });
+        let leadership = lease.as_ref().map_or_else(Leadership::standalone, FileLease::leadership);
+        // Renewed on its own thread, so a slow epoch cannot let the lease lapse
+        let _lease_keeper = lease.map(|lease| {
+            LeaseKeeper::spawn(lease, Duration::from_millis(opts.lease_ttl_ms / 3))
+        });
+        let registry = MetricsRegistry::default();
+        // Counters carry on from the checkpoint's totals rather than restarting at zero
+        if let Some(path) = opts.checkpoint.as_deref().filter(|_| checkpoint.is_some()) {
+            match read_json::<MetricsBaseline>(metrics_checkpoint(path)) {
+                Ok(baseline) => registry.restore_baseline(&baseline),
+                Err(err) => warn!(?err, "metrics baseline not restored; lifetime counters restart"),
+            }
+        }
+        // So do hourly caps, suppressed counts and escalation streaks
+        let gates = match opts.checkpoint.as_deref().filter(|_| checkpoint.is_some()) {
+            Some(path) => read_json::<GateCheckpoint>(gate_checkpoint(path)).unwrap_or_else(|err| {
+                warn!(?err, "alert gate state not restored; caps and streaks restart");
+                GateCheckpoint::default()
+            }),
+            None => GateCheckpoint::default(),
+        };
+        let metrics = registry.for_domain(DOMAIN);
+        let _reporter = (opts.report_interval_ms > 0).then(|| {
+            MetricsReporter::spawn(
+                registry.clone(),
+                Duration::from_millis(opts.report_interval_ms),
+                |sample| {
+                    let json = sample.to_json_line("mfg_sample");
+                    info!(%json, "metrics sample");
+                },
+            )
+        });
+
+        let sampling = SamplingConfig { keep_bps: opts.sample_bps };
+        let sampler = EventSampler::new(sampling);
+        let keep_bps = sampler.keep_bps();
+        let sampled = sampling.is_sampled();
+        if sampled {
+            metrics.record_sample_rate(keep_bps as u64);
+            warn!(keep_bps, "input sampling enabled; totals across machines are estimates");
+        }
 
-        let top_k = opts.top_k;
+        let topk_cfg = TopKConfig { k: opts.top_k, include_ties: opts.topk_include_ties };
         let backlog_threshold = opts.backlog_threshold;
         let prob_threshold = opts.prob_threshold;
+        let interval_z = opts.interval_z;
+        let pushdown = opts.pushdown_subscriptions;
+        let machine_risk = opts.machine_risk;
+        let yield_alert_bps = opts.yield_alert_bps;
+        let throughput_window = opts.throughput_window;
+        let lifecycle = operation_lifecycle(opts.stuck_after);
+        let clear_leadership = leadership.clone();
+        let clear_valve = valve.clone();
+        let clear_rollout = rollout.clone();
+        let hold_leadership = leadership.clone();
+        let hold_valve = valve.clone();
+        let hold_rollout = rollout.clone();
         let metrics_for_dataflow = metrics.clone();
+        let alert_valve = valve.clone();
+        let alert_rollout = rollout.clone();
+        let alert_policy = alert_policy.clone();
+        // Persistent conditions are tracked per (scenario, key) and alert as epochs close
+        let persistence = alert_policy.persistence;
+        let condition = Rc::new(RefCell::new(conditions.clone()));
+        let condition_rows = Rc::clone(&condition);
+        // Scenario top-K rank moves are reported as epochs close
+        let ranks = Rc::new(RefCell::new(RankTracker::new()));
+        let rank_rows = Rc::clone(&ranks);
+        // Scenario labels and tags, kept as scenarios come and go, for alert context
+        let labels = Rc::new(RefCell::new(BTreeMap::new()));
+        let alert_labels = Rc::clone(&labels);
+        let clear_labels = Rc::clone(&labels);
+        // Latest projections of watched machines are published as epochs close
+        let projections = ProjectionTracker::new(opts.projection_key.iter().copied())
+            .with_interval(opts.interval_z);
+        let projections = Rc::new(RefCell::new(projections));
+        let projecting = projections.borrow().is_enabled();
+        let projection_rows = Rc::clone(&projections);
+        let mut persistent_gate = AlertGate::restore(alert_policy.clone(), gates.persistent);
+        // Backlog and queue-clearing alerts share one gate and so one delivery budget
+        let alert_gate = Rc::new(RefCell::new(AlertGate::restore(alert_policy, gates.alerts)));
+        let backlog_gate = Rc::clone(&alert_gate);
+        let clear_gate = Rc::clone(&alert_gate);
+        let persistent_leadership = leadership.clone();
         worker.dataflow::<u64, _, _>(move |scope| {
             let events = input.to_collection(scope);
 
@@ -93,7 +701,8 @@ fn main() -> Result<()> {
             let machine_deltas = events.flat_map(|env| match env.payload {
                 ManufacturingEvent::OperationStart(ref op) => vec![(op.machine_id, 1i64)],
                 ManufacturingEvent::OperationComplete(ref op) => vec![(op.machine_id, -1i64)],
-                ManufacturingEvent::MachineStateChange(_) => Vec::new(),
+                ManufacturingEvent::OperationScrapped(ref op) => vec![(op.machine_id, -1i64)],
+                _ => Vec::new(),
             });
 
             let wip = machine_deltas
@@ -105,36 +714,182 @@ fn main() -> Result<()> {
                     }
                     output.push((sum, 1));
                 });
-
-            let topk = wip
-                .map(|(machine, sum)| ((), (sum, machine)))
-                .reduce(move |_unit, inputs, output| {
-                    let mut vals: Vec<((i64, u64), isize)> =
-                        inputs.iter().map(|(val, cnt)| (*val, *cnt)).collect();
-                    vals.sort_by(|a, b| b.0 .0.cmp(&a.0 .0));
-                    for i in 0..top_k.min(vals.len()) {
-                        output.push((vals[i].0, 1));
+            // Sampling keeps whole machines, so per-machine WIP is exact; the total is estimated
+            wip.probe_with(&mut reduce_probe);
+            if sampled {
+                sampled_total(&wip, keep_bps).inspect(move |((total, variance), _epoch, diff)| {
+                    if *diff > 0 {
+                        let interval = Interval::around(*total, *variance, interval_z);
+                        info!(
+                            estimate = total,
+                            low = interval.low,
+                            high = interval.high,
+                            keep_bps,
+                            "sampled total WIP across machines"
+                        );
                     }
                 });
+            }
+            if projecting {
+                let base_rows = Rc::clone(&projection_rows);
+                wip.inspect(move |((machine, sum), _epoch, diff)| {
+                    base_rows.borrow_mut().update_base(*machine, *sum, *diff);
+                });
+            }
+
+            let topk = top_k(&wip.map(|(machine, sum)| ((), (sum, machine))), topk_cfg);
 
             topk.inspect(|x| info!(?x, "base top machines"))
                 .probe_with(&mut probe);
 
+            // Labor: operators currently on shift and the machine each one staffs
+            let on_shift = events
+                .flat_map(|env| match env.payload {
+                    ManufacturingEvent::OperatorClockedIn(ref e) => {
+                        vec![(e.operator_id, (e.ts_ms.as_millis(), true))]
+                    }
+                    ManufacturingEvent::OperatorClockedOut(ref e) => {
+                        vec![(e.operator_id, (e.ts_ms.as_millis(), false))]
+                    }
+                    _ => Vec::new(),
+                })
+                .reduce(|_operator, inputs, output| {
+                    // Values arrive sorted, so the last one is the latest clock event
+                    if let Some((latest, _)) = inputs.last() {
+                        if latest.1 {
+                            output.push(((), 1));
+                        }
+                    }
+                })
+                .map(|(operator, ())| operator);
+
+            let assignments = events
+                .flat_map(|env| match env.payload {
+                    ManufacturingEvent::OperatorAssigned(ref e) => {
+                        vec![(e.operator_id, (e.ts_ms.as_millis(), e.machine_id))]
+                    }
+                    _ => Vec::new(),
+                })
+                .reduce(|_operator, inputs, output| {
+                    if let Some((latest, _)) = inputs.last() {
+                        output.push((latest.1, 1));
+                    }
+                });
+
+            let staffed = assignments
+                .semijoin(&on_shift)
+                .map(|(_operator, machine)| (machine, ()))
+                .reduce(|_machine, inputs, output| {
+                    let staff: isize = inputs.iter().map(|(_, cnt)| *cnt).sum();
+                    output.push((staff as i64, 1));
+                });
+
+            // Labor-constrained backlog: (wip, staffed operators) per machine
+            let unstaffed = wip
+                .antijoin(&staffed.map(|(machine, _)| machine))
+                .map(|(machine, sum)| (machine, (sum, 0i64)));
+            let labor_backlog = wip.join(&staffed).concat(&unstaffed);
+
+            labor_backlog
+                .filter(|(_machine, (sum, staff))| *sum > 0 && *staff == 0)
+                .inspect(|x| info!(?x, "machine starved of operators"))
+                .probe_with(&mut labor_probe);
+
+            // Materials: on-hand stock, and backlogged machines whose material ran out
+            let movements = events.flat_map(|env| match env.payload {
+                ManufacturingEvent::MaterialReceived(ref e) => vec![(e.material_id, e.units)],
+                ManufacturingEvent::MaterialConsumed(ref e) => vec![(e.material_id, -e.units)],
+                _ => Vec::new(),
+            });
+            let consumers = events.flat_map(|env| match env.payload {
+                ManufacturingEvent::MaterialConsumed(ref e) => vec![(e.material_id, e.machine_id)],
+                _ => Vec::new(),
+            });
+            let starved = starved_machines(&on_hand(&movements), &consumers);
+            wip.join(&starved)
+                .filter(|(_machine, (sum, _material))| *sum > 0)
+                .inspect(|x| info!(?x, "machine starved of material"))
+                .probe_with(&mut material_probe);
+
+            // Per-machine finished operations, as (good, scrapped)
+            let finished_counts = events
+                .flat_map(|env| match env.payload {
+                    ManufacturingEvent::OperationComplete(ref op) => vec![(op.machine_id, true)],
+                    ManufacturingEvent::OperationScrapped(ref op) => vec![(op.machine_id, false)],
+                    _ => Vec::new(),
+                })
+                .reduce(|_machine, inputs, output| {
+                    let mut good: i64 = 0;
+                    let mut scrapped: i64 = 0;
+                    for (passed, cnt) in inputs.iter() {
+                        if **passed {
+                            good += *cnt as i64;
+                        } else {
+                            scrapped += *cnt as i64;
+                        }
+                    }
+                    output.push(((good, scrapped), 1));
+                });
+
+            finished_counts
+                .flat_map(|(machine, (good, scrapped))| {
+                    yield_bps(good, scrapped).map(|bps| (machine, bps))
+                })
+                .filter(move |(_machine, yield_bps)| *yield_bps < yield_alert_bps)
+                .inspect(|x| info!(domain = DOMAIN, ?x, "ALERT: machine yield below threshold"))
+                .probe_with(&mut yield_probe);
+
+            // Throughput: operations finished per machine over the trailing window
+            let finished = events.flat_map(|env| match env.payload {
+                ManufacturingEvent::OperationComplete(ref op) => vec![op.machine_id],
+                ManufacturingEvent::OperationScrapped(ref op) => vec![op.machine_id],
+                _ => Vec::new(),
+            });
+            let throughput = windowed_throughput(&finished, throughput_window);
+            let base_clear = projected_clear(&wip, &throughput, throughput_window);
+
+            // Operation lifecycle: how many finish, and which sit unfinished
+            let last_stage = lifecycle.last_stage();
+            let stages = lifecycle.stages.len();
+            let stuck_after = lifecycle.stuck_after;
+            let arrivals = events.flat_map(move |env| {
+                let stage = lifecycle.stage_of(&env.meta.kind)?;
+                let operation = match env.payload {
+                    ManufacturingEvent::OperationStart(ref op) => (op.job_id, op.operation_id),
+                    ManufacturingEvent::OperationComplete(ref op) => (op.job_id, op.operation_id),
+                    ManufacturingEvent::OperationScrapped(ref op) => (op.job_id, op.operation_id),
+                    _ => return None,
+                };
+                Some((operation, stage))
+            });
+            let operation_stages = current_stage(&arrivals);
+            let lifecycle_counts = stage_counts(&operation_stages);
+            lifecycle_counts.inspect(|x| info!(?x, "operation lifecycle stage count"));
+            conversion_bps(&lifecycle_counts.map(|(stage, count)| (((), stage), count)), stages)
+                .inspect(|x| info!(?x, "operation lifecycle conversion bps"));
+            stuck(&operation_stages, last_stage, stuck_after)
+                .inspect(|x| info!(?x, "operation stuck unfinished"));
+
             // Scenario overlays
-            let pred = pred_input
-                .to_collection(scope)
-                .map(|(sid, machine, delta)| (sid, machine, delta));
+            let pred = pred_input.to_collection(scope);
 
-            let pred_totals = pred
-                .map(|(sid, machine, delta)| ((sid, machine), delta))
+            // Independent overlays' variances add, like their deltas
+            let pred_sums = pred
+                .map(|(sid, machine, delta, variance)| ((sid, machine), (delta, variance)))
                 .reduce(|_key, inputs, output| {
                     let mut sum: i64 = 0;
-                    for (delta, cnt) in inputs.iter() {
+                    let mut variance: i64 = 0;
+                    for ((delta, var), cnt) in inputs.iter() {
                         sum += *delta * (*cnt as i64);
+                        variance = variance.saturating_add(*var * (*cnt as i64));
                     }
-                    output.push((sum, 1));
-                })
-                .map(|((sid, machine), delta)| (sid, machine, delta));
+                    output.push(((sum, variance), 1));
+                });
+            let pred_totals =
+                pred_sums.map(|((sid, machine), (delta, _variance))| (sid, machine, delta));
+            let pred_variance = pred_sums
+                .map(|(sid_machine, (_delta, variance))| (sid_machine, variance))
+                .filter(|(_sid_machine, variance)| *variance != 0);
 
             let scen_weights = scen_weight_input
                 .to_collection(scope)
@@ -144,6 +899,78 @@ fn main() -> Result<()> {
                     output.push((maxp, 1));
                 });
 
+            // Retirement gates every per-scenario collection, whichever input retracts first
+            let retired = retire_input.to_collection(scope);
+            let live = live_scenarios(&scen_weights.map(|(sid, _)| sid), &retired);
+            let scen_weights = gated(&scen_weights, &live);
+
+            let groups = gated(&group_input.to_collection(scope), &live);
+            // Alternatives of one parent never weigh more than certainty together, in any view
+            let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+            let scen_weights = exclusive_weights(&weights_bps, &groups)
+                .map(|(sid, bps)| (sid, bps as f64 / BPS_SCALE as f64));
+
+            gated(&down_input.to_collection(scope), &live)
+                .inspect(|x| info!(?x, "scenario predicts machine down"));
+
+            // Projected yield: each scenario's scrap on top of the machine's record
+            let scrap = scrap_input.to_collection(scope).map(|(sid, machine, units)| {
+                ((sid, machine), units)
+            });
+            let scrap = scrap.reduce(|_key, inputs, output| {
+                let units: i64 = inputs.iter().map(|(units, cnt)| **units * (*cnt as i64)).sum();
+                output.push((units, 1));
+            });
+            gated(&scrap.map(|((sid, machine), units)| (sid, (machine, units))), &live)
+                .map(|(sid, (machine, units))| (machine, (sid, units)))
+                .join(&finished_counts)
+                .flat_map(|(machine, ((sid, units), (good, scrapped)))| {
+                    yield_bps(good, scrapped + units).map(|bps| (sid, machine, bps))
+                })
+                .inspect(|x| info!(?x, "scenario projected machine yield bps"));
+
+            if machine_risk {
+                // Alternatives of one parent add instead of compounding
+                let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+                let members = pred_totals.map(|(sid, machine, _delta)| (machine, sid));
+                combined_probability(&members, &weights_bps, &groups)
+                    .inspect(|x| info!(?x, "machine scenario risk bps"));
+                let deltas = pred_totals.map(|(sid, machine, delta)| ((sid, machine), delta));
+                let expected = expected_delta(&deltas, &weights_bps, &groups);
+                let variance = expected_variance(&pred_variance, &weights_bps, &groups);
+                with_variance(&expected, &variance).inspect(
+                    move |((machine, (delta, variance)), epoch, diff)| {
+                        let interval = Interval::around(*delta, *variance, interval_z);
+                        info!(
+                            machine,
+                            delta,
+                            low = interval.low,
+                            high = interval.high,
+                            epoch,
+                            diff,
+                            "machine expected wip delta"
+                        );
+                    },
+                );
+            }
+            if projecting {
+                let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+                let deltas = pred_totals.map(|(sid, machine, delta)| ((sid, machine), delta));
+                let expected_rows = Rc::clone(&projection_rows);
+                expected_delta(&deltas, &weights_bps, &groups).inspect(
+                    move |((machine, delta), _epoch, diff)| {
+                        expected_rows.borrow_mut().update_expected_delta(*machine, *delta, *diff);
+                    },
+                );
+                let variance_rows = Rc::clone(&projection_rows);
+                expected_variance(&pred_variance, &weights_bps, &groups).inspect(
+                    move |((machine, variance), _epoch, diff)| {
+                        let mut rows = variance_rows.borrow_mut();
+                        rows.update_expected_variance(*machine, *variance, *diff);
+                    },
+                );
+            }
+
             let scenarios_by_unit = scen_weights.map(|(sid, _)| ((), sid));
 
             let base_topk_by_unit = topk.map(|(_unit, (sum, machine))| ((), (machine, sum)));
@@ -156,48 +983,789 @@ fn main() -> Result<()> {
             let scenario_changes = pred_totals_by_machine
                 .join(&base_wip_by_machine)
                 .map(|(machine, ((sid, delta), base_sum))| (sid, (base_sum + delta, machine)));
+            let scenario_changes = gated(&scenario_changes, &live);
 
-            let candidates = base_topk_broadcast.concat(&scenario_changes);
+            // Lifecycle under each scenario: backlog it adds queues as operations started but
+            // not finished, keyed by job ids counted down from u64::MAX so they never meet
+            // real ones. Predicted shrinks are left out, since open operations carry no machine.
+            let backlog = pred_totals.map(|(sid, machine, delta)| (sid, (machine, delta)));
+            let queued = gated(&backlog, &live)
+                .flat_map(|(sid, (machine, delta))| {
+                    (0..delta.max(0) as u64).map(move |n| ((sid, (u64::MAX - machine, n)), 0))
+                });
+            let scenario_lifecycle = scenario_stage_counts(&operation_stages, &queued);
+            scenario_lifecycle.inspect(|x| info!(?x, "scenario operation lifecycle stage count"));
+            conversion_bps(&scenario_lifecycle, stages)
+                .inspect(|x| info!(?x, "scenario operation lifecycle conversion bps"));
+            scenario_changes.probe_with(&mut overlay_probe);
+            if projecting {
+                let scenario_rows = Rc::clone(&projection_rows);
+                scenario_changes.inspect(move |((sid, (sum, machine)), _epoch, diff)| {
+                    scenario_rows.borrow_mut().update_scenario(*sid, *machine, *sum, *diff);
+                });
+            }
 
-            let scenario_topk = candidates
-                .map(|(sid, pair)| (sid, pair))
-                .reduce(move |_sid, inputs, output| {
-                    let mut vals: Vec<((i64, u64), isize)> =
-                        inputs.iter().map(|(val, cnt)| (*val, *cnt)).collect();
-                    vals.sort_by(|a, b| b.0 .0.cmp(&a.0 .0));
-                    for i in 0..top_k.min(vals.len()) {
-                        output.push((vals[i].0, 1));
-                    }
+            // Labor under each scenario: its WIP against base staff plus its staffing deltas
+            let staff_deltas = staff_input
+                .to_collection(scope)
+                .map(|(sid, machine, delta)| ((sid, machine), delta))
+                .reduce(|_key, inputs, output| {
+                    let delta: i64 = inputs.iter().map(|(d, cnt)| **d * (*cnt as i64)).sum();
+                    output.push((delta, 1));
                 });
+            let scenario_wip = scenario_changes.map(|(sid, (sum, machine))| ((sid, machine), sum));
+            let unchanged_staff = scenario_wip
+                .antijoin(&staff_deltas.map(|(key, _)| key))
+                .map(|(key, _sum)| (key, 0i64));
+            let base_staff = labor_backlog.map(|(machine, (_sum, staff))| (machine, staff));
+            scenario_wip
+                .join(&staff_deltas.concat(&unchanged_staff))
+                .map(|((sid, machine), (sum, delta))| (machine, (sid, sum, delta)))
+                .join(&base_staff)
+                .map(|(machine, ((sid, sum, delta), staff))| (sid, (machine, sum, staff + delta)))
+                .filter(|(_sid, (_machine, sum, staff))| *sum > 0 && *staff <= 0)
+                .inspect(|x| info!(?x, "scenario machine starved of operators"))
+                .probe_with(&mut labor_probe);
+
+            let candidates = base_topk_broadcast.concat(&scenario_changes);
+            let candidates = if pushdown {
+                let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+                pushdown_candidates(&candidates, &weights_bps, &predicate_input.to_collection(scope))
+            } else {
+                candidates
+            };
+
+            let scenario_ranks = ranked_top_k(&candidates, topk_cfg);
+            scenario_ranks.probe_with(&mut topk_probe);
+            scenario_ranks.inspect(move |((sid, row), _epoch, diff)| {
+                rank_rows.borrow_mut().update(*sid, *row, *diff);
+            });
+            let scenario_topk =
+                scenario_ranks.map(|(sid, (_rank, machine, sum))| (sid, (sum, machine)));
 
             scenario_topk.inspect(|x| info!(?x, "scenario top machines"));
 
-            let alerts = scenario_topk
-                .map(|(sid, (sum, machine))| (sid, (machine, sum)))
+            // The backlog's variance rides along, for the interval in the alert payload
+            let backlogged = scenario_topk
+                .map(|(sid, (sum, machine))| ((sid, machine), sum))
+                .filter(move |(_sid_machine, sum)| *sum >= backlog_threshold);
+            let alerts = with_variance(&backlogged, &pred_variance)
+                .map(|((sid, machine), (sum, variance))| (sid, (machine, sum, variance)))
                 .join(&scen_weights)
-                .filter(move |(_sid, ((machine, sum), prob))| *sum >= backlog_threshold
-                    && *prob >= prob_threshold)
-                .map(|(sid, ((machine, sum), prob))| (sid, machine, sum, prob));
+                .filter(move |(_sid, (_row, prob))| *prob >= prob_threshold)
+                .map(|(sid, ((machine, sum, variance), prob))| (sid, machine, sum, prob, variance));
+            alerts.probe_with(&mut alert_eval_probe);
 
             let metrics_alerts = metrics_for_dataflow.clone();
+            let clear_metrics = metrics_for_dataflow.clone();
             alerts
                 .inspect(move |alert| {
-                    metrics_alerts.inc_scenario_alerts(1);
-                    info!(?alert, "ALERT: machine backlog risk");
+                    if persistence != Persistence::Immediate {
+                        let ((sid, machine, sum, prob, variance), _epoch, diff) = *alert;
+                        let value = (sum, prob, variance);
+                        condition_rows.borrow_mut().update((sid, machine), value, diff);
+                        return;
+                    }
+                    // A retraction is the condition clearing, not firing again
+                    if alert.2 <= 0 {
+                        return;
+                    }
+                    // Standby keeps its views warm but leaves alerting to the leader
+                    if !leadership.is_leader() {
+                        return;
+                    }
+                    let epoch = EpochTime::new(alert.1);
+                    match alert_rollout.route("machine_backlog", epoch) {
+                        Route::Live => {}
+                        Route::LogOnly => {
+                            info!(?alert, "shadow alert: machine_backlog is log-only");
+                            return;
+                        }
+                        Route::Shed => return,
+                    }
+                    if !alert_valve.admit_alert(epoch) {
+                        return;
+                    }
+                    let (sid, machine, ..) = alert.0;
+                    let key = format!("{sid}:{machine}");
+                    let mut gate = backlog_gate.borrow_mut();
+                    let decision = gate.offer(epoch, epoch.start(1_000), &key);
+                    if let Some(overflow) = gate.take_overflow() {
+                        info!(?overflow, "alert overflow summary");
+                    }
+                    if decision.is_delivered() {
+                        metrics_alerts.inc_scenario_alerts(1);
+                        let (_, _, sum, _prob, variance) = alert.0;
+                        let wip = Quantity::<Units>::from_units(sum);
+                        let interval = Interval::around(sum, variance, interval_z);
+                        info!(
+                            domain = DOMAIN,
+                            ?alert,
+                            label = alert_labels.borrow().get(&sid).map(String::as_str),
+                            %wip,
+                            wip_low = interval.low,
+                            wip_high = interval.high,
+                            ?decision,
+                            "ALERT: machine backlog risk"
+                        );
+                    } else {
+                        metrics_alerts.inc_alerts_suppressed(1);
+                    }
                 })
-                .probe_with(&mut probe);
+                .probe_with(&mut alert_probe);
+
+            // Scenarios that push a machine's queue-clearing epoch later
+            let scenario_wip = scenario_changes.map(|(sid, (sum, machine))| ((sid, machine), sum));
+            scenario_projected_clear(&scenario_wip, &throughput, throughput_window)
+                .map(|((sid, machine), (_wip, epochs))| (machine, (sid, epochs)))
+                .join(&base_clear.map(|(machine, (_wip, epochs))| (machine, epochs)))
+                .filter(|(_machine, ((_sid, scenario_epochs), base_epochs))| {
+                    scenario_epochs > base_epochs
+                })
+                .map(|(machine, ((sid, scenario_epochs), base_epochs))| {
+                    (sid, (machine, scenario_epochs, base_epochs))
+                })
+                .join(&scen_weights)
+                .filter(move |(_sid, (_eta, prob))| *prob >= prob_threshold)
+                .inspect(move |((sid, ((machine, scenario_epochs, base_epochs), prob)), epoch, diff)| {
+                    if *diff <= 0 || !clear_leadership.is_leader() {
+                        return;
+                    }
+                    let epoch = EpochTime::new(*epoch);
+                    match clear_rollout.route("machine_clear_eta", epoch) {
+                        Route::Live => {}
+                        Route::LogOnly => {
+                            info!(
+                                scenario = sid,
+                                machine,
+                                "shadow alert: machine_clear_eta is log-only"
+                            );
+                            return;
+                        }
+                        Route::Shed => return,
+                    }
+                    if !clear_valve.admit_alert(epoch) {
+                        return;
+                    }
+                    let key = format!("clear:{sid}:{machine}");
+                    let mut gate = clear_gate.borrow_mut();
+                    let decision = gate.offer(epoch, epoch.start(1_000), &key);
+                    if let Some(overflow) = gate.take_overflow() {
+                        info!(?overflow, "alert overflow summary");
+                    }
+                    if !decision.is_delivered() {
+                        clear_metrics.inc_alerts_suppressed(1);
+                        return;
+                    }
+                    clear_metrics.inc_scenario_alerts(1);
+                    info!(
+                        domain = DOMAIN,
+                        scenario = sid,
+                        label = clear_labels.borrow().get(sid).map(String::as_str),
+                        machine,
+                        prob,
+                        ?decision,
+                        "ALERT: machine {} clears its queue at epoch {} instead of {} under scenario {}",
+                        machine,
+                        epoch.saturating_add(*scenario_epochs),
+                        epoch.saturating_add(*base_epochs),
+                        sid
+                    );
+                })
+                .probe_with(&mut clear_probe);
+
+            // Quality holds stop every lot derived from a suspect lot and the jobs drawing them
+            let lot_edges = events.flat_map(|env| match env.payload {
+                ManufacturingEvent::LotSplit(ref e) => {
+                    e.into.iter().map(|part| (e.lot_id, *part)).collect()
+                }
+                ManufacturingEvent::LotMerged(ref e) => {
+                    e.lots.iter().map(|lot| (*lot, e.into)).collect()
+                }
+                _ => Vec::new(),
+            });
+            let lot_consumers = events.flat_map(|env| match env.payload {
+                ManufacturingEvent::LotConsumed(ref e) => vec![(e.lot_id, e.job_id)],
+                _ => Vec::new(),
+            });
+            let holds = gated(&hold_input.to_collection(scope), &live)
+                .map(|(sid, lot)| ((sid, lot), lot));
+            let held_lots = descendant_lots(&lot_edges, &holds);
+            let held_jobs = jobs_on_hold(&held_lots, &lot_consumers);
+            hold_impact(&held_lots, &held_jobs)
+                .filter(|(_hold, (_lots, jobs))| *jobs > 0)
+                .map(|((sid, lot), impact)| (sid, (lot, impact)))
+                .join(&scen_weights)
+                .filter(move |(_sid, (_hold, prob))| *prob >= prob_threshold)
+                .inspect(move |((sid, ((lot, (lots, jobs)), prob)), epoch, diff)| {
+                    if *diff <= 0 || !hold_leadership.is_leader() {
+                        return;
+                    }
+                    let epoch = EpochTime::new(*epoch);
+                    match hold_rollout.route("quality_hold", epoch) {
+                        Route::Live => {}
+                        Route::LogOnly => {
+                            info!(scenario = sid, lot, "shadow alert: quality_hold is log-only");
+                            return;
+                        }
+                        Route::Shed => return,
+                    }
+                    if !hold_valve.admit_alert(epoch) {
+                        return;
+                    }
+                    info!(
+                        domain = DOMAIN,
+                        scenario = sid,
+                        lot,
+                        prob,
+                        "ALERT: holding lot {} stops {} jobs across {} lots under scenario {}",
+                        lot,
+                        jobs,
+                        lots,
+                        sid
+                    );
+                })
+                .probe_with(&mut hold_probe);
         });
 
+        // User code run at each epoch boundary, once the epoch's metrics are final
+        let mut epoch_hooks = EpochHooks::new();
+        for (index, command) in opts.epoch_hook_cmd.iter().enumerate() {
+            let hook = hooks::command_hook(command.clone());
+            epoch_hooks.on_epoch_complete(format!("cmd{index}"), hook);
+        }
+        let mut dedup = Deduplicator::new(DedupConfig {
+            ttl_epochs: opts.dedup_ttl_epochs,
+            ..DedupConfig::default()
+        });
+        let mut causal = CausalOrderer::new(CausalConfig {
+            rules: if opts.causal_order { manufacturing_order() } else { Vec::new() },
+            max_wait_epochs: opts.causal_wait_epochs,
+            ..CausalConfig::default()
+        });
+        // Every simulated machine is registered; events naming others are dead-lettered
+        let validator = manufacturing_rules(
+            ValidationConfig {
+                max_skew_ms: opts.max_ts_skew_ms,
+                disabled: opts.disable_rule.clone(),
+                ..ValidationConfig::default()
+            },
+            (0..opts.machines).collect(),
+        );
+        // Recent events per machine, packaged with alerts as replay bundles
+        let replay_epochs = if opts.replay_bundles.is_some() { opts.replay_epochs } else { 0 };
+        let mut recent_events: RecentEvents<u64, ManufacturingEvent> =
+            RecentEvents::new(replay_epochs);
+        let mut dead_letters = match opts.dead_letters.as_ref().map(JsonlSink::open) {
+            Some(Err(err)) => {
+                warn!(?err, "dead-letter file disabled");
+                None
+            }
+            opened => opened.and_then(Result::ok),
+        };
+
         // Synthetic generator
-        let mut epoch: u64 = 0;
+        let mut epoch = EpochTime::ZERO;
+        // Log-only windows run from the start of the run, not from a view's first alert
+        rollout.activate(epoch);
+        // Estimated source lag: wall time spent beyond --epoch-budget-ms, carried over
+        let mut source_lag_ms = 0u64;
         let machines = opts.machines;
         let mut job_counter: u64 = 0;
         let mut active_jobs: Vec<ActiveJob> = Vec::new();
+        let mut timelines = ScenarioTimelines::new(opts.timeline_epochs);
+        let mut heatmap = ImpactHeatmap::new(opts.heatmap_key.iter().copied());
+        let mut projection_sink = match opts
+            .redis_url
+            .as_deref()
+            .map(|url| RedisProjectionSink::connect(url, opts.redis_prefix.as_str()))
+        {
+            Some(Err(err)) => {
+                warn!(?err, "projection publishing disabled");
+                None
+            }
+            connected => connected.and_then(Result::ok),
+        };
+        // Backlog alerts only fire on heavy enough scenarios at or above the threshold
+        let control: ControlPlane<ManufacturingCommand> = ControlPlane::new();
+        if let Some(addr) = &opts.control_addr {
+            match control.serve(addr.as_str()) {
+                Ok(local) => info!(%local, "control plane listening"),
+                Err(err) => warn!(?err, "control plane listener disabled"),
+            }
+        }
+        let bulk_control: ControlPlane<BulkOp> = ControlPlane::new();
+        control.submit(ManufacturingCommand::Subscribe {
+            predicate: SubscriptionPredicate {
+                min_prob_bps: prob_bps(opts.prob_threshold),
+                min_value: Some(opts.backlog_threshold),
+            },
+        });
+
+        let mut absent_operator: Option<u64> = None;
+        // Operators who clocked in this epoch under --operator-absences, each
+        // staffing the machine of the same id
+        let mut shift_starts: Vec<u64> = Vec::new();
+        // Host-side material positions, handed to the shortage predictor
+        let mut material_on_hand: BTreeMap<u64, i64> = BTreeMap::new();
+        let mut material_consumers: BTreeMap<u64, BTreeSet<u64>> = BTreeMap::new();
+        let mut consumed_this_epoch: BTreeMap<u64, i64> = BTreeMap::new();
+        // Each delivery covers slightly less than the ops it has to feed
+        let resupply_units = (opts.ops_per_batch * RESUPPLY_EVERY / MATERIALS) as i64 - 1;
+        // Host-side failure history and load, handed to the failure predictor
+        let mut last_failure: BTreeMap<u64, EpochTime> = BTreeMap::new();
+        let mut failure_gaps: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+        let mut started_this_epoch: BTreeMap<u64, i64> = BTreeMap::new();
+        // Host-side WIP per machine, the base value the backlog guard scales uplift by
+        let mut base_wip: BTreeMap<u64, i64> = BTreeMap::new();
+        let mut down_machines: Vec<u64> = Vec::new();
+        // Host-side lots new jobs draw from, oldest first
+        let mut next_lot: u64 = 1;
+        let mut open_lots: VecDeque<u64> = VecDeque::new();
+        let fair_share = (opts.ops_per_batch / machines.max(1)).max(1) as f64;
 
+        // What-ifs the checkpoint already holds, injected at or before its last epoch
+        let injected_through = scenario_manager.advanced();
         for batch in 0..opts.batches {
             let epoch_timer = EpochTimer::start();
             let completed_epoch = epoch;
+            // Control-plane changes apply from the start of an epoch, never mid-epoch
+            let acks = control.drain(epoch, |command| {
+                match command {
+                    ManufacturingCommand::Subscribe { predicate } => {
+                        predicate_input.insert(predicate.row())
+                    }
+                    ManufacturingCommand::Unsubscribe { predicate } => {
+                        predicate_input.remove(predicate.row())
+                    }
+                    ManufacturingCommand::Config { config } => scenario_manager.set_config(config),
+                    ManufacturingCommand::Predictor {
+                        base_units,
+                        duration_multiplier,
+                        relative_sd,
+                        backlog_tiers,
+                    } => {
+                        let defaults = QueueGrowthPredictor::default();
+                        let swapped = QueueGrowthPredictor {
+                            base_units: base_units.unwrap_or(defaults.base_units),
+                            duration_multiplier: duration_multiplier
+                                .unwrap_or(defaults.duration_multiplier),
+                            relative_sd,
+                            backlog_tiers,
+                            ..defaults
+                        };
+                        predictor = Arc::new(Guarded::new(swapped, guard_cfg.clone()));
+                        scenario_manager.set_predictor(predictor.clone());
+                    }
+                }
+                Ok(())
+            });
+            for ack in acks {
+                info!(?ack, "control change applied");
+            }
+            if batch == 0 {
+                let outcome: ManufacturingExpansionOutcome = scenario_manager.resumed().into();
+                if !outcome.created.is_empty() {
+                    info!(scenarios = outcome.created.len(), "resumed scenario checkpoint");
+                    for meta in &outcome.created {
+                        if let Some(lot) = meta
+                            .external
+                            .as_deref()
+                            .and_then(|tag| tag.strip_prefix("quality_hold:lot:"))
+                            .and_then(|lot| lot.parse::<u64>().ok())
+                        {
+                            hold_input.insert((meta.id, lot));
+                        }
+                        tag_projection(&projections, meta);
+                    }
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut group_input,
+                        &mut down_input,
+                        &mut scrap_input,
+                        &mut staff_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &labels,
+                    );
+                }
+                for scenario in &external_scenarios {
+                    let outcome = scenario_manager.seed_external(epoch, scenario);
+                    for meta in &outcome.created {
+                        info!(?meta, "seeded external scenario");
+                        tag_projection(&projections, meta);
+                    }
+                    for rejection in &outcome.rejected {
+                        warn!(?rejection, "external scenario rejected");
+                    }
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut group_input,
+                        &mut down_input,
+                        &mut scrap_input,
+                        &mut staff_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &labels,
+                    );
+                }
+                for prior in &priors {
+                    let outcome = scenario_manager.seed_prior(epoch, prior);
+                    for meta in &outcome.created {
+                        info!(?meta, "seeded prior scenario");
+                        tag_projection(&projections, meta);
+                    }
+                    for rejection in &outcome.rejected {
+                        warn!(?rejection, "prior scenario rejected");
+                    }
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut group_input,
+                        &mut down_input,
+                        &mut scrap_input,
+                        &mut staff_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &labels,
+                    );
+                }
+                for (lot, scenario) in &hold_scenarios {
+                    let outcome = scenario_manager.seed_external(epoch, scenario);
+                    for meta in &outcome.created {
+                        info!(?meta, lot, "seeded quality-hold scenario");
+                        hold_input.insert((meta.id, *lot));
+                        tag_projection(&projections, meta);
+                    }
+                    for rejection in &outcome.rejected {
+                        warn!(?rejection, "quality-hold scenario rejected");
+                    }
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut group_input,
+                        &mut down_input,
+                        &mut scrap_input,
+                        &mut staff_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &labels,
+                    );
+                }
+                // Resume and seeding feed weights separately; settle them against the tree
+                collect_weights(epoch, &mut scen_weight_input, &scenario_manager);
+            }
+            let due = |at_epoch: EpochTime| {
+                at_epoch == epoch && injected_through.map_or(true, |through| at_epoch > through)
+            };
+            for what_if in what_ifs.iter().filter(|what_if| due(what_if.at_epoch)) {
+                let outcome = scenario_manager.inject_scenario(
+                    epoch,
+                    &what_if.rows,
+                    what_if.probability,
+                    &what_if.label,
+                );
+                for meta in &outcome.created {
+                    info!(?meta, "injected what-if scenario");
+                }
+                for rejection in &outcome.rejected {
+                    warn!(?rejection, "what-if scenario rejected");
+                }
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
+            }
+            if batch == opts.bulk_op_at {
+                for op in &opts.bulk_op {
+                    bulk_control.submit(op.clone());
+                }
+            }
+            // Operator bulk operations apply at the boundary too, after any resume
+            let acks = bulk_control.drain(epoch, |op| {
+                let outcome: ManufacturingExpansionOutcome =
+                    scenario_manager.apply_bulk(epoch, op)?.into();
+                if let Some(audit) = scenario_manager.bulk_audit().last() {
+                    info!(?audit, "bulk operation applied");
+                }
+                count_retired(&metrics, &outcome.retired);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
+                Ok(())
+            });
+            for ack in acks.into_iter().filter(|ack| ack.error.is_some()) {
+                warn!(?ack, "bulk operation not applied");
+            }
+            let outcome: ManufacturingExpansionOutcome =
+                scenario_manager.decay_priors(epoch).into();
+            for (meta, _) in &outcome.retired {
+                info!(scenario = meta.id, prior = ?meta.external, "prior scenario decayed out");
+            }
+            count_retired(&metrics, &outcome.retired);
+            apply_outcome(
+                epoch,
+                &outcome,
+                &mut pred_input,
+                &mut scen_weight_input,
+                &mut retire_input,
+                &mut group_input,
+                &mut down_input,
+                &mut scrap_input,
+                &mut staff_input,
+                &mut timelines,
+                &mut heatmap,
+                &labels,
+            );
+            let outcome: ManufacturingExpansionOutcome =
+                scenario_manager.advance_epoch(epoch).into();
+            count_retired(&metrics, &outcome.retired);
+            apply_outcome(
+                epoch,
+                &outcome,
+                &mut pred_input,
+                &mut scen_weight_input,
+                &mut retire_input,
+                &mut group_input,
+                &mut down_input,
+                &mut scrap_input,
+                &mut staff_input,
+                &mut timelines,
+                &mut heatmap,
+                &labels,
+            );
+            let was_throttled = scenario_manager.is_throttled();
+            for outcome in scenario_manager.observe_lag(epoch, source_lag_ms) {
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
+            }
+            if scenario_manager.is_throttled() != was_throttled {
+                info!(
+                    %epoch,
+                    source_lag_ms,
+                    throttled = !was_throttled,
+                    deferred = scenario_manager.deferred_len(),
+                    "speculation throttle changed"
+                );
+            }
+            profiler.begin_epoch(completed_epoch);
+
+            // One operator per machine; one of them misses every third epoch
+            let ts_ms = epoch.start(1_000);
+            let mut labor_events: Vec<(&str, u64, ManufacturingEvent)> = Vec::new();
+            if batch == 0 {
+                for machine in 0..machines {
+                    labor_events.push((
+                        "OperatorClockedIn",
+                        machine,
+                        ManufacturingEvent::OperatorClockedIn(OperatorClockedIn { operator_id: machine, ts_ms }),
+                    ));
+                    if opts.operator_absences {
+                        shift_starts.push(machine);
+                    }
+                    labor_events.push((
+                        "OperatorAssigned",
+                        machine,
+                        ManufacturingEvent::OperatorAssigned(OperatorAssigned {
+                            operator_id: machine,
+                            machine_id: machine,
+                            ts_ms,
+                        }),
+                    ));
+                }
+            }
+            if let Some(operator_id) = absent_operator.take() {
+                labor_events.push((
+                    "OperatorClockedIn",
+                    operator_id,
+                    ManufacturingEvent::OperatorClockedIn(OperatorClockedIn { operator_id, ts_ms }),
+                ));
+                if opts.operator_absences {
+                    shift_starts.push(operator_id);
+                }
+            }
+            if batch % 3 == 2 {
+                let operator_id = batch % machines;
+                labor_events.push((
+                    "OperatorClockedOut",
+                    operator_id,
+                    ManufacturingEvent::OperatorClockedOut(OperatorClockedOut { operator_id, ts_ms }),
+                ));
+                absent_operator = Some(operator_id);
+            }
+            for (kind, operator_id, payload) in labor_events {
+                let env = EventEnvelope {
+                    meta: EventMeta {
+                        domain: DOMAIN.to_string(),
+                        kind: kind.to_string(),
+                        epoch,
+                        source: "synthetic".to_string(),
+                        key: Some(format!("operator:{}", operator_id)),
+                    },
+                    payload,
+                };
+                if !dedup.admit(&env.meta, ts_ms) {
+                    metrics.inc_duplicate_events(1);
+                    continue;
+                }
+                if !validator.admit(&env, dead_letters.as_mut()) {
+                    metrics.inc_invalid_events(1);
+                    continue;
+                }
+                remember(&mut recent_events, &env);
+                for env in causal.offer(env) {
+                    input.insert(env);
+                }
+                metrics.inc_base_events(1);
+            }
+
+            // Staggered deliveries, one material per epoch at most
+            if opts.material_shortages {
+                for material_id in 0..MATERIALS {
+                    if (batch + material_id) % RESUPPLY_EVERY != 0 {
+                        continue;
+                    }
+                    let received = MaterialReceived { material_id, units: resupply_units, ts_ms };
+                    let env = EventEnvelope {
+                        meta: EventMeta {
+                            domain: DOMAIN.to_string(),
+                            kind: "MaterialReceived".to_string(),
+                            epoch,
+                            source: "synthetic".to_string(),
+                            key: Some(format!("material:{}", material_id)),
+                        },
+                        payload: ManufacturingEvent::MaterialReceived(received),
+                    };
+                    if !dedup.admit(&env.meta, ts_ms) {
+                        metrics.inc_duplicate_events(1);
+                        continue;
+                    }
+                    if !validator.admit(&env, dead_letters.as_mut()) {
+                        metrics.inc_invalid_events(1);
+                        continue;
+                    }
+                    *material_on_hand.entry(material_id).or_default() += resupply_units;
+                    remember(&mut recent_events, &env);
+                    for env in causal.offer(env) {
+                        input.insert(env);
+                    }
+                    metrics.inc_base_events(1);
+                }
+            }
+
+            // Machines down last epoch come back; staggered ones break down
+            if opts.machine_failures {
+                let mut changes: Vec<(u64, MachineStatus)> = down_machines
+                    .drain(..)
+                    .map(|machine_id| (machine_id, MachineStatus::Running))
+                    .collect();
+                for machine_id in 0..machines {
+                    if batch > 0 && (batch + machine_id * 7) % FAIL_EVERY == 0 {
+                        changes.push((machine_id, MachineStatus::Down));
+                    }
+                }
+                for (machine_id, status) in changes {
+                    let change = MachineStateChange { machine_id, status, ts_ms };
+                    let env = EventEnvelope {
+                        meta: EventMeta {
+                            domain: DOMAIN.to_string(),
+                            kind: "MachineStateChange".to_string(),
+                            epoch,
+                            source: "synthetic".to_string(),
+                            key: Some(format!("machine:{}", machine_id)),
+                        },
+                        payload: ManufacturingEvent::MachineStateChange(change.clone()),
+                    };
+                    if !dedup.admit(&env.meta, ts_ms) {
+                        metrics.inc_duplicate_events(1);
+                        continue;
+                    }
+                    if !validator.admit(&env, dead_letters.as_mut()) {
+                        metrics.inc_invalid_events(1);
+                        continue;
+                    }
+                    if change.status == MachineStatus::Down {
+                        let since = last_failure.insert(machine_id, epoch).unwrap_or_default();
+                        let gaps = failure_gaps.entry(machine_id).or_default();
+                        gaps.0 += epoch.since(since);
+                        gaps.1 += 1;
+                        down_machines.push(machine_id);
+                    }
+                    remember(&mut recent_events, &env);
+                    for env in causal.offer(env) {
+                        input.insert(env);
+                    }
+                    metrics.inc_base_events(1);
+                }
+            }
+
+            let mut lot_events: Vec<(&str, String, ManufacturingEvent)> = Vec::new();
             for i in 0..opts.ops_per_batch {
+                let ingest_started = Instant::now();
                 job_counter += 1;
                 let machine = (batch * 5 + i * 11) % machines;
                 let duration_ms = 3_000 + (machine * 250) + ((i % 5) as u64) * 500;
@@ -205,53 +1773,139 @@ fn main() -> Result<()> {
                     job_id: job_counter,
                     operation_id: (i % 4) as u32,
                     machine_id: machine,
-                    ts_ms: epoch * 1_000,
+                    ts_ms: epoch.start(1_000),
                     expected_duration_ms: duration_ms,
                 };
+                let meta = EventMeta {
+                    domain: DOMAIN.to_string(),
+                    kind: "OperationStart".to_string(),
+                    epoch,
+                    source: "synthetic".to_string(),
+                    key: Some(format!("job:{}", op.job_id)),
+                };
+                profiler.record(Stage::IngestDecode, ingest_started.elapsed());
+                if !dedup.admit(&meta, op.ts_ms) {
+                    metrics.inc_duplicate_events(1);
+                    continue;
+                }
+                let env = EventEnvelope {
+                    meta,
+                    payload: ManufacturingEvent::OperationStart(op.clone()),
+                };
+                if !validator.admit(&env, dead_letters.as_mut()) {
+                    metrics.inc_invalid_events(1);
+                    continue;
+                }
+                // Sample whole machines, so each kept machine's WIP is exact
+                if !sampler.admit_key(&format!("machine:{}", op.machine_id)) {
+                    metrics.inc_sampled_out_events(1);
+                    continue;
+                }
 
-                let outcome = scenario_manager.expand_operation(&op);
+                let expand_started = Instant::now();
+                let wip = base_wip.get(&op.machine_id).copied().unwrap_or(0);
+                let outcome = scenario_manager.expand_operation(epoch, &op, Some(wip));
+                *base_wip.entry(op.machine_id).or_default() += 1;
+                if let Some(tracker) = &warm_up {
+                    tracker.observe(epoch, op.machine_id);
+                }
+                profiler.record(Stage::Expansion, expand_started.elapsed());
                 metrics.inc_scenario_created(outcome.created.len() as u64);
-                metrics.inc_scenario_retired(outcome.retired.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                if outcome.shed > 0 {
+                    metrics.inc_scenarios_shed(outcome.shed as u64);
+                    let usage = valve.usage();
+                    warn!(shed = outcome.shed, ?usage, "safety cap bound; shed lightest scenarios");
+                }
+                if !outcome.rejected.is_empty() {
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    if opts.log_rejections {
+                        for rejection in &outcome.rejected {
+                            info!(%epoch, ?rejection, "validator rejected proposed scenario");
+                        }
+                    }
+                }
                 let overlay_changes = outcome.overlays_added.len() + outcome.overlays_removed.len();
                 if overlay_changes > 0 {
                     metrics.inc_predicted_events(overlay_changes as u64);
                 }
-                metrics.record_active_peak(scenario_manager.active_len() as u64);
-                for meta in &outcome.created {
-                    scen_weight_input.insert((meta.id, meta.weight.0));
-                }
-                for ManufacturingScenarioDelta { scenario_id, machine_id, delta_wip } in
-                    &outcome.overlays_added
-                {
-                    pred_input.insert((*scenario_id, *machine_id, *delta_wip));
+                metrics.inc_predictions_clamped(predictor.guard().take_clamped());
+                if let Some(gate) = scenario_manager.warm_up() {
+                    metrics.inc_predictions_warming_up(gate.take_warming());
                 }
-                for ManufacturingScenarioDelta { scenario_id, machine_id, delta_wip } in
-                    &outcome.overlays_removed
-                {
-                    pred_input.remove((*scenario_id, *machine_id, *delta_wip));
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
+
+                remember(&mut recent_events, &env);
+                for env in causal.offer(env) {
+                    input.insert(env);
                 }
-                for meta in &outcome.retired {
-                    scen_weight_input.remove((meta.id, meta.weight.0));
+                metrics.inc_base_events(1);
+                *started_this_epoch.entry(op.machine_id).or_default() += 1;
+
+                if opts.material_shortages {
+                    let material_id = op.machine_id % MATERIALS;
+                    let consumed = MaterialConsumed {
+                        material_id,
+                        machine_id: op.machine_id,
+                        units: 1,
+                        ts_ms: op.ts_ms,
+                    };
+                    let env = EventEnvelope {
+                        meta: EventMeta {
+                            domain: DOMAIN.to_string(),
+                            kind: "MaterialConsumed".to_string(),
+                            epoch,
+                            source: "synthetic".to_string(),
+                            key: Some(format!("job:{}", op.job_id)),
+                        },
+                        payload: ManufacturingEvent::MaterialConsumed(consumed),
+                    };
+                    if validator.admit(&env, dead_letters.as_mut()) {
+                        *material_on_hand.entry(material_id).or_default() -= 1;
+                        *consumed_this_epoch.entry(material_id).or_default() += 1;
+                        material_consumers.entry(material_id).or_default().insert(op.machine_id);
+                        remember(&mut recent_events, &env);
+                        for env in causal.offer(env) {
+                            input.insert(env);
+                        }
+                        metrics.inc_base_events(1);
+                    } else {
+                        metrics.inc_invalid_events(1);
+                    }
                 }
 
-                let env = EventEnvelope {
-                    meta: EventMeta {
-                        domain: "manufacturing".to_string(),
-                        kind: "OperationStart".to_string(),
-                        epoch,
-                        source: "synthetic".to_string(),
-                        key: Some(format!("job:{}", op.job_id)),
-                    },
-                    payload: ManufacturingEvent::OperationStart(op.clone()),
-                };
-                input.insert(env);
-                metrics.inc_base_events(1);
+                if opts.lot_genealogy && !open_lots.is_empty() {
+                    let lot_id = open_lots[op.job_id as usize % open_lots.len()];
+                    let consumed = LotConsumed {
+                        lot_id,
+                        job_id: op.job_id,
+                        machine_id: op.machine_id,
+                        ts_ms: op.ts_ms,
+                    };
+                    let payload = ManufacturingEvent::LotConsumed(consumed);
+                    lot_events.push(("LotConsumed", format!("job:{}", op.job_id), payload));
+                }
 
-                let ready_epoch = epoch + 1 + (machine % 3);
+                let ready_epoch = epoch.saturating_add(1 + machine % 3);
                 active_jobs.push(ActiveJob {
                     job_id: op.job_id,
                     machine_id: op.machine_id,
                     ready_epoch,
+                    reworked: false,
                 });
             }
 
@@ -267,44 +1921,732 @@ fn main() -> Result<()> {
             });
 
             for job in completed {
-                let complete = OperationComplete {
-                    job_id: job.job_id,
-                    operation_id: 0,
-                    machine_id: job.machine_id,
-                    ts_ms: epoch * 1_000 + 500,
+                let complete_ts_ms = EventTime::from_millis(epoch.start(1_000).as_millis() + 500);
+                let line_id = job.machine_id / MACHINES_PER_LINE;
+                // Deterministic quality losses: some jobs take a rework pass, others are scrapped
+                let (kind, payload) = if job.job_id % REWORK_EVERY == 0 && !job.reworked {
+                    active_jobs.push(ActiveJob {
+                        ready_epoch: epoch.saturating_add(2),
+                        reworked: true,
+                        ..job.clone()
+                    });
+                    let rework = ReworkRequired {
+                        job_id: job.job_id,
+                        operation_id: 0,
+                        machine_id: job.machine_id,
+                        line_id,
+                        ts_ms: complete_ts_ms,
+                    };
+                    ("ReworkRequired", ManufacturingEvent::ReworkRequired(rework))
+                } else if job.job_id % SCRAP_EVERY == 0 {
+                    let scrapped = OperationScrapped {
+                        job_id: job.job_id,
+                        operation_id: 0,
+                        machine_id: job.machine_id,
+                        line_id,
+                        ts_ms: complete_ts_ms,
+                    };
+                    ("OperationScrapped", ManufacturingEvent::OperationScrapped(scrapped))
+                } else {
+                    let complete = OperationComplete {
+                        job_id: job.job_id,
+                        operation_id: 0,
+                        machine_id: job.machine_id,
+                        ts_ms: complete_ts_ms,
+                    };
+                    ("OperationComplete", ManufacturingEvent::OperationComplete(complete))
                 };
                 let env = EventEnvelope {
                     meta: EventMeta {
-                        domain: "manufacturing".to_string(),
-                        kind: "OperationComplete".to_string(),
+                        domain: DOMAIN.to_string(),
+                        kind: kind.to_string(),
                         epoch,
                         source: "synthetic".to_string(),
                         key: Some(format!("job:{}", job.job_id)),
                     },
-                    payload: ManufacturingEvent::OperationComplete(complete),
+                    payload,
                 };
-                input.insert(env);
+                if !dedup.admit(&env.meta, complete_ts_ms) {
+                    metrics.inc_duplicate_events(1);
+                    continue;
+                }
+                if !validator.admit(&env, dead_letters.as_mut()) {
+                    metrics.inc_invalid_events(1);
+                    continue;
+                }
+                if !matches!(env.payload, ManufacturingEvent::ReworkRequired(_)) {
+                    *base_wip.entry(job.machine_id).or_default() -= 1;
+                }
+                if opts.reconcile {
+                    if let ManufacturingEvent::OperationComplete(done) = &env.payload {
+                        let outcome = scenario_manager.reconcile(epoch, done);
+                        if !outcome.retired.is_empty() || !outcome.reweighted.is_empty() {
+                            info!(
+                                %epoch,
+                                job = done.job_id,
+                                retired = outcome.retired.len(),
+                                promoted = outcome.reweighted.len(),
+                                "scenarios reconciled against completion"
+                            );
+                        }
+                        count_retired(&metrics, &outcome.retired);
+                        apply_outcome(
+                            epoch,
+                            &outcome,
+                            &mut pred_input,
+                            &mut scen_weight_input,
+                            &mut retire_input,
+                            &mut group_input,
+                            &mut down_input,
+                            &mut scrap_input,
+                            &mut staff_input,
+                            &mut timelines,
+                            &mut heatmap,
+                            &labels,
+                        );
+                    }
+                }
+                remember(&mut recent_events, &env);
+                for env in causal.offer(env) {
+                    input.insert(env);
+                }
                 metrics.inc_base_events(1);
+
+                // Each good completion yields a lot; every few lots are split in two
+                if opts.lot_genealogy && kind == "OperationComplete" {
+                    let lot_id = next_lot;
+                    next_lot += 1;
+                    let created = LotCreated {
+                        lot_id,
+                        material_id: job.machine_id % MATERIALS,
+                        job_id: job.job_id,
+                        ts_ms: complete_ts_ms,
+                    };
+                    let key = format!("lot:{}", lot_id);
+                    let payload = ManufacturingEvent::LotCreated(created);
+                    lot_events.push(("LotCreated", key.clone(), payload));
+                    if lot_id % SPLIT_EVERY == 0 {
+                        let into = vec![next_lot, next_lot + 1];
+                        next_lot += 2;
+                        open_lots.extend(&into);
+                        let split = LotSplit { lot_id, into, ts_ms: complete_ts_ms };
+                        lot_events.push(("LotSplit", key, ManufacturingEvent::LotSplit(split)));
+                    } else {
+                        open_lots.push_back(lot_id);
+                    }
+                }
+            }
+
+            // The two oldest open lots merge now and then; the oldest run out
+            if opts.lot_genealogy {
+                if batch % MERGE_EVERY == MERGE_EVERY - 1 && open_lots.len() >= 2 {
+                    let lots: Vec<u64> = open_lots.drain(..2).collect();
+                    let into = next_lot;
+                    next_lot += 1;
+                    open_lots.push_back(into);
+                    let merged = LotMerged { lots, into, ts_ms };
+                    let key = format!("lot:{}", into);
+                    lot_events.push(("LotMerged", key, ManufacturingEvent::LotMerged(merged)));
+                }
+                while open_lots.len() > OPEN_LOTS {
+                    open_lots.pop_front();
+                }
+            }
+            for (kind, key, payload) in lot_events {
+                let env = EventEnvelope {
+                    meta: EventMeta {
+                        domain: DOMAIN.to_string(),
+                        kind: kind.to_string(),
+                        epoch,
+                        source: "synthetic".to_string(),
+                        key: Some(key),
+                    },
+                    payload,
+                };
+                if !validator.admit(&env, dead_letters.as_mut()) {
+                    metrics.inc_invalid_events(1);
+                    continue;
+                }
+                remember(&mut recent_events, &env);
+                for env in causal.offer(env) {
+                    input.insert(env);
+                }
+                metrics.inc_base_events(1);
+            }
+
+            // Branch on projected shortages once this epoch's consumption is known
+            for (&material_id, &on_hand_units) in &material_on_hand {
+                let material = MaterialStatus {
+                    material_id,
+                    on_hand: on_hand_units,
+                    consumed_per_epoch: consumed_this_epoch.get(&material_id).copied().unwrap_or(0),
+                    consumers: material_consumers
+                        .get(&material_id)
+                        .map(|machines| machines.iter().copied().collect())
+                        .unwrap_or_default(),
+                };
+                let outcome = scenario_manager.expand_shortage(epoch, &material);
+                if !outcome.created.is_empty() {
+                    info!(?material, created = outcome.created.len(), "material shortage scenario");
+                }
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                if outcome.shed > 0 {
+                    metrics.inc_scenarios_shed(outcome.shed as u64);
+                    let usage = valve.usage();
+                    warn!(shed = outcome.shed, ?usage, "safety cap bound; shed lightest scenarios");
+                }
+                if !outcome.rejected.is_empty() {
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                }
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
             }
+            consumed_this_epoch.clear();
 
-            epoch += 1;
-            input.advance_to(epoch);
-            pred_input.advance_to(epoch);
-            scen_weight_input.advance_to(epoch);
+            // Branch on predicted breakdowns of machines that are still up
+            if opts.machine_failures {
+                for machine_id in 0..machines {
+                    if down_machines.contains(&machine_id) {
+                        continue;
+                    }
+                    let load = started_this_epoch.get(&machine_id).copied().unwrap_or(0);
+                    let since = last_failure.get(&machine_id).copied().unwrap_or_default();
+                    let machine = MachineHealth {
+                        machine_id,
+                        epochs_since_failure: epoch.since(since) as i64,
+                        mean_epochs_between_failures: failure_gaps
+                            .get(&machine_id)
+                            .map(|&(total, count)| total as f64 / count as f64),
+                        utilization: load as f64 / fair_share,
+                        load_per_epoch: load,
+                    };
+                    let outcome = scenario_manager.expand_failure(epoch, &machine);
+                    if !outcome.created.is_empty() {
+                        info!(?machine, created = outcome.created.len(), "machine failure scenario");
+                    }
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    count_retired(&metrics, &outcome.retired);
+                    if outcome.shed > 0 {
+                        metrics.inc_scenarios_shed(outcome.shed as u64);
+                        let usage = valve.usage();
+                        warn!(shed = outcome.shed, ?usage, "safety cap bound; shed lightest scenarios");
+                    }
+                    if !outcome.rejected.is_empty() {
+                        metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    }
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut group_input,
+                        &mut down_input,
+                        &mut scrap_input,
+                        &mut staff_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &labels,
+                    );
+                }
+            }
+            // Branch on operators who clocked in missing their shift after all
+            for operator_id in shift_starts.drain(..) {
+                let ts_ms = epoch.start(1_000);
+                let assignment = OperatorAssigned { operator_id, machine_id: operator_id, ts_ms };
+                let outcome = scenario_manager.expand_absence(epoch, &assignment);
+                if !outcome.created.is_empty() {
+                    let created = outcome.created.len();
+                    info!(?assignment, created, "operator absence scenario");
+                }
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                if outcome.shed > 0 {
+                    metrics.inc_scenarios_shed(outcome.shed as u64);
+                    let usage = valve.usage();
+                    warn!(shed = outcome.shed, ?usage, "safety cap bound; shed lightest scenarios");
+                }
+                if !outcome.rejected.is_empty() {
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                }
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
+            }
+            if let Some(gain) = opts.evidence_gain {
+                let busy: BTreeSet<u64> = started_this_epoch
+                    .iter()
+                    .filter(|&(_, &load)| load as f64 > fair_share)
+                    .map(|(&machine_id, _)| machine_id)
+                    .collect();
+                let outcome: ManufacturingExpansionOutcome = scenario_manager
+                    .observe_evidence(|_, overlay| {
+                        let builds_up = overlay.iter().any(|delta| {
+                            delta.delta_wip.units() > 0 && busy.contains(&delta.machine_id)
+                        });
+                        if builds_up {
+                            gain
+                        } else {
+                            1.0
+                        }
+                    })
+                    .into();
+                if !outcome.reweighted.is_empty() {
+                    info!(
+                        %epoch,
+                        busy = busy.len(),
+                        reweighted = outcome.reweighted.len(),
+                        "scenario weights updated from evidence"
+                    );
+                }
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut group_input,
+                    &mut down_input,
+                    &mut scrap_input,
+                    &mut staff_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &labels,
+                );
+            }
+            started_this_epoch.clear();
+
+            for env in causal.advance(epoch) {
+                input.insert(env);
+            }
+            let gc_every = opts.weight_gc_every;
+            if gc_every > 0 && completed_epoch.next().get() % gc_every == 0 {
+                collect_weights(completed_epoch, &mut scen_weight_input, &scenario_manager);
+            }
+            heatmap.snapshot(epoch);
+            epoch = epoch.next();
+            dedup.advance(epoch);
+            recent_events.advance(epoch);
+            timelines.advance(epoch);
+            input.advance_to(epoch.get());
+            pred_input.advance_to(epoch.get());
+            scen_weight_input.advance_to(epoch.get());
+            retire_input.advance_to(epoch.get());
+            group_input.advance_to(epoch.get());
+            down_input.advance_to(epoch.get());
+            scrap_input.advance_to(epoch.get());
+            staff_input.advance_to(epoch.get());
+            hold_input.advance_to(epoch.get());
+            predicate_input.advance_to(epoch.get());
             input.flush();
             pred_input.flush();
             scen_weight_input.flush();
+            retire_input.flush();
+            group_input.flush();
+            down_input.flush();
+            scrap_input.flush();
+            staff_input.flush();
+            hold_input.flush();
+            predicate_input.flush();
+            let flushed_at = Instant::now();
+            profiler.drive(worker, input.time(), flushed_at);
 
             while probe.less_than(input.time()) {
                 worker.step();
             }
+            retire_input.retract_before(&epoch.get());
+            match &pipelines {
+                Some(pipelines) => {
+                    pipelines.drive(worker, &epoch.get(), flushed_at, &metrics);
+                }
+                None => subscriptions.drive(worker, &epoch.get(), flushed_at, &metrics),
+            }
+            let updates = projections.borrow_mut().close_epoch();
+            if let Some(sink) = projection_sink.as_mut() {
+                if let Err(err) = sink.write(&updates) {
+                    warn!(?err, "failed to publish projections");
+                }
+            }
+            for change in ranks.borrow_mut().close_epoch() {
+                info!(
+                    scenario = change.group,
+                    machine = change.key,
+                    from = ?change.from,
+                    to = ?change.to,
+                    value = change.value,
+                    "scenario top-K rank change"
+                );
+            }
+            let fired = condition.borrow_mut().close_epoch(completed_epoch);
+            for ((sid, machine), (sum, prob, variance)) in fired {
+                if !persistent_leadership.is_leader() {
+                    continue;
+                }
+                match rollout.route("machine_backlog", completed_epoch) {
+                    Route::Live => {}
+                    Route::LogOnly => {
+                        info!(scenario = sid, machine, "shadow alert: machine_backlog is log-only");
+                        continue;
+                    }
+                    Route::Shed => continue,
+                }
+                if !valve.admit_alert(completed_epoch) {
+                    continue;
+                }
+                let key = format!("{sid}:{machine}");
+                let ts = completed_epoch.start(1_000);
+                let decision = persistent_gate.offer(completed_epoch, ts, &key);
+                if let Some(overflow) = persistent_gate.take_overflow() {
+                    info!(?overflow, "alert overflow summary");
+                }
+                if decision.is_delivered() {
+                    metrics.inc_scenario_alerts(1);
+                    let wip = Quantity::<Units>::from_units(sum);
+                    let interval = Interval::around(sum, variance, opts.interval_z);
+                    info!(
+                        scenario = sid,
+                        label = labels.borrow().get(&sid).map(String::as_str),
+                        machine,
+                        %wip,
+                        wip_low = interval.low,
+                        wip_high = interval.high,
+                        prob,
+                        ?persistence,
+                        ?decision,
+                        domain = DOMAIN,
+                        "ALERT: persistent machine backlog risk"
+                    );
+                    if let Some(dir) = &opts.replay_bundles {
+                        let bundle = ReplayBundle {
+                            alert: "machine_backlog",
+                            domain: DOMAIN,
+                            epoch: completed_epoch,
+                            scenario_id: sid,
+                            key: machine,
+                            events: recent_events.get(&machine),
+                            lineage: timelines.lineage(sid),
+                            overlays: scenario_manager.lineage_overlays(sid),
+                            predicted: PredictedRow { value: sum, probability: prob, variance },
+                            config: scenario_manager.config(),
+                        };
+                        match bundle.write_to(dir) {
+                            Ok(path) => info!(path = %path.display(), "wrote replay bundle"),
+                            Err(err) => warn!(?err, "failed to write replay bundle"),
+                        }
+                    }
+                } else {
+                    metrics.inc_alerts_suppressed(1);
+                }
+            }
+            // An hour with no later alert still reports what it held back
+            let ts = completed_epoch.start(1_000);
+            for gate in [&mut persistent_gate, &mut *alert_gate.borrow_mut()] {
+                if let Some(overflow) = gate.close_window(ts) {
+                    info!(?overflow, "alert overflow summary");
+                }
+            }
+            let alerts_shed = valve.take_alerts_shed();
+            if alerts_shed > 0 {
+                metrics.inc_alerts_shed(alerts_shed);
+                warn!(epoch = %completed_epoch, alerts_shed, "alert cap bound; dropped alerts");
+            }
             let elapsed = epoch_timer.elapsed();
+            source_lag_ms =
+                (source_lag_ms + elapsed.as_millis() as u64).saturating_sub(opts.epoch_budget_ms);
+            if let Some(tracker) = &warm_up {
+                let ready = tracker.ready_fraction(completed_epoch);
+                metrics.record_predictor_readiness("backlog", ready);
+            }
             let snapshot = metrics.snapshot();
+            if let Some(path) = &opts.metrics_prom {
+                if let Err(err) = std::fs::write(path, registry.to_prometheus_text()) {
+                    warn!(?err, path = %path.display(), "failed to write Prometheus metrics");
+                }
+            }
             let json = snapshot.to_json_line("mfg_epoch", Some(elapsed));
-            info!(epoch = completed_epoch, %json, "epoch complete");
+            info!(epoch = %completed_epoch, %json, "epoch complete");
+            epoch_hooks.run(completed_epoch, &snapshot);
+            let occupancy = scenario_manager.depth_occupancy();
+            info!(epoch = %completed_epoch, ?occupancy, "active scenarios by depth");
+            if let Some(breakdown) = profiler.finish_epoch(completed_epoch) {
+                let json = breakdown.to_json_line("mfg_stages");
+                info!(%json, "stage breakdown");
+            }
+            if let Some(path) = &opts.checkpoint {
+                let every = opts.checkpoint_every;
+                if every > 0 && completed_epoch.next().get() % every == 0 {
+                    let gates = GateCheckpoint::of(&alert_gate.borrow(), &persistent_gate);
+                    save_checkpoint(path, &scenario_manager, &registry, &gates);
+                }
+            }
+        }
+        if let Some(path) = &opts.checkpoint {
+            let gates = GateCheckpoint::of(&alert_gate.borrow(), &persistent_gate);
+            save_checkpoint(path, &scenario_manager, &registry, &gates);
+            info!(path = %path.display(), "wrote scenario, metrics and alert gate checkpoint");
+        }
+        if causal.forced() > 0 {
+            warn!(forced = causal.forced(), "events released before their causal predecessors");
         }
+        if let Some(path) = &opts.heatmap_out {
+            if let Err(err) = heatmap.write_parquet(path) {
+                warn!(?err, path = %path.display(), "failed to write impact heatmap");
+            }
+        }
+        if let Some(path) = &opts.timeline_out {
+            if let Err(err) = std::fs::write(path, timelines.export_json()) {
+                warn!(%err, path = %path.display(), "failed to write scenario timelines");
+            }
+        }
+        if let Some(dead_letters) = dead_letters.as_mut() {
+            if let Err(err) = dead_letters.flush() {
+                warn!(?err, "failed to flush dead letters");
+            }
+        }
+        for sid in &opts.explain_scenario {
+            match scenario_manager.retired_history().to_json(*sid) {
+                Some(json) => info!(scenario = sid, %json, "retired scenario"),
+                None => {
+                    let path = scenario_manager.path_to_root(*sid);
+                    if path.is_empty() {
+                        info!(scenario = sid, "scenario no longer remembered");
+                    } else {
+                        let subtree_weight = scenario_manager.subtree_weight(*sid);
+                        info!(scenario = sid, ?path, subtree_weight, "scenario lineage");
+                    }
+                }
+            }
+        }
+        if let Some(usage) = ResourceUsage::sample() {
+            metrics.record_resources(&usage);
+        }
+        for latency in metrics.subscription_latencies() {
+            info!(?latency, "subscription latency");
+        }
+        let flags = rollout.to_json();
+        info!(%flags, "rollout flags");
         let final_snapshot = metrics.snapshot();
         let json = final_snapshot.to_json_line("mfg_final", None);
         info!(%json, "final metrics summary");
+        let json = metrics.lifetime_snapshot().to_json_line("mfg_lifetime", None);
+        info!(%json, "lifetime metrics summary");
     })
 }
+
+/// Changes accepted through the control plane, one JSON object per line on
+/// `--control-addr`, e.g. `{"command":"predictor","relative_sd":0.2}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum ManufacturingCommand {
+    /// Rank the scenarios and rows this subscription could fire on.
+    Subscribe { predicate: SubscriptionPredicate },
+    Unsubscribe { predicate: SubscriptionPredicate },
+    /// Replace the beam configuration from the next operation on.
+    Config { config: ManufacturingBeamConfig },
+    /// Swap in a backlog predictor, behind the same output guard; omitted
+    /// fields take the predictor's defaults.
+    Predictor {
+        base_units: Option<i64>,
+        duration_multiplier: Option<f64>,
+        #[serde(default)]
+        relative_sd: f64,
+        #[serde(default)]
+        backlog_tiers: Vec<Tier<f64>>,
+    },
+}
+
+/// Count retirements in `metrics`, by reason.
+fn count_retired(metrics: &MetricsRegistry, retired: &[(ScenarioMeta, RetirementReason)]) {
+    for (_, reason) in retired {
+        metrics.inc_scenario_retired_for(reason.name(), 1);
+    }
+}
+
+/// Publish a seeded scenario's per-machine values under its source tag.
+fn tag_projection(projections: &RefCell<ProjectionTracker>, meta: &ScenarioMeta) {
+    if let Some(tag) = &meta.external {
+        projections.borrow_mut().tag(meta.id, tag.as_str());
+    }
+}
+
+/// Write the scenario tree to `path` and the metrics baseline and alert gate
+/// state beside it, logging rather than failing the run.
+fn save_checkpoint(
+    path: &Path,
+    manager: &ManufacturingScenarioManager,
+    registry: &MetricsRegistry,
+    gates: &GateCheckpoint,
+) {
+    if let Err(err) = write_json(&manager.snapshot(), path) {
+        warn!(?err, path = %path.display(), "failed to write scenario checkpoint");
+    }
+    let metrics_path = metrics_checkpoint(path);
+    if let Err(err) = write_json(&registry.baseline(), &metrics_path) {
+        warn!(?err, path = %metrics_path.display(), "failed to write metrics baseline");
+    }
+    let gate_path = gate_checkpoint(path);
+    if let Err(err) = write_json(gates, &gate_path) {
+        warn!(?err, path = %gate_path.display(), "failed to write alert gate state");
+    }
+}
+
+/// State of the demo's two alert gates, checkpointed so delivery policy
+/// carries on across restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GateCheckpoint {
+    /// Gate shared by backlog and queue-clearing alerts.
+    alerts: AlertGateState,
+    /// Gate of alerts raised once the condition has persisted.
+    persistent: AlertGateState,
+}
+
+impl GateCheckpoint {
+    fn of(alerts: &AlertGate, persistent: &AlertGate) -> Self {
+        Self { alerts: alerts.state().clone(), persistent: persistent.state().clone() }
+    }
+}
+
+/// Retract weight rows no live scenario backs, such as those of scenarios that
+/// retired before a restart, and restore live weights the input is missing.
+fn collect_weights(
+    epoch: EpochTime,
+    scen_weight_input: &mut LedgeredInput<u64, (u64, f64)>,
+    manager: &ManufacturingScenarioManager,
+) {
+    let live = manager.active_weights().into_iter().map(|(id, weight, _pinned)| (id, weight));
+    let report = scen_weight_input.reconcile(live);
+    if !report.is_clean() {
+        warn!(%epoch, ?report, "scenario weight input out of step with live scenarios; corrected");
+    }
+}
+
+/// The metrics baseline kept next to the scenario checkpoint at `path`.
+fn metrics_checkpoint(path: &Path) -> PathBuf {
+    path.with_extension("metrics.json")
+}
+
+/// The alert gate state kept next to the scenario checkpoint at `path`.
+fn gate_checkpoint(path: &Path) -> PathBuf {
+    path.with_extension("alerts.json")
+}
+
+/// Feed a beam expansion into the scenario weight, retirement and overlay inputs.
+fn apply_outcome(
+    epoch: EpochTime,
+    outcome: &ManufacturingExpansionOutcome,
+    pred_input: &mut InputSession<u64, (u64, u64, i64, i64), isize>,
+    scen_weight_input: &mut LedgeredInput<u64, (u64, f64)>,
+    retire_input: &mut RetirementInput<u64>,
+    group_input: &mut InputSession<u64, (u64, u64), isize>,
+    down_input: &mut InputSession<u64, (u64, u64), isize>,
+    scrap_input: &mut InputSession<u64, (u64, u64, i64), isize>,
+    staff_input: &mut InputSession<u64, (u64, u64, i64), isize>,
+    timelines: &mut ScenarioTimelines,
+    heatmap: &mut ImpactHeatmap,
+    labels: &RefCell<BTreeMap<u64, String>>,
+) {
+    for meta in &outcome.created {
+        scen_weight_input.insert((meta.id, meta.weight.0));
+        if let Some(group) = meta.exclusion_group {
+            group_input.insert((meta.id, group));
+        }
+        timelines.record_created(epoch, meta);
+        heatmap.record_created(meta);
+        if let Some(label) = meta.describe() {
+            labels.borrow_mut().insert(meta.id, label);
+        }
+    }
+    for ManufacturingScenarioDelta {
+        scenario_id, machine_id, delta_wip, delta_scrapped, variance_wip, status, delta_staff
+    } in &outcome.overlays_added
+    {
+        pred_input.insert((*scenario_id, *machine_id, delta_wip.units(), *variance_wip));
+        if delta_scrapped.units() != 0 {
+            scrap_input.insert((*scenario_id, *machine_id, delta_scrapped.units()));
+        }
+        if *status == Some(MachineStatus::Down) {
+            down_input.insert((*scenario_id, *machine_id));
+        }
+        if *delta_staff != 0 {
+            staff_input.insert((*scenario_id, *machine_id, *delta_staff));
+        }
+        timelines.record_overlay(epoch, *scenario_id);
+        heatmap.record_delta(*scenario_id, *machine_id, delta_wip.units());
+    }
+    for ManufacturingScenarioDelta {
+        scenario_id, machine_id, delta_wip, delta_scrapped, variance_wip, status, delta_staff
+    } in &outcome.overlays_removed
+    {
+        pred_input.remove((*scenario_id, *machine_id, delta_wip.units(), *variance_wip));
+        if delta_scrapped.units() != 0 {
+            scrap_input.remove((*scenario_id, *machine_id, delta_scrapped.units()));
+        }
+        if *status == Some(MachineStatus::Down) {
+            down_input.remove((*scenario_id, *machine_id));
+        }
+        if *delta_staff != 0 {
+            staff_input.remove((*scenario_id, *machine_id, *delta_staff));
+        }
+        heatmap.record_delta(*scenario_id, *machine_id, -delta_wip.units());
+    }
+    for (meta, previous) in &outcome.reweighted {
+        scen_weight_input.remove((meta.id, previous.0));
+        scen_weight_input.insert((meta.id, meta.weight.0));
+        timelines.record_weight(epoch, meta.id, meta.weight.0);
+        heatmap.record_created(meta);
+    }
+
+    for (meta, reason) in &outcome.retired {
+        scen_weight_input.remove((meta.id, meta.weight.0));
+        retire_input.retire(meta.id);
+        if let Some(group) = meta.exclusion_group {
+            group_input.remove((meta.id, group));
+        }
+        timelines.record_retired(epoch, meta.id, *reason);
+        heatmap.record_retired(meta.id);
+        labels.borrow_mut().remove(&meta.id);
+    }
+}
+
+/// Share of finished operations that were not scrapped, in bps.
+fn yield_bps(good: i64, scrapped: i64) -> Option<i64> {
+    let finished = good + scrapped;
+    (finished > 0).then(|| good * 10_000 / finished)
+}
+
+/// Keep an event for replay bundles under the machine it names.
+fn remember(
+    recent: &mut RecentEvents<u64, ManufacturingEvent>,
+    env: &EventEnvelope<ManufacturingEvent>,
+) {
+    if let Some(machine_id) = env.payload.machine_id() {
+        recent.record(machine_id, env.clone());
+    }
+}
@@ -1,30 +1,98 @@
 use anyhow::Result;
 use clap::Parser;
-use tracing::info;
-use tw_runtime::metrics::{EpochTimer, MetricsRegistry};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use tw_runtime::alerting::{
+    AlertGate, AlertGateState, AlertPolicy, Persistence, PersistenceTracker,
+};
+use tw_runtime::control::ControlPlane;
+use tw_runtime::dedup::{DedupConfig, Deduplicator};
+use tw_runtime::eviction::{ArchivedKey, EvictionConfig, IdleKeyEvictor};
+use tw_runtime::hooks::{self, EpochHooks};
+use tw_runtime::jsonl::JsonlSink;
+use tw_runtime::leader::{FileLease, LeaseKeeper, Leadership};
+use tw_runtime::ledger::{LedgeredInput, RetirementInput};
+use tw_runtime::metrics::{EpochTimer, MetricsBaseline, MetricsRegistry};
+use tw_runtime::profiling::{Stage, StageProfiler};
+use tw_runtime::projection::{ProjectionTracker, RedisProjectionSink};
+use tw_runtime::replay::{PredictedRow, RecentEvents, ReplayBundle};
+use tw_runtime::reporter::MetricsReporter;
+use tw_runtime::resources::ResourceUsage;
+use tw_runtime::rollout::{Rollout, RolloutConfig, Route};
+use tw_runtime::sampling::{EventSampler, SamplingConfig};
+use tw_runtime::scheduler::{self, PipelineScheduler};
+use tw_runtime::subscription::Subscriptions;
+use tw_runtime::transform::{split_order_lines, TransformConfig, TransformPipeline};
+use tw_runtime::validation::{order_rules, ValidationConfig};
 use tw_runtime::{init_tracing, start_runtime};
 
 use differential_dataflow::input::InputSession;
-use differential_dataflow::operators::reduce::Reduce;
+use differential_dataflow::operators::reduce::{Count, Reduce};
 use timely::dataflow::operators::probe::{Handle as ProbeHandle, Probe};
 use timely::dataflow::operators::{Inspect, Map};
 
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use tw_core::retail::{OrderLine, OrderPlaced};
-use tw_core::{EventEnvelope, EventMeta};
-use tw_predictors::SpendGrowthPredictor;
-use tw_scenarios::retail::{RetailBeamConfig, RetailScenarioManager};
+use tw_core::quantity::{Interval, Rounding, BPS_SCALE};
+use tw_core::retail::{Channel, ChannelId, Money, OrderLine, OrderPlaced, PriceChanged};
+use tw_core::{EpochTime, EventEnvelope, EventMeta};
+use tw_views::channel::{channel_spend, scenario_channel_spend};
+use tw_views::exclusion::{exclusive_weights, expected_delta, expected_variance};
+use tw_views::interval::with_variance;
+use tw_views::overlay::{gated, live_scenarios, materialized};
+use tw_views::pushdown::{prob_bps, pushdown_candidates, PredicateRow, SubscriptionPredicate};
+use tw_views::reducers::{aggregate, ReducerRegistry};
+use tw_views::rank::RankTracker;
+use tw_views::watchlist::{
+    evaluate_watches, gather_alerts, FanoutRow, KeyWatch, ShardConfig, WatchChange, WatchRow,
+    WatchShards,
+};
+use tw_views::{ranked_top_k, sampled_total, top_k, TopKConfig};
+use tw_predictors::guard::{GuardConfig, Guarded};
+use tw_predictors::warmup::{Readiness, WarmUpConfig, WarmUpGate, WarmUpTracker};
+use tw_predictors::{
+    ConstantElasticityPredictor, FeatureStore, RecaptureShiftPredictor, SpendDeltaPredictor,
+    SpendGrowthPredictor, Tier,
+};
+use tw_scenarios::bulk::BulkOp;
+use tw_scenarios::external::ExternalPlan;
+use tw_scenarios::priors::PriorsConfig;
+use tw_scenarios::state::{read_json, write_json};
+use tw_scenarios::labels::OutcomeLabeler;
+use tw_scenarios::retail::{
+    RetailBeamConfig, RetailExpansionOutcome, RetailOverlay, RetailScenarioManager,
+    RetailScenarioState, SpendSeed,
+};
+use tw_scenarios::heatmap::ImpactHeatmap;
+use tw_scenarios::history::RetirementReason;
+use tw_scenarios::throttle::LagThrottleConfig;
+use tw_scenarios::timeline::ScenarioTimelines;
+use tw_scenarios::validate::Proposal;
+use tw_scenarios::valve::{SafetyCaps, SafetyValve};
+use tw_scenarios::what_if::WhatIfPlan;
+use tw_scenarios::{DepthQuota, Renormalization, ScenarioMeta, WeightMode};
 
 #[derive(Parser, Debug)]
 #[command(name = "retail_demo", about = "Retail branching futures demo with configurable parameters")]
 struct RetailOpts {
     #[arg(long, default_value_t = 5)]
     top_k: usize,
+    /// Keep every key tied with the k-th value instead of cutting at exactly k.
+    #[arg(long)]
+    topk_include_ties: bool,
     #[arg(long, default_value_t = 50)]
     customers: u64,
     #[arg(long, default_value_t = 10)]
     batches: u64,
+    /// Timely worker threads. Worker 0 generates events and owns every side
+    /// effect; the others hold their shards of the dataflow.
+    #[arg(long, default_value_t = 2)]
+    workers: usize,
     #[arg(long, default_value_t = 200)]
     batch_size: u64,
     #[arg(long, default_value_t = 5)]
@@ -35,101 +103,648 @@ struct RetailOpts {
     min_prob: f64,
     #[arg(long, default_value_t = 0.5)]
     branch_prob: f64,
-    #[arg(long, default_value_t = 0.3)]
-    delta_multiplier: f64,
+    #[arg(long, default_value_t = 3_000)]
+    delta_multiplier_bps: i64,
     #[arg(long, default_value_t = 3_000)]
     min_delta_cents: i64,
     #[arg(long, default_value_t = 7)]
     target_customer: u64,
     #[arg(long, default_value_t = 0.2)]
     prob_threshold: f64,
+    /// Standard deviation of predicted spend uplift, in basis points of the order total.
+    #[arg(long, default_value_t = 0)]
+    uplift_sd_bps: i64,
+    /// Alternative spend uplift as <probability>:<bps>, such as 0.6:1000; repeatable. Each
+    /// order then branches one child per tier instead of one at the fixed uplift.
+    #[arg(long)]
+    uplift_tier: Vec<Tier<i64>>,
+    /// Standard deviations either side of a projected value its alert interval spans.
+    #[arg(long, default_value_t = 1.96)]
+    interval_z: f64,
+    /// Registered reducer for per-customer spend totals (sum, count, min, max).
+    #[arg(long, default_value = "sum")]
+    spend_reducer: String,
+    /// JSON file with quiet hours, hourly cap, and escalation settings.
+    #[arg(long)]
+    alert_policy: Option<PathBuf>,
+    /// JSON file of rollout flags; a flagged subscription only logs its alerts at first.
+    #[arg(long)]
+    rollout: Option<PathBuf>,
+    /// Sample metrics on this interval between epochs; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    report_interval_ms: u64,
+    /// Keep a separate beam per customer so unrelated futures don't evict each other.
+    #[arg(long)]
+    partition_beam: bool,
+    /// Orders seen per customer before the spend predictor branches on it; 0 needs none.
+    #[arg(long, default_value_t = 0)]
+    warm_up_observations: u64,
+    /// Epochs of history before the spend predictor branches at all.
+    #[arg(long, default_value_t = 0)]
+    warm_up_epochs: u64,
+    /// Branch with a flat fallback predictor while warming up instead of not branching.
+    #[arg(long)]
+    warm_up_fallback: bool,
+    /// Reject proposed scenarios that would take a customer's spend or a
+    /// SKU's demand below zero.
+    #[arg(long)]
+    reject_negative: bool,
+    /// Log each proposed scenario a validator rejects.
+    #[arg(long)]
+    log_rejections: bool,
+    /// Build scenario views only for watched scenarios: those overlaying the
+    /// target customer, plus any given with --watch-scenario.
+    #[arg(long)]
+    lazy_scenarios: bool,
+    /// Rank only the scenarios and rows a subscription could fire on; the
+    /// scenario top-K log then covers only those.
+    #[arg(long)]
+    pushdown_subscriptions: bool,
+    /// Worker steps per epoch to wait on subscriptions, shared by weight; one
+    /// that runs out is left to catch up later and its alerts land late.
+    #[arg(long)]
+    pipeline_steps: Option<u64>,
+    /// Subscription step weight as name=weight; repeatable, default 1.
+    #[arg(long)]
+    pipeline_weight: Vec<String>,
+    /// Shell command run after each epoch, with TW_EPOCH set and the metrics
+    /// snapshot as JSON on stdin; repeatable.
+    #[arg(long)]
+    epoch_hook_cmd: Vec<String>,
+    /// Accept control commands as JSON lines on this address, e.g. 127.0.0.1:7070.
+    #[arg(long)]
+    control_addr: Option<String>,
+    /// Always materialize this scenario in lazy mode; repeatable.
+    #[arg(long)]
+    watch_scenario: Vec<u64>,
+    /// Register this many customer watches, each alerting when its customer
+    /// enters a scenario's top-K at --prob-threshold or above.
+    #[arg(long, default_value_t = 0)]
+    customer_watches: u64,
+    /// Watches on one customer per shard before its watches spread to another.
+    #[arg(long, default_value_t = 1_000)]
+    watches_per_shard: usize,
+    /// Most shards one customer's watches span.
+    #[arg(long, default_value_t = 16)]
+    max_watch_fanout: u32,
+    /// Cap on active scenarios across all beam partitions.
+    #[arg(long)]
+    global_cap: Option<usize>,
+    /// With --partition-beam, cap on partitions holding active scenarios;
+    /// the least recently branched one is evicted first.
+    #[arg(long)]
+    max_keys: Option<usize>,
+    /// Scale scenario weights by this factor every epoch, so unconfirmed branches fade.
+    #[arg(long)]
+    epoch_decay: Option<f64>,
+    /// Retire scenarios this many epochs after creation unless confirmed since.
+    #[arg(long)]
+    scenario_ttl: Option<u64>,
+    /// Cap on active scenarios at --deep-min-depth or deeper.
+    #[arg(long)]
+    deep_quota: Option<usize>,
+    #[arg(long, default_value_t = 3)]
+    deep_min_depth: u32,
+    /// Beam slots kept for the highest-impact scenarios probability would drop.
+    #[arg(long, default_value_t = 0)]
+    tail_slots: usize,
+    /// Most children one triggering event may create, off the heaviest parents.
+    #[arg(long)]
+    max_children_per_event: Option<usize>,
+    /// Compute and rank scenario weights in Q32.32 fixed point for bit-reproducible runs.
+    #[arg(long)]
+    fixed_point_weights: bool,
+    /// Rescale surviving weights after each expansion: none, sum_to_one or redistribute_to_root.
+    #[arg(long, default_value = "none")]
+    renormalize: Renormalization,
+    /// Operator bulk operation to submit at --bulk-op-at, such as
+    /// retire:tag=sop:, reweight:0.5:12,13 or, with --partition-beam, freeze:42;
+    /// repeatable.
+    #[arg(long)]
+    bulk_op: Vec<BulkOp>,
+    /// Batch at which --bulk-op operations are submitted.
+    #[arg(long, default_value_t = 0)]
+    bulk_op_at: u64,
+    /// Hard cap on active scenarios summed over every manager in the process.
+    #[arg(long)]
+    max_active_scenarios: Option<usize>,
+    /// Hard cap on overlay rows summed over every manager in the process.
+    #[arg(long)]
+    max_overlay_rows: Option<usize>,
+    /// Drop alerts beyond this many per epoch.
+    #[arg(long)]
+    max_alerts_per_epoch: Option<u64>,
+    /// Drop events repeating a (source, kind, key, ts) seen within this many epochs; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    dedup_ttl_epochs: u64,
+    /// Retract customers' base totals after this many epochs without an order; 0 disables.
+    /// Needs the sum reducer.
+    #[arg(long, default_value_t = 0)]
+    evict_idle_epochs: u64,
+    /// Append each evicted customer's last total to this JSONL file.
+    #[arg(long)]
+    evict_archive: Option<PathBuf>,
+    /// Drop events whose ts_ms is further than this from their epoch's clock.
+    #[arg(long)]
+    max_ts_skew_ms: Option<u64>,
+    /// Input rule to leave unenforced; repeatable.
+    #[arg(long)]
+    disable_rule: Vec<String>,
+    /// Append events that break an input rule to this JSONL file.
+    #[arg(long)]
+    dead_letters: Option<PathBuf>,
+    /// Write a replay bundle for each delivered persistent alert into this directory.
+    #[arg(long)]
+    replay_bundles: Option<PathBuf>,
+    /// Epochs of a key's events kept for replay bundles.
+    #[arg(long, default_value_t = 5)]
+    replay_epochs: u64,
+    /// JSON file of ingestion transform rules, applied to each order before insertion.
+    #[arg(long)]
+    transforms: Option<PathBuf>,
+    /// Split multi-line orders into one order per line before insertion.
+    #[arg(long)]
+    split_order_lines: bool,
+    /// Keep this share of event keys, in basis points, and scale aggregates to match.
+    #[arg(long, default_value_t = 10_000)]
+    sample_bps: i64,
+    /// Branch a hypothetical price change of this many bps halfway through; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    price_hike_bps: i64,
+    #[arg(long, default_value_t = 42)]
+    price_hike_sku: u64,
+    /// Branch a hypothetical web outage halfway through, shifting spend to other channels.
+    #[arg(long)]
+    web_outage: bool,
+    /// Branch probability of the web outage scenario.
+    #[arg(long, default_value_t = 0.05)]
+    outage_prob: f64,
+    /// Epochs the web outage lasts; each channel's average spend per epoch so far is
+    /// projected over them, and only that future spend shifts.
+    #[arg(long, default_value_t = 3)]
+    outage_epochs: i64,
+    /// JSON file of externally planned scenarios, seeded at the root on the first epoch.
+    #[arg(long)]
+    external_scenarios: Option<PathBuf>,
+    /// JSON file of prior scenarios per domain, seeded at the root on the first epoch and
+    /// decayed on their schedules.
+    #[arg(long)]
+    priors: Option<PathBuf>,
+    /// JSON file of user-defined what-if scenarios, each injected at its epoch as a pinned
+    /// scenario. When resuming, entries at or before the checkpoint's last epoch are skipped,
+    /// since the checkpoint already holds them.
+    #[arg(long)]
+    what_if: Option<PathBuf>,
+    /// Resume the scenario tree from this checkpoint when it exists, instead of seeding
+    /// external scenarios and priors, and write it back on exit. Metrics counter totals
+    /// are kept beside it in <checkpoint>.metrics.json, alert gate state in
+    /// <checkpoint>.alerts.json.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Also write the checkpoint every this many epochs; 0 only writes it on exit.
+    #[arg(long, default_value_t = 0)]
+    checkpoint_every: u64,
+    /// Reconcile the scenario weight input against the live scenarios every this many
+    /// epochs, retracting weights no retirement took back; 0 only at startup.
+    #[arg(long, default_value_t = 100)]
+    weight_gc_every: u64,
+    /// Settle spend scenarios against each customer's later orders: contradicted branches
+    /// retire and confirmed ones are promoted.
+    #[arg(long)]
+    reconcile: bool,
+    /// Reweight the beam each epoch on which customers ordered: spend scenarios of
+    /// customers who did are scaled by this likelihood ratio, then renormalized.
+    #[arg(long)]
+    evidence_gain: Option<f64>,
+    /// Report a per-stage time breakdown every this many epochs; 0 disables.
+    #[arg(long, default_value_t = 0)]
+    profile_every: u64,
+    /// Write per-scenario lifecycle timelines as JSON to this file on exit.
+    #[arg(long)]
+    timeline_out: Option<PathBuf>,
+    /// Customer whose projected deltas go into the impact heatmap; repeatable.
+    #[arg(long)]
+    heatmap_key: Vec<u64>,
+    /// Write the per-epoch (key × scenario) impact heatmap as Parquet to this file on exit.
+    #[arg(long)]
+    heatmap_out: Option<PathBuf>,
+    /// Customer whose latest base, expected and tagged-scenario spend is published to
+    /// --redis-url as epochs close; repeatable.
+    #[arg(long)]
+    projection_key: Vec<u64>,
+    /// Redis server for published projections, such as redis://127.0.0.1/.
+    #[arg(long)]
+    redis_url: Option<String>,
+    /// Prefix of the Redis keys projections are written under.
+    #[arg(long, default_value = "tw:retail")]
+    redis_prefix: String,
+    /// Create scenarios no deeper than --lag-throttle-depth while the source lags by more
+    /// than this many ms, backfilling skipped expansions once it catches up.
+    #[arg(long)]
+    lag_throttle_ms: Option<u64>,
+    #[arg(long, default_value_t = 1)]
+    lag_throttle_depth: u32,
+    /// Rewrite this file with Prometheus-format metrics, labeled by domain, after every epoch.
+    #[arg(long)]
+    metrics_prom: Option<PathBuf>,
+    /// Wall time the source takes to produce one epoch; slower epochs add to its lag.
+    #[arg(long, default_value_t = 1_000)]
+    epoch_budget_ms: u64,
+    /// Keep timelines of retired scenarios for this many epochs.
+    #[arg(long, default_value_t = 50)]
+    timeline_epochs: u64,
+    /// Remember this many retired scenarios for --explain-scenario.
+    #[arg(long, default_value_t = 256)]
+    retired_history: usize,
+    /// On exit, log what became of this scenario, or its lineage while live; repeatable.
+    #[arg(long)]
+    explain_scenario: Vec<u64>,
+    /// Append each resolved spend prediction, labeled confirmed or refuted, to this JSONL file.
+    #[arg(long)]
+    labels_out: Option<PathBuf>,
+    /// Clamp each predicted delta to this magnitude.
+    #[arg(long)]
+    max_delta: Option<i64>,
+    /// Clamp each predicted delta relative to the key's base value, in basis points.
+    #[arg(long)]
+    max_uplift_bps: Option<i64>,
+    /// Lock file shared with a standby instance; only the holder emits alerts.
+    #[arg(long)]
+    leader_lock: Option<PathBuf>,
+    /// Standby takes over once the leader has not renewed for this long.
+    #[arg(long, default_value_t = 5_000)]
+    lease_ttl_ms: u64,
 }
 
+/// Domain label on events, metrics and alerts.
+const DOMAIN: &str = "retail";
+
 fn main() -> Result<()> {
     init_tracing();
     info!("retail_demo starting");
     let opts = RetailOpts::parse();
     info!(?opts, "retail opts");
+    let alert_policy = match &opts.alert_policy {
+        Some(path) => AlertPolicy::from_json_file(path)?,
+        None => AlertPolicy::default(),
+    };
+    // Condition windows per worker start from this empty tracker
+    let conditions = PersistenceTracker::new(alert_policy.persistence)?;
+    let rollout_cfg = match &opts.rollout {
+        Some(path) => RolloutConfig::from_json_file(path)?,
+        None => RolloutConfig::default(),
+    };
+    // A checkpointed tree already holds the external scenarios and priors it was seeded with
+    let checkpoint: Option<RetailScenarioState> = match &opts.checkpoint {
+        Some(path) if path.exists() => Some(read_json(path)?),
+        _ => None,
+    };
+    let external_scenarios = match &opts.external_scenarios {
+        Some(path) if checkpoint.is_none() => {
+            ExternalPlan::<SpendSeed>::from_json_file(path)?.scenarios
+        }
+        _ => Vec::new(),
+    };
+    let what_ifs = match &opts.what_if {
+        Some(path) => WhatIfPlan::<SpendSeed>::from_json_file(path)?.what_ifs,
+        None => Vec::new(),
+    };
+    let priors = match &opts.priors {
+        Some(path) if checkpoint.is_none() => {
+            PriorsConfig::<SpendSeed>::from_json_file(path, DOMAIN)?.priors
+        }
+        _ => Vec::new(),
+    };
+    let transform_cfg = match &opts.transforms {
+        Some(path) => TransformConfig::from_json_file(path)?,
+        None => TransformConfig::default(),
+    };
     let beam_cfg = RetailBeamConfig {
         max_depth: opts.max_depth,
         beam_width: opts.beam_width,
         min_prob: opts.min_prob,
         branch_prob: opts.branch_prob,
-        delta_multiplier: opts.delta_multiplier,
-        min_delta_cents: opts.min_delta_cents,
+        outage_prob: opts.outage_prob,
+        delta_multiplier_bps: opts.delta_multiplier_bps,
+        min_delta_cents: Money::from_cents(opts.min_delta_cents),
+        rounding: Rounding::default(),
+        global_cap: opts.global_cap,
+        max_keys: opts.max_keys,
+        epoch_decay: opts.epoch_decay,
+        ttl_epochs: opts.scenario_ttl,
+        depth_quotas: opts
+            .deep_quota
+            .map(|max_scenarios| DepthQuota { min_depth: opts.deep_min_depth, max_scenarios })
+            .into_iter()
+            .collect(),
+        tail_slots: opts.tail_slots,
+        max_children_per_event: opts.max_children_per_event,
+        weight_mode: if opts.fixed_point_weights { WeightMode::Fixed } else { WeightMode::Float },
+        renormalization: opts.renormalize,
     };
-    start_runtime(1, move |_index, worker| {
-        info!("retail_demo worker running");
+    let safety_caps = SafetyCaps {
+        max_active_scenarios: opts.max_active_scenarios,
+        max_overlay_rows: opts.max_overlay_rows,
+        max_alerts_per_epoch: opts.max_alerts_per_epoch,
+    };
+    let pipeline_weights: BTreeMap<String, u32> = opts
+        .pipeline_weight
+        .iter()
+        .map(|spec| scheduler::parse_weight(spec))
+        .collect::<Result<_>>()?;
+    let spend_reducer = ReducerRegistry::default().get(&opts.spend_reducer)?;
+    let spend_scalable = matches!(opts.spend_reducer.as_str(), "sum" | "count");
+    // The evictor keeps one total per customer, which only a sum can take back
+    anyhow::ensure!(
+        opts.evict_idle_epochs == 0 || opts.spend_reducer == "sum",
+        "--evict-idle-epochs needs --spend-reducer sum, got {}",
+        opts.spend_reducer
+    );
+    let evicting = opts.evict_idle_epochs > 0;
+    let guard_cfg = GuardConfig {
+        max_delta: opts.max_delta,
+        max_uplift_bps: opts.max_uplift_bps,
+        ..GuardConfig::default()
+    };
+    start_runtime(opts.workers, move |index, worker| {
+        info!(index, "retail_demo worker running");
 
         // Input for typed OrderPlaced events (base world)
         let mut input: InputSession<_, EventEnvelope<OrderPlaced>, isize> = InputSession::new();
-        // Predicted overlay input: (scenario_id, customer_id, delta_amount_cents)
-        let mut pred_input: InputSession<_, (u64, u64, i64), isize> = InputSession::new();
+        // Predicted overlay input: (scenario_id, customer_id, delta_amount_cents, variance)
+        let mut pred_input: InputSession<_, (u64, u64, i64, i64), isize> = InputSession::new();
+        // Totals of idle customers, weighted by cents and retracted from the base totals
+        let mut evict_input: InputSession<_, u64, isize> = InputSession::new();
         // Scenario weights: (scenario_id, probability)
-        let mut scen_weight_input: InputSession<_, (u64, f64), isize> = InputSession::new();
+        let mut scen_weight_input: LedgeredInput<_, (u64, f64)> = LedgeredInput::new();
+        // Retired scenario ids, retracted once their epoch is complete
+        let mut retire_input: RetirementInput<_> = RetirementInput::new();
+        // Channel spend shifts per (scenario, channel)
+        let mut channel_input: InputSession<_, (u64, u64, i64), isize> = InputSession::new();
+        // Exclusion group of each scenario that has one: (scenario_id, group)
+        let mut group_input: InputSession<_, (u64, u64), isize> = InputSession::new();
+        // One predicate row per live subscription, for pushdown
+        let mut predicate_input: InputSession<_, PredicateRow, isize> = InputSession::new();
+        // Scenarios whose views are materialized in lazy mode
+        let lazy_target = opts.lazy_scenarios.then_some(opts.target_customer);
+        let mut watches = ScenarioWatches::new(lazy_target, &opts.watch_scenario);
+        // Customer watches by (customer, shard), and shard counts of hot customers
+        let mut key_watch_input: InputSession<_, WatchRow, isize> = InputSession::new();
+        let mut fanout_input: InputSession<_, FanoutRow, isize> = InputSession::new();
         let mut probe = ProbeHandle::new();
+        // Stage probes in dataflow order, for sampled epoch breakdowns
+        let mut profiler = StageProfiler::new(opts.profile_every);
+        let mut reduce_probe = profiler.probe(Stage::BaseReduce);
+        let mut overlay_probe = profiler.probe(Stage::OverlayJoin);
+        let mut topk_probe = profiler.probe(Stage::TopK);
+        let mut alert_eval_probe = profiler.probe(Stage::AlertEval);
+        // Each subscription completes on its own probe
+        let mut subscriptions = Subscriptions::new();
+        let mut alert_probe = subscriptions.register("target_customer_topk");
+        let mut key_watch_probe = subscriptions.register("customer_watches");
+        let pipelines = opts.pipeline_steps.map(|steps| {
+            let mut pipelines = PipelineScheduler::new(steps);
+            for (name, probe) in subscriptions.probes() {
+                let weight = pipeline_weights.get(name).copied().unwrap_or(1);
+                pipelines.add(name, weight, probe.clone());
+            }
+            pipelines
+        });
 
-        let predictor = Arc::new(SpendGrowthPredictor::default());
-        let mut scenario_manager = RetailScenarioManager::new(beam_cfg.clone(), predictor);
-        let metrics = MetricsRegistry::default();
+        let spend_predictor = SpendGrowthPredictor {
+            uplift_sd_bps: opts.uplift_sd_bps,
+            uplift_tiers: opts.uplift_tier.clone(),
+            ..SpendGrowthPredictor::default()
+        };
+        let mut predictor = Arc::new(Guarded::new(spend_predictor, guard_cfg.clone()));
+        // The spend predictor needs a few orders per customer before it means anything
+        let warm_up = (opts.warm_up_observations > 0 || opts.warm_up_epochs > 0).then(|| {
+            Arc::new(WarmUpTracker::new(WarmUpConfig {
+                min_observations: opts.warm_up_observations,
+                min_epochs: opts.warm_up_epochs,
+            }))
+        });
+        let valve = Arc::new(SafetyValve::new(safety_caps.clone()));
+        let rollout = Arc::new(Rollout::new(rollout_cfg.clone()));
+        // Running base spend per customer, handed to predictors as context and
+        // shared with the non-negative check
+        let base_spend: Arc<Mutex<HashMap<u64, i64>>> = Arc::default();
+        // Order history per customer, read by predictors and snapshotted into labels
+        let customer_features = Arc::new(CustomerFeatures::default());
+        let scenario_manager = match checkpoint.clone() {
+            Some(state) => {
+                RetailScenarioManager::restore(state, beam_cfg.clone(), predictor.clone())
+            }
+            None => RetailScenarioManager::new(beam_cfg.clone(), predictor.clone()),
+        }
+        .with_beam(|beam| {
+            beam.with_safety_valve(valve.clone()).with_retired_history(opts.retired_history)
+        })
+        .with_elasticity_predictor(Arc::new(ConstantElasticityPredictor::default()))
+        .with_channel_predictor(Arc::new(RecaptureShiftPredictor::default()))
+        .with_beam(|beam| beam.with_feature_store(customer_features.clone()));
+        let scenario_manager = match &warm_up {
+            Some(tracker) => {
+                let fallback = opts.warm_up_fallback.then(|| {
+                    Arc::new(SpendGrowthPredictor {
+                        uplift_bps: 0,
+                        ..SpendGrowthPredictor::default()
+                    }) as Arc<dyn SpendDeltaPredictor>
+                });
+                scenario_manager.with_warm_up(WarmUpGate::new(tracker.clone(), fallback))
+            }
+            None => scenario_manager,
+        };
+        let scenario_manager = if opts.partition_beam {
+            scenario_manager.with_partition_key(|order: &OrderPlaced| order.customer_id)
+        } else {
+            scenario_manager
+        };
+        let scenario_manager = if opts.reject_negative {
+            let bases = Arc::clone(&base_spend);
+            scenario_manager.with_beam(|beam| {
+                beam.with_validator("non_negative", move |proposal| {
+                    let bases = bases.lock().unwrap_or_else(|e| e.into_inner());
+                    non_negative(proposal, &bases)
+                })
+            })
+        } else {
+            scenario_manager
+        };
+        let mut scenario_manager = match opts.lag_throttle_ms {
+            Some(max_lag_ms) => scenario_manager.with_beam(|beam| {
+                beam.with_lag_throttle(LagThrottleConfig {
+                    max_lag_ms,
+                    max_depth: opts.lag_throttle_depth,
+                    ..LagThrottleConfig::default()
+                })
+            }),
+            None => scenario_manager,
+        };
+        // Worker 0 alone holds the lease; the others own no side effects and stay standalone
+        let lease = opts.leader_lock.as_ref().filter(|_| index == 0).map(|path| {
+            let holder = format!("retail_demo:{}", std::process::id());
+            FileLease::new(path, holder, Duration::from_millis(opts.lease_ttl_ms))
+        });
+        let leadership = lease.as_ref().map_or_else(Leadership::standalone, FileLease::leadership);
+        // Renewed on its own thread, so a slow epoch cannot let the lease lapse
+        let _lease_keeper = lease.map(|lease| {
+            LeaseKeeper::spawn(lease, Duration::from_millis(opts.lease_ttl_ms / 3))
+        });
+        let registry = MetricsRegistry::default();
+        // Counters carry on from the checkpoint's totals rather than restarting at zero
+        if let Some(path) = opts.checkpoint.as_deref().filter(|_| checkpoint.is_some()) {
+            match read_json::<MetricsBaseline>(metrics_checkpoint(path)) {
+                Ok(baseline) => registry.restore_baseline(&baseline),
+                Err(err) => warn!(?err, "metrics baseline not restored; lifetime counters restart"),
+            }
+        }
+        // So do hourly caps, suppressed counts and escalation streaks
+        let gates = match opts.checkpoint.as_deref().filter(|_| checkpoint.is_some()) {
+            Some(path) => read_json::<GateCheckpoint>(gate_checkpoint(path)).unwrap_or_else(|err| {
+                warn!(?err, "alert gate state not restored; caps and streaks restart");
+                GateCheckpoint::default()
+            }),
+            None => GateCheckpoint::default(),
+        };
+        let metrics = registry.for_domain(DOMAIN);
+        let _reporter = (index == 0 && opts.report_interval_ms > 0).then(|| {
+            MetricsReporter::spawn(
+                registry.clone(),
+                Duration::from_millis(opts.report_interval_ms),
+                |sample| {
+                    let json = sample.to_json_line("retail_sample");
+                    info!(%json, "metrics sample");
+                },
+            )
+        });
+
+        let sampling = SamplingConfig { keep_bps: opts.sample_bps };
+        let sampler = EventSampler::new(sampling);
+        let keep_bps = sampler.keep_bps();
+        let sampled = sampling.is_sampled();
+        if sampled {
+            metrics.record_sample_rate(keep_bps as u64);
+            warn!(keep_bps, "input sampling enabled; totals across customers are estimates");
+        }
 
         // Build dataflow: per-customer totals and global top-K
-        let top_k = opts.top_k;
+        let topk_cfg = TopKConfig { k: opts.top_k, include_ties: opts.topk_include_ties };
         let prob_threshold = opts.prob_threshold;
+        let interval_z = opts.interval_z;
         let target_customer = opts.target_customer;
+        let lazy_scenarios = opts.lazy_scenarios;
+        let pushdown = opts.pushdown_subscriptions;
         let metrics_for_dataflow = metrics.clone();
+        let alert_valve = valve.clone();
+        let alert_rollout = rollout.clone();
+        let alert_policy = alert_policy.clone();
+        // Persistent conditions are tracked per (scenario, key) and alert as epochs close
+        let persistence = alert_policy.persistence;
+        let condition = Rc::new(RefCell::new(conditions.clone()));
+        let condition_rows = Rc::clone(&condition);
+        // Scenario top-K rank moves are reported as epochs close
+        let ranks = Rc::new(RefCell::new(RankTracker::new()));
+        let rank_rows = Rc::clone(&ranks);
+        // Scenario labels and tags, kept as scenarios come and go, for alert context
+        let labels = Rc::new(RefCell::new(BTreeMap::new()));
+        let alert_labels = Rc::clone(&labels);
+        let watch_labels = Rc::clone(&labels);
+        // Latest projections of watched customers are published as epochs close
+        let projections = ProjectionTracker::new(opts.projection_key.iter().copied())
+            .with_interval(opts.interval_z);
+        let projections = Rc::new(RefCell::new(projections));
+        let projecting = projections.borrow().is_enabled();
+        let projection_rows = Rc::clone(&projections);
+        let projected: Arc<BTreeSet<u64>> = Arc::new(opts.projection_key.iter().copied().collect());
+        let mut persistent_gate = AlertGate::restore(alert_policy.clone(), gates.persistent);
+        let alert_gate = Rc::new(RefCell::new(AlertGate::restore(alert_policy, gates.alerts)));
+        let subscription_gate = Rc::clone(&alert_gate);
+        let persistent_leadership = leadership.clone();
+        let watch_leadership = leadership.clone();
         worker.dataflow::<u64, _, _>(move |scope| {
             let orders = input.to_collection(scope);
 
             // Map typed orders to (customer_id, amount_cents)
-            let spends = orders.map(|env| {
-                let cust = env.payload.customer_id;
-                let amt = env.payload.total_cents();
-                (cust, amt)
-            });
+            let spends = orders
+                .map(|env| {
+                    let cust = env.payload.customer_id;
+                    let amt = env.payload.total_cents().cents();
+                    (cust, amt)
+                });
 
-            // Per-customer running totals
-            let totals = spends.reduce(|_cust, inputs, output| {
-                let mut sum: i64 = 0;
-                for (amt, cnt) in inputs.iter() {
-                    sum += *amt * (*cnt as i64);
-                }
-                output.push((sum, 1));
-            });
+            // Per-customer running totals; sampling keeps whole customers, so these are exact.
+            // With eviction the cents ride in the diff, so one retraction of a customer's
+            // total empties the key from the arrangement instead of leaving a zero row.
+            let totals = if evicting {
+                spends
+                    .explode(|(cust, amt)| Some((cust, amt as isize)))
+                    .concat(&evict_input.to_collection(scope).negate())
+                    .count()
+                    .map(|(cust, total)| (cust, total as i64))
+            } else {
+                aggregate(&spends, spend_reducer)
+            };
+            totals.probe_with(&mut reduce_probe);
+            // Watched keys are gathered to worker 0, which publishes projections, like alerts
+            if projecting {
+                let base_rows = Rc::clone(&projection_rows);
+                let keys = Arc::clone(&projected);
+                gather_alerts(&totals.filter(move |(cust, _sum)| keys.contains(cust))).inspect(
+                    move |((cust, sum), _epoch, diff)| {
+                        base_rows.borrow_mut().update_base(*cust, *sum, *diff);
+                    },
+                );
+            }
 
-            // Global top-K customers by spend (base world)
-            let topk = totals
-                .map(|(cust, sum)| ((), (sum, cust)))
-                .reduce(move |_unit, inputs, output| {
-                    let mut vals: Vec<((i64, u64), isize)> =
-                        inputs.iter().map(|(v, c)| (*v, *c)).collect();
-                    // Sort by sum descending
-                    vals.sort_by(|a, b| b.0 .0.cmp(&a.0 .0));
-                    for i in 0..top_k.min(vals.len()) {
-                        output.push((vals[i].0, 1));
+            // Spend across all customers is estimated from the kept ones; extremes don't scale
+            if sampled && spend_scalable {
+                sampled_total(&totals, keep_bps).inspect(move |((total, variance), _epoch, diff)| {
+                    if *diff > 0 {
+                        let interval = Interval::around(*total, *variance, interval_z);
+                        info!(
+                            estimate = total,
+                            low = interval.low,
+                            high = interval.high,
+                            keep_bps,
+                            "sampled total across customers"
+                        );
                     }
                 });
+            }
+
+            // Global top-K customers by spend (base world)
+            let topk = top_k(&totals.map(|(cust, sum)| ((), (sum, cust))), topk_cfg);
 
             topk.inspect(|x| info!(?x, "topk update"))
                 .probe_with(&mut probe);
 
+            // Spend per channel, and under each channel scenario
+            let channels = channel_spend(&orders.map(|env| {
+                (env.payload.channel.id(), env.payload.total_cents().cents())
+            }));
+            channels.inspect(|x| info!(?x, "channel spend"));
+
             // === Scenario overlays and scenario top-K ===
             // Predicted overlay deltas per (scenario, customer)
-            let pred = pred_input
-                .to_collection(scope)
-                .map(|(sid, cust, delta)| (sid, cust, delta));
+            let pred = pred_input.to_collection(scope);
 
-            // Sum predicted deltas per (scenario, customer)
-            let pred_totals = pred
-                .map(|(sid, cust, delta)| ((sid, cust), delta))
+            // Sum predicted deltas per (scenario, customer); independent overlays' variances add
+            let pred_sums = pred
+                .map(|(sid, cust, delta, variance)| ((sid, cust), (delta, variance)))
                 .reduce(|_key, inputs, output| {
                     let mut sum: i64 = 0;
-                    for (delta, cnt) in inputs.iter() {
+                    let mut variance: i64 = 0;
+                    for ((delta, var), cnt) in inputs.iter() {
                         sum += *delta * (*cnt as i64);
+                        variance = variance.saturating_add(*var * (*cnt as i64));
                     }
-                    output.push((sum, 1));
-                })
-                .map(|((sid, cust), delta)| (sid, cust, delta));
+                    output.push(((sum, variance), 1));
+                });
+            let pred_totals = pred_sums.map(|((sid, cust), (delta, _variance))| (sid, cust, delta));
+            let pred_variance = pred_sums
+                .map(|(sid_cust, (_delta, variance))| (sid_cust, variance))
+                .filter(|(_sid_cust, variance)| *variance != 0);
 
             // Scenario weights (sid -> max prob)
             let scen_weights = scen_weight_input
@@ -140,8 +755,51 @@ fn main() -> Result<()> {
                     output.push((maxp, 1));
                 });
 
-            // Active scenarios from weights
-            let scenarios_by_unit = scen_weights.map(|(sid, _p)| ((), sid));
+            // Retirement gates every per-scenario collection, whichever input retracts first
+            let retired = retire_input.to_collection(scope);
+            let live = live_scenarios(&scen_weights.map(|(sid, _)| sid), &retired);
+            let scen_weights = gated(&scen_weights, &live);
+            // Alternatives of one parent never weigh more than certainty together, in any view
+            let groups = gated(&group_input.to_collection(scope), &live);
+            let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+            let scen_weights = exclusive_weights(&weights_bps, &groups)
+                .map(|(sid, bps)| (sid, bps as f64 / BPS_SCALE as f64));
+            if projecting {
+                let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+                let deltas = pred_totals.map(|(sid, cust, delta)| ((sid, cust), delta));
+                let expected_rows = Rc::clone(&projection_rows);
+                let keys = Arc::clone(&projected);
+                let expected = expected_delta(&deltas, &weights_bps, &groups)
+                    .filter(move |(cust, _delta)| keys.contains(cust));
+                gather_alerts(&expected).inspect(move |((cust, delta), _epoch, diff)| {
+                    expected_rows.borrow_mut().update_expected_delta(*cust, *delta, *diff);
+                });
+                let variance_rows = Rc::clone(&projection_rows);
+                let keys = Arc::clone(&projected);
+                let variance = expected_variance(&pred_variance, &weights_bps, &groups)
+                    .filter(move |(cust, _variance)| keys.contains(cust));
+                gather_alerts(&variance).inspect(move |((cust, variance), _epoch, diff)| {
+                    let mut rows = variance_rows.borrow_mut();
+                    rows.update_expected_variance(*cust, *variance, *diff);
+                });
+            }
+
+            let shifts = channel_input
+                .to_collection(scope)
+                .map(|(sid, channel, delta)| (sid, (channel, delta)));
+            let shifts =
+                gated(&shifts, &live).map(|(sid, (channel, delta))| ((sid, channel), delta));
+            scenario_channel_spend(&channels, &shifts)
+                .inspect(|x| info!(?x, "scenario channel spend"));
+
+            // Active scenarios from weights; lazy mode only materializes watched ones
+            let watched = watches.input.to_collection(scope);
+            let materialized_weights = if lazy_scenarios {
+                materialized(&scen_weights, &watched)
+            } else {
+                scen_weights.clone()
+            };
+            let scenarios_by_unit = materialized_weights.map(|(sid, _p)| ((), sid));
 
             // Broadcast base top-K to each scenario
             let base_topk = topk.map(|(_unit, (sum, cust))| (cust, sum));
@@ -156,114 +814,1406 @@ fn main() -> Result<()> {
             let scenario_changed = pred_totals_by_cust
                 .join(&base_totals_by_cust)
                 .map(|(cust, ((sid, delta), base_sum))| (sid, (base_sum + delta, cust)));
+            let scenario_changed = gated(&scenario_changed, &live);
+            let scenario_changed = if lazy_scenarios {
+                materialized(&scenario_changed, &watched)
+            } else {
+                scenario_changed
+            };
+            scenario_changed.probe_with(&mut overlay_probe);
+            if projecting {
+                let scenario_rows = Rc::clone(&projection_rows);
+                let keys = Arc::clone(&projected);
+                let changed =
+                    scenario_changed.filter(move |(_sid, (_sum, cust))| keys.contains(cust));
+                gather_alerts(&changed).inspect(move |((sid, (sum, cust)), _epoch, diff)| {
+                    scenario_rows.borrow_mut().update_scenario(*sid, *cust, *sum, *diff);
+                });
+            }
 
             // Candidates are broadcast base top-K plus changed customers per scenario
             let candidates = base_topk_broadcast.concat(&scenario_changed);
+            let candidates = if pushdown {
+                let weights_bps = scen_weights.map(|(sid, prob)| (sid, prob_bps(prob)));
+                pushdown_candidates(&candidates, &weights_bps, &predicate_input.to_collection(scope))
+            } else {
+                candidates
+            };
 
             // Compute top-K per scenario from candidates
-            let scenario_topk = candidates
-                .map(|(sid, pair)| (sid, pair))
-                .reduce(move |_sid, inputs, output| {
-                    let mut vals: Vec<((i64, u64), isize)> =
-                        inputs.iter().map(|(v, c)| (*v, *c)).collect();
-                    vals.sort_by(|a, b| b.0 .0.cmp(&a.0 .0));
-                    for i in 0..top_k.min(vals.len()) {
-                        output.push((vals[i].0, 1));
-                    }
-                });
+            let scenario_ranks = ranked_top_k(&candidates, topk_cfg);
+            scenario_ranks.probe_with(&mut topk_probe);
+            // Rank moves are reported by worker 0, like alerts
+            gather_alerts(&scenario_ranks).inspect(move |((sid, row), _epoch, diff)| {
+                rank_rows.borrow_mut().update(*sid, *row, *diff);
+            });
+            let scenario_topk = scenario_ranks.map(|(sid, (_rank, cust, sum))| (sid, (sum, cust)));
 
             scenario_topk.inspect(|x| info!(?x, "scenario_topk update"));
 
+            // Customer watches, evaluated on the shards of each customer and gathered for delivery
+            let watch_alerts = evaluate_watches(
+                &scenario_topk,
+                &scen_weights.map(|(sid, prob)| (sid, prob_bps(prob))),
+                &key_watch_input.to_collection(scope),
+                &fanout_input.to_collection(scope),
+            );
+            let watch_alerts = watch_alerts
+                .map(|(watch, (sid, cust, sum, bps))| ((sid, cust), (watch, sum, bps)));
+            let watch_alerts = with_variance(&watch_alerts, &pred_variance);
+            let metrics_watches = metrics_for_dataflow.clone();
+            gather_alerts(&watch_alerts)
+                .inspect(move |(alert, _epoch, diff)| {
+                    if *diff <= 0 || !watch_leadership.is_leader() {
+                        return;
+                    }
+                    let ((sid, cust), ((watch, sum, bps), variance)) = *alert;
+                    let interval = Interval::around(sum, variance, interval_z);
+                    metrics_watches.inc_scenario_alerts(1);
+                    info!(
+                        domain = DOMAIN,
+                        watch,
+                        scenario = sid,
+                        label = watch_labels.borrow().get(&sid).map(String::as_str),
+                        customer = cust,
+                        spend = %Money::from_cents(sum),
+                        spend_low = %Money::from_cents(interval.low),
+                        spend_high = %Money::from_cents(interval.high),
+                        bps,
+                        "ALERT: watched customer in top-K within scenario"
+                    );
+                })
+                .probe_with(&mut key_watch_probe);
+
             // Subscription: target customer enters top-K in any scenario with prob >= p_min
-            let alerts = scenario_topk
-                .map(|(sid, (sum, cust))| (sid, (cust, sum)))
-                .filter(move |(_sid, (cust, _sum))| *cust == target_customer)
+            // The spend's variance rides along, for the interval in the alert payload
+            let targeted = scenario_topk
+                .map(|(sid, (sum, cust))| ((sid, cust), sum))
+                .filter(move |((_sid, cust), _sum)| *cust == target_customer);
+            let alerts = with_variance(&targeted, &pred_variance)
+                .map(|((sid, cust), (sum, variance))| (sid, (cust, sum, variance)))
                 .join(&scen_weights)
-                .filter(move |(_sid, ((_cust, _sum), prob))| *prob >= prob_threshold)
-                .map(|(sid, ((cust, sum), prob))| (sid, cust, sum, prob));
+                .filter(move |(_sid, (_row, prob))| *prob >= prob_threshold)
+                .map(|(sid, ((cust, sum, variance), prob))| (sid, cust, sum, prob, variance));
+            alerts.probe_with(&mut alert_eval_probe);
 
             let metrics_alerts = metrics_for_dataflow.clone();
-            alerts
+            gather_alerts(&alerts)
                 .inspect(move |a| {
-                    metrics_alerts.inc_scenario_alerts(1);
-                    info!(?a, "ALERT: target customer in top-K within scenario");
+                    if persistence != Persistence::Immediate {
+                        let ((sid, cust, sum, prob, variance), _epoch, diff) = *a;
+                        let value = (sum, prob, variance);
+                        condition_rows.borrow_mut().update((sid, cust), value, diff);
+                        return;
+                    }
+                    // A retraction is the condition clearing, not firing again
+                    if a.2 <= 0 {
+                        return;
+                    }
+                    // Standby keeps its views warm but leaves alerting to the leader
+                    if !leadership.is_leader() {
+                        return;
+                    }
+                    let epoch = EpochTime::new(a.1);
+                    match alert_rollout.route("target_customer_topk", epoch) {
+                        Route::Live => {}
+                        Route::LogOnly => {
+                            info!(?a, "shadow alert: target_customer_topk is log-only");
+                            return;
+                        }
+                        Route::Shed => return,
+                    }
+                    if !alert_valve.admit_alert(epoch) {
+                        return;
+                    }
+                    let (sid, cust, ..) = a.0;
+                    let key = format!("{sid}:{cust}");
+                    let mut gate = subscription_gate.borrow_mut();
+                    let decision = gate.offer(epoch, epoch.start(1_000), &key);
+                    if let Some(overflow) = gate.take_overflow() {
+                        info!(?overflow, "alert overflow summary");
+                    }
+                    if decision.is_delivered() {
+                        metrics_alerts.inc_scenario_alerts(1);
+                        let (_, _, sum, _prob, variance) = a.0;
+                        let spend = Money::from_cents(sum);
+                        let interval = Interval::around(sum, variance, interval_z);
+                        info!(
+                            domain = DOMAIN,
+                            ?a,
+                            label = alert_labels.borrow().get(&sid).map(String::as_str),
+                            %spend,
+                            spend_low = %Money::from_cents(interval.low),
+                            spend_high = %Money::from_cents(interval.high),
+                            ?decision,
+                            "ALERT: target customer in top-K within scenario"
+                        );
+                    } else {
+                        metrics_alerts.inc_alerts_suppressed(1);
+                    }
                 })
-                .probe_with(&mut probe);
+                .probe_with(&mut alert_probe);
+        });
+
+        // Worker 0 feeds every input and owns every side effect; the other workers only
+        // step their shards of the dataflow through the same epochs
+        if index > 0 {
+            for batch in 0..opts.batches {
+                let epoch = EpochTime::new(batch).next().get();
+                input.advance_to(epoch);
+                evict_input.advance_to(epoch);
+                pred_input.advance_to(epoch);
+                scen_weight_input.advance_to(epoch);
+                retire_input.advance_to(epoch);
+                channel_input.advance_to(epoch);
+                group_input.advance_to(epoch);
+                predicate_input.advance_to(epoch);
+                watches.input.advance_to(epoch);
+                key_watch_input.advance_to(epoch);
+                fanout_input.advance_to(epoch);
+                input.flush();
+                evict_input.flush();
+                pred_input.flush();
+                scen_weight_input.flush();
+                retire_input.flush();
+                channel_input.flush();
+                group_input.flush();
+                predicate_input.flush();
+                watches.input.flush();
+                key_watch_input.flush();
+                fanout_input.flush();
+                while probe.less_than(input.time()) {
+                    worker.step();
+                }
+            }
+            return;
+        }
+
+        // User code run at each epoch boundary, once the epoch's metrics are final
+        let mut epoch_hooks = EpochHooks::new();
+        for (index, command) in opts.epoch_hook_cmd.iter().enumerate() {
+            let hook = hooks::command_hook(command.clone());
+            epoch_hooks.on_epoch_complete(format!("cmd{index}"), hook);
+        }
+        let mut dedup = Deduplicator::new(DedupConfig {
+            ttl_epochs: opts.dedup_ttl_epochs,
+            ..DedupConfig::default()
         });
 
         // Synthetic generator
-        let mut epoch: u64 = 0;
+        let mut epoch = EpochTime::ZERO;
+        // Log-only windows run from the start of the run, not from a view's first alert
+        rollout.activate(epoch);
+        // Estimated source lag: wall time spent beyond --epoch-budget-ms, carried over
+        let mut source_lag_ms = 0u64;
         let customers = opts.customers;
+        let mut ordered_this_epoch: HashSet<u64> = HashSet::new();
+        // Units sold per SKU, the baseline for price-change scenarios
+        let mut sku_units: HashMap<u64, i64> = HashMap::new();
+        let mut channel_totals: BTreeMap<ChannelId, i64> = BTreeMap::new();
+        let mut timelines = ScenarioTimelines::new(opts.timeline_epochs);
+        let mut heatmap = ImpactHeatmap::new(opts.heatmap_key.iter().copied());
+        let mut projection_sink = match opts
+            .redis_url
+            .as_deref()
+            .map(|url| RedisProjectionSink::connect(url, opts.redis_prefix.as_str()))
+        {
+            Some(Err(err)) => {
+                warn!(?err, "projection publishing disabled");
+                None
+            }
+            connected => connected.and_then(Result::ok),
+        };
+        let control: ControlPlane<RetailCommand> = ControlPlane::new();
+        if let Some(addr) = &opts.control_addr {
+            match control.serve(addr.as_str()) {
+                Ok(local) => info!(%local, "control plane listening"),
+                Err(err) => warn!(?err, "control plane listener disabled"),
+            }
+        }
+        let bulk_control: ControlPlane<BulkOp> = ControlPlane::new();
+        // Watches may be added or withdrawn at runtime; the synthetic ones go in first
+        let watch_control: ControlPlane<WatchChange> = ControlPlane::new();
+        let mut watch_shards = WatchShards::new(ShardConfig {
+            per_shard: opts.watches_per_shard,
+            max_fanout: opts.max_watch_fanout,
+        });
+        for id in 0..opts.customer_watches {
+            watch_control.submit(WatchChange::Add(KeyWatch {
+                id,
+                key: id % customers,
+                predicate: SubscriptionPredicate {
+                    min_prob_bps: prob_bps(opts.prob_threshold),
+                    min_value: None,
+                },
+            }));
+        }
+        // Target-customer membership needs every row of a heavy enough scenario
+        control.submit(RetailCommand::Subscribe {
+            predicate: SubscriptionPredicate {
+                min_prob_bps: prob_bps(opts.prob_threshold),
+                min_value: None,
+            },
+        });
+        // Training labels: predictions resolved against realized spend when scenarios retire
+        let mut label_sink = match opts.labels_out.as_ref().map(JsonlSink::open) {
+            Some(Err(err)) => {
+                warn!(?err, "outcome labels disabled");
+                None
+            }
+            opened => opened.and_then(Result::ok),
+        };
+        let mut labeler = OutcomeLabeler::default().with_features(
+            customer_features.clone(),
+            CustomerFeatures::NAMES.iter().map(|name| name.to_string()).collect(),
+        );
+        let mut evictor = IdleKeyEvictor::new(EvictionConfig { idle_epochs: opts.evict_idle_epochs });
+        let mut key_archive = match opts.evict_archive.as_ref().map(JsonlSink::open) {
+            Some(Err(err)) => {
+                warn!(?err, "evicted key archive disabled");
+                None
+            }
+            opened => opened.and_then(Result::ok),
+        };
+        let validator = order_rules(ValidationConfig {
+            max_skew_ms: opts.max_ts_skew_ms,
+            disabled: opts.disable_rule.clone(),
+            ..ValidationConfig::default()
+        });
+        let transforms = TransformPipeline::new();
+        let transforms = if opts.split_order_lines {
+            transforms.with_transform("split_order_lines", split_order_lines)
+        } else {
+            transforms
+        };
+        let mut transforms = transforms.with_rules(&transform_cfg);
+        // Recent orders per customer, packaged with alerts as replay bundles
+        let replay_epochs = if opts.replay_bundles.is_some() { opts.replay_epochs } else { 0 };
+        let mut recent_orders: RecentEvents<u64, OrderPlaced> = RecentEvents::new(replay_epochs);
+        let mut dead_letters = match opts.dead_letters.as_ref().map(JsonlSink::open) {
+            Some(Err(err)) => {
+                warn!(?err, "dead-letter file disabled");
+                None
+            }
+            opened => opened.and_then(Result::ok),
+        };
+        // What-ifs the checkpoint already holds, injected at or before its last epoch
+        let injected_through = scenario_manager.advanced();
         for batch in 0..opts.batches {
             let epoch_timer = EpochTimer::start();
             let completed_epoch = epoch;
+            // Control-plane changes apply from the start of an epoch, never mid-epoch
+            let acks = control.drain(epoch, |command| {
+                match command {
+                    RetailCommand::Subscribe { predicate } => {
+                        predicate_input.insert(predicate.row())
+                    }
+                    RetailCommand::Unsubscribe { predicate } => {
+                        predicate_input.remove(predicate.row())
+                    }
+                    RetailCommand::Config { config } => scenario_manager.set_config(config),
+                    RetailCommand::Predictor { uplift_bps, uplift_sd_bps, uplift_tiers } => {
+                        let swapped = SpendGrowthPredictor {
+                            uplift_bps,
+                            uplift_sd_bps,
+                            uplift_tiers,
+                            ..SpendGrowthPredictor::default()
+                        };
+                        predictor = Arc::new(Guarded::new(swapped, guard_cfg.clone()));
+                        scenario_manager.set_predictor(predictor.clone());
+                    }
+                }
+                Ok(())
+            });
+            for ack in acks {
+                info!(?ack, "control change applied");
+            }
+            // Each watch also contributes a pushdown predicate, so its rows are ranked
+            let acks = watch_control.drain(epoch, |change| {
+                if let Some(old) = watch_shards.get(change.id()) {
+                    predicate_input.remove(old.predicate.row());
+                }
+                if let WatchChange::Add(watch) = &change {
+                    predicate_input.insert(watch.predicate.row());
+                }
+                let changes = watch_shards.apply(change);
+                for (row, diff) in changes.watches {
+                    key_watch_input.update(row, diff);
+                }
+                for (row, diff) in changes.fanout {
+                    fanout_input.update(row, diff);
+                }
+                Ok(())
+            });
+            if !acks.is_empty() {
+                info!(applied = acks.len(), watches = watch_shards.len(), "watch changes applied");
+            }
+            if batch == 0 {
+                let outcome: RetailExpansionOutcome = scenario_manager.resumed().into();
+                if !outcome.created.is_empty() {
+                    info!(scenarios = outcome.created.len(), "resumed scenario checkpoint");
+                    for meta in &outcome.created {
+                        tag_projection(&projections, meta);
+                    }
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut channel_input,
+                        &mut group_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &mut watches,
+                        &labels,
+                    );
+                }
+                for scenario in &external_scenarios {
+                    let outcome = scenario_manager.seed_external(epoch, scenario);
+                    for meta in &outcome.created {
+                        info!(?meta, "seeded external scenario");
+                        tag_projection(&projections, meta);
+                    }
+                    for rejection in &outcome.rejected {
+                        warn!(?rejection, "external scenario rejected");
+                    }
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut channel_input,
+                        &mut group_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &mut watches,
+                        &labels,
+                    );
+                }
+                for prior in &priors {
+                    let outcome = scenario_manager.seed_prior(epoch, prior);
+                    for meta in &outcome.created {
+                        info!(?meta, "seeded prior scenario");
+                        tag_projection(&projections, meta);
+                    }
+                    for rejection in &outcome.rejected {
+                        warn!(?rejection, "prior scenario rejected");
+                    }
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut channel_input,
+                        &mut group_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &mut watches,
+                        &labels,
+                    );
+                }
+                // Resume and seeding feed weights separately; settle them against the tree
+                collect_weights(epoch, &mut scen_weight_input, &scenario_manager);
+            }
+            let due = |at_epoch: EpochTime| {
+                at_epoch == epoch && injected_through.map_or(true, |through| at_epoch > through)
+            };
+            for what_if in what_ifs.iter().filter(|what_if| due(what_if.at_epoch)) {
+                let outcome = scenario_manager.inject_scenario(
+                    epoch,
+                    &what_if.rows,
+                    what_if.probability,
+                    &what_if.label,
+                );
+                for meta in &outcome.created {
+                    info!(?meta, "injected what-if scenario");
+                }
+                for rejection in &outcome.rejected {
+                    warn!(?rejection, "what-if scenario rejected");
+                }
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut channel_input,
+                    &mut group_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &mut watches,
+                    &labels,
+                );
+            }
+            if batch == opts.bulk_op_at {
+                for op in &opts.bulk_op {
+                    bulk_control.submit(op.clone());
+                }
+            }
+            // Operator bulk operations apply at the boundary too, after any resume
+            let acks = bulk_control.drain(epoch, |op| {
+                let outcome: RetailExpansionOutcome =
+                    scenario_manager.apply_bulk(epoch, op)?.into();
+                if let Some(audit) = scenario_manager.bulk_audit().last() {
+                    info!(?audit, "bulk operation applied");
+                }
+                count_retired(&metrics, &outcome.retired);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut channel_input,
+                    &mut group_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &mut watches,
+                    &labels,
+                );
+                Ok(())
+            });
+            for ack in acks.into_iter().filter(|ack| ack.error.is_some()) {
+                warn!(?ack, "bulk operation not applied");
+            }
+            let outcome: RetailExpansionOutcome = scenario_manager.decay_priors(epoch).into();
+            for (meta, _) in &outcome.retired {
+                info!(scenario = meta.id, prior = ?meta.external, "prior scenario decayed out");
+            }
+            count_retired(&metrics, &outcome.retired);
+            apply_outcome(
+                epoch,
+                &outcome,
+                &mut pred_input,
+                &mut scen_weight_input,
+                &mut retire_input,
+                &mut channel_input,
+                &mut group_input,
+                &mut timelines,
+                &mut heatmap,
+                &mut watches,
+                &labels,
+            );
+            let outcome: RetailExpansionOutcome = scenario_manager.advance_epoch(epoch).into();
+            count_retired(&metrics, &outcome.retired);
+            apply_outcome(
+                epoch,
+                &outcome,
+                &mut pred_input,
+                &mut scen_weight_input,
+                &mut retire_input,
+                &mut channel_input,
+                &mut group_input,
+                &mut timelines,
+                &mut heatmap,
+                &mut watches,
+                &labels,
+            );
+            let was_throttled = scenario_manager.is_throttled();
+            for outcome in scenario_manager.observe_lag(epoch, source_lag_ms) {
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut channel_input,
+                    &mut group_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &mut watches,
+                    &labels,
+                );
+            }
+            if scenario_manager.is_throttled() != was_throttled {
+                info!(
+                    %epoch,
+                    source_lag_ms,
+                    throttled = !was_throttled,
+                    deferred = scenario_manager.deferred_len(),
+                    "speculation throttle changed"
+                );
+            }
+            profiler.begin_epoch(completed_epoch);
+            if opts.price_hike_bps != 0 && batch == opts.batches / 2 {
+                let sku_id = opts.price_hike_sku;
+                let old_cents = Money::from_cents(1000 + ((sku_id % 10) as i64) * 250);
+                let change = PriceChanged {
+                    sku_id,
+                    old_cents,
+                    new_cents: old_cents + old_cents.saturating_scale_bps(opts.price_hike_bps),
+                    ts_ms: epoch.start(1_000),
+                };
+                let base_qty = sku_units.get(&sku_id).copied();
+                let outcome = scenario_manager.expand_price_change(epoch, &change, base_qty);
+                for delta in &outcome.sku_overlays_added {
+                    info!(?change, ?delta, "price-change scenario");
+                }
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                if outcome.shed > 0 {
+                    metrics.inc_scenarios_shed(outcome.shed as u64);
+                    let usage = valve.usage();
+                    warn!(shed = outcome.shed, ?usage, "safety cap bound; shed lightest scenarios");
+                }
+                if !outcome.rejected.is_empty() {
+                    metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                    if opts.log_rejections {
+                        for rejection in &outcome.rejected {
+                            info!(%epoch, ?rejection, "validator rejected proposed scenario");
+                        }
+                    }
+                }
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut channel_input,
+                    &mut group_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &mut watches,
+                    &labels,
+                );
+            }
+            if opts.web_outage && batch == opts.batches / 2 {
+                // Spend already booked stays; the outage moves what each channel would take
+                let epochs_so_far = (batch as i64).max(1);
+                let projected_spend: Vec<_> = channel_totals
+                    .iter()
+                    .map(|(&channel, &cents)| {
+                        (channel, (cents / epochs_so_far).saturating_mul(opts.outage_epochs.max(0)))
+                    })
+                    .collect();
+                let outcome =
+                    scenario_manager.expand_channel_outage(epoch, Channel::Web, &projected_spend);
+                for delta in &outcome.channel_overlays_added {
+                    info!(?delta, "web outage scenario");
+                }
+                metrics.inc_scenario_created(outcome.created.len() as u64);
+                count_retired(&metrics, &outcome.retired);
+                metrics.record_active_peak(scenario_manager.active_len() as u64);
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut channel_input,
+                    &mut group_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &mut watches,
+                    &labels,
+                );
+            }
             for i in 0..opts.batch_size {
                 // Spread spend across customers with some skew
+                let ingest_started = Instant::now();
                 let cust = (batch * 13 + i * 7) % customers;
                 let base: i64 = 1000 + ((i % 10) as i64) * 250; // cents
                 let bonus: i64 = if cust % 7 == 0 { 2000 } else { 0 };
                 let amount = base + bonus;
+                let channel = match i % 5 {
+                    0 | 1 => Channel::Web,
+                    2 => Channel::Marketplace,
+                    _ => Channel::Store(cust % 3),
+                };
                 let order = OrderPlaced {
                     order_id: batch * 10_000 + i,
                     customer_id: cust,
-                    lines: vec![OrderLine { sku_id: (i % 100) as u64, qty: 1, price_cents: amount }],
-                    ts_ms: epoch * 1000,
+                    lines: vec![OrderLine {
+                        sku_id: (i % 100) as u64,
+                        qty: 1,
+                        price_cents: Money::from_cents(amount),
+                    }],
+                    ts_ms: epoch.start(1_000),
+                    channel,
                 };
-                let outcome = scenario_manager.expand_order(&order);
-                metrics.inc_scenario_created(outcome.created.len() as u64);
-                metrics.inc_scenario_retired(outcome.retired.len() as u64);
-                let overlay_changes = outcome.overlays_added.len() + outcome.overlays_removed.len();
-                if overlay_changes > 0 {
-                    metrics.inc_predicted_events(overlay_changes as u64);
-                }
-                metrics.record_active_peak(scenario_manager.active_len() as u64);
-                let env = EventEnvelope {
-                    meta: EventMeta {
-                        domain: "retail".to_string(),
-                        kind: "OrderPlaced".to_string(),
-                        epoch,
-                        source: "synthetic".to_string(),
-                        key: None,
-                    },
-                    payload: order,
+                let meta = EventMeta {
+                    domain: DOMAIN.to_string(),
+                    kind: "OrderPlaced".to_string(),
+                    epoch,
+                    source: "synthetic".to_string(),
+                    key: Some(format!("order:{}", order.order_id)),
                 };
-                input.insert(env);
-                metrics.inc_base_events(1);
-
-                for meta in &outcome.created {
-                    scen_weight_input.insert((meta.id, meta.weight.0));
-                }
+                profiler.record(Stage::IngestDecode, ingest_started.elapsed());
+                let env = EventEnvelope { meta, payload: order };
+                for EventEnvelope { meta, payload: order } in transforms.apply(env) {
+                    if !dedup.admit(&meta, order.ts_ms) {
+                        metrics.inc_duplicate_events(1);
+                        continue;
+                    }
+                    let env = EventEnvelope { meta, payload: order };
+                    if !validator.admit(&env, dead_letters.as_mut()) {
+                        metrics.inc_invalid_events(1);
+                        continue;
+                    }
+                    let EventEnvelope { meta, payload: order } = env;
+                    // Sample whole customers, so each kept customer's totals are exact
+                    if !sampler.admit_key(&format!("customer:{}", order.customer_id)) {
+                        metrics.inc_sampled_out_events(1);
+                        continue;
+                    }
+                    ordered_this_epoch.insert(order.customer_id);
+                    if opts.reconcile {
+                        let outcome = scenario_manager.reconcile(epoch, &order);
+                        if !outcome.retired.is_empty() || !outcome.reweighted.is_empty() {
+                            info!(
+                                %epoch,
+                                order = order.order_id,
+                                retired = outcome.retired.len(),
+                                promoted = outcome.reweighted.len(),
+                                "scenarios reconciled against order"
+                            );
+                        }
+                        count_retired(&metrics, &outcome.retired);
+                        apply_outcome(
+                            epoch,
+                            &outcome,
+                            &mut pred_input,
+                            &mut scen_weight_input,
+                            &mut retire_input,
+                            &mut channel_input,
+                            &mut group_input,
+                            &mut timelines,
+                            &mut heatmap,
+                            &mut watches,
+                            &labels,
+                        );
+                    }
+                    let spent = spend_of(&base_spend, order.customer_id);
+                    let expand_started = Instant::now();
+                    let outcome = scenario_manager.expand_order(epoch, &order, Some(spent));
+                    if let Some(tracker) = &warm_up {
+                        tracker.observe(epoch, order.customer_id);
+                    }
+                    profiler.record(Stage::Expansion, expand_started.elapsed());
+                    let mut bases = base_spend.lock().unwrap_or_else(|e| e.into_inner());
+                    bases.insert(order.customer_id, spent + order.total_cents().cents());
+                    drop(bases);
+                    customer_features.record(order.customer_id, order.total_cents().cents());
+                    for line in &order.lines {
+                        *sku_units.entry(line.sku_id).or_default() += line.qty as i64;
+                    }
+                    let channel_spent = channel_totals.entry(order.channel.id()).or_default();
+                    *channel_spent += order.total_cents().cents();
+                    metrics.inc_scenario_created(outcome.created.len() as u64);
+                    count_retired(&metrics, &outcome.retired);
+                    if outcome.shed > 0 {
+                        metrics.inc_scenarios_shed(outcome.shed as u64);
+                        let usage = valve.usage();
+                        warn!(
+                            shed = outcome.shed,
+                            ?usage,
+                            "safety cap bound; shed lightest scenarios"
+                        );
+                    }
+                    if !outcome.rejected.is_empty() {
+                        metrics.inc_scenarios_rejected(outcome.rejected.len() as u64);
+                        if opts.log_rejections {
+                            for rejection in &outcome.rejected {
+                                info!(%epoch, ?rejection, "validator rejected proposed scenario");
+                            }
+                        }
+                    }
+                    let overlay_changes =
+                        outcome.overlays_added.len() + outcome.overlays_removed.len();
+                    if overlay_changes > 0 {
+                        metrics.inc_predicted_events(overlay_changes as u64);
+                    }
+                    metrics.inc_predictions_clamped(predictor.guard().take_clamped());
+                    if let Some(gate) = scenario_manager.warm_up() {
+                        metrics.inc_predictions_warming_up(gate.take_warming());
+                    }
+                    metrics.record_active_peak(scenario_manager.active_len() as u64);
+                    if let Some(sink) = label_sink.as_mut() {
+                        for delta in &outcome.overlays_added {
+                            let customer = delta.customer_id;
+                            let base = spend_of(&base_spend, customer);
+                            let predicted = delta.delta_cents.cents();
+                            labeler.record_prediction(
+                                epoch,
+                                delta.scenario_id,
+                                customer,
+                                base,
+                                predicted,
+                            );
+                        }
+                        for (retired, _) in &outcome.retired {
+                            let current = |customer| spend_of(&base_spend, customer);
+                            let Some(example) = labeler.resolve(epoch, retired.id, current) else {
+                                continue;
+                            };
+                            if let Err(err) = sink.write(&example) {
+                                warn!(?err, "failed to write outcome label");
+                            }
+                        }
+                    }
+                    evictor.touch(epoch, order.customer_id, order.total_cents().cents());
+                    let env = EventEnvelope { meta, payload: order };
+                    recent_orders.record(env.payload.customer_id, env.clone());
+                    input.insert(env);
+                    metrics.inc_base_events(1);
 
-                for delta in &outcome.overlays_added {
-                    pred_input.insert((delta.scenario_id, delta.customer_id, delta.delta_cents));
+                    apply_outcome(
+                        epoch,
+                        &outcome,
+                        &mut pred_input,
+                        &mut scen_weight_input,
+                        &mut retire_input,
+                        &mut channel_input,
+                        &mut group_input,
+                        &mut timelines,
+                        &mut heatmap,
+                        &mut watches,
+                        &labels,
+                    );
                 }
-
-                for delta in &outcome.overlays_removed {
-                    pred_input.remove((delta.scenario_id, delta.customer_id, delta.delta_cents));
+            }
+            if let Some(gain) = opts.evidence_gain.filter(|_| !ordered_this_epoch.is_empty()) {
+                let ordered = &ordered_this_epoch;
+                let outcome: RetailExpansionOutcome = scenario_manager
+                    .observe_evidence(|_, overlay| match overlay {
+                        RetailOverlay::Spend(delta) if ordered.contains(&delta.customer_id) => gain,
+                        _ => 1.0,
+                    })
+                    .into();
+                if !outcome.reweighted.is_empty() {
+                    info!(
+                        %epoch,
+                        customers = ordered_this_epoch.len(),
+                        reweighted = outcome.reweighted.len(),
+                        "scenario weights updated from evidence"
+                    );
                 }
-
-                for meta in &outcome.retired {
-                    scen_weight_input.remove((meta.id, meta.weight.0));
+                apply_outcome(
+                    epoch,
+                    &outcome,
+                    &mut pred_input,
+                    &mut scen_weight_input,
+                    &mut retire_input,
+                    &mut channel_input,
+                    &mut group_input,
+                    &mut timelines,
+                    &mut heatmap,
+                    &mut watches,
+                    &labels,
+                );
+            }
+            ordered_this_epoch.clear();
+            metrics.record_transforms(transforms.stats());
+            for evicted in evictor.expire(completed_epoch) {
+                evict_input.update(evicted.key, evicted.value as isize);
+                base_spend.lock().unwrap_or_else(|e| e.into_inner()).remove(&evicted.key);
+                customer_features.forget(evicted.key);
+                if let Some(archive) = key_archive.as_mut() {
+                    let archived = ArchivedKey {
+                        key: evicted.key,
+                        last_seen: evicted.last_seen,
+                        evicted_at: completed_epoch,
+                        value: evicted.value,
+                    };
+                    if let Err(err) = archive.write(&archived) {
+                        warn!(?err, "failed to archive evicted customer");
+                    }
                 }
+                metrics.inc_keys_evicted(1);
+            }
+            let gc_every = opts.weight_gc_every;
+            if gc_every > 0 && completed_epoch.next().get() % gc_every == 0 {
+                collect_weights(completed_epoch, &mut scen_weight_input, &scenario_manager);
             }
-            epoch += 1;
-            input.advance_to(epoch);
-            pred_input.advance_to(epoch);
-            scen_weight_input.advance_to(epoch);
+            heatmap.snapshot(epoch);
+            epoch = epoch.next();
+            dedup.advance(epoch);
+            recent_orders.advance(epoch);
+            timelines.advance(epoch);
+            input.advance_to(epoch.get());
+            evict_input.advance_to(epoch.get());
+            pred_input.advance_to(epoch.get());
+            scen_weight_input.advance_to(epoch.get());
+            retire_input.advance_to(epoch.get());
+            channel_input.advance_to(epoch.get());
+            group_input.advance_to(epoch.get());
+            predicate_input.advance_to(epoch.get());
+            watches.input.advance_to(epoch.get());
+            key_watch_input.advance_to(epoch.get());
+            fanout_input.advance_to(epoch.get());
             input.flush();
+            evict_input.flush();
             pred_input.flush();
             scen_weight_input.flush();
+            retire_input.flush();
+            channel_input.flush();
+            group_input.flush();
+            predicate_input.flush();
+            watches.input.flush();
+            key_watch_input.flush();
+            fanout_input.flush();
+            let flushed_at = Instant::now();
+            profiler.drive(worker, input.time(), flushed_at);
             // Drive the dataflow until this epoch completes
             while probe.less_than(input.time()) {
                 worker.step();
             }
+            retire_input.retract_before(&epoch.get());
+            match &pipelines {
+                Some(pipelines) => {
+                    pipelines.drive(worker, &epoch.get(), flushed_at, &metrics);
+                }
+                None => subscriptions.drive(worker, &epoch.get(), flushed_at, &metrics),
+            }
+            let updates = projections.borrow_mut().close_epoch();
+            if let Some(sink) = projection_sink.as_mut() {
+                if let Err(err) = sink.write(&updates) {
+                    warn!(?err, "failed to publish projections");
+                }
+            }
+            for change in ranks.borrow_mut().close_epoch() {
+                info!(
+                    scenario = change.group,
+                    customer = change.key,
+                    from = ?change.from,
+                    to = ?change.to,
+                    value = change.value,
+                    "scenario top-K rank change"
+                );
+            }
+            let fired = condition.borrow_mut().close_epoch(completed_epoch);
+            for ((sid, cust), (sum, prob, variance)) in fired {
+                if !persistent_leadership.is_leader() {
+                    continue;
+                }
+                match rollout.route("target_customer_topk", completed_epoch) {
+                    Route::Live => {}
+                    Route::LogOnly => {
+                        info!(
+                            scenario = sid,
+                            customer = cust,
+                            "shadow alert: target_customer_topk is log-only"
+                        );
+                        continue;
+                    }
+                    Route::Shed => continue,
+                }
+                if !valve.admit_alert(completed_epoch) {
+                    continue;
+                }
+                let key = format!("{sid}:{cust}");
+                let ts = completed_epoch.start(1_000);
+                let decision = persistent_gate.offer(completed_epoch, ts, &key);
+                if let Some(overflow) = persistent_gate.take_overflow() {
+                    info!(?overflow, "alert overflow summary");
+                }
+                if decision.is_delivered() {
+                    metrics.inc_scenario_alerts(1);
+                    let spend = Money::from_cents(sum);
+                    let interval = Interval::around(sum, variance, opts.interval_z);
+                    info!(
+                        scenario = sid,
+                        label = labels.borrow().get(&sid).map(String::as_str),
+                        customer = cust,
+                        %spend,
+                        spend_low = %Money::from_cents(interval.low),
+                        spend_high = %Money::from_cents(interval.high),
+                        prob,
+                        ?persistence,
+                        ?decision,
+                        domain = DOMAIN,
+                        "ALERT: target customer persistently in top-K within scenario"
+                    );
+                    if let Some(dir) = &opts.replay_bundles {
+                        let bundle = ReplayBundle {
+                            alert: "target_customer_topk",
+                            domain: DOMAIN,
+                            epoch: completed_epoch,
+                            scenario_id: sid,
+                            key: cust,
+                            events: recent_orders.get(&cust),
+                            lineage: timelines.lineage(sid),
+                            overlays: scenario_manager.lineage_overlays(sid),
+                            predicted: PredictedRow { value: sum, probability: prob, variance },
+                            config: scenario_manager.config(),
+                        };
+                        match bundle.write_to(dir) {
+                            Ok(path) => info!(path = %path.display(), "wrote replay bundle"),
+                            Err(err) => warn!(?err, "failed to write replay bundle"),
+                        }
+                    }
+                } else {
+                    metrics.inc_alerts_suppressed(1);
+                }
+            }
+            // An hour with no later alert still reports what it held back
+            let ts = completed_epoch.start(1_000);
+            for gate in [&mut persistent_gate, &mut *alert_gate.borrow_mut()] {
+                if let Some(overflow) = gate.close_window(ts) {
+                    info!(?overflow, "alert overflow summary");
+                }
+            }
+            let alerts_shed = valve.take_alerts_shed();
+            if alerts_shed > 0 {
+                metrics.inc_alerts_shed(alerts_shed);
+                warn!(epoch = %completed_epoch, alerts_shed, "alert cap bound; dropped alerts");
+            }
             let elapsed = epoch_timer.elapsed();
+            source_lag_ms =
+                (source_lag_ms + elapsed.as_millis() as u64).saturating_sub(opts.epoch_budget_ms);
+            if let Some(tracker) = &warm_up {
+                let ready = tracker.ready_fraction(completed_epoch);
+                metrics.record_predictor_readiness("spend", ready);
+            }
             let snapshot = metrics.snapshot();
+            if let Some(path) = &opts.metrics_prom {
+                if let Err(err) = std::fs::write(path, registry.to_prometheus_text()) {
+                    warn!(?err, path = %path.display(), "failed to write Prometheus metrics");
+                }
+            }
             let json = snapshot.to_json_line("retail_epoch", Some(elapsed));
-            info!(epoch = completed_epoch, %json, "epoch complete");
+            info!(epoch = %completed_epoch, %json, "epoch complete");
+            epoch_hooks.run(completed_epoch, &snapshot);
+            let occupancy = scenario_manager.depth_occupancy();
+            info!(epoch = %completed_epoch, ?occupancy, "active scenarios by depth");
+            if let Some(breakdown) = profiler.finish_epoch(completed_epoch) {
+                let json = breakdown.to_json_line("retail_stages");
+                info!(%json, "stage breakdown");
+            }
+            if let Some(path) = &opts.checkpoint {
+                let every = opts.checkpoint_every;
+                if every > 0 && completed_epoch.next().get() % every == 0 {
+                    let gates = GateCheckpoint::of(&alert_gate.borrow(), &persistent_gate);
+                    save_checkpoint(path, &scenario_manager, &registry, &gates);
+                }
+            }
+        }
+        if let Some(path) = &opts.checkpoint {
+            let gates = GateCheckpoint::of(&alert_gate.borrow(), &persistent_gate);
+            save_checkpoint(path, &scenario_manager, &registry, &gates);
+            info!(path = %path.display(), "wrote scenario, metrics and alert gate checkpoint");
+        }
+        if let Some(sink) = label_sink.as_mut() {
+            if let Err(err) = sink.flush() {
+                warn!(?err, "failed to flush outcome labels");
+            }
+        }
+        if let Some(archive) = key_archive.as_mut() {
+            if let Err(err) = archive.flush() {
+                warn!(?err, "failed to flush evicted key archive");
+            }
+        }
+        if let Some(dead_letters) = dead_letters.as_mut() {
+            if let Err(err) = dead_letters.flush() {
+                warn!(?err, "failed to flush dead letters");
+            }
+        }
+        if let Some(path) = &opts.heatmap_out {
+            if let Err(err) = heatmap.write_parquet(path) {
+                warn!(?err, path = %path.display(), "failed to write impact heatmap");
+            }
+        }
+        if let Some(path) = &opts.timeline_out {
+            if let Err(err) = std::fs::write(path, timelines.export_json()) {
+                warn!(%err, path = %path.display(), "failed to write scenario timelines");
+            }
+        }
+        for sid in &opts.explain_scenario {
+            match scenario_manager.retired_history().to_json(*sid) {
+                Some(json) => info!(scenario = sid, %json, "retired scenario"),
+                None => {
+                    let path = scenario_manager.path_to_root(*sid);
+                    if path.is_empty() {
+                        info!(scenario = sid, "scenario no longer remembered");
+                    } else {
+                        let subtree_weight = scenario_manager.subtree_weight(*sid);
+                        info!(scenario = sid, ?path, subtree_weight, "scenario lineage");
+                    }
+                }
+            }
+        }
+        if let Some(usage) = ResourceUsage::sample() {
+            metrics.record_resources(&usage);
+        }
+        for latency in metrics.subscription_latencies() {
+            info!(?latency, "subscription latency");
+        }
+        for transform in metrics.transform_stats() {
+            info!(?transform, "ingestion transform");
         }
+        let flags = rollout.to_json();
+        info!(%flags, "rollout flags");
         let final_snapshot = metrics.snapshot();
         let json = final_snapshot.to_json_line("retail_final", None);
         info!(%json, "final metrics summary");
+        let json = metrics.lifetime_snapshot().to_json_line("retail_lifetime", None);
+        info!(%json, "lifetime metrics summary");
     })
 }
+
+/// Count retirements in `metrics`, by reason.
+fn count_retired(metrics: &MetricsRegistry, retired: &[(ScenarioMeta, RetirementReason)]) {
+    for (_, reason) in retired {
+        metrics.inc_scenario_retired_for(reason.name(), 1);
+    }
+}
+
+/// Publish a seeded scenario's per-customer values under its source tag.
+fn tag_projection(projections: &RefCell<ProjectionTracker>, meta: &ScenarioMeta) {
+    if let Some(tag) = &meta.external {
+        projections.borrow_mut().tag(meta.id, tag.as_str());
+    }
+}
+
+/// Write the scenario tree to `path` and the metrics baseline and alert gate
+/// state beside it, logging rather than failing the run.
+fn save_checkpoint(
+    path: &Path,
+    manager: &RetailScenarioManager,
+    registry: &MetricsRegistry,
+    gates: &GateCheckpoint,
+) {
+    if let Err(err) = write_json(&manager.snapshot(), path) {
+        warn!(?err, path = %path.display(), "failed to write scenario checkpoint");
+    }
+    let metrics_path = metrics_checkpoint(path);
+    if let Err(err) = write_json(&registry.baseline(), &metrics_path) {
+        warn!(?err, path = %metrics_path.display(), "failed to write metrics baseline");
+    }
+    let gate_path = gate_checkpoint(path);
+    if let Err(err) = write_json(gates, &gate_path) {
+        warn!(?err, path = %gate_path.display(), "failed to write alert gate state");
+    }
+}
+
+/// State of the demo's two alert gates, checkpointed so delivery policy
+/// carries on across restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GateCheckpoint {
+    /// Gate of alerts raised as soon as the condition holds.
+    alerts: AlertGateState,
+    /// Gate of alerts raised once the condition has persisted.
+    persistent: AlertGateState,
+}
+
+impl GateCheckpoint {
+    fn of(alerts: &AlertGate, persistent: &AlertGate) -> Self {
+        Self { alerts: alerts.state().clone(), persistent: persistent.state().clone() }
+    }
+}
+
+/// Retract weight rows no live scenario backs, such as those of scenarios that
+/// retired before a restart, and restore live weights the input is missing.
+fn collect_weights(
+    epoch: EpochTime,
+    scen_weight_input: &mut LedgeredInput<u64, (u64, f64)>,
+    manager: &RetailScenarioManager,
+) {
+    let live = manager.active_weights().into_iter().map(|(id, weight, _pinned)| (id, weight));
+    let report = scen_weight_input.reconcile(live);
+    if !report.is_clean() {
+        warn!(%epoch, ?report, "scenario weight input out of step with live scenarios; corrected");
+    }
+}
+
+/// The metrics baseline kept next to the scenario checkpoint at `path`.
+fn metrics_checkpoint(path: &Path) -> PathBuf {
+    path.with_extension("metrics.json")
+}
+
+/// The alert gate state kept next to the scenario checkpoint at `path`.
+fn gate_checkpoint(path: &Path) -> PathBuf {
+    path.with_extension("alerts.json")
+}
+
+/// Feed a beam expansion into the scenario weight and overlay inputs.
+fn apply_outcome(
+    epoch: EpochTime,
+    outcome: &RetailExpansionOutcome,
+    pred_input: &mut InputSession<u64, (u64, u64, i64, i64), isize>,
+    scen_weight_input: &mut LedgeredInput<u64, (u64, f64)>,
+    retire_input: &mut RetirementInput<u64>,
+    channel_input: &mut InputSession<u64, (u64, u64, i64), isize>,
+    group_input: &mut InputSession<u64, (u64, u64), isize>,
+    timelines: &mut ScenarioTimelines,
+    heatmap: &mut ImpactHeatmap,
+    watches: &mut ScenarioWatches,
+    labels: &RefCell<BTreeMap<u64, String>>,
+) {
+    watches.update(outcome);
+    for meta in &outcome.created {
+        scen_weight_input.insert((meta.id, meta.weight.0));
+        if let Some(group) = meta.exclusion_group {
+            group_input.insert((meta.id, group));
+        }
+        timelines.record_created(epoch, meta);
+        heatmap.record_created(meta);
+        if let Some(label) = meta.describe() {
+            labels.borrow_mut().insert(meta.id, label);
+        }
+    }
+
+    for delta in &outcome.overlays_added {
+        pred_input.insert((
+            delta.scenario_id,
+            delta.customer_id,
+            delta.delta_cents.cents(),
+            delta.variance,
+        ));
+        timelines.record_overlay(epoch, delta.scenario_id);
+        heatmap.record_delta(delta.scenario_id, delta.customer_id, delta.delta_cents.cents());
+    }
+
+    for delta in &outcome.sku_overlays_added {
+        timelines.record_overlay(epoch, delta.scenario_id);
+    }
+
+    for delta in &outcome.channel_overlays_added {
+        channel_input.insert((delta.scenario_id, delta.channel, delta.delta_cents.cents()));
+        timelines.record_overlay(epoch, delta.scenario_id);
+    }
+
+    for delta in &outcome.overlays_removed {
+        pred_input.remove((
+            delta.scenario_id,
+            delta.customer_id,
+            delta.delta_cents.cents(),
+            delta.variance,
+        ));
+        heatmap.record_delta(delta.scenario_id, delta.customer_id, -delta.delta_cents.cents());
+    }
+
+    for delta in &outcome.channel_overlays_removed {
+        channel_input.remove((delta.scenario_id, delta.channel, delta.delta_cents.cents()));
+    }
+
+    for (meta, previous) in &outcome.reweighted {
+        scen_weight_input.remove((meta.id, previous.0));
+        scen_weight_input.insert((meta.id, meta.weight.0));
+        timelines.record_weight(epoch, meta.id, meta.weight.0);
+        heatmap.record_created(meta);
+    }
+
+    for (meta, reason) in &outcome.retired {
+        scen_weight_input.remove((meta.id, meta.weight.0));
+        retire_input.retire(meta.id);
+        if let Some(group) = meta.exclusion_group {
+            group_input.remove((meta.id, group));
+        }
+        timelines.record_retired(epoch, meta.id, *reason);
+        heatmap.record_retired(meta.id);
+        labels.borrow_mut().remove(&meta.id);
+    }
+}
+
+/// Scenarios lazy mode materializes: those given with --watch-scenario,
+/// and those whose overlays touch the subscribed customer until they retire.
+/// Every outcome passes through [`apply_outcome`], so resumed, seeded,
+/// injected and bulk-created scenarios are watched like expanded ones.
+struct ScenarioWatches {
+    /// The subscribed customer in lazy mode; `None` watches nothing more.
+    target_customer: Option<u64>,
+    watching: HashSet<u64>,
+    input: InputSession<u64, u64, isize>,
+}
+
+impl ScenarioWatches {
+    fn new(target_customer: Option<u64>, pinned: &[u64]) -> Self {
+        let mut input = InputSession::new();
+        let watching: HashSet<u64> = pinned.iter().copied().collect();
+        for sid in &watching {
+            input.insert(*sid);
+        }
+        Self { target_customer, watching, input }
+    }
+
+    fn update(&mut self, outcome: &RetailExpansionOutcome) {
+        let Some(target_customer) = self.target_customer else {
+            return;
+        };
+        for delta in &outcome.overlays_added {
+            if delta.customer_id == target_customer && self.watching.insert(delta.scenario_id) {
+                self.input.insert(delta.scenario_id);
+            }
+        }
+        for (meta, _) in &outcome.retired {
+            if self.watching.remove(&meta.id) {
+                self.input.remove(meta.id);
+            }
+        }
+    }
+}
+
+/// A customer's running base spend.
+fn spend_of(base_spend: &Mutex<HashMap<u64, i64>>, customer: u64) -> i64 {
+    let bases = base_spend.lock().unwrap_or_else(|e| e.into_inner());
+    bases.get(&customer).copied().unwrap_or(0)
+}
+
+/// Changes accepted through the control plane, one JSON object per line on
+/// `--control-addr`, e.g. `{"command":"predictor","uplift_bps":800}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum RetailCommand {
+    /// Rank the scenarios and rows this subscription could fire on.
+    Subscribe { predicate: SubscriptionPredicate },
+    Unsubscribe { predicate: SubscriptionPredicate },
+    /// Replace the beam configuration from the next order on.
+    Config { config: RetailBeamConfig },
+    /// Swap in a spend predictor, behind the same output guard.
+    Predictor {
+        uplift_bps: i64,
+        #[serde(default)]
+        uplift_sd_bps: i64,
+        #[serde(default)]
+        uplift_tiers: Vec<Tier<i64>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct OrderStats {
+    orders: u64,
+    spend_cents: i64,
+    last_order_cents: i64,
+}
+
+/// Per-customer order history, served as predictor features.
+#[derive(Default)]
+struct CustomerFeatures {
+    customers: Mutex<HashMap<u64, OrderStats>>,
+}
+
+impl CustomerFeatures {
+    const NAMES: [&'static str; 3] = ["order_count", "mean_order_cents", "last_order_cents"];
+
+    fn record(&self, customer: u64, cents: i64) {
+        let mut customers = self.customers.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = customers.entry(customer).or_default();
+        stats.orders += 1;
+        stats.spend_cents = stats.spend_cents.saturating_add(cents);
+        stats.last_order_cents = cents;
+    }
+
+    fn forget(&self, customer: u64) {
+        self.customers.lock().unwrap_or_else(|e| e.into_inner()).remove(&customer);
+    }
+}
+
+impl FeatureStore for CustomerFeatures {
+    fn feature(&self, entity: u64, name: &str) -> Option<f64> {
+        let customers = self.customers.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = customers.get(&entity)?;
+        match name {
+            "order_count" => Some(stats.orders as f64),
+            "mean_order_cents" => Some(stats.spend_cents as f64 / stats.orders as f64),
+            "last_order_cents" => Some(stats.last_order_cents as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Rejects overlays that would take the key's base value below zero: a
+/// customer's spend for orders and for each customer an external plan
+/// touches, the changed SKU's units for price changes, the down channel's
+/// spend for channel outages. The key's delta is accumulated along the
+/// proposal's lineage, so a chain of children cannot walk it negative.
+fn non_negative(
+    proposal: &Proposal<'_, RetailOverlay>,
+    base_spend: &HashMap<u64, i64>,
+) -> Result<(), String> {
+    let lineage = &proposal.lineage;
+    let base = proposal.base_value.unwrap_or(0);
+    let delta = match proposal.overlay {
+        RetailOverlay::Spend(delta) => {
+            delta.delta_cents.cents().saturating_add(lineage_spend(lineage, delta.customer_id))
+        }
+        // External plans touch many customers; each is checked against its own base
+        RetailOverlay::Spends(deltas) => {
+            for delta in deltas {
+                let customer = delta.customer_id;
+                let base = base_spend.get(&customer).copied().unwrap_or(0);
+                let projected = base
+                    .saturating_add(lineage_spend(lineage, customer))
+                    .saturating_add(delta.delta_cents.cents());
+                if projected < 0 {
+                    return Err(format!(
+                        "customer {customer} base {base} would fall to {projected}"
+                    ));
+                }
+            }
+            return Ok(());
+        }
+        RetailOverlay::Demand(deltas) => {
+            let own: i64 = deltas.iter().map(|delta| delta.delta_qty).sum();
+            let sku = deltas.first().map(|delta| delta.sku_id);
+            let inherited: i64 = lineage
+                .iter()
+                .filter_map(|overlay| match overlay {
+                    RetailOverlay::Demand(rows) => Some(rows),
+                    _ => None,
+                })
+                .flatten()
+                .filter(|row| Some(row.sku_id) == sku)
+                .map(|row| row.delta_qty)
+                .sum();
+            own.saturating_add(inherited)
+        }
+        // The down channel carries the only loss
+        RetailOverlay::Channel(deltas) => {
+            match deltas.iter().min_by_key(|delta| delta.delta_cents.cents()) {
+                Some(down) => {
+                    let inherited: i64 = lineage
+                        .iter()
+                        .filter_map(|overlay| match overlay {
+                            RetailOverlay::Channel(rows) => Some(rows),
+                            _ => None,
+                        })
+                        .flatten()
+                        .filter(|row| row.channel == down.channel)
+                        .map(|row| row.delta_cents.cents())
+                        .sum();
+                    down.delta_cents.cents().saturating_add(inherited)
+                }
+                None => 0,
+            }
+        }
+    };
+    let projected = base.saturating_add(delta);
+    if projected < 0 {
+        return Err(format!("base {base} would fall to {projected}"));
+    }
+    Ok(())
+}
+
+/// Spend delta the lineage has already applied to `customer_id`.
+fn lineage_spend(lineage: &[&RetailOverlay], customer_id: u64) -> i64 {
+    lineage
+        .iter()
+        .flat_map(|overlay| match overlay {
+            RetailOverlay::Spend(delta) => std::slice::from_ref(delta),
+            RetailOverlay::Spends(deltas) => deltas.as_slice(),
+            _ => &[],
+        })
+        .filter(|delta| delta.customer_id == customer_id)
+        .map(|delta| delta.delta_cents.cents())
+        .sum()
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+use tw_core::quantity::Interval;
+use tw_runtime::replay::StoredBundle;
+
+#[derive(Parser, Debug)]
+#[command(name = "tw", about = "Timely Worlds support tools")]
+struct TwOpts {
+    #[command(subcommand)]
+    command: TwCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum TwCommand {
+    /// Walk through what a replay bundle recorded about one alert.
+    Inspect {
+        /// Bundle written by a demo's --replay-bundles directory.
+        #[arg(long)]
+        bundle: PathBuf,
+        /// Width of the printed interval around the predicted value, in standard deviations.
+        #[arg(long, default_value_t = 1.96)]
+        interval_z: f64,
+    },
+}
+
+fn main() -> Result<()> {
+    match TwOpts::parse().command {
+        TwCommand::Inspect { bundle, interval_z } => inspect(&StoredBundle::read(bundle)?, interval_z),
+    }
+    Ok(())
+}
+
+/// Print the alert the way the run saw it: the scenario row that fired, the
+/// lineage and overlays that produced it, and the key's events in order.
+fn inspect(bundle: &StoredBundle, interval_z: f64) {
+    let predicted = &bundle.predicted;
+    let interval = Interval::around(predicted.value, predicted.variance, interval_z);
+    println!("{} ({}) at epoch {}", bundle.alert, bundle.domain, bundle.epoch);
+    println!("scenario {} key {}", bundle.scenario_id, bundle.key);
+    println!(
+        "predicted {} [{}, {}] with probability {:.4}",
+        predicted.value, interval.low, interval.high, predicted.probability
+    );
+
+    let lineage = bundle.lineage.as_array().map(Vec::as_slice).unwrap_or_default();
+    println!("lineage ({} scenarios, root first)", lineage.len());
+    for timeline in lineage {
+        println!("  {}", compact(timeline));
+    }
+
+    println!("overlays ({} scenarios, alerting scenario first)", bundle.overlays.len());
+    for (scenario_id, rows) in &bundle.overlays {
+        println!("  {scenario_id}: {}", compact(rows));
+    }
+
+    println!("events ({}, oldest first)", bundle.events.len());
+    for event in &bundle.events {
+        println!("  {}", compact(event));
+    }
+
+    println!("config");
+    println!("{}", serde_json::to_string_pretty(&bundle.config).unwrap_or_default());
+}
+
+fn compact(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
@@ -0,0 +1,161 @@
+//! Predictor composition: a source predictor followed by refining stages.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use tw_core::{EventEnvelope, Predicted, Prob};
+
+use crate::{PredictionContext, Predictor};
+
+/// A stage that refines the candidates produced by the stages before it.
+pub trait PredictionStage<T>: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn apply(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &EventEnvelope<T>,
+        candidates: Vec<Predicted<T>>,
+    ) -> Result<Vec<Predicted<T>>>;
+}
+
+/// Drops all candidates unless `pass` holds for the triggering event
+/// (e.g. a churn model gating a spend predictor).
+pub struct GateStage<F> {
+    name: String,
+    pass: F,
+}
+
+impl<F> GateStage<F> {
+    pub fn new(name: impl Into<String>, pass: F) -> Self {
+        Self { name: name.into(), pass }
+    }
+}
+
+impl<T, F> PredictionStage<T> for GateStage<F>
+where
+    F: Fn(&EventEnvelope<T>) -> bool + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(
+        &self,
+        _ctx: &PredictionContext<'_>,
+        event: &EventEnvelope<T>,
+        candidates: Vec<Predicted<T>>,
+    ) -> Result<Vec<Predicted<T>>> {
+        if (self.pass)(event) {
+            Ok(candidates)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Multiplies candidate probabilities by a factor derived from the triggering
+/// event (e.g. an anomaly score), clamped to `[0, 1]`.
+pub struct ProbScaleStage<F> {
+    name: String,
+    factor: F,
+}
+
+impl<F> ProbScaleStage<F> {
+    pub fn new(name: impl Into<String>, factor: F) -> Self {
+        Self { name: name.into(), factor }
+    }
+}
+
+impl<T, F> PredictionStage<T> for ProbScaleStage<F>
+where
+    F: Fn(&EventEnvelope<T>) -> f64 + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(
+        &self,
+        _ctx: &PredictionContext<'_>,
+        event: &EventEnvelope<T>,
+        mut candidates: Vec<Predicted<T>>,
+    ) -> Result<Vec<Predicted<T>>> {
+        let factor = (self.factor)(event);
+        for pred in candidates.iter_mut() {
+            pred.prob = Prob((pred.prob.0 * factor).clamp(0.0, 1.0));
+        }
+        Ok(candidates)
+    }
+}
+
+#[derive(Default)]
+struct StageCounters {
+    invocations: AtomicU64,
+    candidates_in: AtomicU64,
+    candidates_out: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageStats {
+    pub name: String,
+    pub invocations: u64,
+    pub candidates_in: u64,
+    pub candidates_out: u64,
+}
+
+/// Runs `source`, then each stage in order, and is itself a [`Predictor`].
+pub struct PredictorChain<T> {
+    source: Box<dyn Predictor<T> + Send + Sync>,
+    stages: Vec<(Box<dyn PredictionStage<T>>, StageCounters)>,
+}
+
+impl<T> PredictorChain<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    pub fn new(source: impl Predictor<T> + Send + Sync + 'static) -> Self {
+        Self { source: Box::new(source), stages: Vec::new() }
+    }
+
+    pub fn then(mut self, stage: impl PredictionStage<T> + 'static) -> Self {
+        self.stages.push((Box::new(stage), StageCounters::default()));
+        self
+    }
+
+    pub fn stage_stats(&self) -> Vec<StageStats> {
+        self.stages
+            .iter()
+            .map(|(stage, counters)| StageStats {
+                name: stage.name().to_string(),
+                invocations: counters.invocations.load(Ordering::Relaxed),
+                candidates_in: counters.candidates_in.load(Ordering::Relaxed),
+                candidates_out: counters.candidates_out.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+impl<T> Predictor<T> for PredictorChain<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    fn predict(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &EventEnvelope<T>,
+    ) -> Result<Vec<Predicted<T>>> {
+        let mut candidates = self.source.predict(ctx, event)?;
+        for (stage, counters) in &self.stages {
+            counters.invocations.fetch_add(1, Ordering::Relaxed);
+            counters.candidates_in.fetch_add(candidates.len() as u64, Ordering::Relaxed);
+            candidates = stage.apply(ctx, event, candidates)?;
+            counters.candidates_out.fetch_add(candidates.len() as u64, Ordering::Relaxed);
+            if candidates.is_empty() {
+                break;
+            }
+        }
+        Ok(candidates)
+    }
+}
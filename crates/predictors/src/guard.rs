@@ -0,0 +1,129 @@
+//! Guardrails on predictor outputs so one misbehaving model cannot blow up
+//! scenario projections.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tw_core::manufacturing::OperationStart;
+use tw_core::retail::{Money, OrderPlaced};
+
+use crate::{MachineBacklogPredictor, Outcome, PredictionContext, SpendDeltaPredictor};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuardConfig {
+    /// Largest absolute delta a single prediction may carry.
+    #[serde(default)]
+    pub max_delta: Option<i64>,
+    /// Largest delta relative to the key's base value, in basis points.
+    /// Skipped when the context carries no positive base value.
+    #[serde(default)]
+    pub max_uplift_bps: Option<i64>,
+    /// Accept negative predictions instead of replacing them with `fallback`.
+    #[serde(default)]
+    pub allow_negative: bool,
+    /// Used in place of rejected negative predictions.
+    #[serde(default)]
+    pub fallback: i64,
+}
+
+/// Applies a [`GuardConfig`] and counts every prediction it had to change.
+#[derive(Debug, Default)]
+pub struct OutputGuard {
+    cfg: GuardConfig,
+    clamped: AtomicU64,
+}
+
+impl OutputGuard {
+    pub fn new(cfg: GuardConfig) -> Self {
+        Self { cfg, clamped: AtomicU64::new(0) }
+    }
+
+    pub fn apply(&self, raw: i64, base_value: Option<i64>) -> i64 {
+        let mut delta = raw;
+        if delta < 0 && !self.cfg.allow_negative {
+            delta = self.cfg.fallback;
+        }
+        if let Some(max) = self.cfg.max_delta.map(|max| max.max(0)) {
+            delta = delta.clamp(-max, max);
+        }
+        if let (Some(bps), Some(base)) = (self.cfg.max_uplift_bps, base_value.filter(|b| *b > 0)) {
+            let max = Money::from_cents(base).saturating_scale_bps(bps).cents().max(0);
+            delta = delta.clamp(-max, max);
+        }
+        if delta != raw {
+            self.clamped.fetch_add(1, Ordering::Relaxed);
+        }
+        delta
+    }
+
+    /// Predictions clamped or replaced since the last call.
+    pub fn take_clamped(&self) -> u64 {
+        self.clamped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Wraps a delta predictor and passes its output through an [`OutputGuard`].
+pub struct Guarded<P> {
+    inner: P,
+    guard: OutputGuard,
+}
+
+impl<P> Guarded<P> {
+    pub fn new(inner: P, cfg: GuardConfig) -> Self {
+        Self { inner, guard: OutputGuard::new(cfg) }
+    }
+
+    pub fn guard(&self) -> &OutputGuard {
+        &self.guard
+    }
+}
+
+impl<P: SpendDeltaPredictor> SpendDeltaPredictor for Guarded<P> {
+    fn predict_delta(&self, ctx: &PredictionContext<'_>, order: &OrderPlaced) -> Money {
+        let raw = self.inner.predict_delta(ctx, order);
+        Money::from_cents(self.guard.apply(raw.cents(), ctx.base_value))
+    }
+
+    fn delta_variance(&self, ctx: &PredictionContext<'_>, order: &OrderPlaced) -> i64 {
+        self.inner.delta_variance(ctx, order)
+    }
+
+    fn delta_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        order: &OrderPlaced,
+    ) -> Vec<Outcome<Money>> {
+        let outcomes = self.inner.delta_outcomes(ctx, order).into_iter();
+        outcomes
+            .map(|outcome| Outcome {
+                probability: outcome.probability,
+                value: Money::from_cents(self.guard.apply(outcome.value.cents(), ctx.base_value)),
+            })
+            .collect()
+    }
+}
+
+impl<P: MachineBacklogPredictor> MachineBacklogPredictor for Guarded<P> {
+    fn predict_backlog(&self, ctx: &PredictionContext<'_>, op: &OperationStart) -> i64 {
+        let raw = self.inner.predict_backlog(ctx, op);
+        self.guard.apply(raw, ctx.base_value)
+    }
+
+    fn backlog_variance(&self, ctx: &PredictionContext<'_>, op: &OperationStart) -> i64 {
+        self.inner.backlog_variance(ctx, op)
+    }
+
+    fn backlog_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        op: &OperationStart,
+    ) -> Vec<Outcome<i64>> {
+        let outcomes = self.inner.backlog_outcomes(ctx, op).into_iter();
+        outcomes
+            .map(|outcome| Outcome {
+                probability: outcome.probability,
+                value: self.guard.apply(outcome.value, ctx.base_value),
+            })
+            .collect()
+    }
+}
@@ -1,11 +1,61 @@
 //! Predictor trait and baseline stubs.
 
-use anyhow::Result;
-use serde::{de::DeserializeOwned, Serialize};
-use tw-core::{Depth, EventEnvelope, Predicted, Prob, ScenarioId};
+use std::str::FromStr;
 
-use tw_core::manufacturing::OperationStart;
-use tw_core::retail::OrderPlaced;
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tw_core::{Depth, EpochTime, EventEnvelope, Predicted, Prob, ScenarioId};
+
+use tw_core::manufacturing::{MachineId, MaterialId, OperationStart};
+use tw_core::retail::{
+    Channel, ChannelId, CohortId, Money, OrderPlaced, PriceChanged, SkuId, BPS_SCALE,
+};
+
+/// Per-entity features looked up by predictors; backed by whatever store the deployment has.
+pub trait FeatureStore: Send + Sync {
+    fn feature(&self, entity: u64, name: &str) -> Option<f64>;
+}
+
+/// Where in the scenario tree a prediction is being made.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineageSummary {
+    /// Scenario being expanded; 0 is the base world.
+    pub parent: ScenarioId,
+    /// Depth of `parent`; children land one level deeper.
+    pub depth: Depth,
+    /// Cumulative probability of `parent`.
+    pub weight: Prob,
+}
+
+impl LineageSummary {
+    pub fn base() -> Self {
+        Self { parent: 0, depth: 0, weight: Prob(1.0) }
+    }
+
+    pub fn next_depth(&self) -> Depth {
+        self.depth + 1
+    }
+}
+
+/// Everything a predictor may consult besides the triggering event.
+#[derive(Clone, Copy)]
+pub struct PredictionContext<'a> {
+    pub epoch: EpochTime,
+    pub lineage: LineageSummary,
+    /// Current base-view value for the affected key, when the caller tracks one.
+    pub base_value: Option<i64>,
+    pub features: Option<&'a dyn FeatureStore>,
+}
+
+impl<'a> PredictionContext<'a> {
+    pub fn new(epoch: EpochTime, lineage: LineageSummary) -> Self {
+        Self { epoch, lineage, base_value: None, features: None }
+    }
+
+    pub fn feature(&self, entity: u64, name: &str) -> Option<f64> {
+        self.features.and_then(|store| store.feature(entity, name))
+    }
+}
 
 /// A predictor consumes view changes and produces candidate future events for expansion.
 pub trait Predictor<T>
@@ -14,9 +64,8 @@ where
 {
     fn predict(
         &self,
-        parent: ScenarioId,
-        next_depth: Depth,
-        context: &EventEnvelope<T>,
+        ctx: &PredictionContext<'_>,
+        event: &EventEnvelope<T>,
     ) -> Result<Vec<Predicted<T>>>;
 }
 
@@ -29,65 +78,517 @@ where
 {
     fn predict(
         &self,
-        parent: ScenarioId,
-        next_depth: Depth,
-        context: &EventEnvelope<T>,
+        ctx: &PredictionContext<'_>,
+        event: &EventEnvelope<T>,
     ) -> Result<Vec<Predicted<T>>> {
-        let child = parent.wrapping_add(1);
+        let parent = ctx.lineage.parent;
         let pred = Predicted {
             parent_scenario: parent,
-            child_scenario: child,
-            depth: next_depth,
+            child_scenario: parent.wrapping_add(1),
+            depth: ctx.lineage.next_depth(),
             prob: Prob(1.0),
-            event: context.clone(),
+            event: event.clone(),
         };
         Ok(vec![pred])
     }
 }
 
+/// One of several alternative values a predictor foresees, with how likely
+/// it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome<T> {
+    pub probability: f64,
+    pub value: T,
+}
+
+impl<T> Outcome<T> {
+    pub fn certain(value: T) -> Self {
+        Self { probability: 1.0, value }
+    }
+}
+
+/// A probability and what it applies to, parsed from `<probability>:<value>`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tier<T> {
+    pub probability: f64,
+    pub value: T,
+}
+
+impl<T: FromStr> FromStr for Tier<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (probability, value) =
+            s.split_once(':').with_context(|| format!("expected probability:value, got {s}"))?;
+        Ok(Tier {
+            probability: probability.parse().context("parsing probability")?,
+            value: value.parse().context("parsing value")?,
+        })
+    }
+}
+
 pub trait SpendDeltaPredictor: Send + Sync + 'static {
-    fn predict_delta(&self, order: &OrderPlaced) -> i64;
+    fn predict_delta(&self, ctx: &PredictionContext<'_>, order: &OrderPlaced) -> Money;
+
+    /// Variance of [`Self::predict_delta`] for the same inputs, in squared
+    /// cents; 0 for predictors that only give a point estimate.
+    fn delta_variance(&self, _ctx: &PredictionContext<'_>, _order: &OrderPlaced) -> i64 {
+        0
+    }
+
+    /// Alternative deltas with their probabilities, which should sum to at
+    /// most 1; a scenario branches one child per outcome. By default the
+    /// point estimate, with certainty.
+    fn delta_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        order: &OrderPlaced,
+    ) -> Vec<Outcome<Money>> {
+        vec![Outcome::certain(self.predict_delta(ctx, order))]
+    }
 }
 
 pub struct SpendGrowthPredictor {
-    pub uplift_ratio: f64,
-    pub min_delta_cents: i64,
+    /// Uplift applied to the order total, in basis points.
+    pub uplift_bps: i64,
+    pub min_delta_cents: Money,
+    /// Standard deviation of the uplift, in basis points of the order total.
+    pub uplift_sd_bps: i64,
+    /// Alternative uplifts in basis points, such as small, medium and large,
+    /// each with its probability; empty predicts `uplift_bps` alone.
+    pub uplift_tiers: Vec<Tier<i64>>,
 }
 
 impl Default for SpendGrowthPredictor {
     fn default() -> Self {
-        Self { uplift_ratio: 0.3, min_delta_cents: 3_000 }
+        Self {
+            uplift_bps: 3_000,
+            min_delta_cents: Money::from_cents(3_000),
+            uplift_sd_bps: 0,
+            uplift_tiers: Vec::new(),
+        }
+    }
+}
+
+impl SpendGrowthPredictor {
+    fn uplift(&self, order: &OrderPlaced, uplift_bps: i64) -> Money {
+        let base = order.total_cents().max(Money::from_cents(1));
+        std::cmp::max(base.saturating_scale_bps(uplift_bps), self.min_delta_cents)
     }
 }
 
 impl SpendDeltaPredictor for SpendGrowthPredictor {
-    fn predict_delta(&self, order: &OrderPlaced) -> i64 {
-        let base = order.total_cents().max(1);
-        let uplift = ((base as f64) * self.uplift_ratio).round() as i64;
-        std::cmp::max(uplift, self.min_delta_cents)
+    fn predict_delta(&self, _ctx: &PredictionContext<'_>, order: &OrderPlaced) -> Money {
+        self.uplift(order, self.uplift_bps)
+    }
+
+    fn delta_variance(&self, _ctx: &PredictionContext<'_>, order: &OrderPlaced) -> i64 {
+        let base = order.total_cents().max(Money::from_cents(1));
+        square(base.saturating_scale_bps(self.uplift_sd_bps).cents())
+    }
+
+    fn delta_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        order: &OrderPlaced,
+    ) -> Vec<Outcome<Money>> {
+        if self.uplift_tiers.is_empty() {
+            return vec![Outcome::certain(self.predict_delta(ctx, order))];
+        }
+        self.uplift_tiers
+            .iter()
+            .map(|tier| Outcome {
+                probability: tier.probability,
+                value: self.uplift(order, tier.value),
+            })
+            .collect()
+    }
+}
+
+/// Projected demand shift for one SKU within one cohort.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemandDelta {
+    pub sku_id: SkuId,
+    pub cohort_id: CohortId,
+    pub delta_qty: i64,
+    pub delta_cents: Money,
+}
+
+/// Converts a price change into per-SKU, per-cohort demand and spend deltas.
+pub trait ElasticityPredictor: Send + Sync + 'static {
+    fn predict_demand(
+        &self,
+        ctx: &PredictionContext<'_>,
+        change: &PriceChanged,
+    ) -> Vec<DemandDelta>;
+}
+
+/// Constant price elasticity applied to the SKU's baseline units, which the
+/// caller passes as `ctx.base_value`. Cohorts split that baseline by share.
+pub struct ConstantElasticityPredictor {
+    /// Elasticity in basis points; -12_000 means a 1% price rise cuts demand 1.2%.
+    pub elasticity_bps: i64,
+    /// `(cohort, share of baseline units in bps)`.
+    pub cohort_shares_bps: Vec<(CohortId, i64)>,
+}
+
+impl Default for ConstantElasticityPredictor {
+    fn default() -> Self {
+        Self { elasticity_bps: -12_000, cohort_shares_bps: vec![(0, 10_000)] }
     }
 }
 
+impl ElasticityPredictor for ConstantElasticityPredictor {
+    fn predict_demand(
+        &self,
+        ctx: &PredictionContext<'_>,
+        change: &PriceChanged,
+    ) -> Vec<DemandDelta> {
+        let (Some(base_qty), Some(change_bps)) = (ctx.base_value, change.change_bps()) else {
+            return Vec::new();
+        };
+        let scale = (BPS_SCALE as i128) * (BPS_SCALE as i128);
+        self.cohort_shares_bps
+            .iter()
+            .map(|&(cohort_id, share_bps)| {
+                let cohort_qty = (base_qty as i128) * (share_bps as i128) / (BPS_SCALE as i128);
+                let delta_qty =
+                    cohort_qty * (self.elasticity_bps as i128) * (change_bps as i128) / scale;
+                let spend_before = cohort_qty * (change.old_cents.cents() as i128);
+                let spend_after = (cohort_qty + delta_qty) * (change.new_cents.cents() as i128);
+                DemandDelta {
+                    sku_id: change.sku_id,
+                    cohort_id,
+                    delta_qty: saturate_i64(delta_qty),
+                    delta_cents: Money::from_cents(saturate_i64(spend_after - spend_before)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Spend a channel gains or loses under a channel scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelSpendDelta {
+    pub channel: ChannelId,
+    pub delta_cents: Money,
+}
+
+/// Moves spend between channels when one channel goes down.
+pub trait ChannelShiftPredictor: Send + Sync + 'static {
+    /// `projected_spend` holds each channel's spend projected over the
+    /// outage, not spend already booked; the deltas need not sum to zero,
+    /// since some customers give up instead of switching.
+    fn predict_shift(
+        &self,
+        ctx: &PredictionContext<'_>,
+        outage: Channel,
+        projected_spend: &[(ChannelId, i64)],
+    ) -> Vec<ChannelSpendDelta>;
+}
+
+/// The down channel loses its projected spend; `recapture_bps` of it moves
+/// to the other channels in proportion to their own projected spend.
+pub struct RecaptureShiftPredictor {
+    pub recapture_bps: i64,
+}
+
+impl Default for RecaptureShiftPredictor {
+    fn default() -> Self {
+        Self { recapture_bps: 6_000 }
+    }
+}
+
+impl ChannelShiftPredictor for RecaptureShiftPredictor {
+    fn predict_shift(
+        &self,
+        _ctx: &PredictionContext<'_>,
+        outage: Channel,
+        projected_spend: &[(ChannelId, i64)],
+    ) -> Vec<ChannelSpendDelta> {
+        let outage = outage.id();
+        let lost = projected_spend
+            .iter()
+            .find(|(channel, _)| *channel == outage)
+            .map_or(0, |&(_, cents)| cents);
+        if lost <= 0 {
+            return Vec::new();
+        }
+        let others: Vec<_> = projected_spend
+            .iter()
+            .filter(|&&(channel, cents)| channel != outage && cents > 0)
+            .copied()
+            .collect();
+        let others_total: i128 = others.iter().map(|&(_, cents)| cents as i128).sum();
+        let recaptured = (lost as i128) * (self.recapture_bps as i128) / (BPS_SCALE as i128);
+        let mut deltas = vec![ChannelSpendDelta {
+            channel: outage,
+            delta_cents: Money::from_cents(-lost),
+        }];
+        if others_total > 0 {
+            deltas.extend(others.into_iter().map(|(channel, cents)| ChannelSpendDelta {
+                channel,
+                delta_cents: Money::from_cents(saturate_i64(
+                    recaptured * (cents as i128) / others_total,
+                )),
+            }));
+        }
+        deltas
+    }
+}
+
+fn saturate_i64(value: i128) -> i64 {
+    value.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// A standard deviation's variance, saturating instead of overflowing.
+fn square(sd: i64) -> i64 {
+    sd.saturating_mul(sd)
+}
+
 pub trait MachineBacklogPredictor: Send + Sync + 'static {
-    fn predict_backlog(&self, op: &OperationStart) -> i64;
+    fn predict_backlog(&self, ctx: &PredictionContext<'_>, op: &OperationStart) -> i64;
+
+    /// Variance of [`Self::predict_backlog`] for the same inputs, in squared
+    /// units; 0 for predictors that only give a point estimate.
+    fn backlog_variance(&self, _ctx: &PredictionContext<'_>, _op: &OperationStart) -> i64 {
+        0
+    }
+
+    /// Alternative backlogs with their probabilities, which should sum to at
+    /// most 1; a scenario branches one child per outcome. By default the
+    /// point estimate, with certainty.
+    fn backlog_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        op: &OperationStart,
+    ) -> Vec<Outcome<i64>> {
+        vec![Outcome::certain(self.predict_backlog(ctx, op))]
+    }
 }
 
 pub struct QueueGrowthPredictor {
     pub base_units: i64,
     pub duration_multiplier: f64,
     pub min_delta_units: i64,
+    /// Standard deviation of the backlog as a fraction of the estimate.
+    pub relative_sd: f64,
+    /// Alternative factors on the estimate, such as 0.5, 1 and 2, each with
+    /// its probability; empty predicts the estimate alone.
+    pub backlog_tiers: Vec<Tier<f64>>,
 }
 
 impl Default for QueueGrowthPredictor {
     fn default() -> Self {
-        Self { base_units: 1, duration_multiplier: 0.001, min_delta_units: 2 }
+        Self {
+            base_units: 1,
+            duration_multiplier: 0.001,
+            min_delta_units: 2,
+            relative_sd: 0.0,
+            backlog_tiers: Vec::new(),
+        }
     }
 }
 
 impl MachineBacklogPredictor for QueueGrowthPredictor {
-    fn predict_backlog(&self, op: &OperationStart) -> i64 {
+    fn predict_backlog(&self, _ctx: &PredictionContext<'_>, op: &OperationStart) -> i64 {
         let duration_component = ((op.expected_duration_ms as f64) * self.duration_multiplier).round() as i64;
         let estimate = self.base_units + duration_component;
         std::cmp::max(estimate, self.min_delta_units)
     }
+
+    fn backlog_variance(&self, ctx: &PredictionContext<'_>, op: &OperationStart) -> i64 {
+        let sd = (self.predict_backlog(ctx, op) as f64) * self.relative_sd.max(0.0);
+        square(sd.round() as i64)
+    }
+
+    fn backlog_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        op: &OperationStart,
+    ) -> Vec<Outcome<i64>> {
+        let estimate = self.predict_backlog(ctx, op);
+        if self.backlog_tiers.is_empty() {
+            return vec![Outcome::certain(estimate)];
+        }
+        self.backlog_tiers
+            .iter()
+            .map(|tier| {
+                let units = ((estimate as f64) * tier.value).round() as i64;
+                Outcome {
+                    probability: tier.probability,
+                    value: std::cmp::max(units, self.min_delta_units),
+                }
+            })
+            .collect()
+    }
+}
+
+pub trait YieldLossPredictor: Send + Sync + 'static {
+    /// Units expected to be scrapped downstream of this operation.
+    fn predict_scrap_units(&self, ctx: &PredictionContext<'_>, op: &OperationStart) -> i64;
+    /// Extra WIP units expected from rework passes.
+    fn predict_rework_units(&self, ctx: &PredictionContext<'_>, op: &OperationStart) -> i64;
+}
+
+/// Applies fixed scrap and rework rates (basis points) over a horizon of upcoming operations.
+pub struct YieldLossRatePredictor {
+    pub scrap_bps: i64,
+    pub rework_bps: i64,
+    pub horizon_ops: i64,
+}
+
+impl Default for YieldLossRatePredictor {
+    fn default() -> Self {
+        Self { scrap_bps: 500, rework_bps: 400, horizon_ops: 20 }
+    }
+}
+
+impl YieldLossRatePredictor {
+    fn units(&self, bps: i64) -> i64 {
+        (self.horizon_ops.saturating_mul(bps) + 5_000) / 10_000
+    }
 }
+
+impl YieldLossPredictor for YieldLossRatePredictor {
+    fn predict_scrap_units(&self, _ctx: &PredictionContext<'_>, _op: &OperationStart) -> i64 {
+        self.units(self.scrap_bps)
+    }
+
+    fn predict_rework_units(&self, _ctx: &PredictionContext<'_>, _op: &OperationStart) -> i64 {
+        self.units(self.rework_bps)
+    }
+}
+
+/// Supply position of one material, tracked by the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterialStatus {
+    pub material_id: MaterialId,
+    pub on_hand: i64,
+    /// Recent consumption rate, in units per epoch.
+    pub consumed_per_epoch: i64,
+    /// Machines that draw this material.
+    pub consumers: Vec<MachineId>,
+}
+
+pub trait ShortageLikelihoodPredictor: Send + Sync + 'static {
+    /// Probability in `[0, 1]` that the material runs out before resupply.
+    fn shortage_likelihood(&self, ctx: &PredictionContext<'_>, material: &MaterialStatus) -> f64;
+    /// Extra WIP units that queue on `machine_id` while it is starved.
+    fn starved_backlog(
+        &self,
+        ctx: &PredictionContext<'_>,
+        material: &MaterialStatus,
+        machine_id: MachineId,
+    ) -> i64;
+}
+
+/// Compares on-hand cover (epochs of consumption left) against a fixed
+/// resupply lead time: the shorter the cover, the likelier the shortage, and
+/// each consumer queues work for every uncovered epoch.
+pub struct CoverageShortagePredictor {
+    pub lead_epochs: i64,
+    pub backlog_per_epoch: i64,
+}
+
+impl Default for CoverageShortagePredictor {
+    fn default() -> Self {
+        Self { lead_epochs: 5, backlog_per_epoch: 1 }
+    }
+}
+
+impl CoverageShortagePredictor {
+    fn uncovered_epochs(&self, material: &MaterialStatus) -> i64 {
+        if material.consumed_per_epoch <= 0 {
+            return 0;
+        }
+        let cover = material.on_hand.max(0) / material.consumed_per_epoch;
+        (self.lead_epochs - cover).clamp(0, self.lead_epochs)
+    }
+}
+
+impl ShortageLikelihoodPredictor for CoverageShortagePredictor {
+    fn shortage_likelihood(&self, _ctx: &PredictionContext<'_>, material: &MaterialStatus) -> f64 {
+        if self.lead_epochs <= 0 {
+            return 0.0;
+        }
+        self.uncovered_epochs(material) as f64 / self.lead_epochs as f64
+    }
+
+    fn starved_backlog(
+        &self,
+        _ctx: &PredictionContext<'_>,
+        material: &MaterialStatus,
+        _machine_id: MachineId,
+    ) -> i64 {
+        self.uncovered_epochs(material).saturating_mul(self.backlog_per_epoch)
+    }
+}
+
+/// Failure history and load of one machine, tracked by the caller.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachineHealth {
+    pub machine_id: MachineId,
+    /// Epochs since the machine last went down, or since it was first seen.
+    pub epochs_since_failure: i64,
+    /// Observed mean time between failures, once the machine has failed.
+    pub mean_epochs_between_failures: Option<f64>,
+    /// Share of the last epoch the machine was busy, in `[0, 1]`.
+    pub utilization: f64,
+    /// Units of work arriving per epoch that queue while the machine is down.
+    pub load_per_epoch: i64,
+}
+
+pub trait MachineFailurePredictor: Send + Sync + 'static {
+    /// Probability in `[0, 1]` that the machine goes down within the next epoch.
+    fn failure_probability(&self, ctx: &PredictionContext<'_>, machine: &MachineHealth) -> f64;
+    /// Extra WIP units that queue on the machine while it is down.
+    fn downtime_backlog(&self, ctx: &PredictionContext<'_>, machine: &MachineHealth) -> i64;
+}
+
+/// Weibull hazard over wear age: a fully utilized epoch ages the machine one
+/// epoch and an idle one not at all. The characteristic life is the observed
+/// mean time between failures, falling back to `scale_epochs`; `shape` 1.0 is
+/// the memoryless exponential case and larger shapes model wear-out.
+pub struct FailureHazardPredictor {
+    pub shape: f64,
+    pub scale_epochs: f64,
+    /// Epochs a failed machine stays down.
+    pub downtime_epochs: i64,
+}
+
+impl Default for FailureHazardPredictor {
+    fn default() -> Self {
+        Self { shape: 1.5, scale_epochs: 50.0, downtime_epochs: 2 }
+    }
+}
+
+impl FailureHazardPredictor {
+    fn cumulative_hazard(&self, age: f64, scale: f64) -> f64 {
+        (age.max(0.0) / scale).powf(self.shape)
+    }
+}
+
+impl MachineFailurePredictor for FailureHazardPredictor {
+    fn failure_probability(&self, _ctx: &PredictionContext<'_>, machine: &MachineHealth) -> f64 {
+        let scale = machine.mean_epochs_between_failures.unwrap_or(self.scale_epochs);
+        if scale <= 0.0 || self.shape <= 0.0 {
+            return 0.0;
+        }
+        let age = machine.epochs_since_failure as f64;
+        let wear = machine.utilization.clamp(0.0, 1.0);
+        let hazard = self.cumulative_hazard(age + wear, scale) - self.cumulative_hazard(age, scale);
+        (1.0 - (-hazard).exp()).clamp(0.0, 1.0)
+    }
+
+    fn downtime_backlog(&self, _ctx: &PredictionContext<'_>, machine: &MachineHealth) -> i64 {
+        self.downtime_epochs.max(0).saturating_mul(machine.load_per_epoch.max(0))
+    }
+}
+
+pub mod chain;
+pub mod guard;
+pub mod warmup;
@@ -0,0 +1,119 @@
+//! Warm-up gating for predictors that need history before their predictions
+//! mean anything. A predictor reports readiness per key; callers hold off
+//! branching on keys that are not ready, or branch with a fallback.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tw_core::EpochTime;
+
+/// Implemented by whatever knows how much history a predictor has seen.
+pub trait Readiness: Send + Sync {
+    /// Whether predictions for `key` are meaningful as of `epoch`.
+    fn is_ready(&self, epoch: EpochTime, key: u64) -> bool;
+    /// Share of keys seen so far that are ready, in `[0, 1]`.
+    fn ready_fraction(&self, epoch: EpochTime) -> f64;
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WarmUpConfig {
+    /// Observations of a key before predictions for it are ready.
+    #[serde(default)]
+    pub min_observations: u64,
+    /// Epochs since the first observation before any key is ready.
+    #[serde(default)]
+    pub min_epochs: u64,
+}
+
+/// Counts observations per key and readies a key once it has
+/// `min_observations` and the domain has been observed for `min_epochs`.
+#[derive(Debug, Default)]
+pub struct WarmUpTracker {
+    cfg: WarmUpConfig,
+    state: Mutex<WarmUpState>,
+}
+
+#[derive(Debug, Default)]
+struct WarmUpState {
+    first_epoch: Option<EpochTime>,
+    observations: HashMap<u64, u64>,
+    ready_keys: usize,
+}
+
+impl WarmUpTracker {
+    pub fn new(cfg: WarmUpConfig) -> Self {
+        Self { cfg, state: Mutex::default() }
+    }
+
+    /// Record one observation of `key` at `epoch`, such as an event the
+    /// predictor would learn from.
+    pub fn observe(&self, epoch: EpochTime, key: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.first_epoch.get_or_insert(epoch);
+        let seen = state.observations.entry(key).or_default();
+        *seen += 1;
+        if *seen == self.cfg.min_observations.max(1) {
+            state.ready_keys += 1;
+        }
+    }
+
+    fn domain_ready(&self, state: &WarmUpState, epoch: EpochTime) -> bool {
+        state.first_epoch.is_some_and(|first| epoch.since(first) >= self.cfg.min_epochs)
+    }
+}
+
+impl Readiness for WarmUpTracker {
+    fn is_ready(&self, epoch: EpochTime, key: u64) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.domain_ready(&state, epoch)
+            && state.observations.get(&key).copied().unwrap_or(0) >= self.cfg.min_observations
+    }
+
+    fn ready_fraction(&self, epoch: EpochTime) -> f64 {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.observations.is_empty() || !self.domain_ready(&state, epoch) {
+            return 0.0;
+        }
+        state.ready_keys as f64 / state.observations.len() as f64
+    }
+}
+
+/// Gates predictor `P` on a [`Readiness`]: keys still warming up either
+/// don't branch or predict with `fallback`.
+pub struct WarmUpGate<P: ?Sized> {
+    readiness: Arc<dyn Readiness>,
+    fallback: Option<Arc<P>>,
+    warming: AtomicU64,
+}
+
+impl<P: ?Sized> WarmUpGate<P> {
+    pub fn new(readiness: Arc<dyn Readiness>, fallback: Option<Arc<P>>) -> Self {
+        Self { readiness, fallback, warming: AtomicU64::new(0) }
+    }
+
+    /// Whether an event on `key` may branch at `epoch`: it is ready, or a
+    /// fallback stands in. Counts every event on a key still warming up.
+    pub fn admits(&self, epoch: EpochTime, key: u64) -> bool {
+        if self.readiness.is_ready(epoch, key) {
+            return true;
+        }
+        self.warming.fetch_add(1, Ordering::Relaxed);
+        self.fallback.is_some()
+    }
+
+    /// The predictor to use instead while `key` warms up.
+    pub fn fallback(&self, epoch: EpochTime, key: u64) -> Option<&Arc<P>> {
+        self.fallback.as_ref().filter(|_| !self.readiness.is_ready(epoch, key))
+    }
+
+    pub fn readiness(&self) -> &dyn Readiness {
+        self.readiness.as_ref()
+    }
+
+    /// Events on keys still warming up since the last call.
+    pub fn take_warming(&self) -> u64 {
+        self.warming.swap(0, Ordering::Relaxed)
+    }
+}
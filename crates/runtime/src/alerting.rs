@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tw_core::{EpochTime, EventTime};
+
+const HOUR_MS: u64 = 3_600_000;
+
+/// Delivery controls for one subscription's alerts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertPolicy {
+    /// UTC hours `[start, end)` during which alerts are held back; wraps
+    /// midnight when `start > end`.
+    #[serde(default)]
+    pub quiet_hours: Option<(u8, u8)>,
+    /// Maximum alerts delivered per wall-clock hour; extras are summarized.
+    #[serde(default)]
+    pub max_per_hour: Option<u32>,
+    /// Escalate once the condition has fired in this many consecutive epochs.
+    #[serde(default)]
+    pub escalate_after_epochs: Option<u32>,
+    /// How long the condition must hold before an alert is raised at all.
+    #[serde(default)]
+    pub persistence: Persistence,
+}
+
+impl AlertPolicy {
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading alert policy {}", path.display()))?;
+        let policy: Self = serde_json::from_str(&raw)
+            .with_context(|| format!("parsing alert policy {}", path.display()))?;
+        policy
+            .persistence
+            .validate()
+            .with_context(|| format!("checking alert policy {}", path.display()))?;
+        Ok(policy)
+    }
+
+    fn is_quiet(&self, ts: EventTime) -> bool {
+        let Some((start, end)) = self.quiet_hours else {
+            return false;
+        };
+        let hour = ((ts.as_millis() / HOUR_MS) % 24) as u8;
+        if start <= end {
+            hour >= start && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+/// How long a condition must hold, per (scenario, key), before it alerts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Persistence {
+    /// Alert on the first epoch the condition holds.
+    #[default]
+    Immediate,
+    /// Held for this many consecutive epochs.
+    Sustained { epochs: u32 },
+    /// Held in at least `n` of the last `m` epochs, with `n <= m <= 64`.
+    NOfM { n: u32, m: u32 },
+}
+
+impl Persistence {
+    /// Errors on a rule that could never be met: an `NOfM` window that is
+    /// empty, wider than 64 epochs or shorter than `n`.
+    pub fn validate(self) -> Result<()> {
+        if let Persistence::NOfM { n, m } = self {
+            if m == 0 || m > u64::BITS {
+                bail!("n_of_m window m = {m} must be between 1 and {}", u64::BITS);
+            }
+            if n > m {
+                bail!("n_of_m needs n <= m, got n = {n} of m = {m}");
+            }
+        }
+        Ok(())
+    }
+
+    fn window(self) -> u32 {
+        match self {
+            Persistence::Immediate => 1,
+            Persistence::Sustained { epochs } => epochs,
+            Persistence::NOfM { m, .. } => m,
+        }
+        .clamp(1, u64::BITS)
+    }
+
+    /// Bits of history the rule looks at.
+    fn mask(self) -> u64 {
+        match self.window() {
+            u64::BITS => u64::MAX,
+            window => (1 << window) - 1,
+        }
+    }
+
+    fn is_met(self, history: u64) -> bool {
+        let held = history & self.mask();
+        match self {
+            Persistence::Immediate => held & 1 == 1,
+            Persistence::Sustained { .. } => held.count_ones() == self.window(),
+            Persistence::NOfM { n, .. } => held.count_ones() >= n.max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConditionWindow<V> {
+    /// Net multiplicity of the condition's rows; positive while it holds.
+    count: isize,
+    /// Bit `i` is set if the condition held `i` epochs before the last
+    /// closed one; only the rule's window is kept.
+    history: u64,
+    firing: bool,
+    latest: Option<V>,
+}
+
+/// Windowed condition state for one subscription, keyed by
+/// `(scenario, key)`. Feed it the subscription's row updates as they arrive,
+/// then close each epoch to learn which keys have newly met the rule. A key
+/// fires once when it meets the rule and again only after it has lapsed.
+#[derive(Debug, Clone)]
+pub struct PersistenceTracker<K, V> {
+    rule: Persistence,
+    windows: BTreeMap<K, ConditionWindow<V>>,
+    last_closed: Option<EpochTime>,
+}
+
+impl<K: Ord + Clone, V: Clone> PersistenceTracker<K, V> {
+    /// Errors if `rule` fails [`Persistence::validate`].
+    pub fn new(rule: Persistence) -> Result<Self> {
+        rule.validate()?;
+        Ok(Self { rule, windows: BTreeMap::new(), last_closed: None })
+    }
+
+    /// Record a change in the condition's rows for `key`; `value` is the
+    /// row's payload, reported back when the key fires.
+    pub fn update(&mut self, key: K, value: V, diff: isize) {
+        let window = self.windows.entry(key).or_insert_with(|| ConditionWindow {
+            count: 0,
+            history: 0,
+            firing: false,
+            latest: None,
+        });
+        window.count += diff;
+        if diff > 0 {
+            window.latest = Some(value);
+        }
+    }
+
+    /// Close `epoch` and return the keys that newly meet the rule. Epochs
+    /// skipped since the last call carry the condition's state unchanged.
+    pub fn close_epoch(&mut self, epoch: EpochTime) -> Vec<(K, V)> {
+        let elapsed = match self.last_closed {
+            Some(last) if epoch <= last => return Vec::new(),
+            Some(last) => epoch.since(last),
+            None => 1,
+        };
+        self.last_closed = Some(epoch);
+
+        let rule = self.rule;
+        let mut fired = Vec::new();
+        self.windows.retain(|key, window| {
+            let held = window.count > 0;
+            for _ in 0..elapsed.min(u64::BITS as u64) {
+                window.history = ((window.history << 1) | u64::from(held)) & rule.mask();
+            }
+            let met = rule.is_met(window.history);
+            if met && !window.firing {
+                if let Some(value) = &window.latest {
+                    fired.push((key.clone(), value.clone()));
+                }
+            }
+            window.firing = met;
+            held || window.history != 0
+        });
+        fired
+    }
+
+    pub fn rule(&self) -> Persistence {
+        self.rule
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertDecision {
+    Deliver,
+    /// Deliver with escalation; bypasses quiet hours and the hourly cap.
+    Escalate,
+    QuietHours,
+    RateCapped,
+}
+
+impl AlertDecision {
+    pub fn is_delivered(self) -> bool {
+        matches!(self, AlertDecision::Deliver | AlertDecision::Escalate)
+    }
+}
+
+/// Alerts held back during one hourly window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertOverflow {
+    pub window_start_ms: EventTime,
+    pub suppressed: u64,
+}
+
+/// One alert key's run of consecutive firing epochs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FiringStreak {
+    pub last_epoch: EpochTime,
+    pub epochs: u32,
+}
+
+/// Persistable gate state so counters and streaks survive restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertGateState {
+    pub window_start_ms: EventTime,
+    pub delivered_in_window: u32,
+    pub suppressed_in_window: u64,
+    /// Firing streak per alert key; a key drops out once it misses an epoch.
+    #[serde(default)]
+    pub streaks: BTreeMap<String, FiringStreak>,
+}
+
+pub struct AlertGate {
+    policy: AlertPolicy,
+    state: AlertGateState,
+    pending_overflow: Option<AlertOverflow>,
+}
+
+impl AlertGate {
+    pub fn new(policy: AlertPolicy) -> Self {
+        Self::restore(policy, AlertGateState::default())
+    }
+
+    pub fn restore(policy: AlertPolicy, state: AlertGateState) -> Self {
+        Self { policy, state, pending_overflow: None }
+    }
+
+    pub fn state(&self) -> &AlertGateState {
+        &self.state
+    }
+
+    /// Decide whether an alert for `key` raised at `epoch` (wall time `ts`)
+    /// goes out. Offer only rows that fire, not their retractions; `key`
+    /// escalates once it has fired in enough consecutive epochs, whatever
+    /// other keys do.
+    pub fn offer(&mut self, epoch: EpochTime, ts: EventTime, key: &str) -> AlertDecision {
+        self.roll_window(ts);
+        let streak = self.track_streak(epoch, key);
+
+        let escalate = self.policy.escalate_after_epochs.is_some_and(|n| streak >= n);
+        let decision = if escalate {
+            AlertDecision::Escalate
+        } else if self.policy.is_quiet(ts) {
+            AlertDecision::QuietHours
+        } else if self
+            .policy
+            .max_per_hour
+            .is_some_and(|cap| self.state.delivered_in_window >= cap)
+        {
+            AlertDecision::RateCapped
+        } else {
+            AlertDecision::Deliver
+        };
+
+        if decision.is_delivered() {
+            self.state.delivered_in_window = self.state.delivered_in_window.saturating_add(1);
+        } else {
+            self.state.suppressed_in_window += 1;
+        }
+        decision
+    }
+
+    /// Summary of alerts suppressed in the last closed window, if any.
+    pub fn take_overflow(&mut self) -> Option<AlertOverflow> {
+        self.pending_overflow.take()
+    }
+
+    /// Close the hourly window on the epoch clock once `ts` falls outside it, and
+    /// return its summary. Without this a window only closes when a later
+    /// alert arrives, so a quiet hour after a burst never reports it.
+    pub fn close_window(&mut self, ts: EventTime) -> Option<AlertOverflow> {
+        self.roll_window(ts);
+        self.take_overflow()
+    }
+
+    fn roll_window(&mut self, ts: EventTime) {
+        let window_start_ms = EventTime::from_millis(ts.as_millis() - ts.as_millis() % HOUR_MS);
+        if window_start_ms == self.state.window_start_ms {
+            return;
+        }
+        if self.state.suppressed_in_window > 0 {
+            self.pending_overflow = Some(AlertOverflow {
+                window_start_ms: self.state.window_start_ms,
+                suppressed: self.state.suppressed_in_window,
+            });
+        }
+        self.state.window_start_ms = window_start_ms;
+        self.state.delivered_in_window = 0;
+        self.state.suppressed_in_window = 0;
+    }
+
+    /// Extend `key`'s streak to `epoch` and return its length in epochs.
+    fn track_streak(&mut self, epoch: EpochTime, key: &str) -> u32 {
+        let epochs = match self.state.streaks.get(key) {
+            Some(streak) if streak.last_epoch == epoch => return streak.epochs,
+            Some(streak) if streak.last_epoch.next() == epoch => streak.epochs + 1,
+            _ => 1,
+        };
+        self.state.streaks.insert(key.to_string(), FiringStreak { last_epoch: epoch, epochs });
+        // Keys that did not fire in the previous epoch have broken their streak
+        self.state.streaks.retain(|_, streak| streak.last_epoch.next() >= epoch);
+        epochs
+    }
+}
@@ -0,0 +1,158 @@
+//! Per-key causal ordering of events that arrive interleaved across
+//! sources, so views such as lifecycle funnels never see a job complete
+//! before it started.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use tw_core::{EpochTime, EventEnvelope};
+
+/// Events of `kind` wait until every kind in `after` has been released for
+/// the same `EventMeta::key`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CausalRule {
+    pub kind: String,
+    pub after: Vec<String>,
+}
+
+impl CausalRule {
+    pub fn new(kind: impl Into<String>, after: &[&str]) -> Self {
+        Self { kind: kind.into(), after: after.iter().map(|kind| kind.to_string()).collect() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CausalConfig {
+    #[serde(default)]
+    pub rules: Vec<CausalRule>,
+    /// Epochs an event is held for its predecessors before it is released
+    /// anyway.
+    pub max_wait_epochs: u64,
+    /// Bound on held events; the oldest are released first when it binds.
+    pub max_held: usize,
+    /// Epochs a key's released kinds are remembered after its last event.
+    pub ttl_epochs: u64,
+}
+
+impl Default for CausalConfig {
+    fn default() -> Self {
+        Self { rules: Vec::new(), max_wait_epochs: 5, max_held: 10_000, ttl_epochs: 60 }
+    }
+}
+
+/// Manufacturing order: a job's completion, scrap, rework and material
+/// draw follow its start.
+pub fn manufacturing_order() -> Vec<CausalRule> {
+    ["OperationComplete", "OperationScrapped", "ReworkRequired", "MaterialConsumed"]
+        .into_iter()
+        .map(|kind| CausalRule::new(kind, &["OperationStart"]))
+        .collect()
+}
+
+/// Holds events whose predecessors for their key have not been released
+/// yet and releases them, in arrival order, once they have. Events without
+/// a key or a rule for their kind pass straight through.
+pub struct CausalOrderer<T> {
+    cfg: CausalConfig,
+    after: HashMap<String, Vec<String>>,
+    /// Kinds released per key, and the epoch of the key's latest event.
+    released: HashMap<String, (HashSet<String>, EpochTime)>,
+    held: VecDeque<EventEnvelope<T>>,
+    forced: u64,
+}
+
+impl<T> CausalOrderer<T> {
+    pub fn new(cfg: CausalConfig) -> Self {
+        let after =
+            cfg.rules.iter().map(|rule| (rule.kind.clone(), rule.after.clone())).collect();
+        Self { cfg, after, released: HashMap::new(), held: VecDeque::new(), forced: 0 }
+    }
+
+    /// Offer one event; returns the events it releases, itself first when
+    /// its predecessors are in, followed by held events it unblocks.
+    pub fn offer(&mut self, env: EventEnvelope<T>) -> Vec<EventEnvelope<T>> {
+        let mut out = Vec::new();
+        if self.is_ready(&env) {
+            self.release(env, &mut out);
+        } else {
+            self.held.push_back(env);
+            while self.held.len() > self.cfg.max_held {
+                if let Some(env) = self.held.pop_front() {
+                    self.forced += 1;
+                    self.release(env, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    /// Release events held for `max_wait_epochs` as of `epoch`, with what
+    /// they unblock, and forget idle keys past `ttl_epochs`.
+    pub fn advance(&mut self, epoch: EpochTime) -> Vec<EventEnvelope<T>> {
+        let mut out = Vec::new();
+        let max_wait = self.cfg.max_wait_epochs;
+        while let Some(i) = self
+            .held
+            .iter()
+            .position(|env| env.meta.epoch.saturating_add(max_wait) <= epoch)
+        {
+            if let Some(env) = self.held.remove(i) {
+                self.forced += 1;
+                self.release(env, &mut out);
+            }
+        }
+        let ttl = self.cfg.ttl_epochs;
+        let held = &self.held;
+        self.released.retain(|key, (_, last)| {
+            last.saturating_add(ttl) > epoch
+                || held.iter().any(|env| env.meta.key.as_ref() == Some(key))
+        });
+        out
+    }
+
+    pub fn held_len(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Events released without their predecessors, on timeout or to respect
+    /// `max_held`, since startup.
+    pub fn forced(&self) -> u64 {
+        self.forced
+    }
+
+    fn is_ready(&self, env: &EventEnvelope<T>) -> bool {
+        let (Some(key), Some(after)) = (&env.meta.key, self.after.get(&env.meta.kind)) else {
+            return true;
+        };
+        let released = self.released.get(key).map(|(kinds, _)| kinds);
+        after.iter().all(|kind| released.is_some_and(|kinds| kinds.contains(kind)))
+    }
+
+    /// Release `env`, then every held event of its key that it unblocks.
+    fn release(&mut self, env: EventEnvelope<T>, out: &mut Vec<EventEnvelope<T>>) {
+        let key = env.meta.key.clone();
+        self.mark_released(&env);
+        out.push(env);
+        let Some(key) = key else {
+            return;
+        };
+        while let Some(i) = self
+            .held
+            .iter()
+            .position(|held| held.meta.key.as_ref() == Some(&key) && self.is_ready(held))
+        {
+            if let Some(env) = self.held.remove(i) {
+                self.mark_released(&env);
+                out.push(env);
+            }
+        }
+    }
+
+    fn mark_released(&mut self, env: &EventEnvelope<T>) {
+        if let Some(key) = &env.meta.key {
+            let (kinds, last) = self.released.entry(key.clone()).or_default();
+            kinds.insert(env.meta.kind.clone());
+            *last = (*last).max(env.meta.epoch);
+        }
+    }
+}
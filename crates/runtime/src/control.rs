@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+use tw_core::EpochTime;
+
+pub type Ticket = u64;
+
+/// Confirms a control-plane change: the first epoch it applied to, or why
+/// it was not applied.
+#[derive(Debug, Clone, Serialize)]
+pub struct Ack {
+    pub ticket: Ticket,
+    pub effective_epoch: EpochTime,
+    pub error: Option<String>,
+}
+
+struct Queued<C> {
+    ticket: Ticket,
+    change: C,
+    reply: Sender<Ack>,
+}
+
+struct ControlInner<C> {
+    next_ticket: Ticket,
+    pending: VecDeque<Queued<C>>,
+}
+
+/// Queue for subscription registrations, config updates and predictor swaps.
+/// Submitters may run on any thread or connect through [`Self::serve`]; the
+/// driver drains the queue only at epoch boundaries, so every change applies
+/// to whole epochs.
+pub struct ControlPlane<C> {
+    inner: Arc<Mutex<ControlInner<C>>>,
+}
+
+impl<C> Clone for ControlPlane<C> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<C> Default for ControlPlane<C> {
+    fn default() -> Self {
+        let inner = ControlInner { next_ticket: 0, pending: VecDeque::new() };
+        Self { inner: Arc::new(Mutex::new(inner)) }
+    }
+}
+
+impl<C> ControlPlane<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `change`; the receiver yields its [`Ack`] once it is applied.
+    pub fn submit(&self, change: C) -> (Ticket, Receiver<Ack>) {
+        let (reply, acked) = channel();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let ticket = inner.next_ticket;
+        inner.next_ticket += 1;
+        inner.pending.push_back(Queued { ticket, change, reply });
+        (ticket, acked)
+    }
+
+    /// Apply every queued change, in submission order, as of
+    /// `effective_epoch`. Call between epochs, before any input for
+    /// `effective_epoch` is inserted.
+    pub fn drain<F>(&self, effective_epoch: EpochTime, mut apply: F) -> Vec<Ack>
+    where
+        F: FnMut(C) -> Result<()>,
+    {
+        let pending = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut inner.pending)
+        };
+        pending
+            .into_iter()
+            .map(|queued| {
+                let error = apply(queued.change).err().map(|err| format!("{err:#}"));
+                let ack = Ack { ticket: queued.ticket, effective_epoch, error };
+                // The submitter may have stopped waiting
+                let _ = queued.reply.send(ack.clone());
+                ack
+            })
+            .collect()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).pending.len()
+    }
+}
+
+impl<C: DeserializeOwned + Send + 'static> ControlPlane<C> {
+    /// Accept changes on `addr`, one JSON object per line. Each line is
+    /// answered with its [`Ack`] once a drain has applied it, or with an
+    /// `error` object if it does not parse. Returns the bound address.
+    pub fn serve(&self, addr: impl ToSocketAddrs) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(addr).context("binding control listener")?;
+        let local = listener.local_addr()?;
+        let plane = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        warn!(?err, "control connection failed");
+                        continue;
+                    }
+                };
+                let plane = plane.clone();
+                thread::spawn(move || {
+                    if let Err(err) = plane.answer(stream) {
+                        warn!(?err, "control connection closed");
+                    }
+                });
+            }
+        });
+        Ok(local)
+    }
+
+    fn answer(&self, stream: TcpStream) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<C>(&line) {
+                Ok(change) => {
+                    let (_, acked) = self.submit(change);
+                    serde_json::to_string(&acked.recv().context("control plane stopped")?)?
+                }
+                Err(err) => serde_json::json!({ "error": err.to_string() }).to_string(),
+            };
+            writer.write_all(reply.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use tw_core::{EpochTime, EventMeta, EventTime};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    /// Epochs an identity is remembered; 0 disables deduplication.
+    pub ttl_epochs: u64,
+    /// Hard bound on remembered identities; the oldest are forgotten first.
+    pub max_entries: usize,
+    /// Event kinds that are legitimately repeated and never dropped.
+    #[serde(default)]
+    pub repeat_kinds: Vec<String>,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self { ttl_epochs: 0, max_entries: 100_000, repeat_kinds: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct EventIdentity {
+    source: String,
+    kind: String,
+    key: String,
+    ts: EventTime,
+}
+
+/// Drops events already seen with the same `(source, kind, key, ts)` within
+/// the TTL, so at-least-once sources don't double-count aggregates. The kind
+/// keeps distinct events about one key at one instant apart, such as an
+/// operator clocking in and being assigned. Events without a key cannot be
+/// identified and always pass.
+pub struct Deduplicator {
+    cfg: DedupConfig,
+    seen: HashMap<EventIdentity, EpochTime>,
+    order: VecDeque<(EpochTime, EventIdentity)>,
+}
+
+impl Deduplicator {
+    pub fn new(cfg: DedupConfig) -> Self {
+        Self { cfg, seen: HashMap::new(), order: VecDeque::new() }
+    }
+
+    /// Returns `true` if the event should be inserted, `false` for a duplicate.
+    pub fn admit(&mut self, meta: &EventMeta, ts: EventTime) -> bool {
+        if self.cfg.ttl_epochs == 0 || self.cfg.repeat_kinds.contains(&meta.kind) {
+            return true;
+        }
+        let Some(key) = meta.key.as_ref() else {
+            return true;
+        };
+        let identity = EventIdentity {
+            source: meta.source.clone(),
+            kind: meta.kind.clone(),
+            key: key.clone(),
+            ts,
+        };
+        if self.seen.contains_key(&identity) {
+            return false;
+        }
+        self.seen.insert(identity.clone(), meta.epoch);
+        self.order.push_back((meta.epoch, identity));
+        while self.order.len() > self.cfg.max_entries {
+            self.forget_oldest();
+        }
+        true
+    }
+
+    /// Forget identities first seen more than `ttl_epochs` before `epoch`.
+    pub fn advance(&mut self, epoch: EpochTime) {
+        while let Some((seen_at, _)) = self.order.front() {
+            if seen_at.saturating_add(self.cfg.ttl_epochs) > epoch {
+                break;
+            }
+            self.forget_oldest();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((_, identity)) = self.order.pop_front() {
+            self.seen.remove(&identity);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+use tw_core::EpochTime;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct EvictionConfig {
+    /// Epochs without an update after which a key is retracted; 0 keeps keys forever.
+    pub idle_epochs: u64,
+}
+
+/// What a key fed into a base aggregate before it went idle, consolidated.
+#[derive(Debug, Clone)]
+pub struct EvictedKey<K, V> {
+    pub key: K,
+    pub last_seen: EpochTime,
+    pub value: V,
+}
+
+struct KeyValue<V> {
+    last_seen: EpochTime,
+    value: V,
+}
+
+/// Sums the values each key inserted into a base aggregate so keys idle for
+/// `idle_epochs` can be retracted, keeping arrangements bounded by the
+/// number of live keys. Holds one value per key however often it is
+/// touched, so it only suits aggregates a single retraction of the sum
+/// takes back. A key seen again after eviction starts from zero.
+pub struct IdleKeyEvictor<K, V> {
+    cfg: EvictionConfig,
+    keys: HashMap<K, KeyValue<V>>,
+    order: VecDeque<(EpochTime, K)>,
+}
+
+impl<K, V> IdleKeyEvictor<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: AddAssign + Default,
+{
+    pub fn new(cfg: EvictionConfig) -> Self {
+        Self { cfg, keys: HashMap::new(), order: VecDeque::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cfg.idle_epochs > 0
+    }
+
+    /// Add a value inserted for `key` at `epoch`. No-op when disabled.
+    pub fn touch(&mut self, epoch: EpochTime, key: K, value: V) {
+        if !self.is_enabled() {
+            return;
+        }
+        match self.keys.get_mut(&key) {
+            Some(entry) => {
+                if entry.last_seen != epoch {
+                    entry.last_seen = epoch;
+                    self.order.push_back((epoch, key));
+                }
+                entry.value += value;
+            }
+            None => {
+                self.keys.insert(key.clone(), KeyValue { last_seen: epoch, value });
+                self.order.push_back((epoch, key));
+            }
+        }
+    }
+
+    /// Take every key idle for `idle_epochs` as of `epoch`; the caller
+    /// retracts the returned totals from the aggregate's input.
+    pub fn expire(&mut self, epoch: EpochTime) -> Vec<EvictedKey<K, V>> {
+        let mut evicted = Vec::new();
+        if !self.is_enabled() {
+            return evicted;
+        }
+        while let Some((seen_at, _)) = self.order.front() {
+            if seen_at.saturating_add(self.cfg.idle_epochs) > epoch {
+                break;
+            }
+            let Some((seen_at, key)) = self.order.pop_front() else {
+                break;
+            };
+            // Keys touched again since are queued under their later epoch
+            if self.keys.get(&key).is_some_and(|entry| entry.last_seen == seen_at) {
+                if let Some(entry) = self.keys.remove(&key) {
+                    let last_seen = entry.last_seen;
+                    evicted.push(EvictedKey { key, last_seen, value: entry.value });
+                }
+            }
+        }
+        evicted
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Last value of an evicted key, as written to the archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedKey<K> {
+    pub key: K,
+    pub last_seen: EpochTime,
+    pub evicted_at: EpochTime,
+    pub value: i64,
+}
@@ -0,0 +1,74 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use tracing::warn;
+use tw_core::EpochTime;
+
+use crate::metrics::MetricsSnapshot;
+
+/// User code run at an epoch boundary, after the epoch's metrics are final.
+pub type EpochHook = Box<dyn FnMut(EpochTime, &MetricsSnapshot) -> Result<()>>;
+
+/// Named hooks run in registration order once each epoch completes, so
+/// callers can flush caches, trigger downstream jobs or adjust configs
+/// without changing the driver loop. A failing hook is logged and the rest
+/// still run.
+#[derive(Default)]
+pub struct EpochHooks {
+    hooks: Vec<(String, EpochHook)>,
+}
+
+impl EpochHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_epoch_complete<F>(&mut self, name: impl Into<String>, hook: F)
+    where
+        F: FnMut(EpochTime, &MetricsSnapshot) -> Result<()> + 'static,
+    {
+        self.hooks.push((name.into(), Box::new(hook)));
+    }
+
+    /// Run every hook for the completed `epoch`.
+    pub fn run(&mut self, epoch: EpochTime, snapshot: &MetricsSnapshot) {
+        for (name, hook) in &mut self.hooks {
+            if let Err(err) = hook(epoch, snapshot) {
+                warn!(hook = %name, %epoch, ?err, "epoch hook failed");
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+}
+
+/// A hook that runs `command` through `sh -c` once per epoch, with the epoch
+/// in `TW_EPOCH` and the metrics snapshot as JSON on stdin. The epoch loop
+/// waits for it, and a nonzero exit fails the hook.
+pub fn command_hook(command: String) -> impl FnMut(EpochTime, &MetricsSnapshot) -> Result<()> {
+    move |epoch, snapshot| {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .env("TW_EPOCH", epoch.get().to_string())
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("spawning {command:?}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            serde_json::to_writer(&mut stdin, snapshot)?;
+            stdin.write_all(b"\n")?;
+        }
+        let status = child.wait().with_context(|| format!("waiting on {command:?}"))?;
+        if !status.success() {
+            bail!("{command:?} exited with {status}");
+        }
+        Ok(())
+    }
+}
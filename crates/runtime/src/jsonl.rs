@@ -0,0 +1,38 @@
+//! Append-only JSON Lines files: dead letters, archived keys, training
+//! labels and anything else written one record per line.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// Appends serialized records to a JSON Lines file, creating it if missing.
+pub struct JsonlSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl JsonlSink {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        Ok(Self { path: path.to_path_buf(), writer: BufWriter::new(file) })
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)
+            .with_context(|| format!("writing to {}", self.path.display()))?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().with_context(|| format!("flushing {}", self.path.display()))
+    }
+}
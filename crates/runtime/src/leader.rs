@@ -0,0 +1,286 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+/// Cheap, cloneable view of whether this instance currently holds leadership.
+#[derive(Debug, Clone, Default)]
+pub struct Leadership(Arc<AtomicBool>);
+
+impl Leadership {
+    /// Always leader; used when no election is configured.
+    pub fn standalone() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, leader: bool) {
+        self.0.store(leader, Ordering::Relaxed);
+    }
+}
+
+/// Active-standby election over a lock file on shared storage. The file
+/// holds the holder and the generation of its term; the holder renews the
+/// lease by rewriting the file, and a standby takes over once the file has not
+/// been touched for `ttl`. Every write goes to a temporary file renamed into
+/// place, so readers never see a partial term.
+///
+/// A takeover claims the next generation with an exclusive create before
+/// writing it, so of several standbys racing for a stale lease only one
+/// moves to that generation. A holder only renews its own generation, so a
+/// leader that stalled past `ttl` finds the new term and steps down.
+pub struct FileLease {
+    path: PathBuf,
+    holder: String,
+    ttl: Duration,
+    /// Generation of the term this instance holds, while leader.
+    generation: u64,
+    /// Names this instance's temporary file apart from other instances'.
+    stage: String,
+    leadership: Leadership,
+}
+
+/// Instances created in this process, for [`FileLease::stage`].
+static LEASES: AtomicU64 = AtomicU64::new(0);
+
+/// One term of a lease file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Term {
+    /// Empty once the holder released the lease.
+    holder: String,
+    generation: u64,
+}
+
+impl Term {
+    fn parse(text: &str) -> Option<Self> {
+        let (holder, generation) = text.rsplit_once('\n')?;
+        Some(Self { holder: holder.to_string(), generation: generation.trim().parse().ok()? })
+    }
+
+    fn render(&self) -> String {
+        format!("{}\n{}", self.holder, self.generation)
+    }
+}
+
+impl FileLease {
+    pub fn new(path: impl AsRef<Path>, holder: impl Into<String>, ttl: Duration) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            holder: holder.into(),
+            ttl,
+            generation: 0,
+            stage: format!("{}-{}", std::process::id(), LEASES.fetch_add(1, Ordering::Relaxed)),
+            leadership: Leadership::default(),
+        }
+    }
+
+    pub fn leadership(&self) -> Leadership {
+        self.leadership.clone()
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.leadership.is_leader()
+    }
+
+    /// Acquire or renew the lease; call at least once per `ttl`, or hand the
+    /// lease to a [`LeaseKeeper`]. Returns whether this instance is leader
+    /// afterwards.
+    pub fn try_acquire(&mut self) -> Result<bool> {
+        let was_leader = self.is_leader();
+        let leader = if was_leader {
+            self.renew()?
+        } else {
+            self.take_over()?
+        };
+        if leader != was_leader {
+            let generation = self.generation;
+            info!(holder = %self.holder, leader, generation, "leadership changed");
+        }
+        self.leadership.set(leader);
+        Ok(leader)
+    }
+
+    /// Give up leadership so a standby can take over without waiting for the
+    /// TTL. The file keeps the generation, so later terms still count up.
+    pub fn release(&mut self) {
+        if self.is_leader() && self.holds_current_term() {
+            let released = Term { holder: String::new(), generation: self.generation };
+            let _ = self.write_term(&released);
+        }
+        self.leadership.set(false);
+    }
+
+    fn renew(&self) -> Result<bool> {
+        if !self.holds_current_term() {
+            return Ok(false);
+        }
+        self.write_term(&self.own_term(self.generation))?;
+        Ok(true)
+    }
+
+    fn take_over(&mut self) -> Result<bool> {
+        let Some(current) = self.read_term()? else {
+            return self.create_first();
+        };
+        if !current.holder.is_empty() && !self.is_stale(&self.path)? {
+            return Ok(false);
+        }
+        let next = current.generation + 1;
+        if !self.claim(next)? {
+            return Ok(false);
+        }
+        self.write_term(&self.own_term(next))?;
+        let _ = fs::remove_file(self.claim_path(current.generation));
+        // Only a term still in the file after the write is ours
+        self.generation = next;
+        Ok(self.holds_current_term())
+    }
+
+    /// Create the lease file at generation 1 if none exists yet. Linking
+    /// the written file into place fails if another instance got there first.
+    fn create_first(&mut self) -> Result<bool> {
+        let staged = self.write_staged(&self.own_term(1))?;
+        let linked = fs::hard_link(&staged, &self.path);
+        let _ = fs::remove_file(&staged);
+        match linked {
+            Ok(()) => {
+                self.generation = 1;
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(err) => Err(err).with_context(|| format!("creating lease {}", self.path.display())),
+        }
+    }
+
+    /// Claim `generation` for this instance; only one exclusive create of its
+    /// claim file succeeds. A claim left older than `ttl` by an instance that
+    /// died mid-takeover is cleared for the next attempt.
+    fn claim(&self, generation: u64) -> Result<bool> {
+        let path = self.claim_path(generation);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(self.holder.as_bytes())
+                    .with_context(|| format!("writing lease claim {}", path.display()))?;
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                if self.is_stale(&path)? {
+                    let _ = fs::remove_file(&path);
+                }
+                Ok(false)
+            }
+            Err(err) => {
+                Err(err).with_context(|| format!("creating lease claim {}", path.display()))
+            }
+        }
+    }
+
+    fn own_term(&self, generation: u64) -> Term {
+        Term { holder: self.holder.clone(), generation }
+    }
+
+    fn holds_current_term(&self) -> bool {
+        self.read_term().ok().flatten() == Some(self.own_term(self.generation))
+    }
+
+    /// Replace the lease file with `term` in one rename.
+    fn write_term(&self, term: &Term) -> Result<()> {
+        let staged = self.write_staged(term)?;
+        fs::rename(&staged, &self.path)
+            .with_context(|| format!("writing lease {}", self.path.display()))
+    }
+
+    /// Write `term` to this instance's own temporary file next to the lease.
+    fn write_staged(&self, term: &Term) -> Result<PathBuf> {
+        let staged = self.sibling(&format!("{}.tmp", self.stage));
+        fs::write(&staged, term.render())
+            .with_context(|| format!("writing lease {}", staged.display()))?;
+        Ok(staged)
+    }
+
+    fn claim_path(&self, generation: u64) -> PathBuf {
+        self.sibling(&format!("claim{generation}"))
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".");
+        path.push(suffix);
+        path.into()
+    }
+
+    fn is_stale(&self, path: &Path) -> Result<bool> {
+        let modified = match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(true),
+            Err(err) => {
+                return Err(err).with_context(|| format!("reading lease {}", path.display()))
+            }
+        };
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        Ok(age > self.ttl)
+    }
+
+    /// The term in the lease file, or `None` if there is no file yet.
+    fn read_term(&self) -> Result<Option<Term>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Ok(Some(Term::parse(&text).unwrap_or(Term {
+                holder: String::new(),
+                generation: 0,
+            }))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("reading lease {}", self.path.display())),
+        }
+    }
+}
+
+impl Drop for FileLease {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Background thread that keeps a [`FileLease`] acquired or renewed on a
+/// fixed interval, independent of how long an epoch takes. Releases the
+/// lease when dropped.
+pub struct LeaseKeeper {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LeaseKeeper {
+    /// Keep `lease` from now on; `interval` should be well under its TTL.
+    pub fn spawn(mut lease: FileLease, interval: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+        let handle = thread::spawn(move || loop {
+            if let Err(err) = lease.try_acquire() {
+                warn!(%err, "leader lease check failed");
+            }
+            thread::park_timeout(interval);
+            if stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+        });
+        Self { stop, handle: Some(handle) }
+    }
+}
+
+impl Drop for LeaseKeeper {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Inputs that remember what they have been fed, so they can be reconciled
+//! against an authoritative set or retracted once settled.
+//!
+//! The scenario weight input is the motivating case. Each weight row is fed
+//! and retracted by whichever path created or retired its scenario, and a
+//! path that misses a retraction leaves a weight nothing would ever remove.
+//! [`LedgeredInput::reconcile`] against the scenario manager's live weights
+//! emits the corrections. The ledger lives in the process: dataflow state is
+//! not persisted, so after a restart the input starts empty and is refilled
+//! from the scenario checkpoint, which is what it reconciles against.
+
+use std::collections::BTreeMap;
+
+use differential_dataflow::input::InputSession;
+use differential_dataflow::{Collection, Data};
+use serde::{Deserialize, Serialize};
+use timely::dataflow::operators::Input as TimelyInput;
+use timely::dataflow::scopes::ScopeParent;
+use timely::progress::Timestamp;
+use tw_core::ScenarioId;
+
+/// What one reconciliation corrected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// Row copies retracted because the authoritative set lacks them.
+    pub retracted: usize,
+    /// Row copies inserted because the input lacked them.
+    pub restored: usize,
+}
+
+impl ReconcileReport {
+    pub fn is_clean(&self) -> bool {
+        self.retracted == 0 && self.restored == 0
+    }
+}
+
+/// An [`InputSession`] that keeps the net count of every row fed through it.
+pub struct LedgeredInput<T: Timestamp + Clone, D: Data> {
+    session: InputSession<T, D, isize>,
+    rows: BTreeMap<D, isize>,
+}
+
+impl<T: Timestamp + Clone, D: Data> Default for LedgeredInput<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Timestamp + Clone, D: Data> LedgeredInput<T, D> {
+    pub fn new() -> Self {
+        Self { session: InputSession::new(), rows: BTreeMap::new() }
+    }
+
+    pub fn to_collection<G>(&mut self, scope: &mut G) -> Collection<G, D, isize>
+    where
+        G: TimelyInput + ScopeParent<Timestamp = T>,
+    {
+        self.session.to_collection(scope)
+    }
+
+    pub fn insert(&mut self, row: D) {
+        self.update(row, 1);
+    }
+
+    pub fn remove(&mut self, row: D) {
+        self.update(row, -1);
+    }
+
+    pub fn update(&mut self, row: D, diff: isize) {
+        let count = self.rows.entry(row.clone()).or_default();
+        *count += diff;
+        if *count == 0 {
+            self.rows.remove(&row);
+        }
+        self.session.update(row, diff);
+    }
+
+    pub fn advance_to(&mut self, time: T) {
+        self.session.advance_to(time);
+    }
+
+    pub fn flush(&mut self) {
+        self.session.flush();
+    }
+
+    /// Distinct rows with a nonzero count.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Bring the input to exactly one copy of each row in `authoritative`
+    /// and none of anything else. Corrections take effect at the session's
+    /// current time.
+    pub fn reconcile(&mut self, authoritative: impl IntoIterator<Item = D>) -> ReconcileReport {
+        let mut target: BTreeMap<D, isize> =
+            authoritative.into_iter().map(|row| (row, 1)).collect();
+        let mut corrections = Vec::new();
+        for (row, count) in &self.rows {
+            let want = target.remove(row).unwrap_or(0);
+            if *count != want {
+                corrections.push((row.clone(), want - count));
+            }
+        }
+        corrections.extend(target);
+
+        let mut report = ReconcileReport::default();
+        for (row, diff) in corrections {
+            if diff < 0 {
+                report.retracted += diff.unsigned_abs();
+            } else {
+                report.restored += diff.unsigned_abs();
+            }
+            self.update(row, diff);
+        }
+        report
+    }
+}
+
+/// The retirement input behind a live scenario set. A retirement row only has
+/// to outlast the scenario's other rows, which are retracted at the same
+/// time, so each row is retracted again once its time is complete instead of
+/// accumulating for the life of the run.
+pub struct RetirementInput<T: Timestamp + Clone> {
+    session: InputSession<T, ScenarioId, isize>,
+    /// Retired ids not yet retracted, with the time each was retired at.
+    pending: Vec<(T, ScenarioId)>,
+}
+
+impl<T: Timestamp + Clone> Default for RetirementInput<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Timestamp + Clone> RetirementInput<T> {
+    pub fn new() -> Self {
+        Self { session: InputSession::new(), pending: Vec::new() }
+    }
+
+    pub fn to_collection<G>(&mut self, scope: &mut G) -> Collection<G, ScenarioId, isize>
+    where
+        G: TimelyInput + ScopeParent<Timestamp = T>,
+    {
+        self.session.to_collection(scope)
+    }
+
+    /// Retire `scenario_id` at the session's current time.
+    pub fn retire(&mut self, scenario_id: ScenarioId) {
+        self.pending.push((self.session.time().clone(), scenario_id));
+        self.session.insert(scenario_id);
+    }
+
+    /// Retract the rows retired before `complete`, a time the dataflow has
+    /// fully processed; by then the scenarios' other rows are gone from
+    /// every input. Returns how many rows were retracted.
+    pub fn retract_before(&mut self, complete: &T) -> usize {
+        let (settled, pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(time, _)| time.less_than(complete));
+        self.pending = pending;
+        for (_, scenario_id) in &settled {
+            self.session.remove(*scenario_id);
+        }
+        settled.len()
+    }
+
+    pub fn advance_to(&mut self, time: T) {
+        self.session.advance_to(time);
+    }
+
+    pub fn flush(&mut self) {
+        self.session.flush();
+    }
+
+    /// Retirement rows not yet retracted.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
@@ -3,7 +3,27 @@
 use anyhow::Result;
 use tracing::{info, Level};
 
+pub mod alerting;
+pub mod causal;
+pub mod control;
+pub mod dedup;
+pub mod eviction;
+pub mod hooks;
+pub mod jsonl;
+pub mod leader;
+pub mod ledger;
 pub mod metrics;
+pub mod profiling;
+pub mod projection;
+pub mod replay;
+pub mod reporter;
+pub mod resources;
+pub mod rollout;
+pub mod sampling;
+pub mod scheduler;
+pub mod subscription;
+pub mod transform;
+pub mod validation;
 
 pub fn init_tracing() {
     let _ = tracing_subscriber::fmt()
@@ -12,15 +32,22 @@ pub fn init_tracing() {
         .try_init();
 }
 
-/// Start a single-process timely runtime and execute the provided closure once per worker.
+/// Start a single-process timely runtime with `workers` threads and execute the provided
+/// closure once per worker.
 pub fn start_runtime<F>(workers: usize, f: F) -> Result<()>
 where
-    F: Fn(usize, &mut timely::worker::Worker<timely::communication::allocator::Generic>) + Clone + Send + 'static,
+    F: Fn(usize, &mut timely::worker::Worker<timely::communication::allocator::Generic>)
+        + Clone
+        + Send
+        + Sync
+        + 'static,
 {
+    let workers = workers.max(1);
     info!(%workers, "starting timely runtime");
-    timely::execute_from_args(std::env::args(), move |worker| {
+    timely::execute(timely::Config::process(workers), move |worker| {
         let index = worker.index();
         f(index, worker);
-    })?;
+    })
+    .map_err(anyhow::Error::msg)?;
     Ok(())
 }
@@ -1,95 +1,667 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::resources::ResourceUsage;
+use crate::scheduler::PipelineEpoch;
+use crate::transform::TransformStats;
 
 #[derive(Clone, Default)]
 pub struct MetricsRegistry {
     inner: Arc<MetricsInner>,
+    /// Process-wide totals a domain registry also counts into.
+    aggregate: Option<Arc<MetricsInner>>,
 }
 
 #[derive(Default)]
 struct MetricsInner {
     base_events: AtomicU64,
+    duplicate_events: AtomicU64,
+    invalid_events: AtomicU64,
+    sampled_out_events: AtomicU64,
+    sample_keep_bps: AtomicU64,
     predicted_events: AtomicU64,
+    predictions_clamped: AtomicU64,
+    predictions_warming_up: AtomicU64,
     scenario_alerts: AtomicU64,
+    alerts_suppressed: AtomicU64,
+    alerts_shed: AtomicU64,
+    scenarios_shed: AtomicU64,
+    scenarios_rejected: AtomicU64,
+    keys_evicted: AtomicU64,
     scenario_created: AtomicU64,
     scenario_retired: AtomicU64,
     scenario_active_peak: AtomicU64,
+    /// Scenarios active as of the last [`MetricsRegistry::record_active_peak`].
+    scenario_active: AtomicU64,
+    process_rss_bytes: AtomicU64,
+    process_cpu_time_ms: AtomicU64,
+    process_open_fds: AtomicU64,
+    process_threads: AtomicU64,
+    subscription_latency: Mutex<BTreeMap<String, SubscriptionLatency>>,
+    pipeline_latency: Mutex<BTreeMap<String, PipelineLatency>>,
+    transforms: Mutex<BTreeMap<String, TransformStats>>,
+    scenario_retired_by_reason: Mutex<BTreeMap<String, u64>>,
+    /// Share of keys each predictor has warmed up on, in basis points.
+    predictor_ready_bps: Mutex<BTreeMap<String, u64>>,
+    /// Per-domain counters; only the process-wide registry holds any.
+    domains: Mutex<BTreeMap<String, Arc<MetricsInner>>>,
+    /// Lifetime totals from earlier runs, restored from a checkpoint.
+    baseline: Mutex<CounterBaseline>,
+}
+
+/// Lifetime counter totals, persisted with a checkpoint so counters carry
+/// on across restarts instead of resetting: one set for the process-wide
+/// registry and one per domain.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsBaseline {
+    #[serde(default)]
+    pub totals: CounterBaseline,
+    #[serde(default)]
+    pub domains: BTreeMap<String, CounterBaseline>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CounterBaseline {
+    /// By [`MetricsSnapshot`] field name; gauges are not carried over.
+    #[serde(default)]
+    pub counters: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub scenario_retired_by_reason: BTreeMap<String, u64>,
+}
+
+/// Time from input flush until a subscription's output completed an epoch.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SubscriptionLatency {
+    pub name: String,
+    pub epochs: u64,
+    pub last_us: u64,
+    pub max_us: u64,
+    pub total_us: u64,
+}
+
+/// Step budget adherence of one scheduled pipeline. Latencies cover only
+/// the epochs it caught up within budget.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PipelineLatency {
+    pub name: String,
+    pub epochs: u64,
+    pub over_budget_epochs: u64,
+    pub last_budget: u64,
+    pub last_steps: u64,
+    pub total_steps: u64,
+    pub last_us: u64,
+    pub max_us: u64,
+    pub total_us: u64,
 }
 
 impl MetricsRegistry {
     pub fn inc_base_events(&self, delta: u64) {
-        self.inner.base_events.fetch_add(delta, Ordering::Relaxed);
+        self.each(|m| m.base_events.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    pub fn inc_duplicate_events(&self, delta: u64) {
+        self.each(|m| m.duplicate_events.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    pub fn inc_invalid_events(&self, delta: u64) {
+        self.each(|m| m.invalid_events.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    pub fn inc_sampled_out_events(&self, delta: u64) {
+        self.each(|m| m.sampled_out_events.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Mark outputs as sampled at `keep_bps`; unset means unsampled. The
+    /// process-wide totals report the lowest rate any domain samples at.
+    pub fn record_sample_rate(&self, keep_bps: u64) {
+        self.inner.sample_keep_bps.store(keep_bps, Ordering::Relaxed);
+        if let Some(aggregate) = &self.aggregate {
+            let lowest = aggregate
+                .domain_values(|m| m.sample_keep_bps.load(Ordering::Relaxed))
+                .into_iter()
+                .filter(|bps| *bps > 0)
+                .min()
+                .unwrap_or(0);
+            aggregate.sample_keep_bps.store(lowest, Ordering::Relaxed);
+        }
     }
 
     pub fn inc_predicted_events(&self, delta: u64) {
-        self.inner.predicted_events.fetch_add(delta, Ordering::Relaxed);
+        self.each(|m| m.predicted_events.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    pub fn inc_predictions_clamped(&self, delta: u64) {
+        self.each(|m| m.predictions_clamped.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Count events on keys a predictor had not warmed up on, whether they
+    /// were held back or predicted with a fallback.
+    pub fn inc_predictions_warming_up(&self, delta: u64) {
+        self.each(|m| m.predictions_warming_up.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Record the share of keys `predictor` is ready for, in `[0, 1]`.
+    pub fn record_predictor_readiness(&self, predictor: &str, ready_fraction: f64) {
+        let bps = (ready_fraction.clamp(0.0, 1.0) * 10_000.0).round() as u64;
+        self.each(|m| {
+            let mut by_name = m.predictor_ready_bps.lock().unwrap_or_else(|e| e.into_inner());
+            by_name.insert(predictor.to_string(), bps);
+        });
+    }
+
+    pub fn predictor_readiness(&self) -> BTreeMap<String, u64> {
+        self.inner.predictor_ready_bps.lock().unwrap_or_else(|e| e.into_inner()).clone()
     }
 
     pub fn inc_scenario_alerts(&self, delta: u64) {
-        self.inner.scenario_alerts.fetch_add(delta, Ordering::Relaxed);
+        self.each(|m| m.scenario_alerts.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    pub fn inc_alerts_suppressed(&self, delta: u64) {
+        self.each(|m| m.alerts_suppressed.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Alerts dropped by the global per-epoch alert cap.
+    pub fn inc_alerts_shed(&self, delta: u64) {
+        self.each(|m| m.alerts_shed.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Scenarios retired because a global scenario or overlay-row cap bound.
+    pub fn inc_scenarios_shed(&self, delta: u64) {
+        self.each(|m| m.scenarios_shed.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Proposed scenarios turned down by a manager's validators.
+    pub fn inc_scenarios_rejected(&self, delta: u64) {
+        self.each(|m| m.scenarios_rejected.fetch_add(delta, Ordering::Relaxed));
+    }
+
+    /// Base-aggregate keys retracted after going idle.
+    pub fn inc_keys_evicted(&self, delta: u64) {
+        self.each(|m| m.keys_evicted.fetch_add(delta, Ordering::Relaxed));
     }
 
     pub fn inc_scenario_created(&self, delta: u64) {
-        self.inner.scenario_created.fetch_add(delta, Ordering::Relaxed);
+        self.each(|m| m.scenario_created.fetch_add(delta, Ordering::Relaxed));
     }
 
     pub fn inc_scenario_retired(&self, delta: u64) {
-        self.inner.scenario_retired.fetch_add(delta, Ordering::Relaxed);
+        self.each(|m| m.scenario_retired.fetch_add(delta, Ordering::Relaxed));
     }
 
+    /// Count `delta` retirements for `reason`; also counted in the total.
+    pub fn inc_scenario_retired_for(&self, reason: &str, delta: u64) {
+        self.each(|m| {
+            m.scenario_retired.fetch_add(delta, Ordering::Relaxed);
+            let mut by_reason =
+                m.scenario_retired_by_reason.lock().unwrap_or_else(|e| e.into_inner());
+            *by_reason.entry(reason.to_string()).or_default() += delta;
+        });
+    }
+
+    /// Retirements counted through [`Self::inc_scenario_retired_for`], by reason.
+    pub fn scenario_retired_by_reason(&self) -> BTreeMap<String, u64> {
+        self.inner.scenario_retired_by_reason.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Record how many scenarios are active now. The process-wide totals
+    /// keep the peak of the sum across domains, as of each domain's latest.
     pub fn record_active_peak(&self, active: u64) {
-        self.inner
-            .scenario_active_peak
-            .fetch_max(active, Ordering::Relaxed);
+        self.inner.scenario_active.store(active, Ordering::Relaxed);
+        self.inner.scenario_active_peak.fetch_max(active, Ordering::Relaxed);
+        if let Some(aggregate) = &self.aggregate {
+            let total = aggregate
+                .domain_values(|m| m.scenario_active.load(Ordering::Relaxed))
+                .into_iter()
+                .fold(0, u64::saturating_add);
+            aggregate.scenario_active.store(total, Ordering::Relaxed);
+            aggregate.scenario_active_peak.fetch_max(total, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_resources(&self, usage: &ResourceUsage) {
+        self.each(|m| {
+            m.process_rss_bytes.store(usage.rss_bytes, Ordering::Relaxed);
+            m.process_cpu_time_ms.store(usage.cpu_time_ms, Ordering::Relaxed);
+            m.process_open_fds.store(usage.open_fds, Ordering::Relaxed);
+            m.process_threads.store(usage.threads, Ordering::Relaxed);
+        });
     }
 
+    pub fn record_subscription_latency(&self, name: &str, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.each(|m| m.record_subscription_latency(name, micros));
+    }
+
+    pub fn subscription_latencies(&self) -> Vec<SubscriptionLatency> {
+        let by_name = self.inner.subscription_latency.lock().unwrap_or_else(|e| e.into_inner());
+        by_name.values().cloned().collect()
+    }
+
+    pub fn record_pipeline_epoch(&self, epoch: &PipelineEpoch) {
+        self.each(|m| m.record_pipeline_epoch(epoch));
+    }
+
+    pub fn pipeline_latencies(&self) -> Vec<PipelineLatency> {
+        let by_name = self.inner.pipeline_latency.lock().unwrap_or_else(|e| e.into_inner());
+        by_name.values().cloned().collect()
+    }
+
+    /// Replace the per-transform counters with the latest `stats`.
+    pub fn record_transforms(&self, stats: &[TransformStats]) {
+        self.each(|m| {
+            let mut by_name = m.transforms.lock().unwrap_or_else(|e| e.into_inner());
+            for stats in stats {
+                by_name.insert(stats.name.clone(), stats.clone());
+            }
+        });
+    }
+
+    pub fn transform_stats(&self) -> Vec<TransformStats> {
+        let by_name = self.inner.transforms.lock().unwrap_or_else(|e| e.into_inner());
+        by_name.values().cloned().collect()
+    }
+
+    /// Counters since this process started.
     pub fn snapshot(&self) -> MetricsSnapshot {
+        self.inner.snapshot()
+    }
+
+    /// Counters including the totals of earlier runs restored through
+    /// [`Self::restore_baseline`]; gauges are as in [`Self::snapshot`].
+    pub fn lifetime_snapshot(&self) -> MetricsSnapshot {
+        self.inner.lifetime_snapshot()
+    }
+
+    /// Lifetime totals of this registry's process-wide counters and every
+    /// domain's, to persist with a checkpoint.
+    pub fn baseline(&self) -> MetricsBaseline {
+        let root = self.root();
+        let domains = root.domains.lock().unwrap_or_else(|e| e.into_inner());
+        MetricsBaseline {
+            totals: root.lifetime_baseline(),
+            domains: domains
+                .iter()
+                .map(|(domain, inner)| (domain.clone(), inner.lifetime_baseline()))
+                .collect(),
+        }
+    }
+
+    /// Count on from the totals in `baseline`, registering its domains.
+    /// Call on a fresh registry when resuming from a checkpoint; counters
+    /// from this run add to what it restores.
+    pub fn restore_baseline(&self, baseline: &MetricsBaseline) {
+        let root = self.root();
+        *root.baseline.lock().unwrap_or_else(|e| e.into_inner()) = baseline.totals.clone();
+        for (domain, counters) in &baseline.domains {
+            let registry = self.for_domain(domain);
+            *registry.inner.baseline.lock().unwrap_or_else(|e| e.into_inner()) = counters.clone();
+        }
+    }
+
+    /// A registry for one domain's counters, so domains sharing a process can
+    /// be told apart. Its updates also count toward the process-wide totals
+    /// that [`Self::snapshot`] on this registry reports.
+    pub fn for_domain(&self, domain: &str) -> MetricsRegistry {
+        let root = self.root();
+        let mut domains = root.domains.lock().unwrap_or_else(|e| e.into_inner());
+        let inner = Arc::clone(domains.entry(domain.to_string()).or_default());
+        MetricsRegistry { inner, aggregate: Some(Arc::clone(root)) }
+    }
+
+    /// Counters of each domain registered through [`Self::for_domain`].
+    pub fn domain_snapshots(&self) -> BTreeMap<String, MetricsSnapshot> {
+        let domains = self.root().domains.lock().unwrap_or_else(|e| e.into_inner());
+        domains.iter().map(|(domain, inner)| (domain.clone(), inner.snapshot())).collect()
+    }
+
+    /// Counters in the Prometheus text format, one series per domain labeled
+    /// `domain`, or unlabeled totals when no domain is registered. Process
+    /// gauges are never split by domain; retirements are also split by
+    /// `reason` and predictor readiness by `predictor`. Each counter is
+    /// exported as of this run and, suffixed `_lifetime`, including the
+    /// restored baseline. Every family is preceded by its `# TYPE` line.
+    pub fn to_prometheus_text(&self) -> String {
+        let root = self.root();
+        let totals = vec![(None, root.export_values())];
+        let domains = root.domains.lock().unwrap_or_else(|e| e.into_inner());
+        let by_domain: Vec<(Option<&str>, serde_json::Value)> = domains
+            .iter()
+            .map(|(domain, inner)| (Some(domain.as_str()), inner.export_values()))
+            .collect();
+        let names: Vec<String> = totals[0]
+            .1
+            .as_object()
+            .map(|fields| fields.keys().cloned().collect())
+            .unwrap_or_default();
+        let mut snapshot = root.snapshot();
+        let counters: BTreeSet<String> = snapshot
+            .counters_mut()
+            .into_iter()
+            .flat_map(|(name, _)| [name.to_string(), format!("{name}_lifetime")])
+            .collect();
+        let mut out = String::new();
+        for name in names {
+            let series = if by_domain.is_empty() || name.starts_with("process_") {
+                &totals
+            } else {
+                &by_domain
+            };
+            let kind = if counters.contains(&name) { "counter" } else { "gauge" };
+            let mut family = Family::new(kind);
+            for (domain, values) in series {
+                if let Some(value) = values.get(&name).and_then(serde_json::Value::as_u64) {
+                    family.push(&[("domain", *domain)], value);
+                }
+            }
+            family.write(&mut out, &format!("tw_{name}"));
+        }
+        let series: Vec<(Option<&str>, &MetricsInner)> = if domains.is_empty() {
+            vec![(None, root.as_ref())]
+        } else {
+            domains.iter().map(|(domain, inner)| (Some(domain.as_str()), inner.as_ref())).collect()
+        };
+        let mut retired = Family::new("counter");
+        let mut retired_lifetime = Family::new("counter");
+        let mut ready = Family::new("gauge");
+        for (domain, inner) in series {
+            let by_reason =
+                inner.scenario_retired_by_reason.lock().unwrap_or_else(|e| e.into_inner());
+            for (reason, count) in by_reason.iter() {
+                retired.push(&[("domain", domain), ("reason", Some(reason.as_str()))], *count);
+            }
+            let baseline = inner.baseline.lock().unwrap_or_else(|e| e.into_inner());
+            for (reason, count) in merge_counts(&by_reason, &baseline.scenario_retired_by_reason) {
+                let labels = [("domain", domain), ("reason", Some(reason.as_str()))];
+                retired_lifetime.push(&labels, count);
+            }
+            let ready_bps = inner.predictor_ready_bps.lock().unwrap_or_else(|e| e.into_inner());
+            for (predictor, bps) in ready_bps.iter() {
+                ready.push(&[("domain", domain), ("predictor", Some(predictor.as_str()))], *bps);
+            }
+        }
+        retired.write(&mut out, "tw_scenario_retired_by_reason");
+        retired_lifetime.write(&mut out, "tw_scenario_retired_by_reason_lifetime");
+        ready.write(&mut out, "tw_predictor_ready_bps");
+        out
+    }
+
+    fn root(&self) -> &Arc<MetricsInner> {
+        self.aggregate.as_ref().unwrap_or(&self.inner)
+    }
+
+    /// Apply `update` to this registry and, for a domain, to the totals.
+    fn each<R>(&self, update: impl Fn(&MetricsInner) -> R) {
+        update(&self.inner);
+        if let Some(aggregate) = &self.aggregate {
+            update(aggregate);
+        }
+    }
+}
+
+impl MetricsInner {
+    /// `value` of each domain registered under this process-wide registry.
+    fn domain_values(&self, value: impl Fn(&MetricsInner) -> u64) -> Vec<u64> {
+        let domains = self.domains.lock().unwrap_or_else(|e| e.into_inner());
+        domains.values().map(|inner| value(inner)).collect()
+    }
+
+    fn record_subscription_latency(&self, name: &str, micros: u64) {
+        let mut by_name = self.subscription_latency.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = by_name
+            .entry(name.to_string())
+            .or_insert_with(|| SubscriptionLatency { name: name.to_string(), ..Default::default() });
+        entry.epochs += 1;
+        entry.last_us = micros;
+        entry.max_us = entry.max_us.max(micros);
+        entry.total_us = entry.total_us.saturating_add(micros);
+    }
+
+    fn record_pipeline_epoch(&self, epoch: &PipelineEpoch) {
+        let mut by_name = self.pipeline_latency.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = by_name
+            .entry(epoch.name.clone())
+            .or_insert_with(|| PipelineLatency { name: epoch.name.clone(), ..Default::default() });
+        entry.epochs += 1;
+        entry.last_budget = epoch.budget;
+        entry.last_steps = epoch.steps;
+        entry.total_steps = entry.total_steps.saturating_add(epoch.steps);
+        match epoch.latency {
+            Some(latency) => {
+                let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+                entry.last_us = micros;
+                entry.max_us = entry.max_us.max(micros);
+                entry.total_us = entry.total_us.saturating_add(micros);
+            }
+            None => entry.over_budget_epochs += 1,
+        }
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
         MetricsSnapshot {
-            base_events: self.inner.base_events.load(Ordering::Relaxed),
-            predicted_events: self.inner.predicted_events.load(Ordering::Relaxed),
-            scenario_alerts: self.inner.scenario_alerts.load(Ordering::Relaxed),
-            scenario_created: self.inner.scenario_created.load(Ordering::Relaxed),
-            scenario_retired: self.inner.scenario_retired.load(Ordering::Relaxed),
-            scenario_active_peak: self.inner.scenario_active_peak.load(Ordering::Relaxed),
+            base_events: self.base_events.load(Ordering::Relaxed),
+            duplicate_events: self.duplicate_events.load(Ordering::Relaxed),
+            invalid_events: self.invalid_events.load(Ordering::Relaxed),
+            sampled_out_events: self.sampled_out_events.load(Ordering::Relaxed),
+            sample_keep_bps: match self.sample_keep_bps.load(Ordering::Relaxed) {
+                0 => None,
+                bps => Some(bps),
+            },
+            predicted_events: self.predicted_events.load(Ordering::Relaxed),
+            predictions_clamped: self.predictions_clamped.load(Ordering::Relaxed),
+            predictions_warming_up: self.predictions_warming_up.load(Ordering::Relaxed),
+            scenario_alerts: self.scenario_alerts.load(Ordering::Relaxed),
+            alerts_suppressed: self.alerts_suppressed.load(Ordering::Relaxed),
+            alerts_shed: self.alerts_shed.load(Ordering::Relaxed),
+            scenarios_shed: self.scenarios_shed.load(Ordering::Relaxed),
+            scenarios_rejected: self.scenarios_rejected.load(Ordering::Relaxed),
+            keys_evicted: self.keys_evicted.load(Ordering::Relaxed),
+            scenario_created: self.scenario_created.load(Ordering::Relaxed),
+            scenario_retired: self.scenario_retired.load(Ordering::Relaxed),
+            scenario_active_peak: self.scenario_active_peak.load(Ordering::Relaxed),
+            process_rss_bytes: self.process_rss_bytes.load(Ordering::Relaxed),
+            process_cpu_time_ms: self.process_cpu_time_ms.load(Ordering::Relaxed),
+            process_open_fds: self.process_open_fds.load(Ordering::Relaxed),
+            process_threads: self.process_threads.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lifetime_snapshot(&self) -> MetricsSnapshot {
+        let mut snapshot = self.snapshot();
+        let baseline = self.baseline.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, count) in snapshot.counters_mut() {
+            *count = count.saturating_add(baseline.counters.get(name).copied().unwrap_or(0));
+        }
+        snapshot
+    }
+
+    fn lifetime_baseline(&self) -> CounterBaseline {
+        let mut lifetime = self.lifetime_snapshot();
+        let baseline = self.baseline.lock().unwrap_or_else(|e| e.into_inner());
+        let by_reason = self.scenario_retired_by_reason.lock().unwrap_or_else(|e| e.into_inner());
+        CounterBaseline {
+            counters: lifetime
+                .counters_mut()
+                .into_iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect(),
+            scenario_retired_by_reason: merge_counts(
+                &by_reason,
+                &baseline.scenario_retired_by_reason,
+            ),
+        }
+    }
+
+    /// Snapshot fields as exported: this run's, plus `<counter>_lifetime`.
+    fn export_values(&self) -> serde_json::Value {
+        let mut values = serde_json::to_value(self.snapshot()).unwrap_or_default();
+        let mut lifetime = self.lifetime_snapshot();
+        if let Some(fields) = values.as_object_mut() {
+            for (name, count) in lifetime.counters_mut() {
+                fields.insert(format!("{name}_lifetime"), (*count).into());
+            }
+        }
+        values
+    }
+}
+
+/// The samples of one Prometheus metric family, written together under its
+/// `# TYPE` line.
+struct Family {
+    kind: &'static str,
+    samples: Vec<(String, u64)>,
+}
+
+impl Family {
+    fn new(kind: &'static str) -> Self {
+        Self { kind, samples: Vec::new() }
+    }
+
+    /// Add a sample; labels without a value are left out.
+    fn push(&mut self, labels: &[(&str, Option<&str>)], value: u64) {
+        let labels: Vec<String> = labels
+            .iter()
+            .filter_map(|(name, value)| value.map(|value| (name, value)))
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+            .collect();
+        let labels =
+            if labels.is_empty() { String::new() } else { format!("{{{}}}", labels.join(",")) };
+        self.samples.push((labels, value));
+    }
+
+    fn write(&self, out: &mut String, name: &str) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let _ = writeln!(out, "# TYPE {name} {}", self.kind);
+        for (labels, value) in &self.samples {
+            let _ = writeln!(out, "{name}{labels} {value}");
         }
     }
 }
 
+/// A label value with backslashes, quotes and newlines escaped.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Per-key sum of two count maps.
+fn merge_counts(
+    run: &BTreeMap<String, u64>,
+    base: &BTreeMap<String, u64>,
+) -> BTreeMap<String, u64> {
+    let mut merged = base.clone();
+    for (key, count) in run {
+        let entry = merged.entry(key.clone()).or_default();
+        *entry = entry.saturating_add(*count);
+    }
+    merged
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct MetricsSnapshot {
     pub base_events: u64,
+    pub duplicate_events: u64,
+    pub invalid_events: u64,
+    pub sampled_out_events: u64,
+    /// Set when inputs are sampled; aggregates are then scaled estimates.
+    pub sample_keep_bps: Option<u64>,
     pub predicted_events: u64,
+    pub predictions_clamped: u64,
+    pub predictions_warming_up: u64,
     pub scenario_alerts: u64,
+    pub alerts_suppressed: u64,
+    pub alerts_shed: u64,
+    pub scenarios_shed: u64,
+    pub scenarios_rejected: u64,
+    pub keys_evicted: u64,
     pub scenario_created: u64,
     pub scenario_retired: u64,
     pub scenario_active_peak: u64,
+    pub process_rss_bytes: u64,
+    pub process_cpu_time_ms: u64,
+    pub process_open_fds: u64,
+    pub process_threads: u64,
 }
 
 impl MetricsSnapshot {
+    /// The monotonic counters by field name, leaving out gauges and peaks.
+    fn counters_mut(&mut self) -> [(&'static str, &mut u64); 15] {
+        [
+            ("base_events", &mut self.base_events),
+            ("duplicate_events", &mut self.duplicate_events),
+            ("invalid_events", &mut self.invalid_events),
+            ("sampled_out_events", &mut self.sampled_out_events),
+            ("predicted_events", &mut self.predicted_events),
+            ("predictions_clamped", &mut self.predictions_clamped),
+            ("predictions_warming_up", &mut self.predictions_warming_up),
+            ("scenario_alerts", &mut self.scenario_alerts),
+            ("alerts_suppressed", &mut self.alerts_suppressed),
+            ("alerts_shed", &mut self.alerts_shed),
+            ("scenarios_shed", &mut self.scenarios_shed),
+            ("scenarios_rejected", &mut self.scenarios_rejected),
+            ("keys_evicted", &mut self.keys_evicted),
+            ("scenario_created", &mut self.scenario_created),
+            ("scenario_retired", &mut self.scenario_retired),
+        ]
+    }
+
     pub fn to_json_line(&self, label: &str, elapsed: Option<Duration>) -> String {
         #[derive(Serialize)]
         struct Snapshot<'a> {
             label: &'a str,
             base_events: u64,
+            duplicate_events: u64,
+            invalid_events: u64,
+            sampled_out_events: u64,
+            sample_keep_bps: Option<u64>,
             predicted_events: u64,
+            predictions_clamped: u64,
+            predictions_warming_up: u64,
             scenario_alerts: u64,
+            alerts_suppressed: u64,
+            alerts_shed: u64,
+            scenarios_shed: u64,
+            scenarios_rejected: u64,
+            keys_evicted: u64,
             scenario_created: u64,
             scenario_retired: u64,
             scenario_active_peak: u64,
+            process_rss_bytes: u64,
+            process_cpu_time_ms: u64,
+            process_open_fds: u64,
+            process_threads: u64,
             elapsed_ms: Option<u128>,
         }
 
         let payload = Snapshot {
             label,
             base_events: self.base_events,
+            duplicate_events: self.duplicate_events,
+            invalid_events: self.invalid_events,
+            sampled_out_events: self.sampled_out_events,
+            sample_keep_bps: self.sample_keep_bps,
             predicted_events: self.predicted_events,
+            predictions_clamped: self.predictions_clamped,
+            predictions_warming_up: self.predictions_warming_up,
             scenario_alerts: self.scenario_alerts,
+            alerts_suppressed: self.alerts_suppressed,
+            alerts_shed: self.alerts_shed,
+            scenarios_shed: self.scenarios_shed,
+            scenarios_rejected: self.scenarios_rejected,
+            keys_evicted: self.keys_evicted,
             scenario_created: self.scenario_created,
             scenario_retired: self.scenario_retired,
             scenario_active_peak: self.scenario_active_peak,
+            process_rss_bytes: self.process_rss_bytes,
+            process_cpu_time_ms: self.process_cpu_time_ms,
+            process_open_fds: self.process_open_fds,
+            process_threads: self.process_threads,
             elapsed_ms: elapsed.map(|d| d.as_millis()),
         };
         serde_json::to_string(&payload).unwrap_or_else(|_| String::from("{}"))
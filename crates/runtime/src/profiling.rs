@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use timely::communication::Allocate;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+use timely::progress::Timestamp;
+use timely::worker::Worker;
+use tw_core::EpochTime;
+
+/// Logical stages of one epoch. Ingest and expansion run on the host thread;
+/// the rest are dataflow stages observed through probes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    IngestDecode,
+    Expansion,
+    BaseReduce,
+    OverlayJoin,
+    TopK,
+    AlertEval,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: Stage,
+    pub micros: u64,
+}
+
+/// Time spent per stage in one sampled epoch.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageBreakdown {
+    pub epoch: EpochTime,
+    pub stages: Vec<StageTiming>,
+}
+
+impl StageBreakdown {
+    pub fn to_json_line(&self, label: &str) -> String {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            label: &'a str,
+            #[serde(flatten)]
+            breakdown: &'a StageBreakdown,
+        }
+
+        serde_json::to_string(&Line { label, breakdown: self }).unwrap_or_else(|_| String::from("{}"))
+    }
+}
+
+/// Samples every `sample_every`-th epoch (0 disables). Host stages accumulate
+/// explicit timings; dataflow stages are timed by how much later each stage's
+/// probe completes than the one registered before it, so register them in
+/// dataflow order.
+pub struct StageProfiler<T: Timestamp> {
+    sample_every: u64,
+    sampling: bool,
+    host: Vec<(Stage, Duration)>,
+    probes: Vec<(Stage, ProbeHandle<T>)>,
+    dataflow: Vec<(Stage, Duration)>,
+}
+
+impl<T: Timestamp> StageProfiler<T> {
+    pub fn new(sample_every: u64) -> Self {
+        Self {
+            sample_every,
+            sampling: false,
+            host: Vec::new(),
+            probes: Vec::new(),
+            dataflow: Vec::new(),
+        }
+    }
+
+    /// Probe to attach to the output of a dataflow stage with `probe_with`.
+    pub fn probe(&mut self, stage: Stage) -> ProbeHandle<T> {
+        let probe = ProbeHandle::new();
+        self.probes.push((stage, probe.clone()));
+        probe
+    }
+
+    /// Start an epoch; returns whether it is sampled.
+    pub fn begin_epoch(&mut self, epoch: EpochTime) -> bool {
+        self.sampling = self.sample_every > 0 && epoch.get() % self.sample_every == 0;
+        self.host.clear();
+        self.dataflow.clear();
+        self.sampling
+    }
+
+    /// Add host-side time to `stage`; ignored outside sampled epochs.
+    pub fn record(&mut self, stage: Stage, elapsed: Duration) {
+        if !self.sampling {
+            return;
+        }
+        match self.host.iter_mut().find(|(s, _)| *s == stage) {
+            Some((_, total)) => *total += elapsed,
+            None => self.host.push((stage, elapsed)),
+        }
+    }
+
+    /// Step `worker` until every stage probe has completed the times before
+    /// `time`. Only sampled epochs are timed; others return immediately.
+    pub fn drive<A: Allocate>(&mut self, worker: &mut Worker<A>, time: &T, started: Instant) {
+        if !self.sampling {
+            return;
+        }
+        let mut completed: Vec<Option<Duration>> = vec![None; self.probes.len()];
+        while completed.iter().any(Option::is_none) {
+            for (slot, (_, probe)) in completed.iter_mut().zip(&self.probes) {
+                if slot.is_none() && !probe.less_than(time) {
+                    *slot = Some(started.elapsed());
+                }
+            }
+            if completed.iter().any(Option::is_none) {
+                worker.step();
+            }
+        }
+        let mut previous = Duration::ZERO;
+        for ((stage, _), done) in self.probes.iter().zip(completed) {
+            let done = done.unwrap_or_default().max(previous);
+            self.dataflow.push((*stage, done - previous));
+            previous = done;
+        }
+    }
+
+    /// Breakdown for the epoch just finished, if it was sampled.
+    pub fn finish_epoch(&mut self, epoch: EpochTime) -> Option<StageBreakdown> {
+        if !self.sampling {
+            return None;
+        }
+        self.sampling = false;
+        let stages = self
+            .host
+            .drain(..)
+            .chain(self.dataflow.drain(..))
+            .map(|(stage, elapsed)| StageTiming {
+                stage,
+                micros: elapsed.as_micros().min(u64::MAX as u128) as u64,
+            })
+            .collect();
+        Some(StageBreakdown { epoch, stages })
+    }
+}
@@ -0,0 +1,239 @@
+//! Latest projections per watched key, published to a key-value store so
+//! low-latency services can read a current value with one GET instead of
+//! querying the dataflow.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[cfg(feature = "redis")]
+use anyhow::{Context, Result};
+use tw_core::quantity::Interval;
+use tw_core::ScenarioId;
+
+/// Which projection of a key a value is.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Projection {
+    Base,
+    /// Base plus the probability-weighted scenario deltas.
+    Expected,
+    /// Bounds of `Expected` given the scenario deltas' variance, when the
+    /// tracker publishes intervals.
+    ExpectedLow,
+    ExpectedHigh,
+    /// Value under one tagged scenario. Scenarios sharing a tag are
+    /// published side by side.
+    Scenario { tag: String, id: ScenarioId },
+}
+
+/// A changed projection; `value` is `None` once the key no longer has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectionUpdate {
+    pub key: u64,
+    pub projection: Projection,
+    pub value: Option<i64>,
+}
+
+/// Net view rows per watched key, maintained from view updates as they
+/// arrive, retractions included. Closing an epoch yields the projections
+/// that changed since the last close.
+#[derive(Debug, Default)]
+pub struct ProjectionTracker {
+    watched: BTreeSet<u64>,
+    tags: HashMap<ScenarioId, String>,
+    base: BTreeMap<u64, BTreeMap<i64, isize>>,
+    expected_delta: BTreeMap<u64, BTreeMap<i64, isize>>,
+    expected_variance: BTreeMap<u64, BTreeMap<i64, isize>>,
+    interval_z: Option<f64>,
+    scenario: BTreeMap<(u64, ScenarioId), ScenarioRows>,
+    published: BTreeMap<(u64, Projection), i64>,
+    touched: BTreeSet<u64>,
+}
+
+impl ProjectionTracker {
+    pub fn new(watched: impl IntoIterator<Item = u64>) -> Self {
+        Self { watched: watched.into_iter().collect(), ..Self::default() }
+    }
+
+    /// Also publish `Expected` plus or minus `z` standard deviations.
+    pub fn with_interval(mut self, z: f64) -> Self {
+        self.interval_z = Some(z);
+        self
+    }
+
+    /// `false` when no key is watched; updates are then ignored.
+    pub fn is_enabled(&self) -> bool {
+        !self.watched.is_empty()
+    }
+
+    /// Publish per-key values of `scenario_id` under `tag`. Untagged
+    /// scenarios only count toward the expected value.
+    pub fn tag(&mut self, scenario_id: ScenarioId, tag: impl Into<String>) {
+        self.tags.insert(scenario_id, tag.into());
+    }
+
+    /// Record one `(key, value)` update of the base view.
+    pub fn update_base(&mut self, key: u64, value: i64, diff: isize) {
+        if self.watched.contains(&key) {
+            add_row(self.base.entry(key).or_default(), value, diff);
+            self.touched.insert(key);
+        }
+    }
+
+    /// Record one `(key, delta)` update of the expected-delta view.
+    pub fn update_expected_delta(&mut self, key: u64, delta: i64, diff: isize) {
+        if self.watched.contains(&key) {
+            add_row(self.expected_delta.entry(key).or_default(), delta, diff);
+            self.touched.insert(key);
+        }
+    }
+
+    /// Record one `(key, variance)` update of the expected-variance view.
+    pub fn update_expected_variance(&mut self, key: u64, variance: i64, diff: isize) {
+        if self.watched.contains(&key) {
+            add_row(self.expected_variance.entry(key).or_default(), variance, diff);
+            self.touched.insert(key);
+        }
+    }
+
+    /// Record one `(scenario, key, value)` update of a scenario view.
+    pub fn update_scenario(&mut self, scenario_id: ScenarioId, key: u64, value: i64, diff: isize) {
+        if !self.watched.contains(&key) {
+            return;
+        }
+        let Some(tag) = self.tags.get(&scenario_id) else {
+            return;
+        };
+        let scenario = self
+            .scenario
+            .entry((key, scenario_id))
+            .or_insert_with(|| ScenarioRows { tag: tag.clone(), rows: BTreeMap::new() });
+        add_row(&mut scenario.rows, value, diff);
+        self.touched.insert(key);
+    }
+
+    /// Projections of touched keys that changed since the last close, by
+    /// key. A key without a base value has no expected value either.
+    pub fn close_epoch(&mut self) -> Vec<ProjectionUpdate> {
+        self.base.retain(|_, rows| !rows.is_empty());
+        self.expected_delta.retain(|_, rows| !rows.is_empty());
+        self.expected_variance.retain(|_, rows| !rows.is_empty());
+        self.scenario.retain(|_, scenario| !scenario.rows.is_empty());
+        let mut updates = Vec::new();
+        for key in std::mem::take(&mut self.touched) {
+            let base = self.base.get(&key).and_then(current);
+            let delta = self.expected_delta.get(&key).and_then(current).unwrap_or(0);
+            let expected = base.map(|base| base.saturating_add(delta));
+            let mut current_values =
+                vec![(Projection::Base, base), (Projection::Expected, expected)];
+            if let Some(z) = self.interval_z {
+                let variance = self.expected_variance.get(&key).and_then(current).unwrap_or(0);
+                let interval = expected.map(|value| Interval::around(value, variance, z));
+                current_values.push((Projection::ExpectedLow, interval.map(|i| i.low)));
+                current_values.push((Projection::ExpectedHigh, interval.map(|i| i.high)));
+            }
+            // Scenario projections published before or present now
+            let mut scenarios: BTreeMap<ScenarioId, (String, Option<i64>)> = self
+                .published
+                .range((key, Projection::Base)..)
+                .take_while(|((k, _), _)| *k == key)
+                .filter_map(|((_, projection), _)| match projection {
+                    Projection::Scenario { tag, id } => Some((*id, (tag.clone(), None))),
+                    _ => None,
+                })
+                .collect();
+            scenarios.extend(
+                self.scenario
+                    .range((key, ScenarioId::MIN)..=(key, ScenarioId::MAX))
+                    .map(|((_, id), scenario)| {
+                        (*id, (scenario.tag.clone(), current(&scenario.rows)))
+                    }),
+            );
+            for (id, (tag, value)) in scenarios {
+                current_values.push((Projection::Scenario { tag, id }, value));
+            }
+            for (projection, value) in current_values {
+                let id = (key, projection);
+                let previous = match value {
+                    Some(value) => self.published.insert(id.clone(), value),
+                    None => self.published.remove(&id),
+                };
+                if previous != value {
+                    updates.push(ProjectionUpdate { key, projection: id.1, value });
+                }
+            }
+        }
+        updates
+    }
+}
+
+/// Net rows of one scenario at one key, with the tag it is published under.
+#[derive(Debug)]
+struct ScenarioRows {
+    tag: String,
+    rows: BTreeMap<i64, isize>,
+}
+
+fn add_row(rows: &mut BTreeMap<i64, isize>, value: i64, diff: isize) {
+    let count = rows.entry(value).or_default();
+    *count += diff;
+    if *count == 0 {
+        rows.remove(&value);
+    }
+}
+
+/// The row with positive multiplicity. A view holds one row per key and
+/// scenario, so once an epoch's updates have netted there is at most one.
+fn current(rows: &BTreeMap<i64, isize>) -> Option<i64> {
+    rows.iter().find(|(_, count)| **count > 0).map(|(value, _)| *value)
+}
+
+/// Writes projection updates to Redis as plain string keys,
+/// `{prefix}:{key}:base`, `{prefix}:{key}:expected`,
+/// `{prefix}:{key}:expected_low`, `{prefix}:{key}:expected_high` and
+/// `{prefix}:{key}:scenario:{tag}:{id}`, deleting those that disappear. Each
+/// epoch's updates are applied in one transaction, so readers never see a
+/// half-written epoch.
+#[cfg(feature = "redis")]
+pub struct RedisProjectionSink {
+    conn: redis::Connection,
+    prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisProjectionSink {
+    /// Connect to the server at `url`, such as `redis://127.0.0.1/`.
+    pub fn connect(url: &str, prefix: impl Into<String>) -> Result<Self> {
+        let client =
+            redis::Client::open(url).with_context(|| format!("parsing redis url {url}"))?;
+        let conn = client.get_connection().with_context(|| format!("connecting to {url}"))?;
+        Ok(Self { conn, prefix: prefix.into() })
+    }
+
+    /// The Redis key holding `projection` of `key`.
+    pub fn redis_key(&self, key: u64, projection: &Projection) -> String {
+        match projection {
+            Projection::Base => format!("{}:{key}:base", self.prefix),
+            Projection::Expected => format!("{}:{key}:expected", self.prefix),
+            Projection::ExpectedLow => format!("{}:{key}:expected_low", self.prefix),
+            Projection::ExpectedHigh => format!("{}:{key}:expected_high", self.prefix),
+            Projection::Scenario { tag, id } => {
+                format!("{}:{key}:scenario:{tag}:{id}", self.prefix)
+            }
+        }
+    }
+
+    pub fn write(&mut self, updates: &[ProjectionUpdate]) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for update in updates {
+            let redis_key = self.redis_key(update.key, &update.projection);
+            match update.value {
+                Some(value) => pipe.set(redis_key, value).ignore(),
+                None => pipe.del(redis_key).ignore(),
+            };
+        }
+        pipe.query::<()>(&mut self.conn).context("writing projections to redis")
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tw_core::{EpochTime, EventEnvelope, ScenarioId};
+
+/// Events of the last `window_epochs` epochs, per key, so an alert can be
+/// packaged with the input that led to it.
+pub struct RecentEvents<K, T> {
+    window_epochs: u64,
+    events: HashMap<K, VecDeque<EventEnvelope<T>>>,
+}
+
+impl<K, T> RecentEvents<K, T>
+where
+    K: Eq + Hash,
+{
+    pub fn new(window_epochs: u64) -> Self {
+        Self { window_epochs, events: HashMap::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.window_epochs > 0
+    }
+
+    /// Remember `event` under `key`. No-op when the window is 0.
+    pub fn record(&mut self, key: K, event: EventEnvelope<T>) {
+        if self.is_enabled() {
+            self.events.entry(key).or_default().push_back(event);
+        }
+    }
+
+    /// Forget events older than the window as of `epoch`.
+    pub fn advance(&mut self, epoch: EpochTime) {
+        let cutoff = epoch.saturating_sub(self.window_epochs);
+        self.events.retain(|_, events| {
+            while events.front().is_some_and(|event| event.meta.epoch < cutoff) {
+                events.pop_front();
+            }
+            !events.is_empty()
+        });
+    }
+
+    /// Events for `key`, oldest first.
+    pub fn get(&self, key: &K) -> Vec<&EventEnvelope<T>> {
+        self.events.get(key).map(|events| events.iter().collect()).unwrap_or_default()
+    }
+}
+
+/// The scenario row that fired an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PredictedRow {
+    pub value: i64,
+    pub probability: f64,
+    pub variance: i64,
+}
+
+/// Everything needed to reproduce one alert locally: the key's recent
+/// events, the scenario's lineage and overlay rows, the row that fired and
+/// the config the run used.
+#[derive(Debug, Serialize)]
+pub struct ReplayBundle<'a, K, T, L, O, C> {
+    pub alert: &'a str,
+    /// Domain that raised the alert, such as `retail`.
+    pub domain: &'a str,
+    pub epoch: EpochTime,
+    pub scenario_id: ScenarioId,
+    pub key: K,
+    pub events: Vec<&'a EventEnvelope<T>>,
+    pub lineage: L,
+    /// Overlay rows of the scenario and each ancestor still remembered,
+    /// scenario first.
+    pub overlays: Vec<(ScenarioId, &'a O)>,
+    pub predicted: PredictedRow,
+    pub config: &'a C,
+}
+
+impl<K, T, L, O, C> ReplayBundle<'_, K, T, L, O, C>
+where
+    K: Serialize + Display,
+    T: Serialize,
+    L: Serialize,
+    O: Serialize,
+    C: Serialize,
+{
+    /// Write the bundle as pretty JSON into `dir`, one file per alert.
+    pub fn write_to(&self, dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("creating replay bundle dir {}", dir.display()))?;
+        let name =
+            format!("{}-e{}-s{}-k{}.json", self.alert, self.epoch, self.scenario_id, self.key);
+        let path = dir.join(name);
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("writing replay bundle {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// A replay bundle read back without knowing its domain; the domain's own
+/// parts stay JSON.
+#[derive(Debug, Deserialize)]
+pub struct StoredBundle {
+    pub alert: String,
+    pub domain: String,
+    pub epoch: EpochTime,
+    pub scenario_id: ScenarioId,
+    pub key: Value,
+    pub events: Vec<Value>,
+    pub lineage: Value,
+    pub overlays: Vec<(ScenarioId, Value)>,
+    pub predicted: PredictedRow,
+    pub config: Value,
+}
+
+impl StoredBundle {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read(path)
+            .with_context(|| format!("reading replay bundle {}", path.display()))?;
+        serde_json::from_slice(&json)
+            .with_context(|| format!("parsing replay bundle {}", path.display()))
+    }
+}
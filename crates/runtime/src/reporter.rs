@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::metrics::{MetricsRegistry, MetricsSnapshot};
+use crate::resources::ResourceUsage;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MetricsRates {
+    pub base_events_per_sec: f64,
+    pub predicted_events_per_sec: f64,
+    pub alerts_per_min: f64,
+}
+
+/// One reporter tick: counters at sample time plus rates since the previous tick.
+#[derive(Debug, Clone, Copy)]
+pub struct MetricsSample {
+    pub snapshot: MetricsSnapshot,
+    pub rates: MetricsRates,
+    pub interval: Duration,
+}
+
+impl MetricsSample {
+    pub fn to_json_line(&self, label: &str) -> String {
+        #[derive(Serialize)]
+        struct Sample<'a> {
+            label: &'a str,
+            #[serde(flatten)]
+            snapshot: &'a MetricsSnapshot,
+            #[serde(flatten)]
+            rates: &'a MetricsRates,
+            interval_ms: u128,
+        }
+
+        let payload = Sample {
+            label,
+            snapshot: &self.snapshot,
+            rates: &self.rates,
+            interval_ms: self.interval.as_millis(),
+        };
+        serde_json::to_string(&payload).unwrap_or_else(|_| String::from("{}"))
+    }
+}
+
+/// Background thread that samples a [`MetricsRegistry`] on a fixed interval,
+/// independent of epoch boundaries. Stops when dropped.
+pub struct MetricsReporter {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsReporter {
+    pub fn spawn<F>(registry: MetricsRegistry, interval: Duration, mut sink: F) -> Self
+    where
+        F: FnMut(&MetricsSample) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let mut last = registry.snapshot();
+            let mut last_at = Instant::now();
+            loop {
+                std::thread::park_timeout(interval);
+                if stop_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                let elapsed = last_at.elapsed();
+                if elapsed < interval {
+                    continue;
+                }
+                if let Some(usage) = ResourceUsage::sample() {
+                    registry.record_resources(&usage);
+                }
+                let snapshot = registry.snapshot();
+                let sample = MetricsSample {
+                    snapshot,
+                    rates: rates_between(&last, &snapshot, elapsed),
+                    interval: elapsed,
+                };
+                sink(&sample);
+                last = snapshot;
+                last_at = Instant::now();
+            }
+        });
+        Self { stop, handle: Some(handle) }
+    }
+}
+
+impl Drop for MetricsReporter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+fn rates_between(prev: &MetricsSnapshot, next: &MetricsSnapshot, elapsed: Duration) -> MetricsRates {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return MetricsRates::default();
+    }
+    let per_sec = |a: u64, b: u64| b.saturating_sub(a) as f64 / secs;
+    MetricsRates {
+        base_events_per_sec: per_sec(prev.base_events, next.base_events),
+        predicted_events_per_sec: per_sec(prev.predicted_events, next.predicted_events),
+        alerts_per_min: per_sec(prev.scenario_alerts, next.scenario_alerts) * 60.0,
+    }
+}
@@ -0,0 +1,49 @@
+//! Process resource usage read from `/proc/self`, for capacity planning.
+
+use std::fs;
+
+/// `/proc` reports CPU times in USER_HZ ticks, which Linux fixes at 100.
+const USER_HZ: u64 = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub rss_bytes: u64,
+    /// User plus system CPU time consumed so far.
+    pub cpu_time_ms: u64,
+    pub open_fds: u64,
+    pub threads: u64,
+}
+
+impl ResourceUsage {
+    /// Sample the current process; `None` where `/proc` is unavailable.
+    pub fn sample() -> Option<Self> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let stat = fs::read_to_string("/proc/self/stat").ok()?;
+        let open_fds = fs::read_dir("/proc/self/fd").map(|dir| dir.count() as u64).unwrap_or(0);
+        Some(Self {
+            rss_bytes: status_field(&status, "VmRSS:").unwrap_or(0) * 1024,
+            cpu_time_ms: cpu_ticks(&stat).unwrap_or(0) * 1_000 / USER_HZ,
+            open_fds,
+            threads: status_field(&status, "Threads:").unwrap_or(0),
+        })
+    }
+}
+
+/// First number on the `/proc/self/status` line starting with `name`.
+fn status_field(status: &str, name: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
+}
+
+/// `utime + stime` from `/proc/self/stat`. The command name may contain
+/// spaces, so fields are counted from the closing parenthesis.
+fn cpu_ticks(stat: &str) -> Option<u64> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tw_core::EpochTime;
+
+/// Gradual rollout for one view or subscription, by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutFlag {
+    pub name: String,
+    /// Epochs the flag's alerts are only logged, counted from the epoch
+    /// the rollout is activated.
+    pub log_only_epochs: u64,
+    /// Log-only alerts logged per epoch; the rest are shed. Live alerts
+    /// are budgeted by the safety valve instead. Unlimited when absent.
+    #[serde(default)]
+    pub max_shadow_per_epoch: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RolloutConfig {
+    #[serde(default)]
+    pub flags: Vec<RolloutFlag>,
+}
+
+impl RolloutConfig {
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading rollout config {}", path.display()))?;
+        serde_json::from_str(&raw).with_context(|| format!("parsing rollout config {}", path.display()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// Log the alert but keep it away from real sinks.
+    LogOnly,
+    /// Log-only, and the flag's shadow budget for the epoch is spent.
+    Shed,
+    Live,
+}
+
+/// Current state of one flag, for status reporting.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagState {
+    pub name: String,
+    pub log_only_epochs: u64,
+    pub max_shadow_per_epoch: Option<u64>,
+    pub activated_at: Option<EpochTime>,
+    pub live: bool,
+    /// Alerts routed to the log only so far.
+    pub shadowed: u64,
+    /// Log-only alerts dropped over the shadow budget so far.
+    pub shed: u64,
+    #[serde(skip)]
+    shadow_epoch: Option<EpochTime>,
+    #[serde(skip)]
+    shadow_in_epoch: u64,
+}
+
+/// Routes alerts by rollout flag. Names without a flag are always live;
+/// flagged ones stay log-only until [`Rollout::activate`] starts their
+/// windows.
+#[derive(Debug, Default)]
+pub struct Rollout {
+    flags: Mutex<BTreeMap<String, FlagState>>,
+}
+
+impl Rollout {
+    pub fn new(cfg: RolloutConfig) -> Self {
+        let flags = cfg
+            .flags
+            .into_iter()
+            .map(|flag| {
+                let state = FlagState {
+                    name: flag.name.clone(),
+                    log_only_epochs: flag.log_only_epochs,
+                    max_shadow_per_epoch: flag.max_shadow_per_epoch,
+                    activated_at: None,
+                    live: false,
+                    shadowed: 0,
+                    shed: 0,
+                    shadow_epoch: None,
+                    shadow_in_epoch: 0,
+                };
+                (flag.name, state)
+            })
+            .collect();
+        Self { flags: Mutex::new(flags) }
+    }
+
+    /// Start the log-only window of every flag not yet started at `epoch`,
+    /// whether or not its view has alerted.
+    pub fn activate(&self, epoch: EpochTime) {
+        let mut flags = self.flags.lock().unwrap_or_else(|e| e.into_inner());
+        for state in flags.values_mut() {
+            let activated_at = *state.activated_at.get_or_insert(epoch);
+            state.live = epoch.since(activated_at) >= state.log_only_epochs;
+        }
+    }
+
+    /// Where an alert from `name` raised at `epoch` should go.
+    pub fn route(&self, name: &str, epoch: EpochTime) -> Route {
+        let mut flags = self.flags.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(state) = flags.get_mut(name) else {
+            return Route::Live;
+        };
+        state.live = state
+            .activated_at
+            .is_some_and(|activated_at| epoch.since(activated_at) >= state.log_only_epochs);
+        if state.live {
+            return Route::Live;
+        }
+        if state.shadow_epoch != Some(epoch) {
+            state.shadow_epoch = Some(epoch);
+            state.shadow_in_epoch = 0;
+        }
+        if state.max_shadow_per_epoch.is_some_and(|max| state.shadow_in_epoch >= max) {
+            state.shed += 1;
+            return Route::Shed;
+        }
+        state.shadow_in_epoch += 1;
+        state.shadowed += 1;
+        Route::LogOnly
+    }
+
+    pub fn states(&self) -> Vec<FlagState> {
+        let flags = self.flags.lock().unwrap_or_else(|e| e.into_inner());
+        flags.values().cloned().collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.states()).unwrap_or_else(|_| String::from("[]"))
+    }
+}
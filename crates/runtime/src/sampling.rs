@@ -0,0 +1,61 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use tw_core::quantity::BPS_SCALE;
+use tw_core::EventMeta;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SamplingConfig {
+    /// Share of event keys kept, in basis points; 10_000 keeps everything.
+    pub keep_bps: i64,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self { keep_bps: BPS_SCALE }
+    }
+}
+
+impl SamplingConfig {
+    pub fn is_sampled(&self) -> bool {
+        self.keep_bps < BPS_SCALE
+    }
+}
+
+/// Keeps a fixed share of event keys for running production captures on a
+/// small machine. The decision hashes the event key, so every event of a kept
+/// key is kept and per-key streams stay whole; keyless events always pass.
+#[derive(Debug, Clone)]
+pub struct EventSampler {
+    keep_bps: i64,
+}
+
+impl EventSampler {
+    pub fn new(cfg: SamplingConfig) -> Self {
+        Self { keep_bps: cfg.keep_bps.clamp(1, BPS_SCALE) }
+    }
+
+    /// Returns `true` if the event is in the sample.
+    pub fn admit(&self, meta: &EventMeta) -> bool {
+        match meta.key.as_deref() {
+            Some(key) => self.admit_key(key),
+            None => true,
+        }
+    }
+
+    pub fn admit_key(&self, key: &str) -> bool {
+        if self.keep_bps >= BPS_SCALE {
+            return true;
+        }
+        // `DefaultHasher::new` uses fixed keys, so the sample is stable across runs
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % BPS_SCALE as u64) < self.keep_bps as u64
+    }
+
+    /// Effective keep rate in basis points, for scaling sampled aggregates.
+    pub fn keep_bps(&self) -> i64 {
+        self.keep_bps
+    }
+}
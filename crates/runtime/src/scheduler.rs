@@ -0,0 +1,134 @@
+//! Step budgets for pipelines sharing one worker. Each epoch every pipeline
+//! gets a share of the steps the epoch loop will wait for, in proportion to
+//! its weight. A worker step advances every dataflow on the worker, so the
+//! budgets don't divide the work itself: they bound how long the loop waits
+//! on each pipeline. One that spends its share without catching up is
+//! reported over budget and finishes during later epochs' steps instead of
+//! holding the loop up.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use timely::communication::Allocate;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+use timely::progress::Timestamp;
+use timely::worker::Worker;
+
+use crate::metrics::MetricsRegistry;
+
+struct Pipeline<T: Timestamp> {
+    name: String,
+    weight: u32,
+    probe: ProbeHandle<T>,
+}
+
+/// How one pipeline fared in one epoch.
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineEpoch {
+    pub name: String,
+    pub budget: u64,
+    /// Steps taken while waiting on this pipeline.
+    pub steps: u64,
+    /// Time until the pipeline caught up; `None` when it ran out of budget.
+    pub latency: Option<Duration>,
+}
+
+impl PipelineEpoch {
+    pub fn within_budget(&self) -> bool {
+        self.latency.is_some()
+    }
+}
+
+/// Pipelines registered on one worker, each with its own probe and weight.
+pub struct PipelineScheduler<T: Timestamp> {
+    steps_per_epoch: u64,
+    pipelines: Vec<Pipeline<T>>,
+}
+
+impl<T: Timestamp> PipelineScheduler<T> {
+    /// Share `steps_per_epoch` worker steps between pipelines each epoch.
+    pub fn new(steps_per_epoch: u64) -> Self {
+        Self { steps_per_epoch, pipelines: Vec::new() }
+    }
+
+    /// Probe to attach to the pipeline's output with `probe_with`. A weight
+    /// of 0 still gets one step per epoch.
+    pub fn register(&mut self, name: impl Into<String>, weight: u32) -> ProbeHandle<T> {
+        let probe = ProbeHandle::new();
+        self.add(name, weight, probe.clone());
+        probe
+    }
+
+    /// Budget a pipeline whose output is already probed by `probe`.
+    pub fn add(&mut self, name: impl Into<String>, weight: u32, probe: ProbeHandle<T>) {
+        self.pipelines.push(Pipeline { name: name.into(), weight, probe });
+    }
+
+    /// Steps each pipeline may take per epoch, in registration order.
+    pub fn budgets(&self) -> Vec<(&str, u64)> {
+        let total: u64 = self.pipelines.iter().map(|p| u64::from(p.weight)).sum();
+        self.pipelines
+            .iter()
+            .map(|pipeline| {
+                let share = self.steps_per_epoch.saturating_mul(u64::from(pipeline.weight))
+                    / total.max(1);
+                (pipeline.name.as_str(), share.max(1))
+            })
+            .collect()
+    }
+
+    /// Step `worker` until every pipeline has completed the times before
+    /// `time` or spent its budget. A step advances all of them, but it is
+    /// charged to the waiting pipeline that has used the smallest fraction
+    /// of its budget. Each pipeline's outcome is recorded in `metrics`.
+    pub fn drive<A: Allocate>(
+        &self,
+        worker: &mut Worker<A>,
+        time: &T,
+        started: Instant,
+        metrics: &MetricsRegistry,
+    ) -> Vec<PipelineEpoch> {
+        let mut epochs: Vec<PipelineEpoch> = self
+            .budgets()
+            .into_iter()
+            .map(|(name, budget)| PipelineEpoch {
+                name: name.to_string(),
+                budget,
+                steps: 0,
+                latency: None,
+            })
+            .collect();
+        let mut waiting: Vec<usize> = (0..self.pipelines.len()).collect();
+        loop {
+            waiting.retain(|&i| {
+                if self.pipelines[i].probe.less_than(time) {
+                    return epochs[i].steps < epochs[i].budget;
+                }
+                epochs[i].latency = Some(started.elapsed());
+                false
+            });
+            let Some(&next) = waiting.iter().min_by(|&&a, &&b| {
+                let used = |i: usize| epochs[i].steps as f64 / epochs[i].budget as f64;
+                used(a).total_cmp(&used(b))
+            }) else {
+                break;
+            };
+            worker.step();
+            epochs[next].steps += 1;
+        }
+        for epoch in &epochs {
+            metrics.record_pipeline_epoch(epoch);
+        }
+        epochs
+    }
+}
+
+/// Parse a `name=weight` pipeline weight.
+pub fn parse_weight(spec: &str) -> Result<(String, u32)> {
+    let Some((name, weight)) = spec.split_once('=') else {
+        bail!("pipeline weight {spec:?} is not name=weight");
+    };
+    let weight = weight.parse().with_context(|| format!("pipeline weight {spec:?}"))?;
+    Ok((name.to_string(), weight))
+}
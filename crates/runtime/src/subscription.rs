@@ -0,0 +1,71 @@
+use std::time::Instant;
+
+use timely::communication::Allocate;
+use timely::dataflow::operators::probe::Handle as ProbeHandle;
+use timely::progress::Timestamp;
+use timely::worker::Worker;
+
+use crate::metrics::MetricsRegistry;
+
+/// Subscription outputs, each with its own probe, so one subscription's
+/// completion is observable without waiting on the others.
+pub struct Subscriptions<T: Timestamp> {
+    entries: Vec<(String, ProbeHandle<T>)>,
+}
+
+impl<T: Timestamp> Default for Subscriptions<T> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+
+impl<T: Timestamp> Subscriptions<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Probe to attach to the subscription's output with `probe_with`.
+    pub fn register(&mut self, name: impl Into<String>) -> ProbeHandle<T> {
+        let probe = ProbeHandle::new();
+        self.entries.push((name.into(), probe.clone()));
+        probe
+    }
+
+    /// Each subscription's name and probe, in registration order.
+    pub fn probes(&self) -> impl Iterator<Item = (&str, &ProbeHandle<T>)> {
+        self.entries.iter().map(|(name, probe)| (name.as_str(), probe))
+    }
+
+    /// Subscriptions that have not yet completed every time before `time`.
+    pub fn pending<'a>(&'a self, time: &'a T) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(_, probe)| probe.less_than(time))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Step `worker` until every subscription has completed the times before
+    /// `time`, recording each one's latency since `started` as it finishes.
+    pub fn drive<A: Allocate>(
+        &self,
+        worker: &mut Worker<A>,
+        time: &T,
+        started: Instant,
+        metrics: &MetricsRegistry,
+    ) {
+        let mut pending: Vec<&(String, ProbeHandle<T>)> = self.entries.iter().collect();
+        loop {
+            pending.retain(|(name, probe)| {
+                if probe.less_than(time) {
+                    return true;
+                }
+                metrics.record_subscription_latency(name, started.elapsed());
+                false
+            });
+            if pending.is_empty() {
+                break;
+            }
+            worker.step();
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tw_core::retail::OrderPlaced;
+use tw_core::EventEnvelope;
+
+/// Rewrites one incoming event into zero or more events.
+pub type TransformFn<T> = Box<dyn Fn(EventEnvelope<T>) -> Vec<EventEnvelope<T>>>;
+
+/// A transform declared in config rather than code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum TransformRule {
+    /// Drop every event from `source`, such as test traffic.
+    DropSource { source: String },
+    /// Drop events whose key starts with `prefix`.
+    DropKeyPrefix { prefix: String },
+    /// Replace a leading `from` in event keys with `to`.
+    RewriteKeyPrefix { from: String, to: String },
+}
+
+impl TransformRule {
+    pub fn name(&self) -> String {
+        match self {
+            TransformRule::DropSource { source } => format!("drop_source:{source}"),
+            TransformRule::DropKeyPrefix { prefix } => format!("drop_key_prefix:{prefix}"),
+            TransformRule::RewriteKeyPrefix { from, to } => {
+                format!("rewrite_key_prefix:{from}:{to}")
+            }
+        }
+    }
+
+    pub fn apply<T>(&self, mut env: EventEnvelope<T>) -> Vec<EventEnvelope<T>> {
+        match self {
+            TransformRule::DropSource { source } if env.meta.source == *source => Vec::new(),
+            TransformRule::DropKeyPrefix { prefix }
+                if env.meta.key.as_deref().is_some_and(|key| key.starts_with(prefix.as_str())) =>
+            {
+                Vec::new()
+            }
+            TransformRule::RewriteKeyPrefix { from, to } => {
+                let rest = env.meta.key.as_deref().and_then(|key| key.strip_prefix(from.as_str()));
+                if let Some(rest) = rest {
+                    env.meta.key = Some(format!("{to}{rest}"));
+                }
+                vec![env]
+            }
+            _ => vec![env],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransformConfig {
+    /// Applied in order, after any transforms registered in code.
+    #[serde(default)]
+    pub rules: Vec<TransformRule>,
+}
+
+impl TransformConfig {
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading transform config {}", path.display()))?;
+        serde_json::from_str(&raw).with_context(|| format!("parsing transform config {}", path.display()))
+    }
+}
+
+/// Events in and out of one transform since startup.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransformStats {
+    pub name: String,
+    pub events_in: u64,
+    pub events_out: u64,
+    /// Input events that produced no output.
+    pub dropped: u64,
+}
+
+/// Named transforms run in order on each event before it is inserted, so
+/// filtering, re-keying and splitting need no stream processor upstream.
+pub struct TransformPipeline<T> {
+    transforms: Vec<TransformFn<T>>,
+    stats: Vec<TransformStats>,
+}
+
+impl<T> Default for TransformPipeline<T> {
+    fn default() -> Self {
+        Self { transforms: Vec::new(), stats: Vec::new() }
+    }
+}
+
+impl<T: 'static> TransformPipeline<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A pipeline running the rules of `cfg`.
+    pub fn from_config(cfg: &TransformConfig) -> Self {
+        Self::new().with_rules(cfg)
+    }
+
+    pub fn with_rules(self, cfg: &TransformConfig) -> Self {
+        cfg.rules.iter().cloned().fold(self, |pipeline, rule| {
+            pipeline.with_transform(rule.name(), move |env| rule.apply(env))
+        })
+    }
+
+    pub fn with_transform<F>(mut self, name: impl Into<String>, transform: F) -> Self
+    where
+        F: Fn(EventEnvelope<T>) -> Vec<EventEnvelope<T>> + 'static,
+    {
+        self.stats.push(TransformStats { name: name.into(), ..TransformStats::default() });
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    pub fn with_map<F>(self, name: impl Into<String>, map: F) -> Self
+    where
+        F: Fn(EventEnvelope<T>) -> EventEnvelope<T> + 'static,
+    {
+        self.with_transform(name, move |env| vec![map(env)])
+    }
+
+    /// Keep only events for which `keep` returns `true`.
+    pub fn with_filter<F>(self, name: impl Into<String>, keep: F) -> Self
+    where
+        F: Fn(&EventEnvelope<T>) -> bool + 'static,
+    {
+        self.with_transform(name, move |env| if keep(&env) { vec![env] } else { Vec::new() })
+    }
+
+    /// Run `env` through every transform; the result may be empty.
+    pub fn apply(&mut self, env: EventEnvelope<T>) -> Vec<EventEnvelope<T>> {
+        let mut batch = vec![env];
+        for (transform, stats) in self.transforms.iter().zip(self.stats.iter_mut()) {
+            let mut next = Vec::with_capacity(batch.len());
+            for env in batch {
+                let out = transform(env);
+                stats.events_in += 1;
+                stats.events_out += out.len() as u64;
+                if out.is_empty() {
+                    stats.dropped += 1;
+                }
+                next.extend(out);
+            }
+            batch = next;
+        }
+        batch
+    }
+
+    pub fn stats(&self) -> &[TransformStats] {
+        &self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+/// Split a multi-line order into one single-line order per line, keyed
+/// `<key>:<line index>`. Single-line orders pass through unchanged.
+pub fn split_order_lines(env: EventEnvelope<OrderPlaced>) -> Vec<EventEnvelope<OrderPlaced>> {
+    if env.payload.lines.len() <= 1 {
+        return vec![env];
+    }
+    let EventEnvelope { meta, payload } = env;
+    payload
+        .lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let mut meta = meta.clone();
+            meta.key = meta.key.map(|key| format!("{key}:{index}"));
+            let payload = OrderPlaced {
+                order_id: payload.order_id,
+                customer_id: payload.customer_id,
+                lines: vec![line],
+                ts_ms: payload.ts_ms,
+                channel: payload.channel,
+            };
+            EventEnvelope { meta, payload }
+        })
+        .collect()
+}
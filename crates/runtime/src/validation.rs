@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use tw_core::manufacturing::{MachineId, ManufacturingEvent};
+use tw_core::retail::{Money, OrderPlaced};
+use tw_core::{EpochTime, EventEnvelope, EventTime};
+
+use crate::jsonl::JsonlSink;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationConfig {
+    /// Milliseconds of event time per epoch.
+    pub epoch_ms: u64,
+    /// Largest distance allowed between an event's `ts_ms` and its epoch's
+    /// clock; `None` skips the timestamp rule.
+    #[serde(default)]
+    pub max_skew_ms: Option<u64>,
+    /// Rule names that are registered but not enforced.
+    #[serde(default)]
+    pub disabled: Vec<String>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self { epoch_ms: 1_000, max_skew_ms: None, disabled: Vec::new() }
+    }
+}
+
+impl ValidationConfig {
+    /// Checks that `ts` lies within `max_skew_ms` of the clock at `epoch`.
+    pub fn ts_in_range(&self, epoch: EpochTime, ts: EventTime) -> Result<(), String> {
+        let Some(max_skew_ms) = self.max_skew_ms else {
+            return Ok(());
+        };
+        let clock = epoch.start(self.epoch_ms);
+        if ts.abs_diff_ms(clock) > max_skew_ms {
+            return Err(format!(
+                "ts_ms {ts} is more than {max_skew_ms}ms from epoch clock {clock}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A semantic check on one incoming event; `Err` carries the reason.
+pub type InputRule<T> = Box<dyn Fn(&EventEnvelope<T>) -> Result<(), String>>;
+
+/// The first rule an event broke.
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub rule: String,
+    pub kind: String,
+    pub key: Option<String>,
+    pub epoch: EpochTime,
+    pub reason: String,
+}
+
+/// Named semantic rules applied to events before they are inserted, so bad
+/// upstream data is diverted instead of folded into aggregates.
+pub struct InputValidator<T> {
+    cfg: ValidationConfig,
+    rules: Vec<(String, InputRule<T>)>,
+}
+
+impl<T> InputValidator<T> {
+    pub fn new(cfg: ValidationConfig) -> Self {
+        Self { cfg, rules: Vec::new() }
+    }
+
+    pub fn with_rule<F>(mut self, name: impl Into<String>, rule: F) -> Self
+    where
+        F: Fn(&EventEnvelope<T>) -> Result<(), String> + 'static,
+    {
+        let name = name.into();
+        if !self.cfg.disabled.contains(&name) {
+            self.rules.push((name, Box::new(rule)));
+        }
+        self
+    }
+
+    /// Run every enforced rule in registration order, stopping at the first
+    /// violation.
+    pub fn check(&self, env: &EventEnvelope<T>) -> Result<(), Violation> {
+        for (name, rule) in &self.rules {
+            if let Err(reason) = rule(env) {
+                return Err(Violation {
+                    rule: name.clone(),
+                    kind: env.meta.kind.clone(),
+                    key: env.meta.key.clone(),
+                    epoch: env.meta.epoch,
+                    reason,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// `true` if `env` passes; otherwise the violation is logged and, when a
+    /// dead-letter file is given, written there with the event.
+    pub fn admit(&self, env: &EventEnvelope<T>, dead_letters: Option<&mut JsonlSink>) -> bool
+    where
+        T: Serialize,
+    {
+        let Err(violation) = self.check(env) else {
+            return true;
+        };
+        warn!(rule = %violation.rule, reason = %violation.reason, "dropping invalid event");
+        if let Some(dead_letters) = dead_letters {
+            let letter = DeadLetter { violation: &violation, event: env };
+            if let Err(err) = dead_letters.write(&letter) {
+                warn!(?err, "failed to write dead letter");
+            }
+        }
+        false
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// Order rules: `non_negative_price`, `positive_qty` and `ts_in_range`.
+pub fn order_rules(cfg: ValidationConfig) -> InputValidator<OrderPlaced> {
+    let clock = cfg.clone();
+    InputValidator::new(cfg)
+        .with_rule("non_negative_price", |env: &EventEnvelope<OrderPlaced>| {
+            match env.payload.lines.iter().find(|line| line.price_cents < Money::ZERO) {
+                Some(line) => Err(format!("sku {} priced at {}", line.sku_id, line.price_cents)),
+                None => Ok(()),
+            }
+        })
+        .with_rule("positive_qty", |env: &EventEnvelope<OrderPlaced>| {
+            match env.payload.lines.iter().find(|line| line.qty == 0) {
+                Some(line) => Err(format!("sku {} ordered with qty 0", line.sku_id)),
+                None => Ok(()),
+            }
+        })
+        .with_rule("ts_in_range", move |env: &EventEnvelope<OrderPlaced>| {
+            clock.ts_in_range(env.meta.epoch, env.payload.ts_ms)
+        })
+}
+
+/// Manufacturing rules: `known_machine` against `machines` (skipped when
+/// empty), `positive_units` for material movements, `lot_lineage` for lot
+/// splits and merges, and `ts_in_range`.
+pub fn manufacturing_rules(
+    cfg: ValidationConfig,
+    machines: HashSet<MachineId>,
+) -> InputValidator<ManufacturingEvent> {
+    let clock = cfg.clone();
+    InputValidator::new(cfg)
+        .with_rule("known_machine", move |env: &EventEnvelope<ManufacturingEvent>| {
+            match env.payload.machine_id() {
+                Some(machine) if !machines.is_empty() && !machines.contains(&machine) => {
+                    Err(format!("machine {machine} is not in the registry"))
+                }
+                _ => Ok(()),
+            }
+        })
+        .with_rule("positive_units", |env: &EventEnvelope<ManufacturingEvent>| {
+            let units = match &env.payload {
+                ManufacturingEvent::MaterialReceived(e) => e.units,
+                ManufacturingEvent::MaterialConsumed(e) => e.units,
+                _ => return Ok(()),
+            };
+            if units <= 0 {
+                return Err(format!("material movement of {units} units"));
+            }
+            Ok(())
+        })
+        .with_rule("lot_lineage", |env: &EventEnvelope<ManufacturingEvent>| {
+            let (from, into) = match &env.payload {
+                ManufacturingEvent::LotSplit(e) => (std::slice::from_ref(&e.lot_id), &e.into[..]),
+                ManufacturingEvent::LotMerged(e) => (&e.lots[..], std::slice::from_ref(&e.into)),
+                _ => return Ok(()),
+            };
+            if from.is_empty() || into.is_empty() {
+                return Err("lot split or merge names no lots".to_string());
+            }
+            match from.iter().find(|lot| into.contains(lot)) {
+                Some(lot) => Err(format!("lot {lot} derives from itself")),
+                None => Ok(()),
+            }
+        })
+        .with_rule("ts_in_range", move |env: &EventEnvelope<ManufacturingEvent>| {
+            clock.ts_in_range(env.meta.epoch, env.payload.ts_ms())
+        })
+}
+
+/// An event that broke a rule, as written to the dead-letter file.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter<'a, T> {
+    pub violation: &'a Violation,
+    pub event: &'a EventEnvelope<T>,
+}
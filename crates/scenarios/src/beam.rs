@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use tw_core::Depth;
+
+use crate::{DepthQuota, ScenarioMeta, WeightMode};
+
+/// Keep the heaviest candidates: at most `beam_width` per partition, at most
+/// `global_cap` overall, and within every depth quota that covers the
+/// candidate's depth. Then fill up to `tail_slots` more with the highest-impact
+/// scenarios among those evicted, tagged as tail; tail slots come out of
+/// `global_cap` when one is set. `below_floor` (candidates under the
+/// probability floor) are always evicted. Returns `(retained, evicted)`; duplicate ids are dropped.
+pub(crate) fn select_beam(
+    candidates: Vec<ScenarioMeta>,
+    below_floor: Vec<ScenarioMeta>,
+    beam_width: usize,
+    global_cap: Option<usize>,
+    depth_quotas: &[DepthQuota],
+    tail_slots: usize,
+    weight_mode: WeightMode,
+) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+    let main_cap = global_cap.map(|cap| cap.saturating_sub(tail_slots));
+    let (mut retained, mut evicted) =
+        rank_by_weight(candidates, beam_width, main_cap, depth_quotas, weight_mode);
+    for meta in retained.iter_mut() {
+        meta.tail = false;
+    }
+    if tail_slots == 0 {
+        evicted.extend(below_floor);
+        return (retained, evicted);
+    }
+
+    evicted.sort_by(|a, b| b.impact.cmp(&a.impact).then_with(|| weight_mode.rank(a, b)));
+    let tails = evicted.iter().take(tail_slots).take_while(|meta| meta.impact > 0).count();
+    let mut rest = evicted.split_off(tails);
+    for mut meta in evicted {
+        meta.tail = true;
+        retained.push(meta);
+    }
+    rest.extend(below_floor);
+    (retained, rest)
+}
+
+/// The `budget` heaviest of `parents`, shallower first among equals, so one
+/// event extends a bounded number of branches however wide the beam is.
+pub(crate) fn budget_parents(
+    mut parents: Vec<ScenarioMeta>,
+    budget: Option<usize>,
+    weight_mode: WeightMode,
+) -> Vec<ScenarioMeta> {
+    let Some(budget) = budget.filter(|&budget| budget < parents.len()) else {
+        return parents;
+    };
+    parents.sort_by(|a, b| weight_mode.rank(a, b).then_with(|| a.depth.cmp(&b.depth)));
+    parents.truncate(budget);
+    parents
+}
+
+fn rank_by_weight(
+    mut candidates: Vec<ScenarioMeta>,
+    beam_width: usize,
+    global_cap: Option<usize>,
+    depth_quotas: &[DepthQuota],
+    weight_mode: WeightMode,
+) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+    candidates.sort_by(|a, b| weight_mode.rank(a, b));
+
+    let global_cap = global_cap.unwrap_or(usize::MAX);
+    let mut seen: HashSet<u64> = HashSet::new();
+    let mut per_partition: HashMap<Option<u64>, usize> = HashMap::new();
+    let mut per_quota = vec![0usize; depth_quotas.len()];
+    let mut retained = Vec::new();
+    let mut evicted = Vec::new();
+    for meta in candidates {
+        if !seen.insert(meta.id) {
+            continue;
+        }
+        let occupancy = per_partition.entry(meta.partition).or_insert(0);
+        let within_quotas = depth_quotas
+            .iter()
+            .zip(&per_quota)
+            .all(|(quota, used)| meta.depth < quota.min_depth || *used < quota.max_scenarios);
+        if *occupancy < beam_width && retained.len() < global_cap && within_quotas {
+            *occupancy += 1;
+            for (quota, used) in depth_quotas.iter().zip(per_quota.iter_mut()) {
+                if meta.depth >= quota.min_depth {
+                    *used += 1;
+                }
+            }
+            retained.push(meta);
+        } else {
+            evicted.push(meta);
+        }
+    }
+    (retained, evicted)
+}
+
+/// Beam partitions by when an expansion last branched on them.
+#[derive(Debug, Default)]
+pub(crate) struct KeyLru {
+    clock: u64,
+    last_used: HashMap<u64, u64>,
+}
+
+impl KeyLru {
+    pub(crate) fn touch(&mut self, key: u64) {
+        self.clock += 1;
+        self.last_used.insert(key, self.clock);
+    }
+
+    /// Forget keys no longer in `live`; live keys never touched, such as
+    /// those restored from a checkpoint, count as least recently used.
+    pub(crate) fn sync(&mut self, live: &HashSet<u64>) {
+        self.last_used.retain(|key, _| live.contains(key));
+        for key in live {
+            self.last_used.entry(*key).or_insert(0);
+        }
+    }
+
+    /// Forget and return the keys beyond the `max_keys` most recently used.
+    pub(crate) fn evict(&mut self, max_keys: usize) -> HashSet<u64> {
+        let excess = self.last_used.len().saturating_sub(max_keys);
+        if excess == 0 {
+            return HashSet::new();
+        }
+        let mut by_age: Vec<(u64, u64)> =
+            self.last_used.iter().map(|(key, used)| (*used, *key)).collect();
+        by_age.sort_unstable();
+        let evicted: HashSet<u64> = by_age.into_iter().take(excess).map(|(_, key)| key).collect();
+        self.last_used.retain(|key, _| !evicted.contains(key));
+        evicted
+    }
+}
+
+/// Active scenarios per depth.
+pub(crate) fn depth_occupancy(active: &[ScenarioMeta]) -> BTreeMap<Depth, usize> {
+    let mut occupancy = BTreeMap::new();
+    for meta in active {
+        *occupancy.entry(meta.depth).or_insert(0) += 1;
+    }
+    occupancy
+}
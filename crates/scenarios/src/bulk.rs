@@ -0,0 +1,126 @@
+//! Operator interventions on a manager's scenarios as a whole: retiring
+//! everything a filter matches, reweighting a set of scenarios, or freezing
+//! expansion for a key. Queue them on a control plane so each applies at an
+//! epoch boundary; every applied operation leaves a [`BulkAudit`].
+
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tw_core::{Depth, EpochTime, ScenarioId};
+
+use crate::ScenarioMeta;
+
+/// Selects live scenarios; every field set must match, and an empty filter
+/// matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioFilter {
+    /// Prefix of any of the scenario's [`ScenarioMeta::tags`], such as
+    /// `"failure"`, or `"quality_hold:"` for seeds, which carry their
+    /// `source:name` tag. Retiring a seed retires the branches grown from it.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Partition key the scenario was branched under. Only a partitioned
+    /// beam accepts it; unpartitioned scenarios have no key.
+    #[serde(default)]
+    pub key: Option<u64>,
+    #[serde(default)]
+    pub min_depth: Option<Depth>,
+    #[serde(default)]
+    pub max_depth: Option<Depth>,
+}
+
+impl ScenarioFilter {
+    pub fn matches(&self, meta: &ScenarioMeta) -> bool {
+        let tagged = self.tag.as_ref().map_or(true, |prefix| {
+            meta.tags.iter().any(|tag| tag.starts_with(prefix.as_str()))
+        });
+        tagged
+            && self.key.map_or(true, |key| meta.partition == Some(key))
+            && self.min_depth.map_or(true, |depth| meta.depth >= depth)
+            && self.max_depth.map_or(true, |depth| meta.depth <= depth)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOp {
+    /// Retire every live scenario the filter matches, with its descendants.
+    Retire(ScenarioFilter),
+    /// Scale the weights of `scenarios` by `factor`, capped at 1. Ids no
+    /// longer live are skipped.
+    Reweight { scenarios: Vec<ScenarioId>, factor: f64 },
+    /// Stop branching on events under partition `key`; its live scenarios
+    /// stay until pruned or retired. Only a partitioned beam accepts it.
+    Freeze { key: u64 },
+    Thaw { key: u64 },
+    /// Exempt `scenarios` from pruning until unpinned.
+    Pin { scenarios: Vec<ScenarioId> },
+    Unpin { scenarios: Vec<ScenarioId> },
+}
+
+/// Parses `retire[:field=value,...]` with fields `tag`, `key`, `min_depth`
+/// and `max_depth`; `reweight:<factor>:<id>,<id>...`; `freeze:<key>`;
+/// `thaw:<key>`; `pin:<id>,<id>...`; and `unpin:<id>,<id>...`.
+impl FromStr for BulkOp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        match name {
+            "retire" => {
+                let mut filter = ScenarioFilter::default();
+                for field in args.split(',').filter(|field| !field.is_empty()) {
+                    let (field, value) = field
+                        .split_once('=')
+                        .with_context(|| format!("expected field=value, got {field}"))?;
+                    match field {
+                        "tag" => filter.tag = Some(value.to_string()),
+                        "key" => filter.key = Some(value.parse().context("parsing key")?),
+                        "min_depth" => {
+                            filter.min_depth = Some(value.parse().context("parsing min_depth")?)
+                        }
+                        "max_depth" => {
+                            filter.max_depth = Some(value.parse().context("parsing max_depth")?)
+                        }
+                        other => bail!(
+                            "unknown filter {other}; expected tag, key, min_depth or max_depth"
+                        ),
+                    }
+                }
+                Ok(BulkOp::Retire(filter))
+            }
+            "reweight" => {
+                let (factor, ids) =
+                    args.split_once(':').context("expected reweight:<factor>:<id>,<id>...")?;
+                let scenarios = parse_ids(ids)?;
+                let factor = factor.parse().context("parsing factor")?;
+                Ok(BulkOp::Reweight { scenarios, factor })
+            }
+            "freeze" => Ok(BulkOp::Freeze { key: args.parse().context("parsing key")? }),
+            "thaw" => Ok(BulkOp::Thaw { key: args.parse().context("parsing key")? }),
+            "pin" => Ok(BulkOp::Pin { scenarios: parse_ids(args)? }),
+            "unpin" => Ok(BulkOp::Unpin { scenarios: parse_ids(args)? }),
+            other => bail!(
+                "unknown bulk operation {other}; \
+                 expected retire, reweight, freeze, thaw, pin or unpin"
+            ),
+        }
+    }
+}
+
+fn parse_ids(ids: &str) -> anyhow::Result<Vec<ScenarioId>> {
+    ids.split(',')
+        .map(|id| id.parse().with_context(|| format!("parsing scenario id {id}")))
+        .collect()
+}
+
+/// What one applied [`BulkOp`] changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkAudit {
+    pub epoch: EpochTime,
+    pub op: BulkOp,
+    /// Scenarios retired, reweighted, pinned or unpinned; empty for freezes
+    /// and thaws.
+    pub affected: Vec<ScenarioId>,
+}
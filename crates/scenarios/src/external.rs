@@ -0,0 +1,45 @@
+//! Scenarios defined outside the engine, such as S&OP planning cases or
+//! weather forecasts, seeded at the root next to predictor-driven branches.
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// One externally defined scenario: its probability and the overlay rows it
+/// applies, in the domain's row type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalScenario<R> {
+    /// System the scenario came from; tagged on its [`ScenarioMeta`](crate::ScenarioMeta).
+    pub source: String,
+    /// The scenario's name in that system.
+    pub name: String,
+    pub probability: f64,
+    pub rows: Vec<R>,
+}
+
+impl<R> ExternalScenario<R> {
+    /// The tag recorded in [`ScenarioMeta::external`](crate::ScenarioMeta::external).
+    pub fn tag(&self) -> String {
+        format!("{}:{}", self.source, self.name)
+    }
+}
+
+/// A batch of external scenarios, as a planning system exports them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalPlan<R> {
+    pub scenarios: Vec<ExternalScenario<R>>,
+}
+
+#[cfg(feature = "fs")]
+impl<R: serde::de::DeserializeOwned> ExternalPlan<R> {
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading external scenarios {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("parsing external scenarios {}", path.display()))
+    }
+}
@@ -0,0 +1,121 @@
+//! Per-epoch (key × scenario) matrix of projected deltas for watched keys,
+//! for heatmaps of where the beam concentrates risk.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+#[cfg(feature = "parquet")]
+use std::path::Path;
+
+#[cfg(feature = "parquet")]
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tw_core::{EpochTime, ScenarioId};
+
+use crate::ScenarioMeta;
+
+/// One matrix cell: a watched key's summed overlay delta under one scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HeatmapCell {
+    pub epoch: EpochTime,
+    pub key: u64,
+    pub scenario_id: ScenarioId,
+    pub weight: f64,
+    pub delta: i64,
+}
+
+/// Tracks live overlay deltas of watched keys and snapshots them once per
+/// epoch. Cells are kept in long form, one row per nonzero (epoch, key,
+/// scenario); zero cells are left out.
+#[derive(Debug, Default)]
+pub struct ImpactHeatmap {
+    watched: BTreeSet<u64>,
+    deltas: BTreeMap<(u64, ScenarioId), i64>,
+    weights: HashMap<ScenarioId, f64>,
+    cells: Vec<HeatmapCell>,
+}
+
+impl ImpactHeatmap {
+    pub fn new(watched: impl IntoIterator<Item = u64>) -> Self {
+        Self { watched: watched.into_iter().collect(), ..Self::default() }
+    }
+
+    /// `false` when no key is watched; recording is then a no-op.
+    pub fn is_enabled(&self) -> bool {
+        !self.watched.is_empty()
+    }
+
+    pub fn record_created(&mut self, meta: &ScenarioMeta) {
+        if self.is_enabled() {
+            self.weights.insert(meta.id, meta.weight.0);
+        }
+    }
+
+    /// Add `delta` to `key` under `scenario_id`; pass the negated delta when
+    /// an overlay row is removed.
+    pub fn record_delta(&mut self, scenario_id: ScenarioId, key: u64, delta: i64) {
+        if !self.watched.contains(&key) {
+            return;
+        }
+        let cell = self.deltas.entry((key, scenario_id)).or_default();
+        *cell = cell.saturating_add(delta);
+        if *cell == 0 {
+            self.deltas.remove(&(key, scenario_id));
+        }
+    }
+
+    pub fn record_retired(&mut self, scenario_id: ScenarioId) {
+        self.weights.remove(&scenario_id);
+        self.deltas.retain(|(_, sid), _| *sid != scenario_id);
+    }
+
+    /// Append the current matrix as of `epoch`.
+    pub fn snapshot(&mut self, epoch: EpochTime) {
+        let weights = &self.weights;
+        self.cells.extend(self.deltas.iter().map(|(&(key, scenario_id), &delta)| HeatmapCell {
+            epoch,
+            key,
+            scenario_id,
+            weight: weights.get(&scenario_id).copied().unwrap_or(0.0),
+            delta,
+        }));
+    }
+
+    /// Snapshotted cells, by epoch, then key, then scenario.
+    pub fn cells(&self) -> &[HeatmapCell] {
+        &self.cells
+    }
+
+    /// Write every snapshotted cell to a Parquet file with columns `epoch`,
+    /// `key`, `scenario_id`, `weight` and `delta`.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet(&self, path: impl AsRef<Path>) -> Result<()> {
+        use std::sync::Arc;
+
+        use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, UInt64Array};
+        use arrow_schema::{DataType, Field, Schema};
+        use parquet::arrow::ArrowWriter;
+
+        let path = path.as_ref();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("epoch", DataType::UInt64, false),
+            Field::new("key", DataType::UInt64, false),
+            Field::new("scenario_id", DataType::UInt64, false),
+            Field::new("weight", DataType::Float64, false),
+            Field::new("delta", DataType::Int64, false),
+        ]));
+        let cells = &self.cells;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(cells.iter().map(|c| c.epoch.get()))),
+            Arc::new(UInt64Array::from_iter_values(cells.iter().map(|c| c.key))),
+            Arc::new(UInt64Array::from_iter_values(cells.iter().map(|c| c.scenario_id))),
+            Arc::new(Float64Array::from_iter_values(cells.iter().map(|c| c.weight))),
+            Arc::new(Int64Array::from_iter_values(cells.iter().map(|c| c.delta))),
+        ];
+        let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("creating heatmap file {}", path.display()))?;
+        let mut writer = ArrowWriter::try_new(file, schema, None)?;
+        writer.write(&batch)?;
+        writer.close().with_context(|| format!("writing heatmap file {}", path.display()))?;
+        Ok(())
+    }
+}
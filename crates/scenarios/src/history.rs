@@ -0,0 +1,122 @@
+//! Bounded memory of recently retired scenarios, for post-hoc queries.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+use tw_core::{EpochTime, ScenarioId};
+
+use crate::ScenarioMeta;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetirementReason {
+    /// Weight fell below the beam's `min_prob`.
+    BelowMinProb,
+    /// Reached the beam's `max_depth`.
+    MaxDepth,
+    /// Lost its place to heavier scenarios in beam selection.
+    Pruned,
+    /// Shed by a [`SafetyValve`](crate::valve::SafetyValve) cap.
+    Shed,
+    /// An externally seeded scenario its source withdrew.
+    Withdrawn,
+    /// A prior whose probability decayed to its floor.
+    Decayed,
+    /// A realized event contradicted its prediction, or its parent's.
+    Contradicted,
+    /// Its partition was the least recently branched when the beam kept
+    /// more partitions than `max_keys`.
+    KeyEvicted,
+    /// Retired by an operator's bulk operation.
+    Operator,
+    /// Outlived the beam's `ttl_epochs` without being confirmed.
+    Expired,
+}
+
+impl RetirementReason {
+    /// The reason's serialized name, as used in metric labels and logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            RetirementReason::BelowMinProb => "below_min_prob",
+            RetirementReason::MaxDepth => "max_depth",
+            RetirementReason::Pruned => "pruned",
+            RetirementReason::Shed => "shed",
+            RetirementReason::Withdrawn => "withdrawn",
+            RetirementReason::Decayed => "decayed",
+            RetirementReason::Contradicted => "contradicted",
+            RetirementReason::KeyEvicted => "key_evicted",
+            RetirementReason::Operator => "operator",
+            RetirementReason::Expired => "expired",
+        }
+    }
+}
+
+/// A retired scenario as it stood when it left the beam; `meta.weight` is
+/// its final weight.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetiredScenario<O> {
+    #[serde(flatten)]
+    pub meta: ScenarioMeta,
+    pub retired_at: EpochTime,
+    pub reason: RetirementReason,
+    pub overlays: Option<O>,
+}
+
+/// The last `capacity` retired scenarios; the oldest retirements are
+/// forgotten first. A capacity of 0 keeps nothing.
+#[derive(Debug)]
+pub struct RetiredHistory<O> {
+    capacity: usize,
+    entries: HashMap<ScenarioId, RetiredScenario<O>>,
+    order: VecDeque<ScenarioId>,
+}
+
+impl<O> Default for RetiredHistory<O> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<O> RetiredHistory<O> {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    pub fn record(&mut self, retired: RetiredScenario<O>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let id = retired.meta.id;
+        if self.entries.insert(id, retired).is_none() {
+            self.order.push_back(id);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, scenario_id: ScenarioId) -> Option<&RetiredScenario<O>> {
+        self.entries.get(&scenario_id)
+    }
+
+    /// Retired scenarios, oldest retirement first.
+    pub fn iter(&self) -> impl Iterator<Item = &RetiredScenario<O>> + '_ {
+        self.order.iter().filter_map(|id| self.entries.get(id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<O: Serialize> RetiredHistory<O> {
+    pub fn to_json(&self, scenario_id: ScenarioId) -> Option<String> {
+        self.get(scenario_id).and_then(|retired| serde_json::to_string(retired).ok())
+    }
+}
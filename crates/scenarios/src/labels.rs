@@ -0,0 +1,154 @@
+//! Labeled training examples from resolved scenarios.
+//!
+//! A scenario's prediction is resolved when the scenario retires: the base
+//! value it predicted a delta for has either moved as predicted (confirmed)
+//! or not (refuted). Scenarios retired in the epoch they were predicted in,
+//! such as children the beam evicts as soon as they are created, never had
+//! a chance to realize and are dropped unlabeled.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tw_core::quantity::BPS_SCALE;
+use tw_core::{EpochTime, ScenarioId};
+use tw_predictors::FeatureStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Confirmed,
+    Refuted,
+}
+
+/// One resolved prediction, as written to the training sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledExample {
+    pub scenario_id: ScenarioId,
+    /// Entity the prediction was about (customer, machine).
+    pub key: u64,
+    pub predicted_at: EpochTime,
+    pub resolved_at: EpochTime,
+    /// Feature values at prediction time.
+    pub features: BTreeMap<String, f64>,
+    pub base_value: i64,
+    pub predicted_delta: i64,
+    pub realized_delta: i64,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone)]
+struct PendingPrediction {
+    key: u64,
+    predicted_at: EpochTime,
+    features: BTreeMap<String, f64>,
+    base_value: i64,
+    predicted_delta: i64,
+}
+
+/// Remembers each scenario's prediction until the scenario retires, then
+/// labels it against how the base value actually moved.
+pub struct OutcomeLabeler {
+    /// Share of the predicted delta the realized delta must reach, in the
+    /// predicted direction, to confirm.
+    confirm_bps: i64,
+    feature_names: Vec<String>,
+    feature_store: Option<Arc<dyn FeatureStore>>,
+    pending: HashMap<ScenarioId, PendingPrediction>,
+}
+
+impl Default for OutcomeLabeler {
+    fn default() -> Self {
+        Self {
+            confirm_bps: 5_000,
+            feature_names: Vec::new(),
+            feature_store: None,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl OutcomeLabeler {
+    pub fn new(confirm_bps: i64) -> Self {
+        Self { confirm_bps, ..Self::default() }
+    }
+
+    /// Snapshot these features for the prediction's key when it is recorded.
+    pub fn with_features(mut self, store: Arc<dyn FeatureStore>, names: Vec<String>) -> Self {
+        self.feature_store = Some(store);
+        self.feature_names = names;
+        self
+    }
+
+    pub fn record_prediction(
+        &mut self,
+        epoch: EpochTime,
+        scenario_id: ScenarioId,
+        key: u64,
+        base_value: i64,
+        predicted_delta: i64,
+    ) {
+        let features = match &self.feature_store {
+            Some(store) => self
+                .feature_names
+                .iter()
+                .filter_map(|name| store.feature(key, name).map(|value| (name.clone(), value)))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+        self.pending.insert(
+            scenario_id,
+            PendingPrediction { key, predicted_at: epoch, features, base_value, predicted_delta },
+        );
+    }
+
+    /// Label a retired scenario. `current` reads the key's base value now;
+    /// scenarios without a recorded prediction, or retired in the epoch
+    /// they were predicted in, yield `None`.
+    pub fn resolve<F>(
+        &mut self,
+        epoch: EpochTime,
+        scenario_id: ScenarioId,
+        current: F,
+    ) -> Option<LabeledExample>
+    where
+        F: FnOnce(u64) -> i64,
+    {
+        let pending = self.pending.remove(&scenario_id)?;
+        if epoch <= pending.predicted_at {
+            return None;
+        }
+        let realized_delta = current(pending.key).saturating_sub(pending.base_value);
+        let verdict = if self.confirms(pending.predicted_delta, realized_delta) {
+            Verdict::Confirmed
+        } else {
+            Verdict::Refuted
+        };
+        Some(LabeledExample {
+            scenario_id,
+            key: pending.key,
+            predicted_at: pending.predicted_at,
+            resolved_at: epoch,
+            features: pending.features,
+            base_value: pending.base_value,
+            predicted_delta: pending.predicted_delta,
+            realized_delta,
+            verdict,
+        })
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn confirms(&self, predicted: i64, realized: i64) -> bool {
+        if predicted == 0 {
+            return realized == 0;
+        }
+        if predicted.signum() != realized.signum() {
+            return false;
+        }
+        let needed = (predicted.unsigned_abs() as i128) * (self.confirm_bps as i128);
+        (realized.unsigned_abs() as i128) * (BPS_SCALE as i128) >= needed
+    }
+}
@@ -1,3 +1,6 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use tw_core::{Depth, Prob, ScenarioId};
 
@@ -7,7 +10,141 @@ pub struct ScenarioMeta {
     pub parent: Option<ScenarioId>,
     pub depth: Depth,
     pub weight: Prob,
+    /// Independence key of the event that created this branch, when the
+    /// manager partitions its beam.
+    #[serde(default)]
+    pub partition: Option<u64>,
+    /// Overlay magnitude summed along the lineage, in the manager's units
+    /// (cents, units of WIP); ranks scenarios for tail slots.
+    #[serde(default)]
+    pub impact: i64,
+    /// Kept in a tail slot for its impact rather than its probability.
+    #[serde(default)]
+    pub tail: bool,
+    /// Siblings sharing a group are mutually exclusive alternatives of
+    /// their parent; at most one of them happens.
+    #[serde(default)]
+    pub exclusion_group: Option<u64>,
+    /// `source:name` of the external plan a scenario was seeded from or
+    /// branched under; `None` for branches off the predictor-driven tree.
+    #[serde(default)]
+    pub external: Option<String>,
+    /// What the scenario represents, such as `machine 3 goes down`.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Short facets for filtering and grouping, such as `failure` and
+    /// `machine:3`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ScenarioMeta {
+    /// The label followed by the bracketed tags, for logs and alert
+    /// payloads; `None` when the scenario has neither.
+    pub fn describe(&self) -> Option<String> {
+        let tags = (!self.tags.is_empty()).then(|| format!("[{}]", self.tags.join(", ")));
+        match (&self.label, tags) {
+            (Some(label), Some(tags)) => Some(format!("{label} {tags}")),
+            (Some(label), None) => Some(label.clone()),
+            (None, tags) => tags,
+        }
+    }
+}
+
+/// Cap on active scenarios at `min_depth` or deeper, so speculative chains
+/// can't crowd fresh shallow branches out of the beam.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthQuota {
+    pub min_depth: Depth,
+    pub max_scenarios: usize,
+}
+
+/// How scenario weights are computed and ranked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightMode {
+    #[default]
+    Float,
+    /// Q32.32 fixed point: weights are exact multiples of 2^-32, products
+    /// truncate, and ranking ties break by scenario id, so pruning and
+    /// exported weights are bit-reproducible across platforms.
+    Fixed,
+}
+
+impl WeightMode {
+    /// `weight` as this mode stores it.
+    pub fn weight(self, weight: f64) -> f64 {
+        match self {
+            WeightMode::Float => weight,
+            WeightMode::Fixed => Prob::from_fixed(Prob(weight).to_fixed()).0,
+        }
+    }
+
+    pub fn child_weight(self, parent: f64, branch_prob: f64) -> f64 {
+        match self {
+            WeightMode::Float => parent * branch_prob,
+            WeightMode::Fixed => Prob(parent).fixed_mul(Prob(branch_prob)).0,
+        }
+    }
+
+    /// Heavier scenarios first.
+    pub(crate) fn rank(self, a: &ScenarioMeta, b: &ScenarioMeta) -> Ordering {
+        match self {
+            WeightMode::Float => b.weight.0.partial_cmp(&a.weight.0).unwrap_or(Ordering::Equal),
+            WeightMode::Fixed => {
+                b.weight.to_fixed().cmp(&a.weight.to_fixed()).then_with(|| a.id.cmp(&b.id))
+            }
+        }
+    }
+}
+
+/// How surviving weights are rescaled at the end of each expansion, once
+/// pruning has dropped some of the probability mass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Renormalization {
+    /// Leave weights as branched; they no longer sum to anything.
+    #[default]
+    None,
+    /// Scale the beam's weights so they sum to one. Seeded scenarios keep
+    /// theirs.
+    SumToOne,
+    /// Hand the weight of scenarios culled in the expansion to the root's
+    /// surviving children, in proportion to their weight.
+    RedistributeToRoot,
+}
+
+impl FromStr for Renormalization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(Renormalization::None),
+            "sum_to_one" => Ok(Renormalization::SumToOne),
+            "redistribute_to_root" => Ok(Renormalization::RedistributeToRoot),
+            other => anyhow::bail!(
+                "unknown renormalization {other}; expected none, sum_to_one or redistribute_to_root"
+            ),
+        }
+    }
 }
 
+mod beam;
+pub mod bulk;
+pub mod external;
+pub mod heatmap;
+pub mod history;
+pub mod labels;
+pub mod lineage;
+pub mod manager;
+pub mod priors;
+pub mod pruning;
 pub mod retail;
 pub mod manufacturing;
+pub mod state;
+pub mod timeline;
+pub mod throttle;
+pub mod tree;
+pub mod validate;
+pub mod valve;
+pub mod what_if;
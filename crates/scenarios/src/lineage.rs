@@ -0,0 +1,127 @@
+//! Parent/child index over a manager's scenarios, for walking lineages.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use tw_core::ScenarioId;
+
+use crate::ScenarioMeta;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LineageNode {
+    parent: Option<ScenarioId>,
+    children: BTreeSet<ScenarioId>,
+    weight: f64,
+    live: bool,
+}
+
+/// Links every live scenario to its parent. A retired scenario stays in the
+/// index while live scenarios descend from it, so their paths stay whole;
+/// it is dropped with its last live descendant.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LineageIndex {
+    nodes: HashMap<ScenarioId, LineageNode>,
+}
+
+impl LineageIndex {
+    pub fn insert(&mut self, meta: &ScenarioMeta) {
+        let parent = meta.parent.filter(|parent| self.nodes.contains_key(parent));
+        if let Some(node) = parent.and_then(|parent| self.nodes.get_mut(&parent)) {
+            node.children.insert(meta.id);
+        }
+        self.nodes.insert(
+            meta.id,
+            LineageNode { parent, children: BTreeSet::new(), weight: meta.weight.0, live: true },
+        );
+    }
+
+    pub fn reweight(&mut self, scenario_id: ScenarioId, weight: f64) {
+        if let Some(node) = self.nodes.get_mut(&scenario_id) {
+            node.weight = weight;
+        }
+    }
+
+    /// Mark a scenario retired, then drop it and any retired ancestors left
+    /// without descendants.
+    pub fn retire(&mut self, scenario_id: ScenarioId) {
+        let Some(node) = self.nodes.get_mut(&scenario_id) else {
+            return;
+        };
+        node.live = false;
+        let mut next = Some(scenario_id);
+        while let Some(id) = next {
+            let Some(node) = self.nodes.get(&id) else {
+                break;
+            };
+            if node.live || !node.children.is_empty() {
+                break;
+            }
+            next = node.parent;
+            self.nodes.remove(&id);
+            if let Some(parent) = next.and_then(|parent| self.nodes.get_mut(&parent)) {
+                parent.children.remove(&id);
+            }
+        }
+    }
+
+    /// Current weight of a live scenario, or final weight of a retired one.
+    pub fn weight(&self, scenario_id: ScenarioId) -> Option<f64> {
+        self.nodes.get(&scenario_id).map(|node| node.weight)
+    }
+
+    /// Whether `scenario_id` is indexed, live or kept as an ancestor.
+    pub fn contains(&self, scenario_id: ScenarioId) -> bool {
+        self.nodes.contains_key(&scenario_id)
+    }
+
+    /// Parents of `scenario_id`, nearest first, up to a root-level scenario.
+    pub fn ancestors(&self, scenario_id: ScenarioId) -> Vec<ScenarioId> {
+        let mut ancestors = Vec::new();
+        let mut next = self.nodes.get(&scenario_id).and_then(|node| node.parent);
+        while let Some(id) = next {
+            ancestors.push(id);
+            next = self.nodes.get(&id).and_then(|node| node.parent);
+        }
+        ancestors
+    }
+
+    /// `scenario_id` followed by its [`ancestors`](Self::ancestors); empty
+    /// when it is not indexed.
+    pub fn path_to_root(&self, scenario_id: ScenarioId) -> Vec<ScenarioId> {
+        if !self.contains(scenario_id) {
+            return Vec::new();
+        }
+        let mut path = vec![scenario_id];
+        path.extend(self.ancestors(scenario_id));
+        path
+    }
+
+    /// Indexed descendants of `scenario_id`, breadth first. Retired ones are
+    /// included only while they lead to live scenarios.
+    pub fn descendants(&self, scenario_id: ScenarioId) -> Vec<ScenarioId> {
+        let mut descendants = Vec::new();
+        let Some(node) = self.nodes.get(&scenario_id) else {
+            return descendants;
+        };
+        descendants.extend(node.children.iter().copied());
+        let mut next = 0;
+        while let Some(&id) = descendants.get(next) {
+            if let Some(node) = self.nodes.get(&id) {
+                descendants.extend(node.children.iter().copied());
+            }
+            next += 1;
+        }
+        descendants
+    }
+
+    /// Summed weight of the live scenarios in the subtree rooted at
+    /// `scenario_id`, itself included.
+    pub fn subtree_weight(&self, scenario_id: ScenarioId) -> f64 {
+        std::iter::once(scenario_id)
+            .chain(self.descendants(scenario_id))
+            .filter_map(|id| self.nodes.get(&id))
+            .filter(|node| node.live)
+            .map(|node| node.weight)
+            .sum()
+    }
+}
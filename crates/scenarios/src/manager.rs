@@ -0,0 +1,1334 @@
+//! The beam machinery every domain shares: retiring, branching, validating
+//! and pruning scenarios, seeding external ones and backfilling throttled
+//! expansions. A domain supplies a [`DeltaBuilder`] that turns its events into
+//! overlays; [`RetailScenarioManager`](crate::retail::RetailScenarioManager)
+//! and [`ManufacturingScenarioManager`](crate::manufacturing::ManufacturingScenarioManager)
+//! are thin wrappers over [`ScenarioManager`].
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::ensure;
+
+use tw_core::{Depth, EpochTime, Prob, ScenarioId};
+use tw_predictors::{FeatureStore, LineageSummary, PredictionContext};
+
+use crate::beam::{budget_parents, depth_occupancy, KeyLru};
+use crate::bulk::{BulkAudit, BulkOp};
+use crate::history::{RetiredHistory, RetiredScenario, RetirementReason};
+use crate::lineage::LineageIndex;
+use crate::priors::PriorSchedule;
+use crate::pruning::{BeamPruning, PruningStrategy};
+use crate::state::{ExclusionGroupState, ScenarioManagerState};
+use crate::throttle::{Deferred, LagThrottle, LagThrottleConfig};
+use crate::validate::{Proposal, Rejection, Validators};
+use crate::valve::{SafetyValve, ValveUsage};
+use crate::what_if::what_if_tag;
+use crate::{DepthQuota, Renormalization, ScenarioMeta, WeightMode};
+
+/// Beam settings shared by every domain; domain configs add how their deltas
+/// are scaled and each event's branch probability.
+#[derive(Debug, Clone)]
+pub struct BeamConfig {
+    pub max_depth: Depth,
+    pub beam_width: usize,
+    pub min_prob: f64,
+    /// Upper bound on active scenarios across all beam partitions.
+    pub global_cap: Option<usize>,
+    /// Upper bound on partitions with active scenarios; the least recently
+    /// branched partition's scenarios retire to make room for a new one.
+    pub max_keys: Option<usize>,
+    /// Factor beam weights are scaled by per epoch in
+    /// [`ScenarioManager::advance_epoch`]; `None` leaves them as they are.
+    pub epoch_decay: Option<f64>,
+    /// Epochs a beam scenario lives past its creation or last confirmation.
+    pub ttl_epochs: Option<u64>,
+    /// Per-depth caps applied alongside `beam_width`.
+    pub depth_quotas: Vec<DepthQuota>,
+    /// Slots reserved for the highest-impact scenarios that probability
+    /// ranking would drop. `min_prob` still applies: a tail is a scenario
+    /// the beam has no room for, never one under the floor.
+    pub tail_slots: usize,
+    /// Children one triggering event may create, counting each outcome of
+    /// a parent; a parent whose outcomes do not all fit gets none. `None`
+    /// extends every eligible parent with every outcome.
+    pub max_children_per_event: Option<usize>,
+    pub weight_mode: WeightMode,
+    pub renormalization: Renormalization,
+}
+
+/// How one event branches the beam.
+#[derive(Debug, Clone, Copy)]
+pub struct Branch {
+    /// Independence key of the event; children only extend parents created
+    /// under the same key, and `None` only extends unpartitioned parents.
+    pub partition: Option<u64>,
+    /// Base-view value for the event's key, if the caller tracks it.
+    pub base_value: Option<i64>,
+    /// Scales each child's weight from its parent's.
+    pub branch_prob: f64,
+    /// Children of one parent branched on events with the same key are
+    /// mutually exclusive alternatives and share an exclusion group. The
+    /// outcomes of one branch always share one.
+    pub exclusion: Option<ExclusionKey>,
+}
+
+/// The event a child plays out, for grouping alternatives: what kind of event
+/// (say `"failure"`) and the id of what it happens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExclusionKey {
+    pub kind: &'static str,
+    pub id: u64,
+}
+
+/// A domain's side of expansion: whether and how an event `E` branches, the
+/// overlay `D` each child gets, and how overlays are sized.
+pub trait DeltaBuilder<E, D> {
+    /// How `event` branches, or `None` to leave the beam alone, e.g. when
+    /// the domain has no predictor for events of its kind.
+    fn branch(
+        &self,
+        epoch: EpochTime,
+        features: Option<&dyn FeatureStore>,
+        event: &E,
+    ) -> Option<Branch>;
+
+    /// Overlay of child `scenario_id`, branched on `event` from the parent
+    /// described by `ctx`.
+    fn build(&self, ctx: &PredictionContext<'_>, event: &E, scenario_id: ScenarioId) -> D;
+
+    /// Alternative overlays for the children branched from one parent, each
+    /// with its share of the branch probability; the `i`-th is the overlay of
+    /// child `first_id + i`. Shares summing past 1 are scaled down to 1. By
+    /// default one child, built by [`Self::build`], takes the whole branch.
+    fn build_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &E,
+        first_id: ScenarioId,
+    ) -> Vec<(f64, D)> {
+        vec![(1.0, self.build(ctx, event, first_id))]
+    }
+
+    /// Magnitude of `overlay` in the domain's units (cents, units of WIP).
+    fn impact(&self, overlay: &D) -> i64;
+
+    /// Rows `overlay` adds, counted against safety valve caps.
+    fn rows(&self, overlay: &D) -> usize;
+
+    /// What a child branched on `event` with `overlay` represents, in a few
+    /// words; recorded as [`ScenarioMeta::label`].
+    fn label(&self, _event: &E, _overlay: &D) -> Option<String> {
+        None
+    }
+
+    /// Tags recorded on that child as [`ScenarioMeta::tags`].
+    fn tags(&self, _event: &E, _overlay: &D) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// What a realized event says about a scenario's prediction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The prediction held. `miss` is how far the realized value landed from
+    /// it; among confirmed alternatives of one branch the smallest miss wins.
+    Confirmed { miss: u64 },
+    Contradicted,
+}
+
+/// What one manager call changed, with overlays reported whole; domain
+/// wrappers flatten them into their row types.
+#[derive(Debug)]
+pub struct ExpansionOutcome<D> {
+    pub created: Vec<ScenarioMeta>,
+    /// Retired scenarios with why each left the beam.
+    pub retired: Vec<(ScenarioMeta, RetirementReason)>,
+    pub overlays_added: Vec<D>,
+    pub overlays_removed: Vec<D>,
+    /// Scenarios retired because a [`SafetyValve`] cap bound; also in `retired`.
+    pub shed: usize,
+    /// Active scenarios per depth after this call.
+    pub depth_occupancy: BTreeMap<Depth, usize>,
+    /// Proposals turned down by a validator; never created.
+    pub rejected: Vec<Rejection>,
+    /// Parents left unextended because sources lag; backfilled once caught up.
+    pub deferred: usize,
+    /// Scenarios reweighted in place, such as decaying priors, with their
+    /// previous weight; the meta carries the new one.
+    pub reweighted: Vec<(ScenarioMeta, Prob)>,
+}
+
+impl<D> Default for ExpansionOutcome<D> {
+    fn default() -> Self {
+        Self {
+            created: Vec::new(),
+            retired: Vec::new(),
+            overlays_added: Vec::new(),
+            overlays_removed: Vec::new(),
+            shed: 0,
+            depth_occupancy: BTreeMap::new(),
+            rejected: Vec::new(),
+            deferred: 0,
+            reweighted: Vec::new(),
+        }
+    }
+}
+
+/// A beam of scenarios branched on events `E`, each owning an overlay `D`.
+/// Domain logic comes in through the [`DeltaBuilder`] passed to each call.
+pub struct ScenarioManager<E, D> {
+    cfg: BeamConfig,
+    next_id: ScenarioId,
+    active: Vec<ScenarioMeta>,
+    /// Seeded from external plans and priors; outside the beam until withdrawn.
+    external: Vec<ScenarioMeta>,
+    /// Seeded priors by scenario id, with the epoch each was seeded.
+    priors: BTreeMap<ScenarioId, (PriorSchedule, EpochTime)>,
+    feature_store: Option<Arc<dyn FeatureStore>>,
+    valve: Option<Arc<SafetyValve>>,
+    valve_usage: ValveUsage,
+    validators: Validators<D>,
+    overlays: HashMap<ScenarioId, D>,
+    /// The event each branch was created on, until a realized event settles it.
+    triggers: HashMap<ScenarioId, E>,
+    history: RetiredHistory<D>,
+    lineage: LineageIndex,
+    /// Exclusion group per (parent, event kind, event id); parent 0 is the
+    /// root. Kinds are owned so restored groups key the same way.
+    exclusion_groups: HashMap<(ScenarioId, String, u64), u64>,
+    next_group: u64,
+    /// Partitions by recency, while `cfg.max_keys` bounds them.
+    keys: KeyLru,
+    /// Whether the builder keys branches by partition; operations that
+    /// address a key need it.
+    partitioned: bool,
+    /// Partition keys an operator froze; their events do not branch.
+    frozen: BTreeSet<u64>,
+    audit: Vec<BulkAudit>,
+    /// Epoch each beam scenario was created or last confirmed.
+    reinforced: HashMap<ScenarioId, EpochTime>,
+    /// Epoch of the last [`Self::advance_epoch`].
+    advanced: Option<EpochTime>,
+    /// Beam scenarios kept regardless of weight, depth and beam width.
+    pinned: BTreeSet<ScenarioId>,
+    throttle: Option<LagThrottle<E>>,
+    pruning: Box<dyn PruningStrategy>,
+    /// While backfilling, the only parents an expansion may extend.
+    backfill: Option<Vec<ScenarioId>>,
+}
+
+impl<E: Clone, D: Clone> ScenarioManager<E, D> {
+    pub fn new(cfg: BeamConfig) -> Self {
+        Self {
+            cfg,
+            next_id: 1,
+            active: Vec::new(),
+            external: Vec::new(),
+            priors: BTreeMap::new(),
+            feature_store: None,
+            valve: None,
+            valve_usage: ValveUsage::default(),
+            validators: Validators::default(),
+            overlays: HashMap::new(),
+            triggers: HashMap::new(),
+            history: RetiredHistory::default(),
+            lineage: LineageIndex::default(),
+            exclusion_groups: HashMap::new(),
+            next_group: 1,
+            keys: KeyLru::default(),
+            partitioned: false,
+            frozen: BTreeSet::new(),
+            audit: Vec::new(),
+            reinforced: HashMap::new(),
+            advanced: None,
+            pinned: BTreeSet::new(),
+            throttle: None,
+            pruning: Box::new(BeamPruning),
+            backfill: None,
+        }
+    }
+
+    /// Remember the last `capacity` retired scenarios for [`Self::retired`].
+    pub fn with_retired_history(mut self, capacity: usize) -> Self {
+        self.history = RetiredHistory::new(capacity);
+        self
+    }
+
+    /// Enforce process-wide caps shared with other managers after each beam step.
+    pub fn with_safety_valve(mut self, valve: Arc<SafetyValve>) -> Self {
+        self.valve = Some(valve);
+        self
+    }
+
+    /// Check each proposed scenario before it is committed; a rejected
+    /// proposal adds no overlay and never enters the beam.
+    pub fn with_validator<F>(mut self, name: impl Into<String>, validator: F) -> Self
+    where
+        F: Fn(&Proposal<'_, D>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.validators.push(name, Arc::new(validator));
+        self
+    }
+
+    /// Stop expanding below `cfg.max_depth` while sources lag, as reported
+    /// through [`Self::observe_lag`].
+    pub fn with_lag_throttle(mut self, cfg: LagThrottleConfig) -> Self {
+        self.throttle = Some(LagThrottle::new(cfg));
+        self
+    }
+
+    pub fn with_feature_store(mut self, store: Arc<dyn FeatureStore>) -> Self {
+        self.feature_store = Some(store);
+        self
+    }
+
+    /// Accept bulk operations that address a partition key. Set it when the
+    /// [`DeltaBuilder`] fills in [`Branch::partition`]; otherwise no scenario
+    /// has a key to match or freeze.
+    pub fn with_partitioning(mut self) -> Self {
+        self.partitioned = true;
+        self
+    }
+
+    /// Decide which scenarios each expansion keeps with `strategy` instead
+    /// of [`BeamPruning`].
+    pub fn with_pruning(mut self, strategy: Box<dyn PruningStrategy>) -> Self {
+        self.pruning = strategy;
+        self
+    }
+
+    /// Retire expired branches, propose one child per eligible parent with
+    /// the overlay `deltas` builds for `event`, commit the proposals the
+    /// validators accept, prune back to the beam and renormalize what
+    /// survives. Parents a lag throttle holds back are deferred along with
+    /// `event`.
+    pub fn expand<B>(&mut self, deltas: &B, epoch: EpochTime, event: &E) -> ExpansionOutcome<D>
+    where
+        B: DeltaBuilder<E, D> + ?Sized,
+    {
+        let Some(branch) = deltas.branch(epoch, self.feature_store.as_deref(), event) else {
+            return ExpansionOutcome::default();
+        };
+        let Branch { partition, base_value, branch_prob, exclusion } = branch;
+        if partition.is_some_and(|key| self.frozen.contains(&key)) {
+            return ExpansionOutcome::default();
+        }
+        let mut outcome = ExpansionOutcome::default();
+
+        let mut survivors = Vec::new();
+        let mut retired = Vec::new();
+        let weight_mode = self.cfg.weight_mode;
+        let min_prob = weight_mode.weight(self.cfg.min_prob);
+
+        for meta in self.active.drain(..) {
+            if self.pinned.contains(&meta.id) {
+                survivors.push(meta);
+            } else if meta.depth >= self.cfg.max_depth {
+                retired.push((meta, RetirementReason::MaxDepth));
+            } else if meta.weight.0 < min_prob {
+                retired.push((meta, RetirementReason::BelowMinProb));
+            } else {
+                survivors.push(meta);
+            }
+        }
+
+        let mut candidates = survivors.clone();
+
+        let parents_iter = std::iter::once(ScenarioMeta {
+            id: 0,
+            parent: None,
+            depth: 0,
+            weight: Prob(1.0),
+            partition: None,
+            impact: 0,
+            tail: false,
+            exclusion_group: None,
+            external: None,
+            label: None,
+            tags: Vec::new(),
+        })
+        .chain(self.external.clone())
+        .chain(survivors);
+
+        let depth_limit = self.throttle.as_ref().and_then(LagThrottle::depth_limit);
+        let mut skipped = Vec::new();
+        let mut eligible = Vec::new();
+        for parent in parents_iter {
+            if parent.depth >= self.cfg.max_depth || (parent.id != 0 && parent.partition != partition) {
+                continue;
+            }
+            if self.backfill.as_ref().is_some_and(|parents| !parents.contains(&parent.id)) {
+                continue;
+            }
+            if depth_limit.is_some_and(|limit| parent.depth >= limit) {
+                skipped.push(parent.id);
+                continue;
+            }
+            let parent_weight = if parent.id == 0 { 1.0 } else { parent.weight.0 };
+            let child_weight = weight_mode.child_weight(parent_weight, branch_prob);
+            if child_weight < min_prob {
+                continue;
+            }
+            eligible.push(parent);
+        }
+
+        let max_children = self.cfg.max_children_per_event.unwrap_or(usize::MAX);
+        let mut proposals = Vec::new();
+        for parent in budget_parents(eligible, self.cfg.max_children_per_event, weight_mode) {
+            let parent_weight = if parent.id == 0 { 1.0 } else { parent.weight.0 };
+            let ctx = PredictionContext {
+                epoch,
+                lineage: LineageSummary {
+                    parent: parent.id,
+                    depth: parent.depth,
+                    weight: Prob(parent_weight),
+                },
+                base_value,
+                features: self.feature_store.as_deref(),
+            };
+            let first_id = self.next_id;
+            let outcomes = deltas.build_outcomes(&ctx, event, first_id);
+            let count = u64::try_from(outcomes.len()).unwrap_or(u64::MAX);
+            self.next_id = self.next_id.wrapping_add(count);
+
+            // One branch's outcomes are alternatives: at most one plays out
+            let total: f64 = outcomes.iter().map(|(share, _)| share.max(0.0)).sum();
+            let scale = if total > 1.0 { total.recip() } else { 1.0 };
+            let group = match exclusion {
+                Some(key) => Some(self.exclusion_group(parent.id, key)),
+                None if outcomes.len() > 1 => {
+                    let key = ExclusionKey { kind: "outcome", id: first_id };
+                    Some(self.exclusion_group(parent.id, key))
+                }
+                None => None,
+            };
+            // Alternatives are admitted as a set: a partial set would leave
+            // the survivors' shares summing to less than the branch's.
+            let mut siblings = Vec::new();
+            for (index, (share, overlay)) in (0u64..).zip(outcomes) {
+                let child_id = first_id.wrapping_add(index);
+                let share = share.max(0.0) * scale;
+                let child_weight = weight_mode.child_weight(parent_weight, branch_prob * share);
+                if share <= 0.0 || child_weight < min_prob {
+                    continue;
+                }
+                let meta = ScenarioMeta {
+                    id: child_id,
+                    parent: if parent.id == 0 { None } else { Some(parent.id) },
+                    depth: parent.depth + 1,
+                    weight: Prob(child_weight),
+                    partition,
+                    impact: parent.impact.saturating_add(deltas.impact(&overlay)),
+                    tail: false,
+                    exclusion_group: group,
+                    external: parent.external.clone(),
+                    label: deltas.label(event, &overlay),
+                    tags: deltas.tags(event, &overlay),
+                };
+                siblings.push((meta, overlay));
+            }
+            if proposals.len().saturating_add(siblings.len()) > max_children {
+                continue;
+            }
+            proposals.extend(siblings);
+        }
+
+        for (meta, overlay) in proposals {
+            let lineage = meta.parent.map_or_else(Vec::new, |parent| {
+                self.lineage_overlays(parent).into_iter().map(|(_, rows)| rows).collect()
+            });
+            let proposal =
+                Proposal { epoch, base_value, meta: &meta, overlay: &overlay, lineage };
+            if let Err(rejection) = self.validators.check(&proposal) {
+                outcome.rejected.push(rejection);
+                continue;
+            }
+            outcome.overlays_added.push(overlay.clone());
+            self.overlays.insert(meta.id, overlay);
+            self.triggers.insert(meta.id, event.clone());
+            self.lineage.insert(&meta);
+            self.reinforced.insert(meta.id, epoch);
+
+            outcome.created.push(meta.clone());
+            candidates.push(meta);
+        }
+        self.admit(deltas, epoch, candidates, retired, partition, &mut outcome);
+
+        if let Some(throttle) = self.throttle.as_mut().filter(|_| !skipped.is_empty()) {
+            outcome.deferred = skipped.len();
+            throttle.defer(Deferred { parents: skipped, trigger: event.clone() });
+        }
+        outcome
+    }
+
+    /// Select what stays in the beam from `candidates` and the seeded
+    /// scenarios, within the beam, key and safety caps, then retire the rest
+    /// with `retired` and renormalize. Expansion and seeding both admit
+    /// scenarios through here.
+    fn admit<B>(
+        &mut self,
+        deltas: &B,
+        epoch: EpochTime,
+        mut candidates: Vec<ScenarioMeta>,
+        mut retired: Vec<(ScenarioMeta, RetirementReason)>,
+        partition: Option<u64>,
+        outcome: &mut ExpansionOutcome<D>,
+    ) where
+        B: DeltaBuilder<E, D> + ?Sized,
+    {
+        let min_prob = self.cfg.weight_mode.weight(self.cfg.min_prob);
+        let seeded: HashSet<ScenarioId> = self.external.iter().map(|meta| meta.id).collect();
+        candidates.append(&mut self.external);
+        // Pinned scenarios take no part in selection and hold no beam slot
+        let (held, candidates): (Vec<_>, Vec<_>) =
+            candidates.into_iter().partition(|meta| self.pinned.contains(&meta.id));
+        let (below_floor, candidates): (Vec<_>, Vec<_>) =
+            candidates.into_iter().partition(|meta| meta.weight.0 < min_prob);
+
+        let (mut retained, evicted) = self.pruning.select(&self.cfg, candidates, below_floor);
+        retired.extend(evicted.into_iter().map(|meta| {
+            let reason = if meta.weight.0 < min_prob {
+                RetirementReason::BelowMinProb
+            } else {
+                RetirementReason::Pruned
+            };
+            (meta, reason)
+        }));
+        if let Some(max_keys) = self.cfg.max_keys {
+            if let Some(key) = partition {
+                self.keys.touch(key);
+            }
+            let live: HashSet<u64> = retained.iter().filter_map(|meta| meta.partition).collect();
+            self.keys.sync(&live);
+            let evicted = self.keys.evict(max_keys.max(1));
+            if !evicted.is_empty() {
+                let (dropped, kept): (Vec<_>, Vec<_>) = retained
+                    .into_iter()
+                    .partition(|meta| meta.partition.is_some_and(|key| evicted.contains(&key)));
+                retained = kept;
+                retired
+                    .extend(dropped.into_iter().map(|meta| (meta, RetirementReason::KeyEvicted)));
+            }
+        }
+        retained.extend(held);
+        if let Some(valve) = &self.valve {
+            let overlays = &self.overlays;
+            let rows_of = |meta: &ScenarioMeta| {
+                overlays.get(&meta.id).map_or(0, |overlay| deltas.rows(overlay))
+            };
+            let (shed, usage) = valve.enforce(self.valve_usage, &mut retained, rows_of);
+            self.valve_usage = usage;
+            outcome.shed = shed.len();
+            retired.extend(shed.into_iter().map(|meta| (meta, RetirementReason::Shed)));
+        }
+
+        for (meta, reason) in retired {
+            self.pinned.remove(&meta.id);
+            self.lineage.retire(meta.id);
+            self.triggers.remove(&meta.id);
+            let overlays = self.overlays.remove(&meta.id);
+            outcome.overlays_removed.extend(overlays.iter().cloned());
+            self.history.record(RetiredScenario {
+                meta: meta.clone(),
+                retired_at: epoch,
+                reason,
+                overlays,
+            });
+            outcome.retired.push((meta, reason));
+        }
+
+        let (external, active) = retained.into_iter().partition(|meta| seeded.contains(&meta.id));
+        self.external = external;
+        self.active = active;
+        let culled: f64 = outcome
+            .retired
+            .iter()
+            .filter(|(_, reason)| {
+                !matches!(reason, RetirementReason::MaxDepth | RetirementReason::KeyEvicted)
+            })
+            .map(|(meta, _)| meta.weight.0)
+            .sum();
+        self.renormalize(culled, outcome);
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        if !outcome.retired.is_empty() {
+            self.prune_exclusion_groups();
+        }
+    }
+
+    /// Seed a scenario at the root with the overlay `overlay` builds for its
+    /// id, weighted by `probability` and tagged `tag`. A seed is validated and
+    /// admitted like an expansion's child, so it counts against the child,
+    /// beam and safety caps and can be pruned or shed. Branches extend it like
+    /// any other scenario, and inherit its tag, until
+    /// [`Self::retire_external`].
+    pub fn seed_external<B, F>(
+        &mut self,
+        deltas: &B,
+        epoch: EpochTime,
+        tag: String,
+        probability: f64,
+        overlay: F,
+    ) -> ExpansionOutcome<D>
+    where
+        B: DeltaBuilder<E, D> + ?Sized,
+        F: FnOnce(ScenarioId) -> D,
+    {
+        self.seed(deltas, epoch, tag, probability, overlay, false)
+    }
+
+    /// Seed as [`Self::seed_external`] describes, pinning the seed before it
+    /// is admitted if `pin` is set.
+    fn seed<B, F>(
+        &mut self,
+        deltas: &B,
+        epoch: EpochTime,
+        tag: String,
+        probability: f64,
+        overlay: F,
+        pin: bool,
+    ) -> ExpansionOutcome<D>
+    where
+        B: DeltaBuilder<E, D> + ?Sized,
+        F: FnOnce(ScenarioId) -> D,
+    {
+        let mut outcome = ExpansionOutcome::default();
+        if self.cfg.max_children_per_event == Some(0) {
+            return outcome;
+        }
+        let scenario_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let overlay = overlay(scenario_id);
+        // Seeded scenarios are labelled with their name and tagged with their
+        // source and full `source:name` tag, which bulk filters match on
+        let (source, name) = tag.split_once(':').unwrap_or((tag.as_str(), ""));
+        let label = (!name.is_empty()).then(|| name.to_string());
+        let mut tags = vec![source.to_string()];
+        if tag != source {
+            tags.push(tag.clone());
+        }
+        let meta = ScenarioMeta {
+            id: scenario_id,
+            parent: None,
+            depth: 1,
+            weight: Prob(self.cfg.weight_mode.weight(probability.clamp(0.0, 1.0))),
+            partition: None,
+            impact: deltas.impact(&overlay),
+            tail: false,
+            exclusion_group: None,
+            external: Some(tag.clone()),
+            label,
+            tags,
+        };
+        let proposal = Proposal {
+            epoch,
+            base_value: None,
+            meta: &meta,
+            overlay: &overlay,
+            lineage: Vec::new(),
+        };
+        if let Err(rejection) = self.validators.check(&proposal) {
+            outcome.rejected.push(rejection);
+            return outcome;
+        }
+        outcome.overlays_added.push(overlay.clone());
+        self.overlays.insert(scenario_id, overlay);
+        self.lineage.insert(&meta);
+        outcome.created.push(meta.clone());
+        if pin {
+            self.pinned.insert(scenario_id);
+        }
+        self.external.push(meta);
+        let active = std::mem::take(&mut self.active);
+        self.admit(deltas, epoch, active, Vec::new(), None, &mut outcome);
+        outcome
+    }
+
+    /// Withdraw a seeded scenario. Branches already grown from it stay in
+    /// the beam.
+    pub fn retire_external(
+        &mut self,
+        epoch: EpochTime,
+        scenario_id: ScenarioId,
+    ) -> ExpansionOutcome<D> {
+        let mut outcome = ExpansionOutcome::default();
+        if self.external.iter().any(|meta| meta.id == scenario_id) {
+            self.retire_live(epoch, scenario_id, RetirementReason::Withdrawn, &mut outcome);
+        }
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        outcome
+    }
+
+    /// Inject a user's hypothetical as a pinned scenario at the root, seeded
+    /// as [`Self::seed_external`] does under the tag `what_if:<label>`. The
+    /// pin keeps the beam from pruning it; the valve caps still apply.
+    pub fn inject_scenario<B, F>(
+        &mut self,
+        deltas: &B,
+        epoch: EpochTime,
+        probability: f64,
+        label: &str,
+        overlay: F,
+    ) -> ExpansionOutcome<D>
+    where
+        B: DeltaBuilder<E, D> + ?Sized,
+        F: FnOnce(ScenarioId) -> D,
+    {
+        self.seed(deltas, epoch, what_if_tag(label), probability, overlay, true)
+    }
+
+    /// Seed a prior at the root, as [`Self::seed_external`] does. Its weight
+    /// then follows `schedule` through [`Self::decay_priors`].
+    pub fn seed_prior<B, F>(
+        &mut self,
+        deltas: &B,
+        epoch: EpochTime,
+        tag: String,
+        schedule: PriorSchedule,
+        overlay: F,
+    ) -> ExpansionOutcome<D>
+    where
+        B: DeltaBuilder<E, D> + ?Sized,
+        F: FnOnce(ScenarioId) -> D,
+    {
+        let outcome = self.seed_external(deltas, epoch, tag, schedule.probability, overlay);
+        for meta in &outcome.created {
+            if !outcome.retired.iter().any(|(retired, _)| retired.id == meta.id) {
+                self.priors.insert(meta.id, (schedule, epoch));
+            }
+        }
+        outcome
+    }
+
+    /// Reweight seeded priors to their decayed probability at `epoch`, and
+    /// withdraw those that have decayed to their floor. Branches already
+    /// grown from a prior keep the weight they were created with.
+    pub fn decay_priors(&mut self, epoch: EpochTime) -> ExpansionOutcome<D> {
+        let mut outcome = ExpansionOutcome::default();
+        let mut expired = Vec::new();
+        for (id, (schedule, seeded)) in &self.priors {
+            let age = epoch.since(*seeded);
+            if schedule.is_expired(age) {
+                expired.push(*id);
+                continue;
+            }
+            let weight = Prob(self.cfg.weight_mode.weight(schedule.probability_at(age)));
+            let Some(meta) = self.external.iter_mut().find(|meta| meta.id == *id) else {
+                continue;
+            };
+            if meta.weight != weight {
+                let previous = std::mem::replace(&mut meta.weight, weight);
+                self.lineage.reweight(meta.id, weight.0);
+                outcome.reweighted.push((meta.clone(), previous));
+            }
+        }
+        for id in expired {
+            self.retire_live(epoch, id, RetirementReason::Decayed, &mut outcome);
+        }
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        outcome
+    }
+
+    /// Settle branches against a realized event. `judge` sees each live
+    /// branch's triggering event and overlay. A contradicted branch retires
+    /// with its live descendants, and so do the alternatives excluded by a
+    /// confirmed one. When several alternatives of one exclusion group are
+    /// confirmed, only the one with the smallest miss is, and the rest are
+    /// excluded by it. A confirmed branch is promoted to its parent's weight,
+    /// as if its branch were certain. Each branch is settled once.
+    pub fn reconcile<F>(&mut self, epoch: EpochTime, mut judge: F) -> ExpansionOutcome<D>
+    where
+        F: FnMut(&E, &D) -> Option<Verdict>,
+    {
+        let mut outcome = ExpansionOutcome::default();
+        let mut confirmed: Vec<(ScenarioMeta, u64)> = Vec::new();
+        let mut contradicted = Vec::new();
+        for meta in self.active.iter().chain(&self.external) {
+            let (Some(trigger), Some(overlay)) =
+                (self.triggers.get(&meta.id), self.overlays.get(&meta.id))
+            else {
+                continue;
+            };
+            match judge(trigger, overlay) {
+                Some(Verdict::Confirmed { miss }) => confirmed.push((meta.clone(), miss)),
+                Some(Verdict::Contradicted) => contradicted.push(meta.id),
+                None => {}
+            }
+        }
+        // One winner per exclusion group: the closest, then the likeliest
+        let mut winners: HashMap<(Option<ScenarioId>, u64), (u64, f64, ScenarioId)> =
+            HashMap::new();
+        for (meta, miss) in &confirmed {
+            let Some(group) = meta.exclusion_group else {
+                continue;
+            };
+            let entry = (*miss, meta.weight.0, meta.id);
+            winners
+                .entry((meta.parent, group))
+                .and_modify(|best| {
+                    let closer = entry.0 < best.0 || (entry.0 == best.0 && entry.1 > best.1);
+                    if closer {
+                        *best = entry;
+                    }
+                })
+                .or_insert(entry);
+        }
+        let confirmed: Vec<ScenarioMeta> = confirmed
+            .into_iter()
+            .map(|(meta, _)| meta)
+            .filter(|meta| {
+                meta.exclusion_group.map_or(true, |group| {
+                    winners.get(&(meta.parent, group)).is_some_and(|best| best.2 == meta.id)
+                })
+            })
+            .collect();
+        for meta in &confirmed {
+            self.triggers.remove(&meta.id);
+            if self.reinforced.contains_key(&meta.id) {
+                self.reinforced.insert(meta.id, epoch);
+            }
+            let Some(group) = meta.exclusion_group else {
+                continue;
+            };
+            contradicted.extend(
+                self.active
+                    .iter()
+                    .filter(|other| {
+                        other.id != meta.id
+                            && other.parent == meta.parent
+                            && other.exclusion_group == Some(group)
+                    })
+                    .map(|other| other.id),
+            );
+        }
+        let settled: HashSet<ScenarioId> = confirmed.iter().map(|meta| meta.id).collect();
+        contradicted.retain(|id| !settled.contains(id));
+
+        for id in contradicted {
+            let subtree: Vec<ScenarioId> =
+                std::iter::once(id).chain(self.lineage.descendants(id)).collect();
+            for id in subtree {
+                self.retire_live(epoch, id, RetirementReason::Contradicted, &mut outcome);
+            }
+        }
+        for confirmed in confirmed {
+            let weight = match confirmed.parent {
+                Some(parent) => self.lineage.weight(parent).map(Prob),
+                None => Some(Prob(self.cfg.weight_mode.weight(1.0))),
+            };
+            let Some(meta) = self
+                .active
+                .iter_mut()
+                .chain(self.external.iter_mut())
+                .find(|meta| meta.id == confirmed.id)
+            else {
+                continue;
+            };
+            let Some(weight) = weight.filter(|weight| *weight != meta.weight) else {
+                continue;
+            };
+            let previous = std::mem::replace(&mut meta.weight, weight);
+            self.lineage.reweight(meta.id, weight.0);
+            outcome.reweighted.push((meta.clone(), previous));
+        }
+
+        if !outcome.retired.is_empty() {
+            self.prune_exclusion_groups();
+        }
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        outcome
+    }
+
+    /// Bayesian update of every live scenario's weight from observed
+    /// evidence. `likelihood` gives how likely the evidence is under each
+    /// scenario. Posteriors are `weight * likelihood`, renormalized so the
+    /// live set keeps its total weight, and changed weights are reported in
+    /// `reweighted`. Evidence impossible under every scenario changes nothing.
+    pub fn observe_evidence<F>(&mut self, mut likelihood: F) -> ExpansionOutcome<D>
+    where
+        F: FnMut(&ScenarioMeta, &D) -> f64,
+    {
+        let mut outcome = ExpansionOutcome::default();
+        let overlays = &self.overlays;
+        let scaled: Vec<(ScenarioId, f64)> = self
+            .active
+            .iter()
+            .chain(&self.external)
+            .map(|meta| {
+                let fit = overlays.get(&meta.id).map_or(1.0, |overlay| likelihood(meta, overlay));
+                (meta.id, meta.weight.0 * fit.max(0.0))
+            })
+            .collect();
+        let prior_mass: f64 = self.active.iter().chain(&self.external).map(|m| m.weight.0).sum();
+        let posterior_mass: f64 = scaled.iter().map(|(_, weight)| weight).sum();
+        if posterior_mass <= 0.0 || !posterior_mass.is_finite() {
+            outcome.depth_occupancy = depth_occupancy(&self.active);
+            return outcome;
+        }
+        let scale = prior_mass / posterior_mass;
+        let weight_mode = self.cfg.weight_mode;
+        let posteriors: HashMap<ScenarioId, f64> = scaled.into_iter().collect();
+        for meta in self.active.iter_mut().chain(self.external.iter_mut()) {
+            let posterior = posteriors.get(&meta.id).map_or(meta.weight.0, |w| w * scale);
+            let weight = Prob(weight_mode.weight(posterior.clamp(0.0, 1.0)));
+            if weight != meta.weight {
+                let previous = std::mem::replace(&mut meta.weight, weight);
+                self.lineage.reweight(meta.id, weight.0);
+                outcome.reweighted.push((meta.clone(), previous));
+            }
+        }
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        outcome
+    }
+
+    /// Report how far sources lag at `epoch`. Above the throttle's threshold
+    /// only shallow scenarios are created; once caught up, the skipped
+    /// expansions are replayed on their surviving parents and their outcomes
+    /// returned. No-op without a lag throttle.
+    pub fn observe_lag<B>(
+        &mut self,
+        deltas: &B,
+        epoch: EpochTime,
+        lag_ms: u64,
+    ) -> Vec<ExpansionOutcome<D>>
+    where
+        B: DeltaBuilder<E, D> + ?Sized,
+    {
+        let Some(throttle) = &mut self.throttle else {
+            return Vec::new();
+        };
+        let mut outcomes = Vec::new();
+        for Deferred { mut parents, trigger } in throttle.observe_lag(lag_ms) {
+            parents.retain(|id| {
+                self.active.iter().chain(&self.external).any(|meta| meta.id == *id)
+            });
+            if parents.is_empty() {
+                continue;
+            }
+            self.backfill = Some(parents);
+            outcomes.push(self.expand(deltas, epoch, &trigger));
+        }
+        self.backfill = None;
+        outcomes
+    }
+
+    /// Whether expansion is currently limited by source lag.
+    pub fn is_throttled(&self) -> bool {
+        self.throttle.as_ref().is_some_and(LagThrottle::is_lagging)
+    }
+
+    /// Expansions waiting to be backfilled.
+    pub fn deferred_len(&self) -> usize {
+        self.throttle.as_ref().map_or(0, LagThrottle::deferred_len)
+    }
+
+    /// Latest epoch passed to [`Self::advance_epoch`], carried across
+    /// checkpoints; `None` before the first call.
+    pub fn advanced(&self) -> Option<EpochTime> {
+        self.advanced
+    }
+
+    /// Age the beam to `epoch`: scale each scenario's weight by
+    /// `cfg.epoch_decay` for every epoch since the last call, and retire
+    /// scenarios created or last confirmed `cfg.ttl_epochs` or more epochs
+    /// ago, or decayed under `min_prob`. Call once per epoch boundary; seeded
+    /// scenarios follow their own schedules and are left alone.
+    pub fn advance_epoch(&mut self, epoch: EpochTime) -> ExpansionOutcome<D> {
+        let mut outcome = ExpansionOutcome::default();
+        let elapsed = self.advanced.map_or(0, |last| epoch.since(last));
+        self.advanced = Some(self.advanced.map_or(epoch, |last| last.max(epoch)));
+        let weight_mode = self.cfg.weight_mode;
+        let min_prob = weight_mode.weight(self.cfg.min_prob);
+        let decay = self
+            .cfg
+            .epoch_decay
+            .filter(|_| elapsed > 0)
+            .map(|factor| factor.clamp(0.0, 1.0).powf(elapsed as f64));
+
+        let live: HashSet<ScenarioId> = self.active.iter().map(|meta| meta.id).collect();
+        self.reinforced.retain(|id, _| live.contains(id));
+        let mut expired = Vec::new();
+        for meta in self.active.iter_mut() {
+            // Scenarios restored from older checkpoints start their TTL now
+            let reinforced = *self.reinforced.entry(meta.id).or_insert(epoch);
+            let pinned = self.pinned.contains(&meta.id);
+            if !pinned && self.cfg.ttl_epochs.is_some_and(|ttl| epoch.since(reinforced) >= ttl) {
+                expired.push((meta.id, RetirementReason::Expired));
+                continue;
+            }
+            let Some(decay) = decay else {
+                continue;
+            };
+            let weight = Prob(weight_mode.child_weight(meta.weight.0, decay));
+            if weight.0 < min_prob && !pinned {
+                expired.push((meta.id, RetirementReason::BelowMinProb));
+            } else if weight != meta.weight {
+                let previous = std::mem::replace(&mut meta.weight, weight);
+                self.lineage.reweight(meta.id, weight.0);
+                outcome.reweighted.push((meta.clone(), previous));
+            }
+        }
+        for (id, reason) in expired {
+            self.reinforced.remove(&id);
+            self.retire_live(epoch, id, reason, &mut outcome);
+        }
+
+        if !outcome.retired.is_empty() {
+            self.prune_exclusion_groups();
+        }
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        outcome
+    }
+
+    /// Apply an operator's [`BulkOp`] as of `epoch` and record it in
+    /// [`Self::bulk_audit`]. Call between epochs, as the control plane does.
+    pub fn apply_bulk(
+        &mut self,
+        epoch: EpochTime,
+        op: BulkOp,
+    ) -> anyhow::Result<ExpansionOutcome<D>> {
+        let keyed = match &op {
+            BulkOp::Retire(filter) => filter.key.is_some(),
+            BulkOp::Freeze { .. } | BulkOp::Thaw { .. } => true,
+            _ => false,
+        };
+        ensure!(
+            self.partitioned || !keyed,
+            "bulk operation addresses a partition key but the beam is not partitioned"
+        );
+        let mut outcome = ExpansionOutcome::default();
+        let mut affected = Vec::new();
+        match &op {
+            BulkOp::Retire(filter) => {
+                let matched: Vec<ScenarioId> = self
+                    .active
+                    .iter()
+                    .chain(&self.external)
+                    .filter(|meta| filter.matches(meta))
+                    .map(|meta| meta.id)
+                    .collect();
+                for id in matched {
+                    let subtree: Vec<ScenarioId> =
+                        std::iter::once(id).chain(self.lineage.descendants(id)).collect();
+                    for id in subtree {
+                        self.retire_live(epoch, id, RetirementReason::Operator, &mut outcome);
+                    }
+                }
+                affected.extend(outcome.retired.iter().map(|(meta, _)| meta.id));
+                if !outcome.retired.is_empty() {
+                    self.prune_exclusion_groups();
+                }
+            }
+            BulkOp::Reweight { scenarios, factor } => {
+                ensure!(
+                    factor.is_finite() && *factor >= 0.0,
+                    "reweight factor must be finite and non-negative, got {factor}"
+                );
+                let weight_mode = self.cfg.weight_mode;
+                for meta in self.active.iter_mut().chain(self.external.iter_mut()) {
+                    if !scenarios.contains(&meta.id) {
+                        continue;
+                    }
+                    affected.push(meta.id);
+                    let weight = Prob(weight_mode.weight((meta.weight.0 * factor).min(1.0)));
+                    if weight != meta.weight {
+                        let previous = std::mem::replace(&mut meta.weight, weight);
+                        self.lineage.reweight(meta.id, weight.0);
+                        outcome.reweighted.push((meta.clone(), previous));
+                    }
+                }
+            }
+            BulkOp::Freeze { key } => {
+                self.frozen.insert(*key);
+            }
+            BulkOp::Thaw { key } => {
+                self.frozen.remove(key);
+            }
+            BulkOp::Pin { scenarios } => {
+                affected.extend(scenarios.iter().copied().filter(|id| self.pin(*id)));
+            }
+            BulkOp::Unpin { scenarios } => {
+                affected.extend(scenarios.iter().copied().filter(|id| self.unpin(*id)));
+            }
+        }
+        self.audit.push(BulkAudit { epoch, op, affected });
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        Ok(outcome)
+    }
+
+    /// Every bulk operation applied since this manager was built, oldest
+    /// first.
+    pub fn bulk_audit(&self) -> &[BulkAudit] {
+        &self.audit
+    }
+
+    /// Partition keys whose events currently do not branch.
+    pub fn frozen_keys(&self) -> &BTreeSet<u64> {
+        &self.frozen
+    }
+
+    /// Expand and prune with `cfg` from the next call on; scenarios already
+    /// in the beam are kept until that call prunes them.
+    pub fn set_config(&mut self, cfg: BeamConfig) {
+        self.cfg = cfg;
+    }
+
+    /// The store predictors read features from, if one was set.
+    pub fn feature_store(&self) -> Option<&dyn FeatureStore> {
+        self.feature_store.as_deref()
+    }
+
+    /// Checkpoint of the scenario tree; see [`ScenarioManagerState`].
+    pub fn snapshot(&self) -> ScenarioManagerState<E, D> {
+        ScenarioManagerState {
+            next_id: self.next_id,
+            active: self.active.clone(),
+            external: self.external.clone(),
+            priors: self.priors.clone(),
+            overlays: self.overlays.clone(),
+            triggers: self.triggers.clone(),
+            lineage: self.lineage.clone(),
+            next_group: self.next_group,
+            exclusion_groups: self
+                .exclusion_groups
+                .iter()
+                .map(|((parent, kind, id), group)| ExclusionGroupState {
+                    parent: *parent,
+                    kind: kind.clone(),
+                    id: *id,
+                    group: *group,
+                })
+                .collect(),
+            frozen: self.frozen.clone(),
+            reinforced: self.reinforced.clone(),
+            pinned: self.pinned.clone(),
+            advanced: self.advanced,
+        }
+    }
+
+    /// A manager resuming the tree in `state` under `cfg`. Validators, the
+    /// safety valve and other builder settings are not checkpointed; apply
+    /// them again with the `with_*` methods.
+    pub fn restore(state: ScenarioManagerState<E, D>, cfg: BeamConfig) -> Self {
+        let ScenarioManagerState {
+            next_id,
+            active,
+            external,
+            priors,
+            overlays,
+            triggers,
+            lineage,
+            next_group,
+            exclusion_groups,
+            frozen,
+            reinforced,
+            pinned,
+            advanced,
+        } = state;
+        // Seeds checkpointed before they carried their full tag get it back
+        let mut external = external;
+        for meta in &mut external {
+            if let Some(tag) = meta.external.clone().filter(|tag| !meta.tags.contains(tag)) {
+                meta.tags.push(tag);
+            }
+        }
+        let exclusion_groups = exclusion_groups
+            .into_iter()
+            .map(|group| ((group.parent, group.kind, group.id), group.group))
+            .collect();
+        Self {
+            next_id,
+            active,
+            external,
+            priors,
+            overlays,
+            triggers,
+            lineage,
+            next_group,
+            exclusion_groups,
+            frozen,
+            reinforced,
+            pinned,
+            advanced,
+            ..Self::new(cfg)
+        }
+    }
+
+    /// Every live scenario reported as created with its overlay, to fill
+    /// views that start empty after [`Self::restore`].
+    pub fn resumed(&self) -> ExpansionOutcome<D> {
+        let mut outcome = ExpansionOutcome::default();
+        for meta in self.active.iter().chain(&self.external) {
+            if let Some(overlay) = self.overlays.get(&meta.id) {
+                outcome.overlays_added.push(overlay.clone());
+            }
+            outcome.created.push(meta.clone());
+        }
+        outcome.depth_occupancy = depth_occupancy(&self.active);
+        outcome
+    }
+
+    /// Keep a live beam scenario whatever its weight, depth or rank, until
+    /// [`Self::unpin`], a contradicting event or an operator retires it.
+    /// Safety valve caps still apply. Returns whether `scenario_id` is live.
+    pub fn pin(&mut self, scenario_id: ScenarioId) -> bool {
+        let live = self.active.iter().chain(&self.external).any(|meta| meta.id == scenario_id);
+        if live {
+            self.pinned.insert(scenario_id);
+        }
+        live
+    }
+
+    /// Return a pinned scenario to the beam from the next expansion on.
+    pub fn unpin(&mut self, scenario_id: ScenarioId) -> bool {
+        self.pinned.remove(&scenario_id)
+    }
+
+    pub fn pinned(&self) -> &BTreeSet<ScenarioId> {
+        &self.pinned
+    }
+
+    /// Weights of beam and seeded scenarios, with whether each is pinned.
+    pub fn active_weights(&self) -> Vec<(ScenarioId, f64, bool)> {
+        self.active
+            .iter()
+            .chain(&self.external)
+            .map(|meta| (meta.id, meta.weight.0, self.pinned.contains(&meta.id)))
+            .collect()
+    }
+
+    pub fn active_len(&self) -> usize {
+        self.active.len() + self.external.len()
+    }
+
+    pub fn depth_occupancy(&self) -> BTreeMap<Depth, usize> {
+        depth_occupancy(&self.active)
+    }
+
+    /// Overlay rows a live scenario adds, or a recently retired one added.
+    pub fn overlay(&self, scenario_id: ScenarioId) -> Option<&D> {
+        self.overlays.get(&scenario_id).or_else(|| {
+            self.history.get(scenario_id).and_then(|retired| retired.overlays.as_ref())
+        })
+    }
+
+    /// Overlay rows of a scenario and of each ancestor still remembered, the
+    /// scenario first. A retired scenario no longer indexed contributes its
+    /// own rows only.
+    pub fn lineage_overlays(&self, scenario_id: ScenarioId) -> Vec<(ScenarioId, &D)> {
+        let mut path = self.path_to_root(scenario_id);
+        if path.is_empty() {
+            path.push(scenario_id);
+        }
+        path.into_iter().filter_map(|id| self.overlay(id).map(|rows| (id, rows))).collect()
+    }
+
+    /// What became of a recently retired scenario, if still remembered.
+    pub fn retired(&self, scenario_id: ScenarioId) -> Option<&RetiredScenario<D>> {
+        self.history.get(scenario_id)
+    }
+
+    pub fn retired_history(&self) -> &RetiredHistory<D> {
+        &self.history
+    }
+
+    /// Parents of a live scenario, nearest first; retired parents are kept
+    /// while they have live descendants.
+    pub fn ancestors(&self, scenario_id: ScenarioId) -> Vec<ScenarioId> {
+        self.lineage.ancestors(scenario_id)
+    }
+
+    /// Descendants of a scenario, breadth first.
+    pub fn descendants(&self, scenario_id: ScenarioId) -> Vec<ScenarioId> {
+        self.lineage.descendants(scenario_id)
+    }
+
+    /// The scenario followed by its ancestors.
+    pub fn path_to_root(&self, scenario_id: ScenarioId) -> Vec<ScenarioId> {
+        self.lineage.path_to_root(scenario_id)
+    }
+
+    /// Summed weight of the live scenarios under a scenario, itself included.
+    pub fn subtree_weight(&self, scenario_id: ScenarioId) -> f64 {
+        self.lineage.subtree_weight(scenario_id)
+    }
+
+    /// Retire one beam or seeded scenario outside beam selection; no-op if it
+    /// is not live.
+    fn retire_live(
+        &mut self,
+        epoch: EpochTime,
+        scenario_id: ScenarioId,
+        reason: RetirementReason,
+        outcome: &mut ExpansionOutcome<D>,
+    ) {
+        self.priors.remove(&scenario_id);
+        self.pinned.remove(&scenario_id);
+        let meta = if let Some(index) = self.active.iter().position(|m| m.id == scenario_id) {
+            self.active.remove(index)
+        } else if let Some(index) = self.external.iter().position(|m| m.id == scenario_id) {
+            self.external.remove(index)
+        } else {
+            return;
+        };
+        self.lineage.retire(meta.id);
+        self.triggers.remove(&meta.id);
+        let overlays = self.overlays.remove(&meta.id);
+        outcome.overlays_removed.extend(overlays.iter().cloned());
+        self.history.record(RetiredScenario {
+            meta: meta.clone(),
+            retired_at: epoch,
+            reason,
+            overlays,
+        });
+        outcome.retired.push((meta, reason));
+    }
+
+    /// Rescale the beam's weights per `cfg.renormalization`, given the
+    /// weight `culled` from it in this expansion, and report the changes in
+    /// `outcome.reweighted`.
+    fn renormalize(&mut self, culled: f64, outcome: &mut ExpansionOutcome<D>) {
+        let renormalization = self.cfg.renormalization;
+        let rescaled = move |meta: &&mut ScenarioMeta| match renormalization {
+            Renormalization::None => false,
+            Renormalization::SumToOne => true,
+            Renormalization::RedistributeToRoot => meta.parent.is_none(),
+        };
+        let total: f64 = self.active.iter_mut().filter(rescaled).map(|meta| meta.weight.0).sum();
+        let target = match renormalization {
+            Renormalization::None => return,
+            Renormalization::SumToOne => 1.0,
+            Renormalization::RedistributeToRoot => total + culled,
+        };
+        if total <= 0.0 || !total.is_finite() {
+            return;
+        }
+        let scale = target / total;
+        let weight_mode = self.cfg.weight_mode;
+        for meta in self.active.iter_mut().filter(rescaled) {
+            let weight = Prob(weight_mode.weight((meta.weight.0 * scale).clamp(0.0, 1.0)));
+            if weight != meta.weight {
+                let previous = std::mem::replace(&mut meta.weight, weight);
+                self.lineage.reweight(meta.id, weight.0);
+                outcome.reweighted.push((meta.clone(), previous));
+            }
+        }
+    }
+
+    /// Forget exclusion groups of parents no longer live.
+    fn prune_exclusion_groups(&mut self) {
+        let live: HashSet<ScenarioId> =
+            self.active.iter().chain(&self.external).map(|meta| meta.id).collect();
+        self.exclusion_groups.retain(|(parent, _, _), _| *parent == 0 || live.contains(parent));
+    }
+
+    fn exclusion_group(&mut self, parent: ScenarioId, key: ExclusionKey) -> u64 {
+        let next_group = &mut self.next_group;
+        let entry = self.exclusion_groups.entry((parent, key.kind.to_string(), key.id));
+        *entry.or_insert_with(|| {
+            let group = *next_group;
+            *next_group += 1;
+            group
+        })
+    }
+}
@@ -1,20 +1,59 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-use tw_core::manufacturing::OperationStart;
-use tw_core::Prob;
-use tw_predictors::MachineBacklogPredictor;
+use tw_core::manufacturing::{
+    MachineId, MachineStatus, MaterialId, OperationComplete, OperationStart, OperatorAssigned,
+};
+use tw_core::quantity::{Quantity, Units};
+use tw_core::{Depth, EpochTime, Prob};
+use tw_predictors::{
+    FeatureStore, LineageSummary, MachineBacklogPredictor, MachineFailurePredictor,
+    MachineHealth, MaterialStatus, PredictionContext, ShortageLikelihoodPredictor,
+    YieldLossPredictor,
+};
+use tw_predictors::warmup::WarmUpGate;
 
-use crate::ScenarioMeta;
+use crate::external::ExternalScenario;
+use crate::history::RetirementReason;
+use crate::manager::{
+    BeamConfig, Branch, DeltaBuilder, ExclusionKey, ExpansionOutcome, ScenarioManager, Verdict,
+};
+use crate::priors::PriorScenario;
+use crate::state::ScenarioManagerState;
+use crate::validate::Rejection;
+use crate::{DepthQuota, Renormalization, ScenarioMeta, WeightMode};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManufacturingScenarioDelta {
     pub scenario_id: u64,
     pub machine_id: u64,
-    pub delta_wip: i64,
+    pub delta_wip: Quantity<Units>,
+    /// Projected scrapped units, i.e. completions lost to yield.
+    #[serde(default)]
+    pub delta_scrapped: Quantity<Units>,
+    /// Variance of `delta_wip` in squared units, as the predictor gave it;
+    /// 0 for point estimates.
+    #[serde(default)]
+    pub variance_wip: i64,
+    /// The machine state the scenario predicts, such as `Down` for a
+    /// breakdown; `None` when it predicts no state change.
+    #[serde(default)]
+    pub status: Option<MachineStatus>,
+    /// Change in operators staffing the machine, such as -1 for an absence.
+    #[serde(default)]
+    pub delta_staff: i64,
+}
+
+/// One machine's WIP change in an externally defined scenario.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WipSeed {
+    pub machine_id: MachineId,
+    pub delta_wip: Quantity<Units>,
+    #[serde(default)]
+    pub delta_scrapped: Quantity<Units>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,8 +62,60 @@ pub struct ManufacturingBeamConfig {
     pub beam_width: usize,
     pub min_prob: f64,
     pub branch_prob: f64,
+    /// Branch probability of an assigned operator missing their shift; 0
+    /// disables absence branching.
+    #[serde(default = "default_absence_prob")]
+    pub absence_prob: f64,
     pub delta_multiplier: f64,
     pub min_delta_units: i64,
+    /// Upper bound on active scenarios across all beam partitions.
+    #[serde(default)]
+    pub global_cap: Option<usize>,
+    /// Upper bound on partitions with active scenarios; the least recently
+    /// branched partition's scenarios retire to make room for a new one.
+    #[serde(default)]
+    pub max_keys: Option<usize>,
+    /// Factor beam weights are scaled by per epoch; `None` disables decay.
+    #[serde(default)]
+    pub epoch_decay: Option<f64>,
+    /// Epochs a scenario lives past its creation or last confirmation.
+    #[serde(default)]
+    pub ttl_epochs: Option<u64>,
+    /// Per-depth caps applied alongside `beam_width`.
+    #[serde(default)]
+    pub depth_quotas: Vec<DepthQuota>,
+    /// Slots reserved for the highest-impact scenarios that probability
+    /// ranking would drop; scenarios under `min_prob` never qualify.
+    #[serde(default)]
+    pub tail_slots: usize,
+    /// Children one triggering event may create, branched off the heaviest
+    /// eligible parents; `None` extends every eligible parent.
+    #[serde(default)]
+    pub max_children_per_event: Option<usize>,
+    #[serde(default)]
+    pub weight_mode: WeightMode,
+    #[serde(default)]
+    pub renormalization: Renormalization,
+}
+
+impl ManufacturingBeamConfig {
+    /// The settings the shared beam runs on.
+    pub fn beam_config(&self) -> BeamConfig {
+        BeamConfig {
+            max_depth: self.max_depth,
+            beam_width: self.beam_width,
+            min_prob: self.min_prob,
+            global_cap: self.global_cap,
+            max_keys: self.max_keys,
+            epoch_decay: self.epoch_decay,
+            ttl_epochs: self.ttl_epochs,
+            depth_quotas: self.depth_quotas.clone(),
+            tail_slots: self.tail_slots,
+            max_children_per_event: self.max_children_per_event,
+            weight_mode: self.weight_mode,
+            renormalization: self.renormalization,
+        }
+    }
 }
 
 impl Default for ManufacturingBeamConfig {
@@ -34,146 +125,738 @@ impl Default for ManufacturingBeamConfig {
             beam_width: 16,
             min_prob: 0.1,
             branch_prob: 0.45,
+            absence_prob: default_absence_prob(),
             delta_multiplier: 0.5,
             min_delta_units: 2,
+            global_cap: None,
+            max_keys: None,
+            epoch_decay: None,
+            ttl_epochs: None,
+            depth_quotas: Vec::new(),
+            tail_slots: 0,
+            max_children_per_event: None,
+            weight_mode: WeightMode::default(),
+            renormalization: Renormalization::default(),
         }
     }
 }
 
+fn default_absence_prob() -> f64 {
+    0.1
+}
+
 #[derive(Debug, Default)]
 pub struct ManufacturingExpansionOutcome {
     pub created: Vec<ScenarioMeta>,
-    pub retired: Vec<ScenarioMeta>,
+    /// Retired scenarios with why each left the beam.
+    pub retired: Vec<(ScenarioMeta, RetirementReason)>,
     pub overlays_added: Vec<ManufacturingScenarioDelta>,
     pub overlays_removed: Vec<ManufacturingScenarioDelta>,
+    /// Scenarios retired because a [`SafetyValve`](crate::valve::SafetyValve)
+    /// cap bound; also in `retired`.
+    pub shed: usize,
+    /// Active scenarios per depth after this expansion.
+    pub depth_occupancy: BTreeMap<Depth, usize>,
+    /// Proposals turned down by a validator; never created.
+    pub rejected: Vec<Rejection>,
+    /// Parents left unextended because sources lag; backfilled once caught up.
+    pub deferred: usize,
+    /// Scenarios reweighted in place, such as decaying priors, with their
+    /// previous weight; the meta carries the new one.
+    pub reweighted: Vec<(ScenarioMeta, Prob)>,
 }
 
-pub struct ManufacturingScenarioManager {
-    cfg: ManufacturingBeamConfig,
-    predictor: Arc<dyn MachineBacklogPredictor>,
-    next_id: u64,
-    active: Vec<ScenarioMeta>,
-    overlays: HashMap<u64, ManufacturingScenarioDelta>,
+/// The event an expansion branched on, kept to backfill throttled expansions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ManufacturingTrigger {
+    Operation(OperationStart, Option<i64>),
+    Shortage(MaterialStatus),
+    Failure(MachineHealth),
+    /// An operator's assignment, branched on as the operator not showing up.
+    Absence(OperatorAssigned),
 }
 
-impl ManufacturingScenarioManager {
-    pub fn new(cfg: ManufacturingBeamConfig, predictor: Arc<dyn MachineBacklogPredictor>) -> Self {
-        Self {
-            cfg,
-            predictor,
-            next_id: 1,
-            active: Vec::new(),
-            overlays: HashMap::new(),
+/// Maps a triggering event to its independence key for beam partitioning.
+pub type PartitionKeyFn = Arc<dyn Fn(&OperationStart) -> u64 + Send + Sync>;
+
+impl From<ExpansionOutcome<Vec<ManufacturingScenarioDelta>>> for ManufacturingExpansionOutcome {
+    fn from(outcome: ExpansionOutcome<Vec<ManufacturingScenarioDelta>>) -> Self {
+        ManufacturingExpansionOutcome {
+            created: outcome.created,
+            retired: outcome.retired,
+            overlays_added: outcome.overlays_added.into_iter().flatten().collect(),
+            overlays_removed: outcome.overlays_removed.into_iter().flatten().collect(),
+            shed: outcome.shed,
+            depth_occupancy: outcome.depth_occupancy,
+            rejected: outcome.rejected,
+            deferred: outcome.deferred,
+            reweighted: outcome.reweighted,
         }
     }
+}
 
-    pub fn expand_operation(&mut self, op: &OperationStart) -> ManufacturingExpansionOutcome {
-        let mut outcome = ManufacturingExpansionOutcome::default();
+/// A shortage that has been branched on and still holds.
+#[derive(Debug, Clone, Default)]
+struct OpenShortage {
+    likelihood: f64,
+    /// Starved backlog per consuming machine.
+    starved: BTreeMap<MachineId, i64>,
+}
 
-        let mut survivors = Vec::new();
-        let mut retired = Vec::new();
+/// Manufacturing's side of expansion: the predictor each kind of event
+/// branches with, and how backlog deltas are scaled. Shortages and breakdowns
+/// branch with their predicted likelihood, and the children one parent
+/// branches on the same material or machine are alternatives of each other.
+struct ManufacturingDeltas {
+    cfg: ManufacturingBeamConfig,
+    predictor: Arc<dyn MachineBacklogPredictor>,
+    yield_predictor: Option<Arc<dyn YieldLossPredictor>>,
+    shortage_predictor: Option<Arc<dyn ShortageLikelihoodPredictor>>,
+    failure_predictor: Option<Arc<dyn MachineFailurePredictor>>,
+    partition_key: Option<PartitionKeyFn>,
+    /// Holds operations on machines the backlog predictor has not warmed up on.
+    warm_up: Option<WarmUpGate<dyn MachineBacklogPredictor>>,
+    /// Shortages branched once and not branched again until they clear.
+    open_shortages: BTreeMap<MaterialId, OpenShortage>,
+    /// Per machine, the epoch of the last failure it has been branched on a
+    /// breakdown since; `None` once restored, until the next call reads it.
+    branched_failures: BTreeMap<MachineId, Option<i64>>,
+}
 
-        for meta in self.active.drain(..) {
-            if meta.weight.0 < self.cfg.min_prob || meta.depth >= self.cfg.max_depth {
-                retired.push(meta);
-            } else {
-                survivors.push(meta);
+impl DeltaBuilder<ManufacturingTrigger, Vec<ManufacturingScenarioDelta>> for ManufacturingDeltas {
+    fn branch(
+        &self,
+        epoch: EpochTime,
+        features: Option<&dyn FeatureStore>,
+        event: &ManufacturingTrigger,
+    ) -> Option<Branch> {
+        let root = |base_value| PredictionContext {
+            epoch,
+            lineage: LineageSummary { parent: 0, depth: 0, weight: Prob(1.0) },
+            base_value,
+            features,
+        };
+        // Shortages, breakdowns and absences are not tied to a partition key,
+        // so they only extend unpartitioned branches
+        match event {
+            ManufacturingTrigger::Operation(op, base_value) => {
+                if let Some(gate) = &self.warm_up {
+                    if !gate.admits(epoch, op.machine_id) {
+                        return None;
+                    }
+                }
+                Some(Branch {
+                    partition: self.partition_key.as_ref().map(|key| key(op)),
+                    base_value: *base_value,
+                    branch_prob: self.cfg.branch_prob,
+                    exclusion: None,
+                })
+            }
+            ManufacturingTrigger::Shortage(material) => {
+                let predictor = self.shortage_predictor.as_ref()?;
+                let base_value = Some(material.on_hand);
+                let likelihood =
+                    predictor.shortage_likelihood(&root(base_value), material).clamp(0.0, 1.0);
+                (likelihood > 0.0).then_some(Branch {
+                    partition: None,
+                    base_value,
+                    branch_prob: likelihood,
+                    exclusion: Some(ExclusionKey { kind: "shortage", id: material.material_id }),
+                })
+            }
+            ManufacturingTrigger::Failure(machine) => {
+                let predictor = self.failure_predictor.as_ref()?;
+                let probability =
+                    predictor.failure_probability(&root(None), machine).clamp(0.0, 1.0);
+                (probability > 0.0).then_some(Branch {
+                    partition: None,
+                    base_value: None,
+                    branch_prob: probability,
+                    exclusion: Some(ExclusionKey { kind: "failure", id: machine.machine_id }),
+                })
+            }
+            ManufacturingTrigger::Absence(assignment) => {
+                (self.cfg.absence_prob > 0.0).then_some(Branch {
+                    partition: None,
+                    base_value: None,
+                    branch_prob: self.cfg.absence_prob.min(1.0),
+                    exclusion: Some(ExclusionKey { kind: "absence", id: assignment.operator_id }),
+                })
             }
         }
+    }
 
-        let mut candidates = survivors.clone();
+    fn build(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &ManufacturingTrigger,
+        scenario_id: u64,
+    ) -> Vec<ManufacturingScenarioDelta> {
+        match event {
+            ManufacturingTrigger::Operation(op, _) => {
+                let predictor = self.backlog_predictor(ctx, op);
+                let predicted_delta = predictor.predict_backlog(ctx, op);
+                let variance_wip = predictor.backlog_variance(ctx, op);
+                self.backlog_delta(ctx, scenario_id, op, predicted_delta, variance_wip)
+            }
+            ManufacturingTrigger::Shortage(material) => {
+                let Some(predictor) = &self.shortage_predictor else {
+                    return Vec::new();
+                };
+                material
+                    .consumers
+                    .iter()
+                    .map(|&machine_id| {
+                        (machine_id, predictor.starved_backlog(ctx, material, machine_id))
+                    })
+                    .filter(|(_, units)| *units != 0)
+                    .map(|(machine_id, units)| ManufacturingScenarioDelta {
+                        scenario_id,
+                        machine_id,
+                        delta_wip: Quantity::from_units(units),
+                        delta_scrapped: Quantity::from_units(0),
+                        variance_wip: 0,
+                        status: None,
+                        delta_staff: 0,
+                    })
+                    .collect()
+            }
+            ManufacturingTrigger::Failure(machine) => {
+                let Some(predictor) = &self.failure_predictor else {
+                    return Vec::new();
+                };
+                let units = predictor.downtime_backlog(ctx, machine);
+                vec![ManufacturingScenarioDelta {
+                    scenario_id,
+                    machine_id: machine.machine_id,
+                    delta_wip: Quantity::from_units(units),
+                    delta_scrapped: Quantity::from_units(0),
+                    variance_wip: 0,
+                    status: Some(MachineStatus::Down),
+                    delta_staff: 0,
+                }]
+            }
+            ManufacturingTrigger::Absence(assignment) => vec![ManufacturingScenarioDelta {
+                scenario_id,
+                machine_id: assignment.machine_id,
+                delta_wip: Quantity::from_units(0),
+                delta_scrapped: Quantity::from_units(0),
+                variance_wip: 0,
+                status: None,
+                delta_staff: -1,
+            }],
+        }
+    }
 
-        let parents_iter = std::iter::once(ScenarioMeta {
-            id: 0,
-            parent: None,
-            depth: 0,
-            weight: Prob(1.0),
-        })
-        .chain(survivors.into_iter());
+    fn build_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &ManufacturingTrigger,
+        first_id: u64,
+    ) -> Vec<(f64, Vec<ManufacturingScenarioDelta>)> {
+        let ManufacturingTrigger::Operation(op, _) = event else {
+            return vec![(1.0, self.build(ctx, event, first_id))];
+        };
+        let predictor = self.backlog_predictor(ctx, op);
+        let variance_wip = predictor.backlog_variance(ctx, op);
+        (0u64..)
+            .zip(predictor.backlog_outcomes(ctx, op))
+            .map(|(index, outcome)| {
+                let scenario_id = first_id.wrapping_add(index);
+                let overlay = self.backlog_delta(ctx, scenario_id, op, outcome.value, variance_wip);
+                (outcome.probability, overlay)
+            })
+            .collect()
+    }
+
+    fn impact(&self, overlay: &Vec<ManufacturingScenarioDelta>) -> i64 {
+        overlay
+            .iter()
+            .fold(0i64, |sum, d| sum.saturating_add(d.delta_wip.units().saturating_abs()))
+    }
 
-        let predicted_delta = self.predict_delta(op);
+    fn rows(&self, overlay: &Vec<ManufacturingScenarioDelta>) -> usize {
+        overlay.len()
+    }
 
-        for parent in parents_iter {
-            if parent.depth >= self.cfg.max_depth {
-                continue;
+    fn label(
+        &self,
+        event: &ManufacturingTrigger,
+        overlay: &Vec<ManufacturingScenarioDelta>,
+    ) -> Option<String> {
+        let label = match event {
+            ManufacturingTrigger::Operation(op, _) => {
+                let wip: Quantity<Units> = overlay.iter().map(|d| d.delta_wip).sum();
+                format!("machine {} queues {} more", op.machine_id, wip)
             }
-            let parent_weight = if parent.id == 0 { 1.0 } else { parent.weight.0 };
-            let child_weight = parent_weight * self.cfg.branch_prob;
-            if child_weight < self.cfg.min_prob {
-                continue;
+            ManufacturingTrigger::Shortage(material) => {
+                format!("material {} runs short", material.material_id)
             }
-            let child_id = self.next_id;
-            self.next_id = self.next_id.wrapping_add(1);
-
-            let meta = ScenarioMeta {
-                id: child_id,
-                parent: if parent.id == 0 { None } else { Some(parent.id) },
-                depth: parent.depth + 1,
-                weight: Prob(child_weight),
-            };
+            ManufacturingTrigger::Failure(machine) => {
+                format!("machine {} goes down", machine.machine_id)
+            }
+            ManufacturingTrigger::Absence(OperatorAssigned { operator_id, machine_id, .. }) => {
+                format!("operator {} misses machine {}", operator_id, machine_id)
+            }
+        };
+        Some(label)
+    }
 
-            let delta = ManufacturingScenarioDelta {
-                scenario_id: child_id,
-                machine_id: op.machine_id,
-                delta_wip: predicted_delta,
-            };
+    fn tags(
+        &self,
+        event: &ManufacturingTrigger,
+        _overlay: &Vec<ManufacturingScenarioDelta>,
+    ) -> Vec<String> {
+        match event {
+            ManufacturingTrigger::Operation(op, _) => {
+                vec!["backlog".to_string(), format!("machine:{}", op.machine_id)]
+            }
+            ManufacturingTrigger::Shortage(material) => {
+                vec!["shortage".to_string(), format!("material:{}", material.material_id)]
+            }
+            ManufacturingTrigger::Failure(machine) => {
+                vec!["failure".to_string(), format!("machine:{}", machine.machine_id)]
+            }
+            ManufacturingTrigger::Absence(assignment) => vec![
+                "absence".to_string(),
+                format!("operator:{}", assignment.operator_id),
+                format!("machine:{}", assignment.machine_id),
+            ],
+        }
+    }
+}
 
-            self.overlays.insert(child_id, delta.clone());
+impl ManufacturingDeltas {
+    /// The backlog predictor for `op`, or the warm-up fallback while its
+    /// machine is warming up.
+    fn backlog_predictor(
+        &self,
+        ctx: &PredictionContext<'_>,
+        op: &OperationStart,
+    ) -> &Arc<dyn MachineBacklogPredictor> {
+        self.warm_up
+            .as_ref()
+            .and_then(|gate| gate.fallback(ctx.epoch, op.machine_id))
+            .unwrap_or(&self.predictor)
+    }
 
-            outcome.created.push(meta.clone());
-            outcome.overlays_added.push(delta);
-            candidates.push(meta);
+    /// A predicted backlog as scenario `scenario_id`'s overlay, scaled and
+    /// floored as configured, with the yield predictor's rework and scrap.
+    fn backlog_delta(
+        &self,
+        ctx: &PredictionContext<'_>,
+        scenario_id: u64,
+        op: &OperationStart,
+        mut predicted_delta: i64,
+        mut variance_wip: i64,
+    ) -> Vec<ManufacturingScenarioDelta> {
+        let multiplier = self.cfg.delta_multiplier;
+        if (multiplier - 1.0).abs() > f64::EPSILON {
+            predicted_delta = ((predicted_delta as f64) * multiplier).round() as i64;
+            variance_wip = ((variance_wip as f64) * multiplier * multiplier).round() as i64;
         }
+        let predicted_delta = std::cmp::max(predicted_delta, self.cfg.min_delta_units);
+        let starved_units = self.expected_starvation(op.machine_id);
+        let (rework_units, scrapped_units) = match &self.yield_predictor {
+            Some(yield_predictor) => (
+                yield_predictor.predict_rework_units(ctx, op),
+                yield_predictor.predict_scrap_units(ctx, op),
+            ),
+            None => (0, 0),
+        };
+        vec![ManufacturingScenarioDelta {
+            scenario_id,
+            machine_id: op.machine_id,
+            delta_wip: Quantity::from_units(predicted_delta + rework_units + starved_units),
+            delta_scrapped: Quantity::from_units(scrapped_units),
+            variance_wip,
+            status: None,
+            delta_staff: 0,
+        }]
+    }
 
-        let mut seen: HashSet<u64> = HashSet::new();
-        let mut sorted = candidates;
-        sorted.sort_by(|a, b| b.weight.0.partial_cmp(&a.weight.0).unwrap_or(Ordering::Equal));
+    /// Backlog `machine_id` is expected to queue while its materials are
+    /// short: each open shortage's starved backlog scaled by its likelihood.
+    fn expected_starvation(&self, machine_id: MachineId) -> i64 {
+        self.open_shortages
+            .values()
+            .filter_map(|shortage| {
+                let units = *shortage.starved.get(&machine_id)?;
+                Some((units as f64 * shortage.likelihood).round() as i64)
+            })
+            .sum()
+    }
 
-        let mut retained = Vec::new();
-        for meta in sorted {
-            if seen.contains(&meta.id) {
-                continue;
-            }
-            seen.insert(meta.id);
-            if retained.len() < self.cfg.beam_width {
-                retained.push(meta);
-            } else {
-                retired.push(meta);
-            }
+    /// A shortage's likelihood and starved backlog per consumer, judged at
+    /// the root.
+    fn assess_shortage(
+        &self,
+        epoch: EpochTime,
+        features: Option<&dyn FeatureStore>,
+        material: &MaterialStatus,
+    ) -> Option<OpenShortage> {
+        let predictor = self.shortage_predictor.as_ref()?;
+        let ctx = PredictionContext {
+            epoch,
+            lineage: LineageSummary { parent: 0, depth: 0, weight: Prob(1.0) },
+            base_value: Some(material.on_hand),
+            features,
+        };
+        let likelihood = predictor.shortage_likelihood(&ctx, material).clamp(0.0, 1.0);
+        let starved = material
+            .consumers
+            .iter()
+            .map(|&machine_id| (machine_id, predictor.starved_backlog(&ctx, material, machine_id)))
+            .filter(|(_, units)| *units != 0)
+            .collect();
+        (likelihood > 0.0).then_some(OpenShortage { likelihood, starved })
+    }
+}
+
+/// The WIP deltas a seeded scenario applies.
+fn wip_overlay(scenario_id: u64, rows: &[WipSeed]) -> Vec<ManufacturingScenarioDelta> {
+    rows.iter()
+        .map(|row| ManufacturingScenarioDelta {
+            scenario_id,
+            machine_id: row.machine_id,
+            delta_wip: row.delta_wip,
+            delta_scrapped: row.delta_scrapped,
+            variance_wip: 0,
+            status: None,
+            delta_staff: 0,
+        })
+        .collect()
+}
+
+/// Checkpoint of a [`ManufacturingScenarioManager`]'s scenario tree; see
+/// [`ScenarioManagerState`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ManufacturingScenarioState(
+    ScenarioManagerState<ManufacturingTrigger, Vec<ManufacturingScenarioDelta>>,
+);
+
+/// The shared beam as manufacturing instantiates it.
+pub type ManufacturingBeam = ScenarioManager<ManufacturingTrigger, Vec<ManufacturingScenarioDelta>>;
+
+/// The shared [`ScenarioManager`] branched on operations, material shortages
+/// and machine breakdowns. Overlay rows per scenario cover one machine for
+/// operations and every consuming machine for shortages. Calls that need no
+/// manufacturing event or predictor, such as
+/// [`ScenarioManager::advance_epoch`], go to the manager it derefs to.
+pub struct ManufacturingScenarioManager {
+    deltas: ManufacturingDeltas,
+    beam: ManufacturingBeam,
+}
+
+impl ManufacturingScenarioManager {
+    pub fn new(cfg: ManufacturingBeamConfig, predictor: Arc<dyn MachineBacklogPredictor>) -> Self {
+        Self {
+            beam: ScenarioManager::new(cfg.beam_config()),
+            deltas: ManufacturingDeltas {
+                cfg,
+                predictor,
+                yield_predictor: None,
+                shortage_predictor: None,
+                failure_predictor: None,
+                partition_key: None,
+                warm_up: None,
+                open_shortages: BTreeMap::new(),
+                branched_failures: BTreeMap::new(),
+            },
         }
+    }
 
-        for meta in retired.iter() {
-            if let Some(delta) = self.overlays.remove(&meta.id) {
-                outcome.overlays_removed.push(delta);
+    /// A manager resuming from `state`; see [`ScenarioManager::restore`].
+    pub fn restore(
+        state: ManufacturingScenarioState,
+        cfg: ManufacturingBeamConfig,
+        predictor: Arc<dyn MachineBacklogPredictor>,
+    ) -> Self {
+        let mut manager = Self::new(cfg, predictor);
+        manager.beam = ScenarioManager::restore(state.0, manager.deltas.cfg.beam_config());
+        // Shortages and breakdowns with a live branch stay branched; their
+        // likelihood and failure cycle are read on the next call
+        for meta in manager.beam.resumed().created {
+            let id = |prefix: &str| {
+                let id = meta.tags.iter().find_map(|tag| tag.strip_prefix(prefix))?;
+                id.parse().ok()
+            };
+            if meta.tags.iter().any(|tag| tag == "shortage") {
+                if let Some(material_id) = id("material:") {
+                    manager.deltas.open_shortages.entry(material_id).or_default();
+                }
+            } else if meta.tags.iter().any(|tag| tag == "failure") {
+                if let Some(machine_id) = id("machine:") {
+                    manager.deltas.branched_failures.insert(machine_id, None);
+                }
             }
         }
+        manager
+    }
 
-        outcome.retired.extend(retired);
-        self.active = retained;
+    pub fn snapshot(&self) -> ManufacturingScenarioState {
+        ManufacturingScenarioState(self.beam.snapshot())
+    }
 
-        outcome
+    pub fn config(&self) -> &ManufacturingBeamConfig {
+        &self.deltas.cfg
     }
 
-    pub fn active_weights(&self) -> Vec<(u64, f64)> {
-        self.active
-            .iter()
-            .map(|meta| (meta.id, meta.weight.0))
-            .collect()
+    /// Use `cfg` from the next event on; see [`ScenarioManager::set_config`].
+    pub fn set_config(&mut self, cfg: ManufacturingBeamConfig) {
+        self.beam.set_config(cfg.beam_config());
+        self.deltas.cfg = cfg;
     }
 
-    pub fn active_len(&self) -> usize {
-        self.active.len()
+    /// Branch later operations with `predictor`; existing overlays are kept.
+    pub fn set_predictor(&mut self, predictor: Arc<dyn MachineBacklogPredictor>) {
+        self.deltas.predictor = predictor;
     }
 
-    fn predict_delta(&self, op: &OperationStart) -> i64 {
-        let mut delta = self.predictor.predict_backlog(op);
-        if (self.cfg.delta_multiplier - 1.0).abs() > f64::EPSILON {
-            delta = ((delta as f64) * self.cfg.delta_multiplier).round() as i64;
+    /// Partition the beam by an independence key: events only extend branches
+    /// created under the same key, and `beam_width` applies per partition.
+    pub fn with_partition_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&OperationStart) -> u64 + Send + Sync + 'static,
+    {
+        self.deltas.partition_key = Some(Arc::new(key));
+        self.beam = self.beam.with_partitioning();
+        self
+    }
+
+    /// Configure the shared beam through its own builders, such as
+    /// [`ScenarioManager::with_safety_valve`].
+    pub fn with_beam<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(ManufacturingBeam) -> ManufacturingBeam,
+    {
+        self.beam = configure(self.beam);
+        self
+    }
+
+    /// Fold projected rework into WIP deltas and record projected scrap on overlays.
+    pub fn with_yield_predictor(mut self, predictor: Arc<dyn YieldLossPredictor>) -> Self {
+        self.deltas.yield_predictor = Some(predictor);
+        self
+    }
+
+    /// Branch supply-shortage scenarios with this predictor.
+    pub fn with_shortage_predictor(
+        mut self,
+        predictor: Arc<dyn ShortageLikelihoodPredictor>,
+    ) -> Self {
+        self.deltas.shortage_predictor = Some(predictor);
+        self
+    }
+
+    /// Branch on operations only on machines `gate` reports ready, or with
+    /// its fallback predictor until they are.
+    pub fn with_warm_up(mut self, gate: WarmUpGate<dyn MachineBacklogPredictor>) -> Self {
+        self.deltas.warm_up = Some(gate);
+        self
+    }
+
+    /// Branch machine-breakdown scenarios with this predictor.
+    pub fn with_failure_predictor(mut self, predictor: Arc<dyn MachineFailurePredictor>) -> Self {
+        self.deltas.failure_predictor = Some(predictor);
+        self
+    }
+
+    /// Expand the beam on a base event seen at `epoch`; `base_value` is the
+    /// current base-view value for the event's key, if the caller tracks it.
+    pub fn expand_operation(
+        &mut self,
+        epoch: EpochTime,
+        op: &OperationStart,
+        base_value: Option<i64>,
+    ) -> ManufacturingExpansionOutcome {
+        self.expand(epoch, &ManufacturingTrigger::Operation(op.clone(), base_value))
+    }
+
+    /// Branch a supply-shortage scenario for `material`: the child starves
+    /// every consuming machine, and its branch probability is the predicted
+    /// shortage likelihood. Shortages are not tied to a partition key, so they
+    /// only extend unpartitioned branches. No-op without a shortage predictor.
+    ///
+    /// A shortage branches once and stays open until its likelihood drops to
+    /// zero; later calls while it is open only refresh the expected
+    /// starvation that operation branches on its consumers add to their
+    /// backlog deltas.
+    pub fn expand_shortage(
+        &mut self,
+        epoch: EpochTime,
+        material: &MaterialStatus,
+    ) -> ManufacturingExpansionOutcome {
+        let material_id = material.material_id;
+        let features = self.beam.feature_store();
+        let Some(shortage) = self.deltas.assess_shortage(epoch, features, material) else {
+            self.deltas.open_shortages.remove(&material_id);
+            return ManufacturingExpansionOutcome::default();
+        };
+        if let Some(open) = self.deltas.open_shortages.get_mut(&material_id) {
+            *open = shortage;
+            return ManufacturingExpansionOutcome::default();
         }
-        if delta < self.cfg.min_delta_units {
-            self.cfg.min_delta_units
-        } else {
-            delta
+        let outcome = self.expand(epoch, &ManufacturingTrigger::Shortage(material.clone()));
+        if !outcome.created.is_empty() {
+            self.deltas.open_shortages.insert(material_id, shortage);
         }
+        outcome
+    }
+
+    /// Branch a breakdown scenario for `machine`: the child sees the machine
+    /// go `Down` and queue its downtime backlog, with the predicted failure
+    /// probability as branch probability. Like shortages, breakdowns only
+    /// extend unpartitioned branches. No-op without a failure predictor.
+    ///
+    /// A machine is branched on once per failure cycle: after a breakdown
+    /// branch, later calls are no-ops until `machine` reports a newer
+    /// failure.
+    pub fn expand_failure(
+        &mut self,
+        epoch: EpochTime,
+        machine: &MachineHealth,
+    ) -> ManufacturingExpansionOutcome {
+        let cycle = i64::try_from(epoch.get()).unwrap_or(i64::MAX) - machine.epochs_since_failure;
+        match self.deltas.branched_failures.get_mut(&machine.machine_id) {
+            Some(Some(branched)) if *branched == cycle => {
+                return ManufacturingExpansionOutcome::default();
+            }
+            Some(branched @ None) => {
+                *branched = Some(cycle);
+                return ManufacturingExpansionOutcome::default();
+            }
+            _ => {}
+        }
+        let outcome = self.expand(epoch, &ManufacturingTrigger::Failure(machine.clone()));
+        if !outcome.created.is_empty() {
+            self.deltas.branched_failures.insert(machine.machine_id, Some(cycle));
+        }
+        outcome
+    }
+
+    /// Branch an absence scenario for `assignment`: the child loses the
+    /// operator from the machine's staff, with `absence_prob` as branch
+    /// probability. Call it as an operator starts a shift. Like breakdowns,
+    /// absences only extend unpartitioned branches.
+    pub fn expand_absence(
+        &mut self,
+        epoch: EpochTime,
+        assignment: &OperatorAssigned,
+    ) -> ManufacturingExpansionOutcome {
+        self.expand(epoch, &ManufacturingTrigger::Absence(assignment.clone()))
+    }
+
+    /// Settle operation branches against the realized completion of their
+    /// job. A late completion confirms a predicted backlog growth on the
+    /// machine and contradicts a predicted shrink; an on-time one does the
+    /// opposite. See [`ScenarioManager::reconcile`].
+    pub fn reconcile(
+        &mut self,
+        epoch: EpochTime,
+        done: &OperationComplete,
+    ) -> ManufacturingExpansionOutcome {
+        self.beam
+            .reconcile(epoch, |trigger, overlay| {
+                let ManufacturingTrigger::Operation(op, _) = trigger else {
+                    return None;
+                };
+                if op.job_id != done.job_id || op.machine_id != done.machine_id {
+                    return None;
+                }
+                let predicted: i64 = overlay
+                    .iter()
+                    .filter(|delta| delta.machine_id == op.machine_id)
+                    .map(|delta| delta.delta_wip.units())
+                    .sum();
+                if predicted == 0 {
+                    return None;
+                }
+                let due_ms = op.ts_ms.as_millis().saturating_add(op.expected_duration_ms);
+                let late = done.ts_ms.as_millis() > due_ms;
+                if late == (predicted > 0) {
+                    Some(Verdict::Confirmed { miss: 0 })
+                } else {
+                    Some(Verdict::Contradicted)
+                }
+            })
+            .into()
+    }
+
+    /// Seed an externally defined scenario at the root, weighted by its
+    /// supplied probability and tagged with its source. Seeds are admitted
+    /// like branches, within the beam and safety caps; predictor-driven
+    /// branches extend them and carry their tag until
+    /// [`ScenarioManager::retire_external`].
+    pub fn seed_external(
+        &mut self,
+        epoch: EpochTime,
+        scenario: &ExternalScenario<WipSeed>,
+    ) -> ManufacturingExpansionOutcome {
+        let overlay = |scenario_id| wip_overlay(scenario_id, &scenario.rows);
+        let tag = scenario.tag();
+        self.beam.seed_external(&self.deltas, epoch, tag, scenario.probability, overlay).into()
+    }
+
+    /// Inject a hypothetical with the overlay rows in `deltas` as a pinned
+    /// scenario tagged `what_if:<label>`; withdraw it with
+    /// [`ScenarioManager::retire_external`].
+    pub fn inject_scenario(
+        &mut self,
+        epoch: EpochTime,
+        deltas: &[WipSeed],
+        probability: f64,
+        label: &str,
+    ) -> ManufacturingExpansionOutcome {
+        let overlay = |scenario_id| wip_overlay(scenario_id, deltas);
+        self.beam.inject_scenario(&self.deltas, epoch, probability, label, overlay).into()
+    }
+
+    /// Seed a prior at the root, as [`Self::seed_external`] does under the
+    /// tag `prior:<name>`. Its weight then follows the prior's decay schedule
+    /// through [`ScenarioManager::decay_priors`].
+    pub fn seed_prior(
+        &mut self,
+        epoch: EpochTime,
+        prior: &PriorScenario<WipSeed>,
+    ) -> ManufacturingExpansionOutcome {
+        let overlay = |scenario_id| wip_overlay(scenario_id, &prior.rows);
+        self.beam.seed_prior(&self.deltas, epoch, prior.tag(), prior.schedule, overlay).into()
+    }
+
+    /// Report how far sources lag at `epoch`; see [`ScenarioManager::observe_lag`].
+    pub fn observe_lag(
+        &mut self,
+        epoch: EpochTime,
+        lag_ms: u64,
+    ) -> Vec<ManufacturingExpansionOutcome> {
+        self.beam.observe_lag(&self.deltas, epoch, lag_ms).into_iter().map(Into::into).collect()
+    }
+
+    pub fn warm_up(&self) -> Option<&WarmUpGate<dyn MachineBacklogPredictor>> {
+        self.deltas.warm_up.as_ref()
+    }
+
+    fn expand(
+        &mut self,
+        epoch: EpochTime,
+        event: &ManufacturingTrigger,
+    ) -> ManufacturingExpansionOutcome {
+        self.beam.expand(&self.deltas, epoch, event).into()
+    }
+}
+
+impl Deref for ManufacturingScenarioManager {
+    type Target = ManufacturingBeam;
+
+    fn deref(&self) -> &Self::Target {
+        &self.beam
+    }
+}
+
+impl DerefMut for ManufacturingScenarioManager {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.beam
     }
 }
@@ -0,0 +1,107 @@
+//! Scenarios a domain registers at startup, such as a seasonal demand spike
+//! or next week's scheduled maintenance, so the beam has meaningful futures
+//! before enough events arrive to drive predictors. A prior is seeded like
+//! an external scenario and its probability decays on a schedule until it is
+//! withdrawn.
+
+#[cfg(feature = "fs")]
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Source half of a seeded prior's `prior:<name>` tag.
+pub const PRIOR_SOURCE: &str = "prior";
+
+/// How a prior's probability fades with the epochs since it was seeded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Decay {
+    /// Held at its initial probability.
+    #[default]
+    Hold,
+    /// Falls linearly to zero over `epochs`.
+    Linear { epochs: u64 },
+    /// Halves every `epochs`.
+    HalfLife { epochs: u64 },
+}
+
+impl Decay {
+    /// Share of the initial probability left `age` epochs after seeding.
+    pub fn factor(self, age: u64) -> f64 {
+        match self {
+            Decay::Hold => 1.0,
+            Decay::Linear { epochs } => 1.0 - (age as f64 / epochs.max(1) as f64).min(1.0),
+            Decay::HalfLife { epochs } => 0.5_f64.powf(age as f64 / epochs.max(1) as f64),
+        }
+    }
+}
+
+/// A prior's probability over the epochs since it was seeded.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PriorSchedule {
+    /// Probability when seeded.
+    pub probability: f64,
+    #[serde(default)]
+    pub decay: Decay,
+    /// Withdrawn once its decayed probability is at or below this.
+    #[serde(default)]
+    pub min_probability: f64,
+}
+
+impl PriorSchedule {
+    /// Probability `age` epochs after seeding.
+    pub fn probability_at(&self, age: u64) -> f64 {
+        self.probability.clamp(0.0, 1.0) * self.decay.factor(age)
+    }
+
+    pub fn is_expired(&self, age: u64) -> bool {
+        self.probability_at(age) <= self.min_probability
+    }
+}
+
+/// One prior scenario and the overlay rows it applies, in the domain's row
+/// type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorScenario<R> {
+    pub name: String,
+    #[serde(flatten)]
+    pub schedule: PriorSchedule,
+    pub rows: Vec<R>,
+}
+
+impl<R> PriorScenario<R> {
+    /// The tag recorded in [`ScenarioMeta::external`](crate::ScenarioMeta::external).
+    pub fn tag(&self) -> String {
+        format!("{PRIOR_SOURCE}:{}", self.name)
+    }
+}
+
+/// The priors one domain registers at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorsConfig<R> {
+    pub priors: Vec<PriorScenario<R>>,
+}
+
+#[cfg(feature = "fs")]
+impl<R: serde::de::DeserializeOwned> PriorsConfig<R> {
+    /// Load `domain`'s priors from a file mapping each domain to its list of
+    /// priors; a domain the file doesn't mention has none.
+    pub fn from_json_file(path: impl AsRef<Path>, domain: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading priors config {}", path.display()))?;
+        let mut domains: BTreeMap<String, serde_json::Value> = serde_json::from_str(&raw)
+            .with_context(|| format!("parsing priors config {}", path.display()))?;
+        let Some(priors) = domains.remove(domain) else {
+            return Ok(Self { priors: Vec::new() });
+        };
+        let priors = serde_json::from_value(priors).with_context(|| {
+            format!("parsing {domain} priors in {}", path.display())
+        })?;
+        Ok(Self { priors })
+    }
+}
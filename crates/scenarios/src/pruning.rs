@@ -0,0 +1,179 @@
+//! How a beam step decides which scenarios stay. [`BeamPruning`] is the
+//! default; the others trade the per-partition beam for other notions of
+//! which futures are worth keeping.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use tw_core::Depth;
+
+use crate::beam::select_beam;
+use crate::manager::BeamConfig;
+use crate::ScenarioMeta;
+
+/// Chooses the scenarios a beam step keeps.
+pub trait PruningStrategy: Send + Sync {
+    /// Split one step's scenarios into `(retained, evicted)`. `candidates`
+    /// are at or above `cfg.min_prob`; `below_floor` are under it, such as
+    /// seeds planned below the floor, and are always evicted. Every
+    /// scenario passed in comes back in exactly one of the two.
+    fn select(
+        &self,
+        cfg: &BeamConfig,
+        candidates: Vec<ScenarioMeta>,
+        below_floor: Vec<ScenarioMeta>,
+    ) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>);
+}
+
+/// The heaviest `beam_width` per partition within `global_cap` and the
+/// depth quotas, plus `tail_slots` high-impact scenarios.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BeamPruning;
+
+impl PruningStrategy for BeamPruning {
+    fn select(
+        &self,
+        cfg: &BeamConfig,
+        candidates: Vec<ScenarioMeta>,
+        below_floor: Vec<ScenarioMeta>,
+    ) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+        select_beam(
+            candidates,
+            below_floor,
+            cfg.beam_width,
+            cfg.global_cap,
+            &cfg.depth_quotas,
+            cfg.tail_slots,
+            cfg.weight_mode,
+        )
+    }
+}
+
+/// Every scenario weighing at least `min_prob`, however many, within
+/// `global_cap`. Ignores the beam width, depth quotas and tail slots.
+#[derive(Debug, Clone, Copy)]
+pub struct ThresholdPruning {
+    pub min_prob: f64,
+}
+
+impl PruningStrategy for ThresholdPruning {
+    fn select(
+        &self,
+        cfg: &BeamConfig,
+        mut candidates: Vec<ScenarioMeta>,
+        below_floor: Vec<ScenarioMeta>,
+    ) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+        let min_prob = cfg.weight_mode.weight(self.min_prob);
+        candidates.sort_by(|a, b| cfg.weight_mode.rank(a, b));
+        let (kept, mut evicted): (Vec<_>, Vec<_>) =
+            candidates.into_iter().partition(|meta| meta.weight.0 >= min_prob);
+        let (retained, over_cap) = fill(kept, usize::MAX, cfg.global_cap);
+        evicted.extend(over_cap);
+        evicted.extend(below_floor);
+        (retained, evicted)
+    }
+}
+
+/// `beam_width` slots per partition dealt round-robin across depths,
+/// heaviest first within each, so deep chains and fresh branches keep a
+/// comparable share; slots a depth cannot fill go to the others. Then
+/// `global_cap` keeps the heaviest. Ignores depth quotas and tail slots.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StratifiedPruning;
+
+impl PruningStrategy for StratifiedPruning {
+    fn select(
+        &self,
+        cfg: &BeamConfig,
+        candidates: Vec<ScenarioMeta>,
+        below_floor: Vec<ScenarioMeta>,
+    ) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+        let mut strata: HashMap<Option<u64>, BTreeMap<Depth, Vec<ScenarioMeta>>> = HashMap::new();
+        for meta in candidates {
+            strata.entry(meta.partition).or_default().entry(meta.depth).or_default().push(meta);
+        }
+        let mut retained = Vec::new();
+        let mut evicted = Vec::new();
+        for depths in strata.into_values() {
+            let mut queues: Vec<VecDeque<ScenarioMeta>> = depths
+                .into_values()
+                .map(|mut metas| {
+                    metas.sort_by(|a, b| cfg.weight_mode.rank(a, b));
+                    metas.into()
+                })
+                .collect();
+            let mut slots = cfg.beam_width;
+            while slots > 0 && queues.iter().any(|queue| !queue.is_empty()) {
+                for queue in queues.iter_mut() {
+                    if slots == 0 {
+                        break;
+                    }
+                    if let Some(meta) = queue.pop_front() {
+                        retained.push(meta);
+                        slots -= 1;
+                    }
+                }
+            }
+            evicted.extend(queues.into_iter().flatten());
+        }
+        retained.sort_by(|a, b| cfg.weight_mode.rank(a, b));
+        let (retained, over_cap) = fill(retained, usize::MAX, cfg.global_cap);
+        evicted.extend(over_cap);
+        evicted.extend(below_floor);
+        (retained, evicted)
+    }
+}
+
+/// The `beam_width` scenarios per partition with the largest expected
+/// impact, `weight * (1 + impact_weight * |impact|)`, within `global_cap`;
+/// an `impact_weight` of 0 ranks by weight alone. Ignores depth quotas and
+/// tail slots.
+#[derive(Debug, Clone, Copy)]
+pub struct UtilityPruning {
+    pub impact_weight: f64,
+}
+
+impl UtilityPruning {
+    pub fn utility(&self, meta: &ScenarioMeta) -> f64 {
+        meta.weight.0 * (1.0 + self.impact_weight * meta.impact.unsigned_abs() as f64)
+    }
+}
+
+impl PruningStrategy for UtilityPruning {
+    fn select(
+        &self,
+        cfg: &BeamConfig,
+        mut candidates: Vec<ScenarioMeta>,
+        below_floor: Vec<ScenarioMeta>,
+    ) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+        candidates.sort_by(|a, b| {
+            self.utility(b).total_cmp(&self.utility(a)).then_with(|| a.id.cmp(&b.id))
+        });
+        let (retained, mut evicted) = fill(candidates, cfg.beam_width, cfg.global_cap);
+        evicted.extend(below_floor);
+        (retained, evicted)
+    }
+}
+
+/// Keep `sorted` in order up to `per_partition` per partition and
+/// `global_cap` overall; clears tail tags on what is kept.
+fn fill(
+    sorted: Vec<ScenarioMeta>,
+    per_partition: usize,
+    global_cap: Option<usize>,
+) -> (Vec<ScenarioMeta>, Vec<ScenarioMeta>) {
+    let global_cap = global_cap.unwrap_or(usize::MAX);
+    let mut occupancy: HashMap<Option<u64>, usize> = HashMap::new();
+    let mut retained = Vec::new();
+    let mut evicted = Vec::new();
+    for mut meta in sorted {
+        let used = occupancy.entry(meta.partition).or_insert(0);
+        if *used < per_partition && retained.len() < global_cap {
+            *used += 1;
+            meta.tail = false;
+            retained.push(meta);
+        } else {
+            evicted.push(meta);
+        }
+    }
+    (retained, evicted)
+}
@@ -1,20 +1,64 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-use tw_core::retail::OrderPlaced;
-use tw_core::Prob;
-use tw_predictors::SpendDeltaPredictor;
+use tw_core::quantity::Rounding;
+use tw_core::retail::{
+    Channel, ChannelId, CohortId, CustomerId, Money, OrderPlaced, PriceChanged, SkuId, BPS_SCALE,
+};
+use tw_core::{Depth, EpochTime, Prob};
+use tw_predictors::{
+    ChannelShiftPredictor, ElasticityPredictor, FeatureStore, PredictionContext,
+    SpendDeltaPredictor,
+};
+use tw_predictors::warmup::WarmUpGate;
 
-use crate::ScenarioMeta;
+use crate::external::ExternalScenario;
+use crate::history::RetirementReason;
+use crate::manager::{
+    BeamConfig, Branch, DeltaBuilder, ExclusionKey, ExpansionOutcome, ScenarioManager, Verdict,
+};
+use crate::priors::PriorScenario;
+use crate::state::ScenarioManagerState;
+use crate::validate::Rejection;
+use crate::{DepthQuota, Renormalization, ScenarioMeta, WeightMode};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetailScenarioDelta {
     pub scenario_id: u64,
     pub customer_id: u64,
-    pub delta_cents: i64,
+    pub delta_cents: Money,
+    /// Variance of `delta_cents` in squared cents, as the predictor gave it;
+    /// 0 for point estimates.
+    #[serde(default)]
+    pub variance: i64,
+}
+
+/// Demand shift for one SKU and cohort under a price-change scenario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkuScenarioDelta {
+    pub scenario_id: u64,
+    pub sku_id: SkuId,
+    pub cohort_id: CohortId,
+    pub delta_qty: i64,
+    pub delta_cents: Money,
+}
+
+/// One customer's spend delta in an externally defined scenario.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SpendSeed {
+    pub customer_id: CustomerId,
+    pub delta_cents: Money,
+}
+
+/// Spend shifted into or out of one channel under a channel scenario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelScenarioDelta {
+    pub scenario_id: u64,
+    pub channel: ChannelId,
+    pub delta_cents: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,8 +67,64 @@ pub struct RetailBeamConfig {
     pub beam_width: usize,
     pub min_prob: f64,
     pub branch_prob: f64,
-    pub delta_multiplier: f64,
-    pub min_delta_cents: i64,
+    /// Branch probability of a channel outage, which unlike an order's
+    /// branch is not something the customer base does every epoch.
+    #[serde(default = "default_outage_prob")]
+    pub outage_prob: f64,
+    /// Multiplier applied to predicted deltas, in basis points.
+    pub delta_multiplier_bps: i64,
+    pub min_delta_cents: Money,
+    /// Rounding used when applying `delta_multiplier_bps`.
+    #[serde(default)]
+    pub rounding: Rounding,
+    /// Upper bound on active scenarios across all beam partitions.
+    #[serde(default)]
+    pub global_cap: Option<usize>,
+    /// Upper bound on partitions with active scenarios; the least recently
+    /// branched partition's scenarios retire to make room for a new one.
+    #[serde(default)]
+    pub max_keys: Option<usize>,
+    /// Factor beam weights are scaled by per epoch; `None` disables decay.
+    #[serde(default)]
+    pub epoch_decay: Option<f64>,
+    /// Epochs a scenario lives past its creation or last confirmation.
+    #[serde(default)]
+    pub ttl_epochs: Option<u64>,
+    /// Per-depth caps applied alongside `beam_width`.
+    #[serde(default)]
+    pub depth_quotas: Vec<DepthQuota>,
+    /// Slots reserved for the highest-impact scenarios that probability
+    /// ranking would drop; scenarios under `min_prob` never qualify.
+    #[serde(default)]
+    pub tail_slots: usize,
+    /// Children one triggering event may create, branched off the heaviest
+    /// eligible parents; `None` extends every eligible parent.
+    #[serde(default)]
+    pub max_children_per_event: Option<usize>,
+    #[serde(default)]
+    pub weight_mode: WeightMode,
+    #[serde(default)]
+    pub renormalization: Renormalization,
+}
+
+impl RetailBeamConfig {
+    /// The settings the shared beam runs on.
+    pub fn beam_config(&self) -> BeamConfig {
+        BeamConfig {
+            max_depth: self.max_depth,
+            beam_width: self.beam_width,
+            min_prob: self.min_prob,
+            global_cap: self.global_cap,
+            max_keys: self.max_keys,
+            epoch_decay: self.epoch_decay,
+            ttl_epochs: self.ttl_epochs,
+            depth_quotas: self.depth_quotas.clone(),
+            tail_slots: self.tail_slots,
+            max_children_per_event: self.max_children_per_event,
+            weight_mode: self.weight_mode,
+            renormalization: self.renormalization,
+        }
+    }
 }
 
 impl Default for RetailBeamConfig {
@@ -34,146 +134,584 @@ impl Default for RetailBeamConfig {
             beam_width: 32,
             min_prob: 0.1,
             branch_prob: 0.5,
-            delta_multiplier: 0.3,
-            min_delta_cents: 3_000,
+            outage_prob: default_outage_prob(),
+            delta_multiplier_bps: 3_000,
+            min_delta_cents: Money::from_cents(3_000),
+            rounding: Rounding::default(),
+            global_cap: None,
+            max_keys: None,
+            epoch_decay: None,
+            ttl_epochs: None,
+            depth_quotas: Vec::new(),
+            tail_slots: 0,
+            max_children_per_event: None,
+            weight_mode: WeightMode::default(),
+            renormalization: Renormalization::default(),
         }
     }
 }
 
+fn default_outage_prob() -> f64 {
+    0.05
+}
+
 #[derive(Debug, Default)]
 pub struct RetailExpansionOutcome {
     pub created: Vec<ScenarioMeta>,
-    pub retired: Vec<ScenarioMeta>,
+    /// Retired scenarios with why each left the beam.
+    pub retired: Vec<(ScenarioMeta, RetirementReason)>,
     pub overlays_added: Vec<RetailScenarioDelta>,
     pub overlays_removed: Vec<RetailScenarioDelta>,
+    pub sku_overlays_added: Vec<SkuScenarioDelta>,
+    pub sku_overlays_removed: Vec<SkuScenarioDelta>,
+    pub channel_overlays_added: Vec<ChannelScenarioDelta>,
+    pub channel_overlays_removed: Vec<ChannelScenarioDelta>,
+    /// Scenarios retired because a [`SafetyValve`](crate::valve::SafetyValve)
+    /// cap bound; also in `retired`.
+    pub shed: usize,
+    /// Active scenarios per depth after this expansion.
+    pub depth_occupancy: BTreeMap<Depth, usize>,
+    /// Proposals turned down by a validator; never created.
+    pub rejected: Vec<Rejection>,
+    /// Parents left unextended because sources lag; backfilled once caught up.
+    pub deferred: usize,
+    /// Scenarios reweighted in place, such as decaying priors, with their
+    /// previous weight; the meta carries the new one.
+    pub reweighted: Vec<(ScenarioMeta, Prob)>,
 }
 
-pub struct RetailScenarioManager {
-    cfg: RetailBeamConfig,
-    predictor: Arc<dyn SpendDeltaPredictor>,
-    next_id: u64,
-    active: Vec<ScenarioMeta>,
-    overlays: HashMap<u64, RetailScenarioDelta>,
+/// The event an expansion branched on, kept to backfill throttled expansions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RetailTrigger {
+    Order(OrderPlaced, Option<i64>),
+    PriceChange(PriceChanged, Option<i64>),
+    ChannelOutage(Channel, Vec<(ChannelId, i64)>),
 }
 
-impl RetailScenarioManager {
-    pub fn new(cfg: RetailBeamConfig, predictor: Arc<dyn SpendDeltaPredictor>) -> Self {
-        Self {
-            cfg,
-            predictor,
-            next_id: 1,
-            active: Vec::new(),
-            overlays: HashMap::new(),
+/// Overlay owned by one scenario: a customer spend delta from an order, the
+/// customer spend deltas of an external plan, the SKU demand deltas from a
+/// price change, or the channel spend shifts from a channel outage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetailOverlay {
+    Spend(RetailScenarioDelta),
+    Spends(Vec<RetailScenarioDelta>),
+    Demand(Vec<SkuScenarioDelta>),
+    Channel(Vec<ChannelScenarioDelta>),
+}
+
+/// Maps a triggering event to its independence key for beam partitioning.
+pub type PartitionKeyFn = Arc<dyn Fn(&OrderPlaced) -> u64 + Send + Sync>;
+
+impl From<ExpansionOutcome<RetailOverlay>> for RetailExpansionOutcome {
+    fn from(outcome: ExpansionOutcome<RetailOverlay>) -> Self {
+        let ExpansionOutcome {
+            created,
+            retired,
+            overlays_added,
+            overlays_removed,
+            shed,
+            depth_occupancy,
+            rejected,
+            deferred,
+            reweighted,
+        } = outcome;
+        let mut flat = RetailExpansionOutcome {
+            created,
+            retired,
+            shed,
+            depth_occupancy,
+            rejected,
+            deferred,
+            reweighted,
+            ..RetailExpansionOutcome::default()
+        };
+        for overlay in overlays_added {
+            match overlay {
+                RetailOverlay::Spend(delta) => flat.overlays_added.push(delta),
+                RetailOverlay::Spends(deltas) => flat.overlays_added.extend(deltas),
+                RetailOverlay::Demand(deltas) => flat.sku_overlays_added.extend(deltas),
+                RetailOverlay::Channel(deltas) => flat.channel_overlays_added.extend(deltas),
+            }
+        }
+        for overlay in overlays_removed {
+            match overlay {
+                RetailOverlay::Spend(delta) => flat.overlays_removed.push(delta),
+                RetailOverlay::Spends(deltas) => flat.overlays_removed.extend(deltas),
+                RetailOverlay::Demand(deltas) => flat.sku_overlays_removed.extend(deltas),
+                RetailOverlay::Channel(deltas) => flat.channel_overlays_removed.extend(deltas),
+            }
         }
+        flat
     }
+}
 
-    pub fn expand_order(&mut self, order: &OrderPlaced) -> RetailExpansionOutcome {
-        let mut outcome = RetailExpansionOutcome::default();
+/// Retail's side of expansion: the predictor each kind of event branches
+/// with, and how its deltas are scaled.
+struct RetailDeltas {
+    cfg: RetailBeamConfig,
+    predictor: Arc<dyn SpendDeltaPredictor>,
+    elasticity: Option<Arc<dyn ElasticityPredictor>>,
+    channel_shift: Option<Arc<dyn ChannelShiftPredictor>>,
+    partition_key: Option<PartitionKeyFn>,
+    /// Holds orders from customers the spend predictor has not warmed up on.
+    warm_up: Option<WarmUpGate<dyn SpendDeltaPredictor>>,
+}
 
-        let mut survivors = Vec::new();
-        let mut retired = Vec::new();
+impl DeltaBuilder<RetailTrigger, RetailOverlay> for RetailDeltas {
+    fn branch(
+        &self,
+        epoch: EpochTime,
+        _features: Option<&dyn FeatureStore>,
+        event: &RetailTrigger,
+    ) -> Option<Branch> {
+        let branch_prob = self.cfg.branch_prob;
+        // Price changes and outages are not tied to a partition key, so they
+        // only extend unpartitioned branches
+        match event {
+            RetailTrigger::Order(order, base_value) => {
+                if let Some(gate) = &self.warm_up {
+                    if !gate.admits(epoch, order.customer_id) {
+                        return None;
+                    }
+                }
+                Some(Branch {
+                    partition: self.partition_key.as_ref().map(|key| key(order)),
+                    base_value: *base_value,
+                    branch_prob,
+                    exclusion: Some(ExclusionKey { kind: "spend", id: order.customer_id }),
+                })
+            }
+            RetailTrigger::PriceChange(change, base_qty) => {
+                self.elasticity.as_ref().map(|_| Branch {
+                    partition: None,
+                    base_value: *base_qty,
+                    branch_prob,
+                    exclusion: Some(ExclusionKey { kind: "price", id: change.sku_id }),
+                })
+            }
+            RetailTrigger::ChannelOutage(outage, projected_spend) => {
+                self.channel_shift.as_ref().map(|_| Branch {
+                    partition: None,
+                    base_value: projected_spend
+                        .iter()
+                        .find(|(channel, _)| *channel == outage.id())
+                        .map(|&(_, cents)| cents),
+                    branch_prob: self.cfg.outage_prob,
+                    exclusion: Some(ExclusionKey { kind: "outage", id: outage.id() }),
+                })
+            }
+        }
+    }
 
-        for meta in self.active.drain(..) {
-            if meta.weight.0 < self.cfg.min_prob || meta.depth >= self.cfg.max_depth {
-                retired.push(meta);
-            } else {
-                survivors.push(meta);
+    fn build(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &RetailTrigger,
+        scenario_id: u64,
+    ) -> RetailOverlay {
+        match event {
+            RetailTrigger::Order(order, _) => {
+                let predictor = self.spend_predictor(ctx, order);
+                let delta = predictor.predict_delta(ctx, order);
+                let variance = predictor.delta_variance(ctx, order);
+                self.spend_delta(scenario_id, order, delta, variance)
+            }
+            RetailTrigger::PriceChange(change, _) => {
+                let deltas = self.elasticity.iter().flat_map(|predictor| {
+                    predictor.predict_demand(ctx, change).into_iter().map(|d| SkuScenarioDelta {
+                        scenario_id,
+                        sku_id: d.sku_id,
+                        cohort_id: d.cohort_id,
+                        delta_qty: d.delta_qty,
+                        delta_cents: d.delta_cents,
+                    })
+                });
+                RetailOverlay::Demand(deltas.collect())
+            }
+            RetailTrigger::ChannelOutage(outage, projected_spend) => {
+                let deltas = self.channel_shift.iter().flat_map(|predictor| {
+                    predictor.predict_shift(ctx, *outage, projected_spend).into_iter().map(|d| {
+                        ChannelScenarioDelta {
+                            scenario_id,
+                            channel: d.channel,
+                            delta_cents: d.delta_cents,
+                        }
+                    })
+                });
+                RetailOverlay::Channel(deltas.collect())
             }
         }
+    }
 
-        let mut candidates = survivors.clone();
+    fn build_outcomes(
+        &self,
+        ctx: &PredictionContext<'_>,
+        event: &RetailTrigger,
+        first_id: u64,
+    ) -> Vec<(f64, RetailOverlay)> {
+        let RetailTrigger::Order(order, _) = event else {
+            return vec![(1.0, self.build(ctx, event, first_id))];
+        };
+        let predictor = self.spend_predictor(ctx, order);
+        let variance = predictor.delta_variance(ctx, order);
+        (0u64..)
+            .zip(predictor.delta_outcomes(ctx, order))
+            .map(|(index, outcome)| {
+                let scenario_id = first_id.wrapping_add(index);
+                (outcome.probability, self.spend_delta(scenario_id, order, outcome.value, variance))
+            })
+            .collect()
+    }
 
-        let parents_iter = std::iter::once(ScenarioMeta {
-            id: 0,
-            parent: None,
-            depth: 0,
-            weight: Prob(1.0),
-        })
-        .chain(survivors.into_iter());
+    fn impact(&self, overlay: &RetailOverlay) -> i64 {
+        match overlay {
+            RetailOverlay::Spend(delta) => delta.delta_cents.cents().saturating_abs(),
+            RetailOverlay::Spends(deltas) => deltas
+                .iter()
+                .fold(0i64, |sum, d| sum.saturating_add(d.delta_cents.cents().saturating_abs())),
+            RetailOverlay::Demand(deltas) => deltas
+                .iter()
+                .fold(0i64, |sum, d| sum.saturating_add(d.delta_cents.cents().saturating_abs())),
+            RetailOverlay::Channel(deltas) => deltas
+                .iter()
+                .fold(0i64, |sum, d| sum.saturating_add(d.delta_cents.cents().saturating_abs())),
+        }
+    }
 
-        let predicted_delta = self.predict_delta(order);
+    fn rows(&self, overlay: &RetailOverlay) -> usize {
+        match overlay {
+            RetailOverlay::Spend(_) => 1,
+            RetailOverlay::Spends(deltas) => deltas.len(),
+            RetailOverlay::Demand(deltas) => deltas.len(),
+            RetailOverlay::Channel(deltas) => deltas.len(),
+        }
+    }
 
-        for parent in parents_iter {
-            if parent.depth >= self.cfg.max_depth {
-                continue;
+    fn label(&self, event: &RetailTrigger, overlay: &RetailOverlay) -> Option<String> {
+        match (event, overlay) {
+            (RetailTrigger::Order(order, _), RetailOverlay::Spend(delta)) => Some(format!(
+                "customer {} spends {} more",
+                order.customer_id, delta.delta_cents
+            )),
+            (RetailTrigger::PriceChange(change, _), _) => Some(format!(
+                "sku {} repriced from {} to {}",
+                change.sku_id, change.old_cents, change.new_cents
+            )),
+            (RetailTrigger::ChannelOutage(outage, _), _) => {
+                Some(format!("channel {outage:?} goes down"))
             }
-            let parent_weight = if parent.id == 0 { 1.0 } else { parent.weight.0 };
-            let child_weight = parent_weight * self.cfg.branch_prob;
-            if child_weight < self.cfg.min_prob {
-                continue;
-            }
-            let child_id = self.next_id;
-            self.next_id = self.next_id.wrapping_add(1);
-
-            let meta = ScenarioMeta {
-                id: child_id,
-                parent: if parent.id == 0 { None } else { Some(parent.id) },
-                depth: parent.depth + 1,
-                weight: Prob(child_weight),
-            };
-
-            let delta = RetailScenarioDelta {
-                scenario_id: child_id,
-                customer_id: order.customer_id,
-                delta_cents: predicted_delta,
-            };
-
-            self.overlays.insert(child_id, delta.clone());
-
-            outcome.created.push(meta.clone());
-            outcome.overlays_added.push(delta);
-            candidates.push(meta);
+            _ => None,
         }
+    }
 
-        let mut seen: HashSet<u64> = HashSet::new();
-        let mut sorted = candidates;
-        sorted.sort_by(|a, b| b.weight.0.partial_cmp(&a.weight.0).unwrap_or(Ordering::Equal));
-
-        let mut retained = Vec::new();
-        for meta in sorted {
-            if seen.contains(&meta.id) {
-                continue;
+    fn tags(&self, event: &RetailTrigger, _overlay: &RetailOverlay) -> Vec<String> {
+        match event {
+            RetailTrigger::Order(order, _) => {
+                vec!["spend".to_string(), format!("customer:{}", order.customer_id)]
             }
-            seen.insert(meta.id);
-            if retained.len() < self.cfg.beam_width {
-                retained.push(meta);
-            } else {
-                retired.push(meta);
+            RetailTrigger::PriceChange(change, _) => {
+                vec!["price_change".to_string(), format!("sku:{}", change.sku_id)]
+            }
+            RetailTrigger::ChannelOutage(outage, _) => {
+                vec!["channel_outage".to_string(), format!("channel:{}", outage.id())]
             }
         }
+    }
+}
 
-        for meta in retired.iter() {
-            if let Some(delta) = self.overlays.remove(&meta.id) {
-                outcome.overlays_removed.push(delta);
-            }
+impl RetailDeltas {
+    /// The spend predictor for `order`, or the warm-up fallback while its
+    /// customer is warming up.
+    fn spend_predictor(
+        &self,
+        ctx: &PredictionContext<'_>,
+        order: &OrderPlaced,
+    ) -> &Arc<dyn SpendDeltaPredictor> {
+        self.warm_up
+            .as_ref()
+            .and_then(|gate| gate.fallback(ctx.epoch, order.customer_id))
+            .unwrap_or(&self.predictor)
+    }
+
+    /// A predicted spend delta as scenario `scenario_id`'s overlay, scaled
+    /// and floored as configured.
+    fn spend_delta(
+        &self,
+        scenario_id: u64,
+        order: &OrderPlaced,
+        mut delta: Money,
+        mut variance: i64,
+    ) -> RetailOverlay {
+        let multiplier_bps = self.cfg.delta_multiplier_bps;
+        if multiplier_bps != BPS_SCALE {
+            delta = delta.saturating_scale_bps_with(multiplier_bps, self.cfg.rounding);
+            variance = scale_variance(variance, multiplier_bps);
+        }
+        RetailOverlay::Spend(RetailScenarioDelta {
+            scenario_id,
+            customer_id: order.customer_id,
+            delta_cents: std::cmp::max(delta, self.cfg.min_delta_cents),
+            variance,
+        })
+    }
+}
+
+/// The customer spend deltas a seeded scenario applies.
+fn spend_overlay(scenario_id: u64, rows: &[SpendSeed]) -> RetailOverlay {
+    let deltas = rows.iter().map(|row| RetailScenarioDelta {
+        scenario_id,
+        customer_id: row.customer_id,
+        delta_cents: row.delta_cents,
+        variance: 0,
+    });
+    RetailOverlay::Spends(deltas.collect())
+}
+
+/// The variance of a delta scaled by `bps`, which scales by its square.
+fn scale_variance(variance: i64, bps: i64) -> i64 {
+    let scale = BPS_SCALE as i128;
+    let scaled = (variance as i128) * (bps as i128) * (bps as i128) / (scale * scale);
+    scaled.min(i64::MAX as i128) as i64
+}
+
+/// Checkpoint of a [`RetailScenarioManager`]'s scenario tree; see
+/// [`ScenarioManagerState`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RetailScenarioState(ScenarioManagerState<RetailTrigger, RetailOverlay>);
+
+/// The shared beam as retail instantiates it.
+pub type RetailBeam = ScenarioManager<RetailTrigger, RetailOverlay>;
+
+/// The shared [`ScenarioManager`] branched on orders, price changes and
+/// channel outages. Calls that need no retail event or predictor, such as
+/// [`ScenarioManager::advance_epoch`], go to the manager it derefs to.
+pub struct RetailScenarioManager {
+    deltas: RetailDeltas,
+    beam: RetailBeam,
+}
+
+impl RetailScenarioManager {
+    pub fn new(cfg: RetailBeamConfig, predictor: Arc<dyn SpendDeltaPredictor>) -> Self {
+        Self {
+            beam: ScenarioManager::new(cfg.beam_config()),
+            deltas: RetailDeltas {
+                cfg,
+                predictor,
+                elasticity: None,
+                channel_shift: None,
+                partition_key: None,
+                warm_up: None,
+            },
         }
+    }
 
-        outcome.retired.extend(retired);
-        self.active = retained;
+    /// A manager resuming from `state`; see [`ScenarioManager::restore`].
+    pub fn restore(
+        state: RetailScenarioState,
+        cfg: RetailBeamConfig,
+        predictor: Arc<dyn SpendDeltaPredictor>,
+    ) -> Self {
+        let mut manager = Self::new(cfg, predictor);
+        manager.beam = ScenarioManager::restore(state.0, manager.deltas.cfg.beam_config());
+        manager
+    }
 
-        outcome
+    pub fn snapshot(&self) -> RetailScenarioState {
+        RetailScenarioState(self.beam.snapshot())
     }
 
-    pub fn active_weights(&self) -> Vec<(u64, f64)> {
-        self.active
-            .iter()
-            .map(|meta| (meta.id, meta.weight.0))
-            .collect()
+    pub fn config(&self) -> &RetailBeamConfig {
+        &self.deltas.cfg
     }
 
-    pub fn active_len(&self) -> usize {
-        self.active.len()
+    /// Use `cfg` from the next event on; see [`ScenarioManager::set_config`].
+    pub fn set_config(&mut self, cfg: RetailBeamConfig) {
+        self.beam.set_config(cfg.beam_config());
+        self.deltas.cfg = cfg;
     }
 
-    fn predict_delta(&self, order: &OrderPlaced) -> i64 {
-        let mut delta = self.predictor.predict_delta(order);
-        if (self.cfg.delta_multiplier - 1.0).abs() > f64::EPSILON {
-            delta = ((delta as f64) * self.cfg.delta_multiplier).round() as i64;
-        }
-        if delta < self.cfg.min_delta_cents {
-            self.cfg.min_delta_cents
-        } else {
-            delta
-        }
+    /// Branch later orders with `predictor`; existing overlays are kept.
+    pub fn set_predictor(&mut self, predictor: Arc<dyn SpendDeltaPredictor>) {
+        self.deltas.predictor = predictor;
+    }
+
+    /// Partition the beam by an independence key: events only extend branches
+    /// created under the same key, and `beam_width` applies per partition.
+    pub fn with_partition_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&OrderPlaced) -> u64 + Send + Sync + 'static,
+    {
+        self.deltas.partition_key = Some(Arc::new(key));
+        self.beam = self.beam.with_partitioning();
+        self
+    }
+
+    /// Configure the shared beam through its own builders, such as
+    /// [`ScenarioManager::with_safety_valve`].
+    pub fn with_beam<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(RetailBeam) -> RetailBeam,
+    {
+        self.beam = configure(self.beam);
+        self
+    }
+
+    /// Branch on orders only from customers `gate` reports ready, or with
+    /// its fallback predictor until they are.
+    pub fn with_warm_up(mut self, gate: WarmUpGate<dyn SpendDeltaPredictor>) -> Self {
+        self.deltas.warm_up = Some(gate);
+        self
+    }
+
+    /// Branch price-change scenarios ("10% hike on SKU 42") using this predictor.
+    pub fn with_elasticity_predictor(mut self, predictor: Arc<dyn ElasticityPredictor>) -> Self {
+        self.deltas.elasticity = Some(predictor);
+        self
+    }
+
+    /// Branch channel-outage scenarios ("web is down") using this predictor.
+    pub fn with_channel_predictor(mut self, predictor: Arc<dyn ChannelShiftPredictor>) -> Self {
+        self.deltas.channel_shift = Some(predictor);
+        self
+    }
+
+    /// Expand the beam on a base event seen at `epoch`; `base_value` is the
+    /// current base-view value for the event's key, if the caller tracks it.
+    pub fn expand_order(
+        &mut self,
+        epoch: EpochTime,
+        order: &OrderPlaced,
+        base_value: Option<i64>,
+    ) -> RetailExpansionOutcome {
+        self.expand(epoch, &RetailTrigger::Order(order.clone(), base_value))
+    }
+
+    /// Expand the beam on a (real or hypothetical) price change; `base_qty` is
+    /// the SKU's baseline units. Price changes are not tied to a partition key,
+    /// so they only extend unpartitioned branches. No-op without an
+    /// elasticity predictor.
+    pub fn expand_price_change(
+        &mut self,
+        epoch: EpochTime,
+        change: &PriceChanged,
+        base_qty: Option<i64>,
+    ) -> RetailExpansionOutcome {
+        self.expand(epoch, &RetailTrigger::PriceChange(change.clone(), base_qty))
+    }
+
+    /// Expand the beam on a hypothetical outage of `outage`, shifting spend
+    /// between channels rather than scaling totals; `projected_spend` is each
+    /// channel's spend projected over the outage, so the overlay moves only
+    /// future spend. Branches with `outage_prob`. Like price changes, outages
+    /// only extend unpartitioned branches. No-op without a channel predictor.
+    pub fn expand_channel_outage(
+        &mut self,
+        epoch: EpochTime,
+        outage: Channel,
+        projected_spend: &[(ChannelId, i64)],
+    ) -> RetailExpansionOutcome {
+        self.expand(epoch, &RetailTrigger::ChannelOutage(outage, projected_spend.to_vec()))
+    }
+
+    /// Settle spend branches against a realized later order from their
+    /// customer. An order at least as large as the predicted extra spend
+    /// confirms the branch, and of a branch's spend tiers only the largest
+    /// one the order covers stays; any order contradicts a predicted drop. See
+    /// [`ScenarioManager::reconcile`].
+    pub fn reconcile(&mut self, epoch: EpochTime, order: &OrderPlaced) -> RetailExpansionOutcome {
+        let spent = order.total_cents().cents();
+        self.beam
+            .reconcile(epoch, |trigger, overlay| {
+                let (RetailTrigger::Order(placed, _), RetailOverlay::Spend(delta)) =
+                    (trigger, overlay)
+                else {
+                    return None;
+                };
+                if placed.customer_id != order.customer_id
+                    || placed.order_id == order.order_id
+                    || placed.ts_ms > order.ts_ms
+                {
+                    return None;
+                }
+                let predicted = delta.delta_cents.cents();
+                if predicted < 0 {
+                    Some(Verdict::Contradicted)
+                } else if spent >= predicted {
+                    Some(Verdict::Confirmed { miss: (spent - predicted).unsigned_abs() })
+                } else {
+                    None
+                }
+            })
+            .into()
+    }
+
+    /// Seed an externally defined scenario at the root, weighted by its
+    /// supplied probability and tagged with its source. Seeds are admitted
+    /// like branches, within the beam and safety caps; predictor-driven
+    /// branches extend them and carry their tag until
+    /// [`ScenarioManager::retire_external`].
+    pub fn seed_external(
+        &mut self,
+        epoch: EpochTime,
+        scenario: &ExternalScenario<SpendSeed>,
+    ) -> RetailExpansionOutcome {
+        let overlay = |scenario_id| spend_overlay(scenario_id, &scenario.rows);
+        let tag = scenario.tag();
+        self.beam.seed_external(&self.deltas, epoch, tag, scenario.probability, overlay).into()
+    }
+
+    /// Inject a hypothetical with the overlay rows in `deltas` as a pinned
+    /// scenario tagged `what_if:<label>`; withdraw it with
+    /// [`ScenarioManager::retire_external`].
+    pub fn inject_scenario(
+        &mut self,
+        epoch: EpochTime,
+        deltas: &[SpendSeed],
+        probability: f64,
+        label: &str,
+    ) -> RetailExpansionOutcome {
+        let overlay = |scenario_id| spend_overlay(scenario_id, deltas);
+        self.beam.inject_scenario(&self.deltas, epoch, probability, label, overlay).into()
+    }
+
+    /// Seed a prior at the root, as [`Self::seed_external`] does under the
+    /// tag `prior:<name>`. Its weight then follows the prior's decay schedule
+    /// through [`ScenarioManager::decay_priors`].
+    pub fn seed_prior(
+        &mut self,
+        epoch: EpochTime,
+        prior: &PriorScenario<SpendSeed>,
+    ) -> RetailExpansionOutcome {
+        let overlay = |scenario_id| spend_overlay(scenario_id, &prior.rows);
+        self.beam.seed_prior(&self.deltas, epoch, prior.tag(), prior.schedule, overlay).into()
+    }
+
+    /// Report how far sources lag at `epoch`; see [`ScenarioManager::observe_lag`].
+    pub fn observe_lag(&mut self, epoch: EpochTime, lag_ms: u64) -> Vec<RetailExpansionOutcome> {
+        self.beam.observe_lag(&self.deltas, epoch, lag_ms).into_iter().map(Into::into).collect()
+    }
+
+    pub fn warm_up(&self) -> Option<&WarmUpGate<dyn SpendDeltaPredictor>> {
+        self.deltas.warm_up.as_ref()
+    }
+
+    fn expand(&mut self, epoch: EpochTime, event: &RetailTrigger) -> RetailExpansionOutcome {
+        self.beam.expand(&self.deltas, epoch, event).into()
+    }
+}
+
+impl Deref for RetailScenarioManager {
+    type Target = RetailBeam;
+
+    fn deref(&self) -> &Self::Target {
+        &self.beam
+    }
+}
+
+impl DerefMut for RetailScenarioManager {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.beam
     }
 }
@@ -0,0 +1,84 @@
+//! Checkpoints of a manager's scenario tree, so a long-running process can
+//! resume after a restart without losing its beam.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use anyhow::{Context, Result};
+#[cfg(feature = "fs")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tw_core::{EpochTime, ScenarioId};
+
+use crate::lineage::LineageIndex;
+use crate::priors::PriorSchedule;
+use crate::ScenarioMeta;
+
+/// The scenario tree of a [`ScenarioManager`](crate::manager::ScenarioManager):
+/// live scenarios with their overlays and triggering events, seeded priors,
+/// lineage, exclusion groups, frozen keys, pins and the next ids to hand
+/// out. Retired history, the bulk audit trail and deferred expansions are
+/// not kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioManagerState<E, D> {
+    pub next_id: ScenarioId,
+    pub active: Vec<ScenarioMeta>,
+    #[serde(default)]
+    pub external: Vec<ScenarioMeta>,
+    #[serde(default)]
+    pub priors: BTreeMap<ScenarioId, (PriorSchedule, EpochTime)>,
+    pub overlays: HashMap<ScenarioId, D>,
+    pub triggers: HashMap<ScenarioId, E>,
+    #[serde(default)]
+    pub lineage: LineageIndex,
+    pub next_group: u64,
+    /// Groups of live parents, so siblings branched after a restore join
+    /// the alternatives branched before it.
+    #[serde(default)]
+    pub exclusion_groups: Vec<ExclusionGroupState>,
+    #[serde(default)]
+    pub frozen: BTreeSet<u64>,
+    /// Epoch each beam scenario was created or last confirmed, for its TTL.
+    #[serde(default)]
+    pub reinforced: HashMap<ScenarioId, EpochTime>,
+    #[serde(default)]
+    pub pinned: BTreeSet<ScenarioId>,
+    /// Latest epoch the beam was advanced to, so a resumed run can tell
+    /// which scheduled injections the tree already holds.
+    #[serde(default)]
+    pub advanced: Option<EpochTime>,
+}
+
+/// The exclusion group of the children `parent` branched on event `kind`
+/// about `id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExclusionGroupState {
+    pub parent: ScenarioId,
+    pub kind: String,
+    pub id: u64,
+    pub group: u64,
+}
+
+/// Write `state` as JSON to `path` through a temporary file renamed into
+/// place, so a crash mid-write leaves the previous checkpoint whole.
+#[cfg(feature = "fs")]
+pub fn write_json<S: Serialize>(state: &S, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    let json = serde_json::to_vec(state).context("serializing scenario checkpoint")?;
+    std::fs::write(&tmp, json)
+        .with_context(|| format!("writing scenario checkpoint {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("replacing scenario checkpoint {}", path.display()))
+}
+
+#[cfg(feature = "fs")]
+pub fn read_json<S: DeserializeOwned>(path: impl AsRef<Path>) -> Result<S> {
+    let path = path.as_ref();
+    let raw = std::fs::read(path)
+        .with_context(|| format!("reading scenario checkpoint {}", path.display()))?;
+    serde_json::from_slice(&raw)
+        .with_context(|| format!("parsing scenario checkpoint {}", path.display()))
+}
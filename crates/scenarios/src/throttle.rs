@@ -0,0 +1,91 @@
+//! Lag-aware throttling of deep speculation. While sources fall behind,
+//! managers only create shallow scenarios and queue the expansions they skip,
+//! then backfill them once the sources catch up.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use tw_core::{Depth, ScenarioId};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LagThrottleConfig {
+    /// Source lag above which expansion is limited to `max_depth`.
+    pub max_lag_ms: u64,
+    /// Lag at or below which full expansion resumes and deferred expansions
+    /// are backfilled.
+    #[serde(default)]
+    pub resume_lag_ms: u64,
+    /// Deepest scenario created while throttled.
+    pub max_depth: Depth,
+    /// Deferred expansions kept for backfill; the oldest are dropped first.
+    pub max_deferred: usize,
+}
+
+impl Default for LagThrottleConfig {
+    fn default() -> Self {
+        Self { max_lag_ms: 30_000, resume_lag_ms: 0, max_depth: 1, max_deferred: 1_024 }
+    }
+}
+
+/// An expansion skipped while throttled: the event it branched on and the
+/// parents it did not extend.
+#[derive(Debug, Clone)]
+pub struct Deferred<T> {
+    pub parents: Vec<ScenarioId>,
+    pub trigger: T,
+}
+
+/// Tracks whether sources lag and queues the expansions skipped meanwhile.
+#[derive(Debug)]
+pub struct LagThrottle<T> {
+    cfg: LagThrottleConfig,
+    lagging: bool,
+    deferred: VecDeque<Deferred<T>>,
+    dropped: u64,
+}
+
+impl<T> LagThrottle<T> {
+    pub fn new(cfg: LagThrottleConfig) -> Self {
+        Self { cfg, lagging: false, deferred: VecDeque::new(), dropped: 0 }
+    }
+
+    /// Record the current source lag. Once it falls to `resume_lag_ms`, the
+    /// deferred expansions are returned, oldest first, for the caller to replay.
+    pub fn observe_lag(&mut self, lag_ms: u64) -> Vec<Deferred<T>> {
+        if lag_ms > self.cfg.max_lag_ms {
+            self.lagging = true;
+        } else if lag_ms <= self.cfg.resume_lag_ms {
+            self.lagging = false;
+        }
+        if self.lagging {
+            return Vec::new();
+        }
+        self.deferred.drain(..).collect()
+    }
+
+    pub fn is_lagging(&self) -> bool {
+        self.lagging
+    }
+
+    /// Parents at this depth or deeper are not extended right now.
+    pub fn depth_limit(&self) -> Option<Depth> {
+        self.lagging.then_some(self.cfg.max_depth)
+    }
+
+    pub fn defer(&mut self, deferred: Deferred<T>) {
+        self.deferred.push_back(deferred);
+        while self.deferred.len() > self.cfg.max_deferred {
+            self.deferred.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    pub fn deferred_len(&self) -> usize {
+        self.deferred.len()
+    }
+
+    /// Deferred expansions dropped to respect `max_deferred` since startup.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
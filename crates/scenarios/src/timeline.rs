@@ -0,0 +1,137 @@
+//! Per-scenario lifecycle timelines for Gantt-style visualisation.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tw_core::{Depth, EpochTime, ScenarioId};
+
+use crate::history::RetirementReason;
+use crate::ScenarioMeta;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimelineEntry {
+    Created {
+        epoch: EpochTime,
+        parent: Option<ScenarioId>,
+        depth: Depth,
+        weight: f64,
+        label: Option<String>,
+        tags: Vec<String>,
+    },
+    OverlayActivated { epoch: EpochTime },
+    WeightChanged { epoch: EpochTime, weight: f64 },
+    Retired { epoch: EpochTime, reason: RetirementReason },
+}
+
+impl TimelineEntry {
+    pub fn epoch(&self) -> EpochTime {
+        match self {
+            TimelineEntry::Created { epoch, .. }
+            | TimelineEntry::OverlayActivated { epoch }
+            | TimelineEntry::WeightChanged { epoch, .. }
+            | TimelineEntry::Retired { epoch, .. } => *epoch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Timeline {
+    pub scenario_id: ScenarioId,
+    pub entries: Vec<TimelineEntry>,
+    #[serde(skip)]
+    retired_at: Option<EpochTime>,
+}
+
+/// Records scenario lifecycle events. Timelines of scenarios retired more
+/// than `retain_epochs` ago are dropped on [`ScenarioTimelines::advance`];
+/// live scenarios are always kept.
+#[derive(Debug)]
+pub struct ScenarioTimelines {
+    retain_epochs: u64,
+    timelines: BTreeMap<ScenarioId, Timeline>,
+}
+
+impl ScenarioTimelines {
+    pub fn new(retain_epochs: u64) -> Self {
+        Self { retain_epochs, timelines: BTreeMap::new() }
+    }
+
+    pub fn record_created(&mut self, epoch: EpochTime, meta: &ScenarioMeta) {
+        self.push(
+            meta.id,
+            TimelineEntry::Created {
+                epoch,
+                parent: meta.parent,
+                depth: meta.depth,
+                weight: meta.weight.0,
+                label: meta.label.clone(),
+                tags: meta.tags.clone(),
+            },
+        );
+    }
+
+    pub fn record_overlay(&mut self, epoch: EpochTime, scenario_id: ScenarioId) {
+        self.push(scenario_id, TimelineEntry::OverlayActivated { epoch });
+    }
+
+    pub fn record_weight(&mut self, epoch: EpochTime, scenario_id: ScenarioId, weight: f64) {
+        self.push(scenario_id, TimelineEntry::WeightChanged { epoch, weight });
+    }
+
+    pub fn record_retired(
+        &mut self,
+        epoch: EpochTime,
+        scenario_id: ScenarioId,
+        reason: RetirementReason,
+    ) {
+        self.push(scenario_id, TimelineEntry::Retired { epoch, reason });
+        if let Some(timeline) = self.timelines.get_mut(&scenario_id) {
+            timeline.retired_at = Some(epoch);
+        }
+    }
+
+    /// Drop timelines whose scenario retired before the retention window.
+    pub fn advance(&mut self, epoch: EpochTime) {
+        let cutoff = epoch.saturating_sub(self.retain_epochs);
+        self.timelines
+            .retain(|_, timeline| timeline.retired_at.map_or(true, |retired| retired >= cutoff));
+    }
+
+    pub fn get(&self, scenario_id: ScenarioId) -> Option<&Timeline> {
+        self.timelines.get(&scenario_id)
+    }
+
+    /// Timelines from the oldest retained ancestor down to `scenario_id`.
+    pub fn lineage(&self, scenario_id: ScenarioId) -> Vec<&Timeline> {
+        let mut lineage = Vec::new();
+        let mut next = Some(scenario_id);
+        while let Some(timeline) = next.and_then(|id| self.get(id)) {
+            lineage.push(timeline);
+            next = timeline.entries.iter().find_map(|entry| match entry {
+                TimelineEntry::Created { parent, .. } => *parent,
+                _ => None,
+            });
+        }
+        lineage.reverse();
+        lineage
+    }
+
+    pub fn to_json(&self, scenario_id: ScenarioId) -> Option<String> {
+        self.get(scenario_id).and_then(|timeline| serde_json::to_string(timeline).ok())
+    }
+
+    /// All retained timelines as a JSON array, ordered by scenario id.
+    pub fn export_json(&self) -> String {
+        let timelines: Vec<&Timeline> = self.timelines.values().collect();
+        serde_json::to_string(&timelines).unwrap_or_else(|_| String::from("[]"))
+    }
+
+    fn push(&mut self, scenario_id: ScenarioId, entry: TimelineEntry) {
+        self.timelines
+            .entry(scenario_id)
+            .or_insert_with(|| Timeline { scenario_id, entries: Vec::new(), retired_at: None })
+            .entries
+            .push(entry);
+    }
+}
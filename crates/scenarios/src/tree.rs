@@ -0,0 +1,62 @@
+//! Scenario trees and overlay values computed without a dataflow, for
+//! clients such as a browser UI that only hold scenario metadata and
+//! overlay rows.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use tw_core::ScenarioId;
+
+use crate::ScenarioMeta;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioNode {
+    #[serde(flatten)]
+    pub meta: ScenarioMeta,
+    pub children: Vec<ScenarioNode>,
+}
+
+/// Arrange scenarios under their parents. Scenarios whose parent is absent
+/// (retired, or a base-world child) become roots; siblings keep input order.
+pub fn build_tree(metas: &[ScenarioMeta]) -> Vec<ScenarioNode> {
+    let known: HashMap<ScenarioId, usize> =
+        metas.iter().enumerate().map(|(idx, meta)| (meta.id, idx)).collect();
+    let mut children: HashMap<ScenarioId, Vec<&ScenarioMeta>> = HashMap::new();
+    let mut roots = Vec::new();
+    for meta in metas {
+        match meta.parent.filter(|parent| known.contains_key(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(meta),
+            None => roots.push(meta),
+        }
+    }
+
+    fn attach(
+        meta: &ScenarioMeta,
+        children: &HashMap<ScenarioId, Vec<&ScenarioMeta>>,
+    ) -> ScenarioNode {
+        let kids = children
+            .get(&meta.id)
+            .map(|kids| kids.iter().map(|kid| attach(kid, children)).collect())
+            .unwrap_or_default();
+        ScenarioNode { meta: meta.clone(), children: kids }
+    }
+
+    roots.into_iter().map(|meta| attach(meta, &children)).collect()
+}
+
+/// Per-scenario values for every key an overlay touches: the base value
+/// plus the scenario's summed deltas, matching the dataflow overlay views.
+/// Keys missing from `base` start from zero.
+pub fn overlay_values<K, I>(base: &BTreeMap<K, i64>, deltas: I) -> BTreeMap<(ScenarioId, K), i64>
+where
+    K: Ord + Clone,
+    I: IntoIterator<Item = (ScenarioId, K, i64)>,
+{
+    let mut values: BTreeMap<(ScenarioId, K), i64> = BTreeMap::new();
+    for (sid, key, delta) in deltas {
+        let base_value = base.get(&key).copied().unwrap_or(0);
+        let value = values.entry((sid, key)).or_insert(base_value);
+        *value = value.saturating_add(delta);
+    }
+    values
+}
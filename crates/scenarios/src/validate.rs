@@ -0,0 +1,69 @@
+//! Policy hooks between proposing a scenario and committing it.
+//!
+//! Managers expand in two phases: the predictor proposes one child per
+//! eligible parent, then each proposal runs through the registered
+//! validators before its overlay is committed and it competes for the beam.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use tw_core::{EpochTime, ScenarioId};
+
+use crate::ScenarioMeta;
+
+/// A proposed child scenario and the overlay it would add.
+pub struct Proposal<'a, O> {
+    pub epoch: EpochTime,
+    /// Base-view value for the triggering event's key, if the caller tracks it.
+    pub base_value: Option<i64>,
+    pub meta: &'a ScenarioMeta,
+    pub overlay: &'a O,
+    /// Overlays of the parent and each ancestor still remembered, nearest
+    /// first, so a validator can judge the child against its whole lineage.
+    pub lineage: Vec<&'a O>,
+}
+
+/// Returns `Err(reason)` to reject a proposal.
+pub type ValidatorFn<O> = Arc<dyn Fn(&Proposal<'_, O>) -> Result<(), String> + Send + Sync>;
+
+/// A proposal a validator turned down.
+#[derive(Debug, Clone, Serialize)]
+pub struct Rejection {
+    pub scenario_id: ScenarioId,
+    pub validator: String,
+    pub reason: String,
+}
+
+/// Named validators, run in registration order; the first rejection wins.
+pub struct Validators<O> {
+    validators: Vec<(String, ValidatorFn<O>)>,
+}
+
+impl<O> Default for Validators<O> {
+    fn default() -> Self {
+        Self { validators: Vec::new() }
+    }
+}
+
+impl<O> Validators<O> {
+    pub fn push(&mut self, name: impl Into<String>, validator: ValidatorFn<O>) {
+        self.validators.push((name.into(), validator));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty()
+    }
+
+    pub fn check(&self, proposal: &Proposal<'_, O>) -> Result<(), Rejection> {
+        for (name, validator) in &self.validators {
+            if let Err(reason) = validator(proposal) {
+                return Err(Rejection {
+                    scenario_id: proposal.meta.id,
+                    validator: name.clone(),
+                    reason,
+                });
+            }
+        }
+        Ok(())
+    }
+}
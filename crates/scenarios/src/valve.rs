@@ -0,0 +1,137 @@
+//! Process-wide safety caps on scenario state, shared by every manager.
+
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tw_core::EpochTime;
+
+use crate::ScenarioMeta;
+
+/// Hard limits that hold regardless of beam configuration; `None` is unbounded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SafetyCaps {
+    /// Active scenarios summed over all managers sharing the valve.
+    #[serde(default)]
+    pub max_active_scenarios: Option<usize>,
+    /// Overlay rows summed over all managers sharing the valve.
+    #[serde(default)]
+    pub max_overlay_rows: Option<usize>,
+    #[serde(default)]
+    pub max_alerts_per_epoch: Option<u64>,
+}
+
+/// Scenarios and overlay rows one manager currently holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValveUsage {
+    pub scenarios: usize,
+    pub overlay_rows: usize,
+}
+
+#[derive(Debug, Default)]
+struct ValveState {
+    usage: ValveUsage,
+    alert_epoch: EpochTime,
+    alerts_in_epoch: u64,
+}
+
+/// Enforces [`SafetyCaps`] across managers. When a cap binds, the lightest
+/// scenarios are shed first and, among equal weights, the deepest; alerts
+/// beyond the per-epoch cap are dropped in arrival order.
+#[derive(Debug, Default)]
+pub struct SafetyValve {
+    caps: SafetyCaps,
+    state: Mutex<ValveState>,
+    alerts_shed: AtomicU64,
+}
+
+impl SafetyValve {
+    pub fn new(caps: SafetyCaps) -> Self {
+        Self { caps, ..Self::default() }
+    }
+
+    pub fn caps(&self) -> &SafetyCaps {
+        &self.caps
+    }
+
+    /// Trim a manager's `retained` scenarios so its usage, replacing `held`,
+    /// fits the global caps. Returns the shed scenarios and the usage the
+    /// manager now holds.
+    pub fn enforce<F>(
+        &self,
+        held: ValveUsage,
+        retained: &mut Vec<ScenarioMeta>,
+        rows_of: F,
+    ) -> (Vec<ScenarioMeta>, ValveUsage)
+    where
+        F: Fn(&ScenarioMeta) -> usize,
+    {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let others = ValveUsage {
+            scenarios: state.usage.scenarios.saturating_sub(held.scenarios),
+            overlay_rows: state.usage.overlay_rows.saturating_sub(held.overlay_rows),
+        };
+        let scenario_room = self
+            .caps
+            .max_active_scenarios
+            .map_or(usize::MAX, |cap| cap.saturating_sub(others.scenarios));
+        let row_room = self
+            .caps
+            .max_overlay_rows
+            .map_or(usize::MAX, |cap| cap.saturating_sub(others.overlay_rows));
+
+        retained.sort_by(|a, b| {
+            b.weight.0
+                .partial_cmp(&a.weight.0)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.depth.cmp(&b.depth))
+        });
+        let mut now = ValveUsage::default();
+        let mut keep = 0;
+        for meta in retained.iter() {
+            let rows = rows_of(meta);
+            if now.scenarios + 1 > scenario_room || now.overlay_rows + rows > row_room {
+                break;
+            }
+            now.scenarios += 1;
+            now.overlay_rows += rows;
+            keep += 1;
+        }
+        let shed = retained.split_off(keep);
+
+        state.usage = ValveUsage {
+            scenarios: others.scenarios + now.scenarios,
+            overlay_rows: others.overlay_rows + now.overlay_rows,
+        };
+        (shed, now)
+    }
+
+    /// Whether one more alert may fire in `epoch`.
+    pub fn admit_alert(&self, epoch: EpochTime) -> bool {
+        let Some(cap) = self.caps.max_alerts_per_epoch else {
+            return true;
+        };
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.alert_epoch != epoch {
+            state.alert_epoch = epoch;
+            state.alerts_in_epoch = 0;
+        }
+        if state.alerts_in_epoch >= cap {
+            self.alerts_shed.fetch_add(1, AtomicOrdering::Relaxed);
+            return false;
+        }
+        state.alerts_in_epoch += 1;
+        true
+    }
+
+    /// Alerts dropped by the per-epoch cap since the last call.
+    pub fn take_alerts_shed(&self) -> u64 {
+        self.alerts_shed.swap(0, AtomicOrdering::Relaxed)
+    }
+
+    /// Usage summed over all managers.
+    pub fn usage(&self) -> ValveUsage {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).usage
+    }
+}
@@ -0,0 +1,54 @@
+//! Hypotheticals a user poses, such as "machine 3 goes down tomorrow" or
+//! "customer 7 doubles spend", injected as pinned scenarios with the overlay
+//! rows the user supplies. Pinned, they hold no beam slot and are never
+//! pruned; they stay until withdrawn.
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tw_core::EpochTime;
+
+/// Source half of an injected scenario's `what_if:<label>` tag.
+pub const WHAT_IF_SOURCE: &str = "what_if";
+
+/// One hypothetical and the overlay rows it applies, in the domain's row
+/// type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhatIf<R> {
+    pub label: String,
+    pub probability: f64,
+    /// Epoch boundary to inject it at; the first one by default.
+    #[serde(default)]
+    pub at_epoch: EpochTime,
+    pub rows: Vec<R>,
+}
+
+impl<R> WhatIf<R> {
+    /// The tag recorded in [`ScenarioMeta::external`](crate::ScenarioMeta::external).
+    pub fn tag(&self) -> String {
+        what_if_tag(&self.label)
+    }
+}
+
+pub fn what_if_tag(label: &str) -> String {
+    format!("{WHAT_IF_SOURCE}:{label}")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhatIfPlan<R> {
+    pub what_ifs: Vec<WhatIf<R>>,
+}
+
+#[cfg(feature = "fs")]
+impl<R: serde::de::DeserializeOwned> WhatIfPlan<R> {
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading what-if scenarios {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("parsing what-if scenarios {}", path.display()))
+    }
+}
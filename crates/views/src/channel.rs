@@ -0,0 +1,53 @@
+//! Per-channel retail spend, with channel scenarios layered on top.
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Reduce, Threshold};
+use differential_dataflow::Collection;
+use timely::dataflow::Scope;
+
+use tw_core::retail::ChannelId;
+
+use crate::overlay::ScenarioRows;
+
+/// Total spend per channel from `(channel, cents)` rows.
+pub fn channel_spend<G>(spend: &Collection<G, (ChannelId, i64)>) -> Collection<G, (ChannelId, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    spend.reduce(|_channel, inputs, output| {
+        let mut cents: i64 = 0;
+        for (amount, cnt) in inputs.iter() {
+            cents = cents.saturating_add(amount.saturating_mul(*cnt as i64));
+        }
+        output.push((cents, 1));
+    })
+}
+
+/// Spend per channel under each scenario that shifts it: the channel's base
+/// spend plus the scenario's deltas for it. Channels a scenario leaves alone
+/// read [`channel_spend`]; a channel without base spend starts from zero.
+pub fn scenario_channel_spend<G>(
+    base: &Collection<G, (ChannelId, i64)>,
+    shifts: &ScenarioRows<G, ChannelId, i64>,
+) -> ScenarioRows<G, ChannelId, i64>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    let shifted = shifts.reduce(|_key, inputs, output| {
+        let mut cents: i64 = 0;
+        for (delta, cnt) in inputs.iter() {
+            cents = cents.saturating_add(delta.saturating_mul(*cnt as i64));
+        }
+        output.push((cents, 1isize));
+    });
+    let by_channel = shifted.map(|((sid, channel), delta)| (channel, (sid, delta)));
+    let with_base = by_channel
+        .join(base)
+        .map(|(channel, ((sid, delta), base))| ((sid, channel), base.saturating_add(delta)));
+    let without_base = by_channel
+        .antijoin(&base.map(|(channel, _)| channel).distinct())
+        .map(|(channel, (sid, delta))| ((sid, channel), delta));
+    with_base.concat(&without_base)
+}
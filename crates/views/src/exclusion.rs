@@ -0,0 +1,148 @@
+//! Probability views that respect mutually exclusive scenarios.
+//!
+//! Scenarios sharing an exclusion group are alternative futures of one
+//! parent: at most one of them happens, so their probabilities add instead
+//! of combining as independent events. Scenarios without a group are
+//! treated as independent of every other scenario.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Reduce, Threshold};
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+use tw_core::quantity::BPS_SCALE;
+use tw_core::ScenarioId;
+
+use crate::overlay::ScenarioRows;
+
+/// Identifies a set of mutually exclusive sibling scenarios.
+pub type ExclusionGroup = u64;
+
+/// Scenario weights in basis points, with every exclusion group scaled
+/// down so its members sum to at most certainty.
+pub fn exclusive_weights<G>(
+    weights_bps: &Collection<G, (ScenarioId, i64)>,
+    groups: &Collection<G, (ScenarioId, ExclusionGroup)>,
+) -> Collection<G, (ScenarioId, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    let scaled = weights_bps
+        .join(groups)
+        .map(|(sid, (bps, group))| (group, (sid, bps)))
+        .reduce(|_group, inputs, output| {
+            let total: i64 = inputs.iter().map(|((_sid, bps), cnt)| *bps * (*cnt as i64)).sum();
+            for ((sid, bps), cnt) in inputs.iter() {
+                let bps = if total > BPS_SCALE {
+                    ((*bps as i128) * (BPS_SCALE as i128) / (total as i128)) as i64
+                } else {
+                    *bps
+                };
+                output.push(((*sid, bps), *cnt));
+            }
+        })
+        .map(|(_group, row)| row);
+    let ungrouped = weights_bps.antijoin(&groups.map(|(sid, _group)| sid).distinct());
+    scaled.concat(&ungrouped)
+}
+
+/// Probability, in basis points, that at least one scenario of each set
+/// happens. Members of one exclusion group add; groups and ungrouped
+/// scenarios combine as independent events.
+pub fn combined_probability<G, S>(
+    members: &Collection<G, (S, ScenarioId)>,
+    weights_bps: &Collection<G, (ScenarioId, i64)>,
+    groups: &Collection<G, (ScenarioId, ExclusionGroup)>,
+) -> Collection<G, (S, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    S: ExchangeData + Hash,
+{
+    let weighted = members
+        .distinct()
+        .map(|(set, sid)| (sid, set))
+        .join(&exclusive_weights(weights_bps, groups));
+    // Ungrouped scenarios form singleton groups keyed by their own id
+    let grouped = weighted
+        .join(groups)
+        .map(|(_sid, ((set, bps), group))| ((set, (true, group)), bps));
+    let ungrouped = weighted
+        .antijoin(&groups.map(|(sid, _group)| sid).distinct())
+        .map(|(sid, (set, bps))| ((set, (false, sid)), bps));
+
+    grouped
+        .concat(&ungrouped)
+        .reduce(|_set_group, inputs, output| {
+            let sum: i64 = inputs.iter().map(|(bps, cnt)| *bps * (*cnt as i64)).sum();
+            output.push((sum.min(BPS_SCALE), 1isize));
+        })
+        .map(|((set, _group), bps)| (set, bps))
+        .reduce(|_set, inputs, output| {
+            let none: f64 = inputs
+                .iter()
+                .map(|(bps, cnt)| (1.0 - **bps as f64 / BPS_SCALE as f64).powi(*cnt as i32))
+                .product();
+            output.push((((1.0 - none) * BPS_SCALE as f64).round() as i64, 1));
+        })
+}
+
+/// Probability-weighted sum of each key's overlay deltas across scenarios,
+/// using [`exclusive_weights`] so alternatives of one parent never count
+/// for more than certainty.
+pub fn expected_delta<G, K>(
+    deltas: &ScenarioRows<G, K, i64>,
+    weights_bps: &Collection<G, (ScenarioId, i64)>,
+    groups: &Collection<G, (ScenarioId, ExclusionGroup)>,
+) -> Collection<G, (K, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    deltas
+        .map(|((sid, key), delta)| (sid, (key, delta)))
+        .join(&exclusive_weights(weights_bps, groups))
+        .map(|(_sid, ((key, delta), bps))| (key, (delta, bps)))
+        .reduce(|_key, inputs, output| {
+            let sum: i128 = inputs
+                .iter()
+                .map(|((delta, bps), cnt)| (*delta as i128) * (*bps as i128) * (*cnt as i128))
+                .sum();
+            let expected = sum / (BPS_SCALE as i128);
+            output.push((expected.clamp(i64::MIN as i128, i64::MAX as i128) as i64, 1));
+        })
+}
+
+/// Variance of [`expected_delta`] when each overlay's delta is uncertain:
+/// every `((scenario, key), variance)` row counts with its weight squared,
+/// treating overlays as independent.
+pub fn expected_variance<G, K>(
+    variances: &ScenarioRows<G, K, i64>,
+    weights_bps: &Collection<G, (ScenarioId, i64)>,
+    groups: &Collection<G, (ScenarioId, ExclusionGroup)>,
+) -> Collection<G, (K, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    variances
+        .map(|((sid, key), variance)| (sid, (key, variance)))
+        .join(&exclusive_weights(weights_bps, groups))
+        .map(|(_sid, ((key, variance), bps))| (key, (variance, bps)))
+        .reduce(|_key, inputs, output| {
+            let sum: i128 = inputs
+                .iter()
+                .map(|((variance, bps), cnt)| {
+                    (*variance as i128) * (*bps as i128) * (*bps as i128) * (*cnt as i128)
+                })
+                .sum();
+            let scale = BPS_SCALE as i128;
+            let variance = sum / (scale * scale);
+            output.push((variance.clamp(0, i64::MAX as i128) as i64, 1));
+        })
+}
@@ -0,0 +1,181 @@
+//! Lifecycle funnels: keyed entities moving through ordered stages, such as
+//! an operation being started and then finished.
+//!
+//! Each key counts toward every stage up to the furthest one it has reached,
+//! so a skipped or late event never makes a later stage outnumber an earlier
+//! one. Scenario rows `((scenario_id, key), stage)` replace a key's stage
+//! within the scenario, the same way overlays replace base values.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Reduce, Threshold};
+use differential_dataflow::{Collection, ExchangeData};
+use serde::{Deserialize, Serialize};
+use timely::dataflow::Scope;
+
+use tw_core::quantity::BPS_SCALE;
+use tw_core::ScenarioId;
+
+use crate::overlay::ScenarioRows;
+
+/// Position of a stage in its funnel, 0 being the first.
+pub type Stage = usize;
+
+/// One lifecycle stage and the event kinds that put a key there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunnelStage {
+    pub name: String,
+    pub kinds: Vec<String>,
+}
+
+impl FunnelStage {
+    pub fn new(name: impl Into<String>, kinds: &[&str]) -> Self {
+        Self { name: name.into(), kinds: kinds.iter().map(|kind| kind.to_string()).collect() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunnelConfig {
+    pub stages: Vec<FunnelStage>,
+    /// Epochs a key may sit short of the last stage before it counts as
+    /// stuck.
+    pub stuck_after: u64,
+}
+
+impl FunnelConfig {
+    /// The stage an event of `kind` moves its key to, if any.
+    pub fn stage_of(&self, kind: &str) -> Option<Stage> {
+        self.stages.iter().position(|stage| stage.kinds.iter().any(|k| k == kind))
+    }
+
+    pub fn last_stage(&self) -> Stage {
+        self.stages.len().saturating_sub(1)
+    }
+}
+
+/// Operations started and then finished, whether completed or scrapped.
+pub fn operation_lifecycle(stuck_after: u64) -> FunnelConfig {
+    FunnelConfig {
+        stages: vec![
+            FunnelStage::new("started", &["OperationStart"]),
+            FunnelStage::new("finished", &["OperationComplete", "OperationScrapped"]),
+        ],
+        stuck_after,
+    }
+}
+
+/// Furthest stage each key has reached, from `(key, stage)` arrivals.
+pub fn current_stage<G, K>(arrivals: &Collection<G, (K, Stage)>) -> Collection<G, (K, Stage)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    arrivals.reduce(|_key, inputs, output| {
+        let furthest = inputs.iter().filter(|(_, cnt)| *cnt > 0).map(|(stage, _)| **stage).max();
+        if let Some(stage) = furthest {
+            output.push((stage, 1));
+        }
+    })
+}
+
+/// Keys that have reached each stage. Stages no key reached have no row.
+pub fn stage_counts<G, K>(current: &Collection<G, (K, Stage)>) -> Collection<G, (Stage, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    sum_counts(&current.flat_map(|(_key, stage)| (0..=stage).map(|stage| (stage, 1))))
+}
+
+/// Per-scenario [`stage_counts`] for every scenario with a stage row.
+/// Keys a scenario does not touch count at their base stage; a scenario
+/// row for a key the base has not seen adds the key.
+pub fn scenario_stage_counts<G, K>(
+    base: &Collection<G, (K, Stage)>,
+    overlay: &ScenarioRows<G, K, Stage>,
+) -> Collection<G, ((ScenarioId, Stage), i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    let touched = overlay.map(|((sid, _key), _stage)| ((), sid)).distinct();
+    let from_base = stage_counts(base)
+        .map(|(stage, count)| ((), (stage, count)))
+        .join(&touched)
+        .map(|((), ((stage, count), sid))| ((sid, stage), count));
+
+    // Each overlaid key swaps its base contribution for the scenario's
+    let by_key = overlay.map(|((sid, key), stage)| (key, (sid, stage)));
+    let added = by_key.flat_map(|(_key, (sid, stage))| {
+        (0..=stage).map(move |stage| ((sid, stage), 1))
+    });
+    let removed = by_key
+        .map(|(key, (sid, _stage))| (key, sid))
+        .join(base)
+        .flat_map(|(_key, (sid, stage))| (0..=stage).map(move |stage| ((sid, stage), -1)));
+
+    sum_counts(&from_base.concat(&added).concat(&removed))
+}
+
+/// Conversion from the previous stage into each stage after the first, in
+/// basis points, per group: `()` for the base funnel or the scenario id for
+/// scenario funnels. A stage no key reached after a populated one converts
+/// at 0.
+pub fn conversion_bps<G, Grp>(
+    counts: &Collection<G, ((Grp, Stage), i64)>,
+    stages: usize,
+) -> Collection<G, ((Grp, Stage), i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+{
+    let previous = counts
+        .filter(move |((_grp, stage), _count)| stage + 1 < stages)
+        .map(|((grp, stage), count)| ((grp, stage + 1), count));
+    let converted = previous.join(counts).flat_map(|(stage, (entered, reached))| {
+        let bps = (reached as i128) * (BPS_SCALE as i128) / (entered as i128).max(1);
+        i64::try_from(bps).ok().map(|bps| (stage, bps))
+    });
+    let dropped = previous
+        .antijoin(&counts.map(|(stage, _count)| stage))
+        .map(|(stage, _entered)| (stage, 0));
+    converted.concat(&dropped)
+}
+
+/// `(key, stage)` for keys short of `last_stage` that have not moved for
+/// `stuck_after` epochs. A key's current stage is delayed by `stuck_after`,
+/// so it turns stuck and recovers with the dataflow frontier instead of
+/// needing a clock input.
+pub fn stuck<G, K>(
+    current: &Collection<G, (K, Stage)>,
+    last_stage: Stage,
+    stuck_after: u64,
+) -> Collection<G, (K, Stage)>
+where
+    G: Scope<Timestamp = u64>,
+    K: ExchangeData + Hash,
+{
+    let stuck_after = stuck_after.max(1);
+    let open = current.filter(move |(_key, stage)| *stage < last_stage);
+    let aged = open.delay(move |time| time.saturating_add(stuck_after)).distinct();
+    open.map(|row| (row, ())).semijoin(&aged).map(|(row, ())| row)
+}
+
+fn sum_counts<G, Grp>(counts: &Collection<G, (Grp, i64)>) -> Collection<G, (Grp, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+{
+    counts.reduce(|_grp, inputs, output| {
+        let sum: i64 = inputs.iter().map(|(count, cnt)| **count * (*cnt as i64)).sum();
+        if sum != 0 {
+            output.push((sum, 1));
+        }
+    })
+}
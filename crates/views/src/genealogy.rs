@@ -0,0 +1,78 @@
+//! Lot genealogy: the lots descending from a suspect lot through splits and
+//! merges, and the jobs a quality hold on it would stop.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Iterate, Join, Reduce, Threshold};
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+use tw_core::manufacturing::{JobId, LotId};
+
+/// Every lot reachable from each `(key, lot)` root along `edges`, the root
+/// included. Edges run `(parent, child)`: from a split lot to each part, and
+/// from each merged lot to the result. Keys say why a lot is suspect, such
+/// as the hold scenario naming it.
+pub fn descendant_lots<G, K>(
+    edges: &Collection<G, (LotId, LotId)>,
+    roots: &Collection<G, (K, LotId)>,
+) -> Collection<G, (K, LotId)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    roots.iterate(|reached| {
+        let edges = edges.enter(&reached.scope());
+        let roots = roots.enter(&reached.scope());
+        reached
+            .map(|(key, lot)| (lot, key))
+            .join_map(&edges, |_lot, key, child| (key.clone(), *child))
+            .concat(&roots)
+            .distinct()
+    })
+}
+
+/// `(key, job)` for every job that consumed one of a key's lots.
+pub fn jobs_on_hold<G, K>(
+    lots: &Collection<G, (K, LotId)>,
+    consumers: &Collection<G, (LotId, JobId)>,
+) -> Collection<G, (K, JobId)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    lots.map(|(key, lot)| (lot, key))
+        .join_map(&consumers.distinct(), |_lot, key, job| (key.clone(), *job))
+        .distinct()
+}
+
+/// `(lots, jobs)` a hold would stop, per key with at least one lot.
+pub fn hold_impact<G, K>(
+    lots: &Collection<G, (K, LotId)>,
+    jobs: &Collection<G, (K, JobId)>,
+) -> Collection<G, (K, (i64, i64))>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    lots.map(|(key, _lot)| (key, true))
+        .concat(&jobs.map(|(key, _job)| (key, false)))
+        .reduce(|_key, inputs, output| {
+            let mut lots: i64 = 0;
+            let mut jobs: i64 = 0;
+            for (is_lot, cnt) in inputs.iter() {
+                if **is_lot {
+                    lots += *cnt as i64;
+                } else {
+                    jobs += *cnt as i64;
+                }
+            }
+            if lots > 0 {
+                output.push(((lots, jobs), 1));
+            }
+        })
+}
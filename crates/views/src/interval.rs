@@ -0,0 +1,33 @@
+//! Uncertainty on projected values.
+//!
+//! Overlay rows may carry a variance next to their delta. Overlays are
+//! treated as independent, so the variances of the overlays summed into one
+//! projected value add, and
+//! [`Interval::around`](tw_core::quantity::Interval::around) turns a value
+//! and its variance into bounds.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Threshold};
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+/// Each row with its key's variance; keys without a variance row are point
+/// estimates and get 0.
+pub fn with_variance<G, K, V>(
+    rows: &Collection<G, (K, V)>,
+    variances: &Collection<G, (K, i64)>,
+) -> Collection<G, (K, (V, i64))>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+    V: ExchangeData,
+{
+    let known = rows.join(variances);
+    let point = rows
+        .antijoin(&variances.map(|(key, _variance)| key).distinct())
+        .map(|(key, value)| (key, (value, 0)));
+    known.concat(&point)
+}
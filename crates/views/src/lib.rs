@@ -1,19 +1,224 @@
 //! Reusable view builders (top-K, windows, joins, graphs).
 
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::{Collection, ExchangeData};
 use timely::dataflow::Scope;
+use tw_core::quantity::{Rounding, BPS_SCALE};
 
+#[derive(Debug, Clone, Copy)]
 pub struct TopKConfig {
     pub k: usize,
+    /// Also emit every key tied with the k-th value, so membership doesn't
+    /// depend on which of the tied keys happens to sort first.
+    pub include_ties: bool,
 }
 
 impl Default for TopKConfig {
     fn default() -> Self {
-        Self { k: 10 }
+        Self { k: 10, include_ties: false }
     }
 }
 
-/// Placeholder for a top-K builder; concrete implementations will live here.
-pub fn top_k_placeholder<G: Scope>(_cfg: TopKConfig, _scope: &mut G) {
-    // Implementation to be added in MVP Phase 1.
+/// Top-K `(value, key)` rows per group, by value descending. Ties are broken
+/// by key ascending, so equal values yield the same output on every epoch
+/// instead of flapping with arrangement order.
+pub fn top_k<G, Grp, K>(
+    values: &Collection<G, (Grp, (i64, K))>,
+    cfg: TopKConfig,
+) -> Collection<G, (Grp, (i64, K))>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+    K: ExchangeData + Hash,
+{
+    values.reduce(move |_grp, inputs, output| {
+        for val in ranked(inputs, cfg) {
+            output.push((val.clone(), 1));
+        }
+    })
+}
+
+/// Top-K per group as `(rank, key, value)` rows, rank 1 being the largest
+/// value. Ranks are positions in the same order as [`top_k`], so tied keys
+/// get consecutive ranks. A change that moves a key retracts its old row and
+/// adds the new one in the same epoch; [`rank::RankTracker`] turns those
+/// pairs into rank-change records.
+pub fn ranked_top_k<G, Grp, K>(
+    values: &Collection<G, (Grp, (i64, K))>,
+    cfg: TopKConfig,
+) -> Collection<G, (Grp, (usize, K, i64))>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+    K: ExchangeData + Hash,
+{
+    values.reduce(move |_grp, inputs, output| {
+        for (pos, (value, key)) in ranked(inputs, cfg).into_iter().enumerate() {
+            output.push(((pos + 1, key.clone(), *value), 1));
+        }
+    })
+}
+
+/// The rows `top_k` keeps out of one group's reduce input, best first.
+fn ranked<'a, K: Ord>(inputs: &[(&'a (i64, K), isize)], cfg: TopKConfig) -> Vec<&'a (i64, K)> {
+    let mut vals: Vec<&(i64, K)> = inputs
+        .iter()
+        .filter(|(_, cnt)| *cnt > 0)
+        .map(|(val, _)| *val)
+        .collect();
+    vals.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    let mut take = cfg.k.min(vals.len());
+    if cfg.include_ties && take > 0 {
+        let cutoff = vals[take - 1].0;
+        while take < vals.len() && vals[take].0 == cutoff {
+            take += 1;
+        }
+    }
+    vals.truncate(take);
+    vals
 }
 
+/// Estimate the total of `values` over every key, kept or not, from a sample
+/// that keeps whole keys at `keep_bps`, as `(estimate, variance)` for
+/// [`Interval::around`](tw_core::quantity::Interval::around). Each kept key
+/// stands in for `10_000 / keep_bps` keys, and a key kept with probability p
+/// adds (1 - p) / p² times its squared value to the variance. Per-key values
+/// of kept keys are exact and are left as they are.
+pub fn sampled_total<G, K>(
+    values: &Collection<G, (K, i64)>,
+    keep_bps: i64,
+) -> Collection<G, (i64, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData,
+{
+    let keep_bps = keep_bps.clamp(1, BPS_SCALE) as i128;
+    let scale = BPS_SCALE as i128;
+    values
+        .map(|(_key, value)| ((), value))
+        .reduce(move |_unit, inputs, output| {
+            let mut sum: i128 = 0;
+            let mut squares: i128 = 0;
+            for (value, count) in inputs.iter() {
+                let value = **value as i128;
+                let count = *count as i128;
+                sum = sum.saturating_add(value.saturating_mul(count));
+                squares = squares.saturating_add(value.saturating_mul(value).saturating_mul(count));
+            }
+            let estimate = Rounding::default().divide(sum.saturating_mul(scale), keep_bps);
+            let spread = squares.saturating_mul(scale - keep_bps).saturating_mul(scale);
+            let variance = Rounding::default().divide(spread, keep_bps * keep_bps);
+            let clamp = |value: i128| value.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+            output.push(((clamp(estimate), clamp(variance)), 1));
+        })
+        .map(|((), total)| total)
+}
+
+pub mod channel;
+pub mod exclusion;
+pub mod funnel;
+pub mod genealogy;
+pub mod interval;
+pub mod material;
+pub mod overlay;
+pub mod pushdown;
+pub mod rank;
+pub mod reducers;
+pub mod share;
+pub mod throughput;
+pub mod watchlist;
+pub mod window_join;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use differential_dataflow::input::InputSession;
+    use timely::dataflow::ProbeHandle;
+
+    use super::{top_k, TopKConfig};
+
+    type Row = ((), (i64, u64));
+
+    /// Run `top_k` over `epochs` of `(row, diff)` input changes and return the
+    /// output changes each epoch produced.
+    fn top_k_changes(cfg: TopKConfig, epochs: Vec<Vec<(Row, isize)>>) -> Vec<Vec<(Row, isize)>> {
+        timely::execute_directly(move |worker| {
+            let mut values: InputSession<u64, Row, isize> = InputSession::new();
+            let changes = Rc::new(RefCell::new(vec![Vec::new(); epochs.len()]));
+            let mut probe = ProbeHandle::new();
+            worker.dataflow(|scope| {
+                let sink = Rc::clone(&changes);
+                top_k(&values.to_collection(scope), cfg)
+                    .inspect(move |(row, time, diff)| {
+                        sink.borrow_mut()[*time as usize].push((*row, *diff));
+                    })
+                    .probe_with(&mut probe);
+            });
+            for (epoch, updates) in (0u64..).zip(epochs) {
+                values.advance_to(epoch);
+                for (row, diff) in updates {
+                    values.update(row, diff);
+                }
+                values.advance_to(epoch + 1);
+                values.flush();
+                while probe.less_than(&(epoch + 1)) {
+                    worker.step();
+                }
+            }
+            let mut changes = changes.borrow().clone();
+            for epoch in &mut changes {
+                epoch.sort();
+            }
+            changes
+        })
+    }
+
+    fn row(value: i64, key: u64) -> Row {
+        ((), (value, key))
+    }
+
+    #[test]
+    fn tied_keys_do_not_churn_across_epochs() {
+        let cfg = TopKConfig { k: 2, include_ties: false };
+        let changes = top_k_changes(
+            cfg,
+            vec![
+                (1..=5).map(|key| (row(10, key), 1)).collect(),
+                // Another key ties after the kept ones
+                vec![(row(10, 9), 1)],
+                // Tied keys, kept or not, are retracted and re-added unchanged
+                vec![(row(10, 2), -1), (row(10, 2), 1), (row(10, 4), -1), (row(10, 4), 1)],
+                // A dropped tied key leaves; the kept ones are unaffected
+                vec![(row(10, 5), -1)],
+            ],
+        );
+        assert_eq!(changes[0], vec![(row(10, 1), 1), (row(10, 2), 1)]);
+        assert!(changes[1..].iter().all(Vec::is_empty), "tied keys churned: {changes:?}");
+    }
+
+    #[test]
+    fn tied_keys_are_kept_together_across_epochs() {
+        let cfg = TopKConfig { k: 2, include_ties: true };
+        let changes = top_k_changes(
+            cfg,
+            vec![
+                (1..=4).map(|key| (row(10, key), 1)).collect(),
+                // A new leader pushes the cutoff down a slot; the tie still holds
+                vec![(row(20, 7), 1)],
+                // A tied key moving away leaves the rest of the tie in place
+                vec![(row(10, 3), -1), (row(5, 3), 1)],
+            ],
+        );
+        assert_eq!(changes[0], (1..=4).map(|key| (row(10, key), 1)).collect::<Vec<_>>());
+        assert_eq!(changes[1], vec![(row(20, 7), 1)]);
+        assert_eq!(changes[2], vec![(row(10, 3), -1)]);
+    }
+}
@@ -0,0 +1,41 @@
+//! Material availability and the machines a stockout starves.
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Reduce, Threshold};
+use differential_dataflow::Collection;
+use timely::dataflow::Scope;
+
+use tw_core::manufacturing::{MachineId, MaterialId};
+
+/// On-hand units per material from signed movements: receipts positive,
+/// consumption negative.
+pub fn on_hand<G>(movements: &Collection<G, (MaterialId, i64)>) -> Collection<G, (MaterialId, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    movements.reduce(|_material, inputs, output| {
+        let mut units: i64 = 0;
+        for (delta, cnt) in inputs.iter() {
+            units += *delta * (*cnt as i64);
+        }
+        output.push((units, 1));
+    })
+}
+
+/// `(machine, material)` for every machine that draws a material with
+/// nothing on hand.
+pub fn starved_machines<G>(
+    on_hand: &Collection<G, (MaterialId, i64)>,
+    consumers: &Collection<G, (MaterialId, MachineId)>,
+) -> Collection<G, (MachineId, MaterialId)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    consumers
+        .distinct()
+        .join(on_hand)
+        .filter(|(_material, (_machine, units))| *units <= 0)
+        .map(|(material, (machine, _units))| (machine, material))
+}
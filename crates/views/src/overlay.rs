@@ -0,0 +1,184 @@
+//! Helpers for composing scenario overlays with base views.
+//!
+//! An overlaid view is split into base rows `(key, value)` and scenario rows
+//! `((scenario_id, key), value)` that replace the base value for that key
+//! within the scenario. Keys a scenario does not touch read the base row.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Threshold};
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+use tw_core::ScenarioId;
+
+/// Per-scenario rows keyed by `(scenario_id, key)`.
+pub type ScenarioRows<G, K, V> = Collection<G, ((ScenarioId, K), V)>;
+
+/// The authoritative set of live scenarios: every scenario with a
+/// membership row (such as its weight) and no retirement row. Scenario ids
+/// are never reused, so a retirement row wins over membership rows still in
+/// flight from another input; it can be retracted once they are gone.
+pub fn live_scenarios<G>(
+    members: &Collection<G, ScenarioId>,
+    retired: &Collection<G, ScenarioId>,
+) -> Collection<G, ScenarioId>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    members
+        .distinct()
+        .map(|sid| (sid, ()))
+        .antijoin(&retired.distinct())
+        .map(|(sid, ())| sid)
+}
+
+/// Keep only rows of scenarios in `live`. Every per-scenario collection a
+/// view derives from (weights, overlays, broadcast base rows) goes through
+/// this, so a retired scenario's rows all retract at its retirement epoch
+/// whatever order the inputs retract them in.
+pub fn gated<G, V>(
+    rows: &Collection<G, (ScenarioId, V)>,
+    live: &Collection<G, ScenarioId>,
+) -> Collection<G, (ScenarioId, V)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    V: ExchangeData,
+{
+    rows.semijoin(live)
+}
+
+/// Keep only rows of scenarios in `watched`. Lazy pipelines route
+/// per-scenario rows through this before building scenario views, so
+/// unwatched scenarios hold nothing beyond their overlays; watching one
+/// builds its views from base + overlays at the next epoch.
+pub fn materialized<G, V>(
+    rows: &Collection<G, (ScenarioId, V)>,
+    watched: &Collection<G, ScenarioId>,
+) -> Collection<G, (ScenarioId, V)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    V: ExchangeData,
+{
+    rows.semijoin(&watched.distinct())
+}
+
+/// Join two overlaid views on key within each scenario.
+///
+/// Produces rows only for `(scenario, key)` pairs that at least one side
+/// overlays; the side without an overlay contributes its base value. Pairs no
+/// scenario touches are exactly `left_base.join(right_base)`, which callers
+/// already hold and can share across scenarios.
+pub fn scenario_join<G, K, V1, V2>(
+    left_base: &Collection<G, (K, V1)>,
+    left_overlay: &ScenarioRows<G, K, V1>,
+    right_base: &Collection<G, (K, V2)>,
+    right_overlay: &ScenarioRows<G, K, V2>,
+) -> ScenarioRows<G, K, (V1, V2)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+    V1: ExchangeData,
+    V2: ExchangeData,
+{
+    let touched = left_overlay
+        .map(|(sk, _)| sk)
+        .concat(&right_overlay.map(|(sk, _)| sk))
+        .distinct();
+
+    let left = resolve_overlay(&touched, left_overlay, left_base);
+    let right = resolve_overlay(&touched, right_overlay, right_base);
+    left.join(&right)
+}
+
+/// Value of one side for every touched `(scenario, key)`: the overlay row if
+/// present, otherwise the base row for the key.
+fn resolve_overlay<G, K, V>(
+    touched: &Collection<G, (ScenarioId, K)>,
+    overlay: &ScenarioRows<G, K, V>,
+    base: &Collection<G, (K, V)>,
+) -> ScenarioRows<G, K, V>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+    V: ExchangeData,
+{
+    let overlaid_keys = overlay.map(|(sk, _)| sk).distinct();
+    let from_base = touched
+        .map(|sk| (sk, ()))
+        .antijoin(&overlaid_keys)
+        .map(|((sid, key), ())| (key, sid))
+        .join(base)
+        .map(|(key, (sid, val))| ((sid, key), val));
+    overlay.concat(&from_base)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    use differential_dataflow::input::InputSession;
+    use timely::dataflow::ProbeHandle;
+    use tw_runtime::ledger::RetirementInput;
+
+    use super::live_scenarios;
+
+    #[test]
+    fn retirement_in_the_epoch_a_child_is_created() {
+        timely::execute_directly(|worker| {
+            let mut members: InputSession<u64, u64, isize> = InputSession::new();
+            let mut retired: RetirementInput<u64> = RetirementInput::new();
+            let live = Rc::new(RefCell::new(BTreeMap::<u64, isize>::new()));
+            let mut probe = ProbeHandle::new();
+            worker.dataflow(|scope| {
+                let sink = Rc::clone(&live);
+                live_scenarios(&members.to_collection(scope), &retired.to_collection(scope))
+                    .inspect(move |(sid, _time, diff)| {
+                        *sink.borrow_mut().entry(*sid).or_default() += diff;
+                    })
+                    .probe_with(&mut probe);
+            });
+            let mut settle = |epoch: u64,
+                              members: &mut InputSession<u64, u64, isize>,
+                              retired: &mut RetirementInput<u64>| {
+                members.advance_to(epoch);
+                retired.advance_to(epoch);
+                members.flush();
+                retired.flush();
+                while probe.less_than(&epoch) {
+                    worker.step();
+                }
+                retired.retract_before(&epoch);
+            };
+            let live_ids = || -> Vec<u64> {
+                live.borrow().iter().filter(|(_, n)| **n > 0).map(|(sid, _)| *sid).collect()
+            };
+
+            // Epoch 0: the parent is created
+            members.insert(1);
+            settle(1, &mut members, &mut retired);
+            assert_eq!(live_ids(), vec![1]);
+
+            // Epoch 1: the parent branches a child and retires in the same epoch
+            members.insert(2);
+            members.remove(1);
+            retired.retire(1);
+            settle(2, &mut members, &mut retired);
+            assert_eq!(live_ids(), vec![2]);
+            assert!(retired.is_empty(), "the retirement row is retracted once settled");
+
+            // Epoch 2: the retracted retirement row does not revive the parent
+            settle(3, &mut members, &mut retired);
+            assert_eq!(live_ids(), vec![2]);
+            assert!(live.borrow().values().all(|n| *n == 0 || *n == 1));
+        });
+    }
+}
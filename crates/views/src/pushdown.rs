@@ -0,0 +1,82 @@
+//! Subscription filters pushed below per-scenario top-K.
+//!
+//! Each live subscription contributes a [`SubscriptionPredicate`] row; the
+//! loosest bounds over all of them prune scenarios and candidate rows before
+//! ranking, so unwatched work is never ranked. Predicates are data, so
+//! adding or removing a subscription updates only the rows whose admission
+//! changes instead of rebuilding the dataflow.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Reduce};
+use differential_dataflow::{Collection, ExchangeData};
+use serde::{Deserialize, Serialize};
+use timely::dataflow::Scope;
+
+use tw_core::quantity::BPS_SCALE;
+use tw_core::ScenarioId;
+
+/// Minimal scenario and key bounds one subscription needs to evaluate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionPredicate {
+    /// Scenarios below this weight, in basis points, can't fire the subscription.
+    pub min_prob_bps: i64,
+    /// Rows below this value can't fire it; `None` needs every row.
+    pub min_value: Option<i64>,
+}
+
+/// A predicate as a dataflow row: `(min_prob_bps, min_value)`.
+pub type PredicateRow = (i64, Option<i64>);
+
+impl SubscriptionPredicate {
+    pub fn row(self) -> PredicateRow {
+        (self.min_prob_bps, self.min_value)
+    }
+}
+
+/// A scenario weight in basis points, for comparing against predicates.
+pub fn prob_bps(prob: f64) -> i64 {
+    (prob * BPS_SCALE as f64).round() as i64
+}
+
+/// Candidate rows per scenario that some subscription could still fire on.
+///
+/// Dropping rows below `min_value` leaves the rank of every row above it
+/// unchanged, so top-K over the pruned candidates is exact for subscriptions
+/// that only fire on top-K rows at or above their bound. With no predicates
+/// nothing is admitted.
+pub fn pushdown_candidates<G, K>(
+    candidates: &Collection<G, (ScenarioId, (i64, K))>,
+    weights_bps: &Collection<G, (ScenarioId, i64)>,
+    predicates: &Collection<G, PredicateRow>,
+) -> Collection<G, (ScenarioId, (i64, K))>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    let loosest = predicates.map(|row| ((), row)).reduce(|_unit, inputs, output| {
+        let min_prob_bps = inputs.iter().map(|((bps, _), _)| *bps).min();
+        let min_value = inputs
+            .iter()
+            .map(|((_, bound), _)| *bound)
+            .try_fold(i64::MAX, |acc, bound| bound.map(|bound| acc.min(bound)));
+        if let Some(min_prob_bps) = min_prob_bps {
+            output.push(((min_prob_bps, min_value), 1));
+        }
+    });
+
+    let admitted = weights_bps
+        .map(|(sid, bps)| ((), (sid, bps)))
+        .join(&loosest)
+        .filter(|(_unit, ((_sid, bps), (min_prob_bps, _)))| *bps >= *min_prob_bps)
+        .map(|(_unit, ((sid, _bps), (_, min_value)))| (sid, min_value));
+
+    candidates
+        .join(&admitted)
+        .filter(|(_sid, ((value, _key), min_value))| {
+            min_value.map_or(true, |bound| *value >= bound)
+        })
+        .map(|(sid, (row, _min_value))| (sid, row))
+}
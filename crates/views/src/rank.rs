@@ -0,0 +1,97 @@
+//! Rank changes out of a [`ranked_top_k`](crate::ranked_top_k) view, such as
+//! "customer 7 moved from #6 to #4 in scenario 12".
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// One key's move within a group's top-K between two closed epochs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankChange<Grp, K> {
+    pub group: Grp,
+    pub key: K,
+    /// Rank before the move; `None` when the key entered the top-K.
+    pub from: Option<usize>,
+    /// Rank after the move; `None` when the key left the top-K.
+    pub to: Option<usize>,
+    /// The key's value at its new rank, or its last value if it left.
+    pub value: i64,
+}
+
+impl<Grp, K> RankChange<Grp, K> {
+    pub fn is_entry(&self) -> bool {
+        self.from.is_none()
+    }
+
+    pub fn is_exit(&self) -> bool {
+        self.to.is_none()
+    }
+}
+
+/// Current ranks per `(group, key)`, maintained from the ranked view's row
+/// updates. Feed it every update as it arrives, retractions included, then
+/// close each epoch to learn which keys moved. Updates within an epoch may
+/// arrive in any order; only the net rows at close are compared.
+#[derive(Debug, Clone)]
+pub struct RankTracker<Grp, K> {
+    /// Net multiplicity of each `(rank, value)` row per key.
+    rows: BTreeMap<(Grp, K), BTreeMap<(usize, i64), isize>>,
+    /// Rank and value as of the last closed epoch.
+    ranks: BTreeMap<(Grp, K), (usize, i64)>,
+    touched: BTreeSet<(Grp, K)>,
+}
+
+impl<Grp, K> Default for RankTracker<Grp, K> {
+    fn default() -> Self {
+        Self { rows: BTreeMap::new(), ranks: BTreeMap::new(), touched: BTreeSet::new() }
+    }
+}
+
+impl<Grp: Ord + Clone, K: Ord + Clone> RankTracker<Grp, K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one `(group, (rank, key, value))` update of the ranked view.
+    pub fn update(&mut self, group: Grp, (rank, key, value): (usize, K, i64), diff: isize) {
+        let id = (group, key);
+        let rows = self.rows.entry(id.clone()).or_default();
+        let count = rows.entry((rank, value)).or_default();
+        *count += diff;
+        if *count == 0 {
+            rows.remove(&(rank, value));
+        }
+        if rows.is_empty() {
+            self.rows.remove(&id);
+        }
+        self.touched.insert(id);
+    }
+
+    /// Rank changes since the last close, by group, then key.
+    pub fn close_epoch(&mut self) -> Vec<RankChange<Grp, K>> {
+        let mut changes = Vec::new();
+        for id in std::mem::take(&mut self.touched) {
+            let current = self.rows.get(&id).and_then(|rows| {
+                rows.iter().find(|(_, count)| **count > 0).map(|(row, _)| *row)
+            });
+            let previous = match current {
+                Some(row) => self.ranks.insert(id.clone(), row),
+                None => self.ranks.remove(&id),
+            };
+            let from = previous.map(|(rank, _)| rank);
+            let to = current.map(|(rank, _)| rank);
+            if from == to {
+                continue;
+            }
+            let Some((_, value)) = current.or(previous) else {
+                continue;
+            };
+            let (group, key) = id;
+            changes.push(RankChange { group, key, from, to, value });
+        }
+        changes
+    }
+
+    /// The key's rank as of the last closed epoch.
+    pub fn rank(&self, group: &Grp, key: &K) -> Option<usize> {
+        self.ranks.get(&(group.clone(), key.clone())).map(|(rank, _)| *rank)
+    }
+}
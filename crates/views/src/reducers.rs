@@ -0,0 +1,95 @@
+//! Named aggregate functions, so view aggregates can be chosen by name
+//! instead of by forking the view builders.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Reduce;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+/// Folds one key's `(value, multiplicity)` rows into its aggregate; `None`
+/// emits no row for the key.
+pub type ReducerFn = Arc<dyn Fn(&[(i64, isize)]) -> Option<i64> + Send + Sync>;
+
+/// Reducers by name. [`Default`] registers `sum`, `count`, `min` and `max`.
+#[derive(Clone)]
+pub struct ReducerRegistry {
+    reducers: BTreeMap<String, ReducerFn>,
+}
+
+impl Default for ReducerRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.insert("sum", |rows| {
+            Some(rows.iter().map(|(val, cnt)| val.saturating_mul(*cnt as i64)).sum())
+        });
+        registry.insert("count", |rows| Some(rows.iter().map(|(_, cnt)| *cnt as i64).sum()));
+        registry.insert("min", |rows| rows.iter().filter(|(_, cnt)| *cnt > 0).map(|(v, _)| *v).min());
+        registry.insert("max", |rows| rows.iter().filter(|(_, cnt)| *cnt > 0).map(|(v, _)| *v).max());
+        registry
+    }
+}
+
+impl fmt::Debug for ReducerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.reducers.keys()).finish()
+    }
+}
+
+impl ReducerRegistry {
+    /// A registry without the built-in reducers.
+    pub fn empty() -> Self {
+        Self { reducers: BTreeMap::new() }
+    }
+
+    /// Register `reducer` under `name`; names are unique, built-ins included.
+    pub fn register<F>(&mut self, name: impl Into<String>, reducer: F) -> Result<()>
+    where
+        F: Fn(&[(i64, isize)]) -> Option<i64> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        if self.reducers.contains_key(&name) {
+            bail!("reducer {name:?} is already registered");
+        }
+        self.insert(name, reducer);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<ReducerFn> {
+        match self.reducers.get(name) {
+            Some(reducer) => Ok(reducer.clone()),
+            None => bail!("unknown reducer {name:?} (registered: {:?})", self),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.reducers.keys().map(String::as_str)
+    }
+
+    fn insert<F>(&mut self, name: impl Into<String>, reducer: F)
+    where
+        F: Fn(&[(i64, isize)]) -> Option<i64> + Send + Sync + 'static,
+    {
+        self.reducers.insert(name.into(), Arc::new(reducer));
+    }
+}
+
+/// Aggregate each key's values with `reducer`.
+pub fn aggregate<G, K>(values: &Collection<G, (K, i64)>, reducer: ReducerFn) -> Collection<G, (K, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    K: ExchangeData + Hash,
+{
+    values.reduce(move |_key, inputs, output| {
+        let rows: Vec<(i64, isize)> = inputs.iter().map(|(val, cnt)| (**val, *cnt)).collect();
+        if let Some(agg) = reducer(&rows) {
+            output.push((agg, 1));
+        }
+    })
+}
@@ -0,0 +1,86 @@
+//! Share-of-total views: each key's value relative to its group total.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Reduce, Threshold};
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+use crate::overlay::ScenarioRows;
+
+/// `(value, group_total)` for one key; see [`share_bps`].
+pub type Share = (i64, i64);
+
+/// Share in basis points, or `None` when the group total is zero.
+pub fn share_bps((value, total): Share) -> Option<i64> {
+    if total == 0 {
+        return None;
+    }
+    i64::try_from((value as i128) * 10_000 / (total as i128)).ok()
+}
+
+/// Share of each `(group, key)` value in its group's total.
+pub fn share_of_total<G, Grp, K>(
+    values: &Collection<G, ((Grp, K), i64)>,
+) -> Collection<G, ((Grp, K), Share)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+    K: ExchangeData + Hash,
+{
+    let totals = group_totals(&values.map(|((grp, _key), val)| (grp, val)));
+    values
+        .map(|((grp, key), val)| (grp, (key, val)))
+        .join(&totals)
+        .map(|(grp, ((key, val), total))| ((grp, key), (val, total)))
+}
+
+/// Per-scenario shares for every key in a group the scenario overlays.
+///
+/// Changing one key moves its group's total, so every key of a touched group
+/// gets a scenario row; groups no overlay touches read [`share_of_total`].
+pub fn scenario_share_of_total<G, Grp, K>(
+    base: &Collection<G, ((Grp, K), i64)>,
+    overlay: &ScenarioRows<G, (Grp, K), i64>,
+) -> ScenarioRows<G, (Grp, K), Share>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+    K: ExchangeData + Hash,
+{
+    let touched_groups = overlay
+        .map(|((sid, (grp, _key)), _val)| (grp, sid))
+        .distinct();
+    let overlaid_keys = overlay.map(|(sk, _val)| sk).distinct();
+
+    let resolved = base
+        .map(|((grp, key), val)| (grp, (key, val)))
+        .join(&touched_groups)
+        .map(|(grp, ((key, val), sid))| ((sid, (grp, key)), val))
+        .antijoin(&overlaid_keys)
+        .concat(overlay);
+
+    let totals = group_totals(&resolved.map(|((sid, (grp, _key)), val)| ((sid, grp), val)));
+    resolved
+        .map(|((sid, (grp, key)), val)| ((sid, grp), (key, val)))
+        .join(&totals)
+        .map(|((sid, grp), ((key, val), total))| ((sid, (grp, key)), (val, total)))
+}
+
+fn group_totals<G, Grp>(values: &Collection<G, (Grp, i64)>) -> Collection<G, (Grp, i64)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    Grp: ExchangeData + Hash,
+{
+    values.reduce(|_grp, inputs, output| {
+        let mut sum: i64 = 0;
+        for (val, cnt) in inputs.iter() {
+            sum += *val * (*cnt as i64);
+        }
+        output.push((sum, 1));
+    })
+}
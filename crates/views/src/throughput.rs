@@ -0,0 +1,82 @@
+//! Throughput and queue-clearing projections for epoch-timestamped views.
+
+use std::hash::Hash;
+
+use differential_dataflow::operators::{Join, Reduce};
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+use crate::overlay::ScenarioRows;
+
+/// Completions per key over the trailing `window` epochs. Each completion is
+/// retracted `window` epochs after it lands, so the view slides with the
+/// dataflow frontier instead of needing a clock input.
+pub fn windowed_throughput<G, K>(
+    completions: &Collection<G, K>,
+    window: u64,
+) -> Collection<G, (K, i64)>
+where
+    G: Scope<Timestamp = u64>,
+    K: ExchangeData + Hash,
+{
+    let window = window.max(1);
+    let expired = completions.delay(move |time| time.saturating_add(window)).negate();
+    completions
+        .concat(&expired)
+        .map(|key| (key, ()))
+        .reduce(|_key, inputs, output| {
+            let completed: isize = inputs.iter().map(|(_, cnt)| *cnt).sum();
+            if completed > 0 {
+                output.push((completed as i64, 1));
+            }
+        })
+}
+
+/// Epochs needed to work off `wip` at `completed` operations per `window`
+/// epochs, rounded up; `None` when nothing completed in the window.
+pub fn epochs_to_clear(wip: i64, completed: i64, window: u64) -> Option<u64> {
+    if wip <= 0 {
+        return Some(0);
+    }
+    if completed <= 0 {
+        return None;
+    }
+    let needed = (wip as u128) * (window.max(1) as u128);
+    let epochs = needed.div_ceil(completed as u128);
+    Some(u64::try_from(epochs).unwrap_or(u64::MAX))
+}
+
+/// `(key, (wip, epochs_to_clear))` for every key with completions in the
+/// window; keys with no recent rate have nothing to project from.
+pub fn projected_clear<G, K>(
+    wip: &Collection<G, (K, i64)>,
+    throughput: &Collection<G, (K, i64)>,
+    window: u64,
+) -> Collection<G, (K, (i64, u64))>
+where
+    G: Scope<Timestamp = u64>,
+    K: ExchangeData + Hash,
+{
+    wip.join(throughput).flat_map(move |(key, (wip, completed))| {
+        epochs_to_clear(wip, completed, window).map(|epochs| (key, (wip, epochs)))
+    })
+}
+
+/// [`projected_clear`] for keys whose WIP a scenario overlays. Scenarios
+/// move the queue, not the rate, so the base throughput applies.
+pub fn scenario_projected_clear<G, K>(
+    overlay_wip: &ScenarioRows<G, K, i64>,
+    throughput: &Collection<G, (K, i64)>,
+    window: u64,
+) -> ScenarioRows<G, K, (i64, u64)>
+where
+    G: Scope<Timestamp = u64>,
+    K: ExchangeData + Hash,
+{
+    overlay_wip
+        .map(|((sid, key), wip)| (key, (sid, wip)))
+        .join(throughput)
+        .flat_map(move |(key, ((sid, wip), completed))| {
+            epochs_to_clear(wip, completed, window).map(|epochs| ((sid, key), (wip, epochs)))
+        })
+}
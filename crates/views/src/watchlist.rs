@@ -0,0 +1,233 @@
+//! Subscriptions that each watch one key, evaluated across workers.
+//!
+//! With thousands of subscriptions, no single worker should hold them all.
+//! Watch rows are keyed by `(key, shard)`, so the dataflow's exchange routes
+//! each to the worker owning that pair, and each candidate change visits only
+//! the shards of its own key. A hot key's watches spread over several
+//! shards; [`WatchShards`] decides how many as subscriptions come and go,
+//! and [`gather_alerts`] brings the shards' alerts back to one worker.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, Threshold};
+use differential_dataflow::{AsCollection, Collection, ExchangeData};
+use serde::{Deserialize, Serialize};
+use timely::dataflow::operators::Exchange;
+use timely::dataflow::Scope;
+
+use tw_core::ScenarioId;
+
+use crate::pushdown::{PredicateRow, SubscriptionPredicate};
+
+pub type WatchId = u64;
+
+/// A watch as a dataflow row: `((key, shard), (watch_id, predicate))`.
+pub type WatchRow = ((u64, u32), (WatchId, PredicateRow));
+
+/// How many shards a hot key's watches span: `(key, shards)`. Keys without
+/// a row have one.
+pub type FanoutRow = (u64, u32);
+
+/// `(watch_id, (scenario, key, value, weight_bps))` for each candidate row a
+/// watch fires on.
+pub type WatchAlert = (WatchId, (ScenarioId, u64, i64, i64));
+
+/// One subscription: fire when `key` has a candidate row meeting `predicate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyWatch {
+    pub id: WatchId,
+    pub key: u64,
+    pub predicate: SubscriptionPredicate,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ShardConfig {
+    /// Watches on one key per shard before the key spreads to another.
+    pub per_shard: usize,
+    /// Most shards one key spans.
+    pub max_fanout: u32,
+}
+
+impl Default for ShardConfig {
+    fn default() -> Self {
+        Self { per_shard: 1_000, max_fanout: 16 }
+    }
+}
+
+/// A watch registered or withdrawn at runtime, such as through a control
+/// plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchChange {
+    Add(KeyWatch),
+    Remove(WatchId),
+}
+
+impl WatchChange {
+    pub fn id(&self) -> WatchId {
+        match self {
+            WatchChange::Add(watch) => watch.id,
+            WatchChange::Remove(id) => *id,
+        }
+    }
+}
+
+/// Row changes to apply to the watch and fanout inputs.
+#[derive(Debug, Clone, Default)]
+pub struct ShardChanges {
+    pub watches: Vec<(WatchRow, isize)>,
+    pub fanout: Vec<(FanoutRow, isize)>,
+}
+
+/// Registered watches and the shard each one lives on. Adding or removing a
+/// watch can change its key's fanout, which moves the key's other watches
+/// between shards; the returned [`ShardChanges`] carry those moves.
+#[derive(Debug, Clone, Default)]
+pub struct WatchShards {
+    cfg: ShardConfig,
+    watches: BTreeMap<WatchId, KeyWatch>,
+    by_key: BTreeMap<u64, BTreeSet<WatchId>>,
+}
+
+impl WatchShards {
+    pub fn new(cfg: ShardConfig) -> Self {
+        Self { cfg, ..Self::default() }
+    }
+
+    pub fn apply(&mut self, change: WatchChange) -> ShardChanges {
+        match change {
+            WatchChange::Add(watch) => self.add(watch),
+            WatchChange::Remove(id) => self.remove(id),
+        }
+    }
+
+    /// Register `watch`, replacing any watch with the same id.
+    pub fn add(&mut self, watch: KeyWatch) -> ShardChanges {
+        let mut changes = self.remove(watch.id);
+        let before = self.fanout(watch.key);
+        let after = self.shards_for(self.count(watch.key) + 1);
+        self.move_watches(watch.key, before, after, &mut changes);
+        self.watches.insert(watch.id, watch);
+        self.by_key.entry(watch.key).or_default().insert(watch.id);
+        changes.watches.push((row(&watch, after), 1));
+        changes
+    }
+
+    /// Unregister watch `id`; no changes if it isn't registered.
+    pub fn remove(&mut self, id: WatchId) -> ShardChanges {
+        let mut changes = ShardChanges::default();
+        let Some(watch) = self.watches.remove(&id) else {
+            return changes;
+        };
+        let before = self.fanout(watch.key);
+        changes.watches.push((row(&watch, before), -1));
+        if let Some(ids) = self.by_key.get_mut(&watch.key) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.by_key.remove(&watch.key);
+            }
+        }
+        let after = self.fanout(watch.key);
+        self.move_watches(watch.key, before, after, &mut changes);
+        changes
+    }
+
+    pub fn get(&self, id: WatchId) -> Option<&KeyWatch> {
+        self.watches.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Shards `key`'s watches currently span.
+    pub fn fanout(&self, key: u64) -> u32 {
+        self.shards_for(self.count(key))
+    }
+
+    fn count(&self, key: u64) -> usize {
+        self.by_key.get(&key).map_or(0, BTreeSet::len)
+    }
+
+    fn shards_for(&self, watches: usize) -> u32 {
+        let shards = watches.div_ceil(self.cfg.per_shard.max(1));
+        u32::try_from(shards).unwrap_or(u32::MAX).clamp(1, self.cfg.max_fanout.max(1))
+    }
+
+    /// Move `key`'s registered watches from `before` shards to `after`.
+    fn move_watches(&self, key: u64, before: u32, after: u32, changes: &mut ShardChanges) {
+        if before == after {
+            return;
+        }
+        for watch in self.by_key.get(&key).into_iter().flatten().map(|id| &self.watches[id]) {
+            if shard(watch.id, before) != shard(watch.id, after) {
+                changes.watches.push((row(watch, before), -1));
+                changes.watches.push((row(watch, after), 1));
+            }
+        }
+        if before > 1 {
+            changes.fanout.push(((key, before), -1));
+        }
+        if after > 1 {
+            changes.fanout.push(((key, after), 1));
+        }
+    }
+}
+
+fn shard(id: WatchId, fanout: u32) -> u32 {
+    (id % u64::from(fanout.max(1))) as u32
+}
+
+fn row(watch: &KeyWatch, fanout: u32) -> WatchRow {
+    ((watch.key, shard(watch.id, fanout)), (watch.id, watch.predicate.row()))
+}
+
+/// Every watch that a `(scenario, (value, key))` candidate row fires on.
+///
+/// A candidate change is copied to each shard of its key and joined with
+/// the watches there, so the watch arrangement is spread across workers by
+/// `(key, shard)`. A watch fires while its scenario's weight is at least its
+/// `min_prob_bps` and the row's value is at least its `min_value`.
+pub fn evaluate_watches<G>(
+    candidates: &Collection<G, (ScenarioId, (i64, u64))>,
+    weights_bps: &Collection<G, (ScenarioId, i64)>,
+    watches: &Collection<G, WatchRow>,
+    fanout: &Collection<G, FanoutRow>,
+) -> Collection<G, WatchAlert>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    let changes = candidates
+        .join(weights_bps)
+        .map(|(sid, ((value, key), bps))| (key, (sid, value, bps)));
+    let spread = changes.join(fanout).flat_map(|(key, (change, shards))| {
+        (0..shards).map(move |shard| ((key, shard), change))
+    });
+    let single = changes
+        .antijoin(&fanout.map(|(key, _shards)| key).distinct())
+        .map(|(key, change)| ((key, 0), change));
+
+    spread
+        .concat(&single)
+        .join(watches)
+        .filter(|(_shard, ((_sid, value, bps), (_id, (min_prob_bps, min_value))))| {
+            *bps >= *min_prob_bps && min_value.map_or(true, |bound| *value >= bound)
+        })
+        .map(|((key, _shard), ((sid, value, bps), (id, _predicate)))| (id, (sid, key, value, bps)))
+}
+
+/// Route every shard's alerts to worker 0, for delivery from one place.
+pub fn gather_alerts<G, D>(alerts: &Collection<G, D>) -> Collection<G, D>
+where
+    G: Scope,
+    G::Timestamp: ExchangeData,
+    D: ExchangeData,
+{
+    alerts.inner.exchange(|_update| 0).as_collection()
+}
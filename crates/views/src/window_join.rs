@@ -0,0 +1,105 @@
+//! Interval joins between two event streams: each left event paired with
+//! the right events on its key whose event time falls within a window
+//! around it, such as order lines and inventory adjustments to the same SKU
+//! within an hour.
+//!
+//! Rows carry their own event time as [`Timed`] values, so the window is a
+//! filter on a plain join rather than on dataflow time. Retracting an event
+//! on either side retracts exactly the pairs it formed, however late it
+//! arrives.
+
+use std::hash::Hash;
+
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::Join;
+use differential_dataflow::{Collection, ExchangeData};
+use timely::dataflow::Scope;
+
+use tw_core::retail::{OrderId, SkuId};
+
+/// A value with its event time in milliseconds, as
+/// [`EventTime::as_millis`](tw_core::EventTime::as_millis) gives it.
+pub type Timed<V> = (u64, V);
+
+/// `(key, (left, right))` pairs a [`WindowJoin`] matched.
+pub type WindowPairs<G, K, L, R> = Collection<G, (K, (Timed<L>, Timed<R>))>;
+
+/// Which right events a left event pairs with: those from `before_ms`
+/// before it through `after_ms` after it, both inclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowJoin {
+    before_ms: u64,
+    after_ms: u64,
+}
+
+impl WindowJoin {
+    /// Right events at the same millisecond only; widen with
+    /// [`Self::before`] and [`Self::after`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Right events within `window_ms` either side of the left event.
+    pub fn within(window_ms: u64) -> Self {
+        Self { before_ms: window_ms, after_ms: window_ms }
+    }
+
+    pub fn before(mut self, window_ms: u64) -> Self {
+        self.before_ms = window_ms;
+        self
+    }
+
+    pub fn after(mut self, window_ms: u64) -> Self {
+        self.after_ms = window_ms;
+        self
+    }
+
+    /// Whether a right event at `right_ms` pairs with a left one at `left_ms`.
+    pub fn contains(&self, left_ms: u64, right_ms: u64) -> bool {
+        right_ms >= left_ms.saturating_sub(self.before_ms)
+            && right_ms <= left_ms.saturating_add(self.after_ms)
+    }
+
+    /// Every `(key, ((left_ms, left), (right_ms, right)))` pair inside the
+    /// window. Each left row is matched against all right rows on its key,
+    /// so a key with many events on both sides costs their product.
+    pub fn join<G, K, L, R>(
+        &self,
+        left: &Collection<G, (K, Timed<L>)>,
+        right: &Collection<G, (K, Timed<R>)>,
+    ) -> WindowPairs<G, K, L, R>
+    where
+        G: Scope,
+        G::Timestamp: Lattice + Ord,
+        K: ExchangeData + Hash,
+        L: ExchangeData,
+        R: ExchangeData,
+    {
+        let window = *self;
+        left.join(right).filter(move |(_key, ((left_ms, _), (right_ms, _)))| {
+            window.contains(*left_ms, *right_ms)
+        })
+    }
+}
+
+/// Orders that drew down a SKU shortly before it ran short: for each
+/// `(sku, (adjusted_ms, delta_qty))` inventory adjustment that took stock
+/// away, the `(sku, (ordered_ms, order_id))` order lines up to `window_ms`
+/// before it, as `((sku, adjusted_ms), order_id)`.
+pub fn stockout_attribution<G>(
+    order_lines: &Collection<G, (SkuId, Timed<OrderId>)>,
+    adjustments: &Collection<G, (SkuId, Timed<i32>)>,
+    window_ms: u64,
+) -> Collection<G, ((SkuId, u64), OrderId)>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    let drawdowns = adjustments.filter(|(_sku, (_adjusted_ms, delta_qty))| *delta_qty < 0);
+    WindowJoin::new()
+        .before(window_ms)
+        .join(&drawdowns, order_lines)
+        .map(|(sku, ((adjusted_ms, _delta_qty), (_ordered_ms, order_id)))| {
+            ((sku, adjusted_ms), order_id)
+        })
+}